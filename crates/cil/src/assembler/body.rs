@@ -0,0 +1,544 @@
+//! Method bodies: instructions, labels, locals and exception handling blocks
+
+use std::collections::HashMap;
+
+use super::{MethodDecl, ParamDecl, StandAloneSigDecl, error, lexer::LexemeKind, parser::Parser};
+use crate::{
+    Result,
+    body::{ExceptionClause, ExceptionClauseKind},
+    encoder::encode_instructions,
+    image::MethodHeader,
+    meta::{StandAloneSigHandle, Token},
+    opcodes::{
+        AsmOperand, Instruction, Operand, OperandType, Prefixes, RawOpcode, decode_instructions,
+    },
+    signature::{Element, LocalVarSignature, StandaloneMethodSignature},
+    tables::ParamAttributes,
+};
+
+struct PendingInstruction {
+    prefixes: Prefixes,
+    opcode: RawOpcode,
+    /// Labels of the branch or `switch` targets
+    targets: Vec<String>,
+    line: usize,
+}
+
+/// Start or end of an exception handling block, as an instruction index or a label
+enum Point {
+    Index(usize),
+    Label(String, usize),
+}
+
+enum PendingClauseKind {
+    Catch(Token),
+    Filter(Point),
+    Finally,
+    Fault,
+}
+
+struct PendingClause {
+    kind: PendingClauseKind,
+    try_start: Point,
+    try_end: Point,
+    handler_start: Point,
+    handler_end: Point,
+}
+
+struct Body {
+    instructions: Vec<PendingInstruction>,
+    /// Index of the instruction each label is placed before
+    labels: HashMap<String, usize>,
+    clauses: Vec<PendingClause>,
+    /// Prefixes waiting for the instruction they apply to
+    prefixes: Prefixes,
+    max_stack: u16,
+    locals: Vec<Element>,
+    local_names: Vec<Option<String>>,
+    /// Names of the arguments, including `this` for instance methods
+    argument_names: Vec<Option<String>>,
+    init_locals: bool,
+    /// Index into the parameters of the method that `.custom` directives apply to
+    param: Option<usize>,
+    entry_point: bool,
+}
+
+impl Parser<'_> {
+    /// Parses the body of a method declaration into `method`, returns whether it is the entry point.
+    /// `names` are the names of the parameters, without `this`.
+    pub(super) fn method_body(
+        &mut self,
+        method: &mut MethodDecl,
+        names: &[Option<String>],
+    ) -> Result<bool> {
+        let mut body = Body {
+            instructions: Vec::new(),
+            labels: HashMap::new(),
+            clauses: Vec::new(),
+            prefixes: Prefixes::default(),
+            max_stack: 8,
+            locals: Vec::new(),
+            local_names: Vec::new(),
+            argument_names: method
+                .signature
+                .header
+                .has_this()
+                .then_some(None)
+                .into_iter()
+                .chain(names.iter().cloned())
+                .collect(),
+            init_locals: false,
+            param: None,
+            entry_point: false,
+        };
+
+        self.expect_punct("{")?;
+        self.block(&mut body, method, names)?;
+        if !body.prefixes.is_empty() {
+            return Err(self.error("prefix without an instruction"));
+        }
+
+        let entry_point = body.entry_point;
+        method.body = self.finish_body(body)?;
+        Ok(entry_point)
+    }
+
+    /// Statements up to and including the closing brace of a block
+    fn block(
+        &mut self,
+        body: &mut Body,
+        method: &mut MethodDecl,
+        names: &[Option<String>],
+    ) -> Result<()> {
+        while !self.eat_punct("}") {
+            self.statement(body, method, names)?;
+        }
+        Ok(())
+    }
+
+    fn statement(
+        &mut self,
+        body: &mut Body,
+        method: &mut MethodDecl,
+        names: &[Option<String>],
+    ) -> Result<()> {
+        let param = body.param.take();
+        match self.peek() {
+            Some(LexemeKind::Directive(directive)) => {
+                self.advance();
+                match directive.as_str() {
+                    ".custom" => {
+                        let attribute = self.custom_attribute()?;
+                        match param {
+                            Some(index) => method.params[index].custom_attributes.push(attribute),
+                            None => method.custom_attributes.push(attribute),
+                        }
+                        body.param = param;
+                    }
+                    ".param" => body.param = Some(self.param(method, names)?),
+                    ".override" => {
+                        self.eat_keyword("method");
+                        let declaration = self.method_def_or_ref()?;
+                        method.overrides.push(declaration);
+                    }
+                    ".entrypoint" => body.entry_point = true,
+                    ".maxstack" => body.max_stack = self.integer_as()?,
+                    ".locals" => self.locals(body)?,
+                    ".try" => self.try_block(body, method, names)?,
+                    _ => {
+                        self.set_position(self.position() - 1);
+                        return Err(self.unexpected("an instruction"));
+                    }
+                }
+            }
+            Some(LexemeKind::Punct("{")) => {
+                self.advance();
+                self.block(body, method, names)?;
+            }
+            Some(LexemeKind::Name { value, .. })
+                if self.peek_at(1) == Some(&LexemeKind::Punct(":")) =>
+            {
+                if body
+                    .labels
+                    .insert(value.clone(), body.instructions.len())
+                    .is_some()
+                {
+                    return Err(self.error(format!("duplicate label {value}")));
+                }
+                self.set_position(self.position() + 2);
+            }
+            Some(LexemeKind::Name { quoted: false, .. }) => self.instruction(body)?,
+            _ => return Err(self.unexpected("an instruction")),
+        }
+        Ok(())
+    }
+
+    /// `.param [n]` with an optional default value, returns the index of the parameter
+    fn param(&mut self, method: &mut MethodDecl, names: &[Option<String>]) -> Result<usize> {
+        self.expect_punct("[")?;
+        let sequence = self.integer_as::<u16>()?;
+        self.expect_punct("]")?;
+        if sequence as usize > method.signature.parameters.len() {
+            return Err(self.error(format!("parameter {sequence} is out of range")));
+        }
+
+        let index = match method.params.iter().position(|p| p.sequence >= sequence) {
+            Some(index) if method.params[index].sequence == sequence => index,
+            position => {
+                let index = position.unwrap_or(method.params.len());
+                let name = (sequence as usize)
+                    .checked_sub(1)
+                    .and_then(|i| names.get(i).cloned().flatten());
+                method.params.insert(
+                    index,
+                    ParamDecl {
+                        flags: ParamAttributes(0),
+                        sequence,
+                        name,
+                        default_value: None,
+                        custom_attributes: Vec::new(),
+                    },
+                );
+                index
+            }
+        };
+
+        if self.eat_punct("=") {
+            let param = &mut method.params[index];
+            param.default_value = Some(self.constant()?);
+            param.flags.0 |= 0x1000;
+        }
+        Ok(index)
+    }
+
+    /// `.locals [init] (type name, ...)`, which adds to the locals declared before
+    fn locals(&mut self, body: &mut Body) -> Result<()> {
+        if self.eat_keyword("init") {
+            body.init_locals = true;
+        }
+        self.expect_punct("(")?;
+        if self.eat_punct(")") {
+            return Ok(());
+        }
+        loop {
+            if self.eat_punct("[") {
+                let slot = self.integer_as::<usize>()?;
+                if slot != body.locals.len() {
+                    return Err(self.error(format!("local slot {slot} is out of order")));
+                }
+                self.expect_punct("]")?;
+            }
+            body.locals.push(self.parse_type()?);
+            body.local_names.push(match self.peek() {
+                Some(LexemeKind::Name { .. }) => Some(self.dotted_name()?),
+                _ => None,
+            });
+            if !self.eat_punct(",") {
+                break;
+            }
+        }
+        self.expect_punct(")")
+    }
+
+    fn label(&mut self) -> Result<String> {
+        match self.peek() {
+            Some(LexemeKind::Name { value, .. }) => {
+                self.advance();
+                Ok(value.clone())
+            }
+            _ => Err(self.unexpected("a label")),
+        }
+    }
+
+    fn label_point(&mut self) -> Result<Point> {
+        let line = self.line();
+        Ok(Point::Label(self.label()?, line))
+    }
+
+    /// `.try` followed by either blocks in braces or the labels of the protected and handler ranges
+    fn try_block(
+        &mut self,
+        body: &mut Body,
+        method: &mut MethodDecl,
+        names: &[Option<String>],
+    ) -> Result<()> {
+        if !self.eat_punct("{") {
+            let try_start = self.label_point()?;
+            self.expect_keyword("to")?;
+            let try_end = self.label_point()?;
+            let kind = if self.eat_keyword("catch") {
+                PendingClauseKind::Catch(self.type_token()?)
+            } else if self.eat_keyword("filter") {
+                PendingClauseKind::Filter(self.label_point()?)
+            } else if self.eat_keyword("finally") {
+                PendingClauseKind::Finally
+            } else if self.eat_keyword("fault") {
+                PendingClauseKind::Fault
+            } else {
+                return Err(self.unexpected("an exception handler"));
+            };
+            self.expect_keyword("handler")?;
+            let handler_start = self.label_point()?;
+            self.expect_keyword("to")?;
+            let handler_end = self.label_point()?;
+            body.clauses.push(PendingClause {
+                kind,
+                try_start,
+                try_end,
+                handler_start,
+                handler_end,
+            });
+            return Ok(());
+        }
+
+        let try_start = body.instructions.len();
+        self.block(body, method, names)?;
+        let try_end = body.instructions.len();
+
+        let first_clause = body.clauses.len();
+        loop {
+            let kind = if self.eat_keyword("catch") {
+                PendingClauseKind::Catch(self.type_token()?)
+            } else if self.eat_keyword("filter") {
+                let filter_start = body.instructions.len();
+                self.expect_punct("{")?;
+                self.block(body, method, names)?;
+                PendingClauseKind::Filter(Point::Index(filter_start))
+            } else if self.eat_keyword("finally") {
+                PendingClauseKind::Finally
+            } else if self.eat_keyword("fault") {
+                PendingClauseKind::Fault
+            } else {
+                break;
+            };
+
+            let handler_start = body.instructions.len();
+            self.expect_punct("{")?;
+            self.block(body, method, names)?;
+            // Nested blocks were parsed first, so inner clauses precede outer ones as required
+            body.clauses.push(PendingClause {
+                kind,
+                try_start: Point::Index(try_start),
+                try_end: Point::Index(try_end),
+                handler_start: Point::Index(handler_start),
+                handler_end: Point::Index(body.instructions.len()),
+            });
+        }
+        if body.clauses.len() == first_clause {
+            return Err(self.unexpected("an exception handler"));
+        }
+        Ok(())
+    }
+
+    fn instruction(&mut self, body: &mut Body) -> Result<()> {
+        let line = self.line();
+        let name = self.label()?;
+        let opcode = RawOpcode::from_asm_name(&name)
+            .ok_or_else(|| error(line, format!("unknown instruction {name}")))?;
+
+        let mut targets = Vec::new();
+        let operand = match opcode.operand_type() {
+            OperandType::InlineNone => AsmOperand::None,
+            OperandType::ShortInlineBrTarget | OperandType::InlineBrTarget => {
+                targets.push(self.label()?);
+                AsmOperand::None
+            }
+            OperandType::InlineSwitch => {
+                self.expect_punct("(")?;
+                if !self.eat_punct(")") {
+                    loop {
+                        targets.push(self.label()?);
+                        if !self.eat_punct(",") {
+                            break;
+                        }
+                    }
+                    self.expect_punct(")")?;
+                }
+                AsmOperand::None
+            }
+            OperandType::ShortInlineI | OperandType::InlineI | OperandType::InlineI8 => {
+                AsmOperand::Integer(self.integer()?)
+            }
+            OperandType::ShortInlineR | OperandType::InlineR => match self.peek() {
+                Some(LexemeKind::Float(value)) => {
+                    self.advance();
+                    AsmOperand::Float(*value)
+                }
+                // The bytes of the value, for NaN and infinities
+                Some(LexemeKind::Bytes(bytes)) => {
+                    let value = match *bytes.as_slice() {
+                        [a, b, c, d] => f32::from_le_bytes([a, b, c, d]) as f64,
+                        [a, b, c, d, e, f, g, h] => f64::from_le_bytes([a, b, c, d, e, f, g, h]),
+                        _ => return Err(self.error("float must be given as 4 or 8 bytes")),
+                    };
+                    self.advance();
+                    AsmOperand::Float(value)
+                }
+                _ => AsmOperand::Integer(self.integer()?),
+            },
+            OperandType::ShortInlineVar | OperandType::InlineVar => match self.peek() {
+                Some(LexemeKind::Name { value, .. }) => {
+                    let index = if name.contains("loc") {
+                        body.local_names
+                            .iter()
+                            .position(|n| n.as_deref() == Some(value.as_str()))
+                    } else {
+                        body.argument_names
+                            .iter()
+                            .position(|n| n.as_deref() == Some(value.as_str()))
+                    };
+                    let index =
+                        index.ok_or_else(|| self.error(format!("undefined variable {value}")))?;
+                    self.advance();
+                    AsmOperand::Integer(index as i64)
+                }
+                _ => AsmOperand::Integer(self.integer()?),
+            },
+            OperandType::InlineMethod => AsmOperand::Token(self.method_token()?),
+            OperandType::InlineField => AsmOperand::Token(self.field_token()?),
+            OperandType::InlineType => AsmOperand::Token(self.type_token()?),
+            OperandType::InlineTok => AsmOperand::Token(if self.eat_keyword("method") {
+                self.method_token()?
+            } else if self.eat_keyword("field") {
+                self.field_token()?
+            } else {
+                self.type_token()?
+            }),
+            OperandType::InlineString => {
+                let value = self.string()?;
+                AsmOperand::Token(self.user_string_token(value))
+            }
+            OperandType::InlineSig => {
+                let header = self.calling_convention()?;
+                let return_type = self.parse_type()?;
+                let (parameters, sentinel) = self.parameters(false)?;
+                let signature = StandaloneMethodSignature {
+                    header,
+                    generic_param_count: 0,
+                    return_type,
+                    parameters: parameters.into_iter().map(|p| p.element).collect(),
+                    sentinel,
+                };
+                AsmOperand::Token(self.stand_alone_sig(StandAloneSigDecl::Method(signature)))
+            }
+        };
+
+        let opcode = match opcode {
+            // Sized to the targets so the provisional offsets are right
+            RawOpcode::Switch { .. } => RawOpcode::Switch {
+                targets: vec![0; targets.len()],
+            },
+            opcode => opcode
+                .with_operand(operand)
+                .ok_or_else(|| error(line, format!("invalid operand for {name}")))?,
+        };
+        if !body.prefixes.add(&opcode) {
+            body.instructions.push(PendingInstruction {
+                prefixes: std::mem::take(&mut body.prefixes),
+                opcode,
+                targets,
+                line,
+            });
+        }
+        Ok(())
+    }
+
+    fn stand_alone_sig(&mut self, signature: StandAloneSigDecl) -> Token {
+        let sigs = &mut self.module.stand_alone_sigs;
+        let row = match sigs.iter().position(|s| *s == signature) {
+            Some(index) => index + 1,
+            None => {
+                sigs.push(signature);
+                sigs.len()
+            }
+        };
+        StandAloneSigHandle(row as u32).token()
+    }
+
+    /// Resolves the labels and encodes the instructions, `None` for bodies without any
+    fn finish_body(&mut self, body: Body) -> Result<Option<(MethodHeader, Vec<Instruction>)>> {
+        if body.instructions.is_empty() {
+            return Ok(None);
+        }
+
+        // Offsets the instructions would have as written, the encoder resolves the branches again
+        let mut offsets = Vec::with_capacity(body.instructions.len() + 1);
+        let mut offset = 0;
+        for instruction in &body.instructions {
+            offsets.push(offset);
+            offset += (instruction.prefixes.size() + instruction.opcode.size()) as u32;
+        }
+        offsets.push(offset);
+
+        let resolve = |label: &str, line: usize| {
+            body.labels
+                .get(label)
+                .map(|&index| offsets[index])
+                .ok_or_else(|| error(line, format!("undefined label {label}")))
+        };
+        let point = |point: &Point| match point {
+            Point::Index(index) => Ok(offsets[*index]),
+            Point::Label(label, line) => resolve(label, *line),
+        };
+
+        let mut instructions = Vec::with_capacity(body.instructions.len());
+        for (pending, &offset) in body.instructions.iter().zip(&offsets) {
+            let targets = pending
+                .targets
+                .iter()
+                .map(|label| resolve(label, pending.line))
+                .collect::<Result<Vec<_>>>()?;
+            let operand = match &pending.opcode {
+                RawOpcode::Switch { .. } => Operand::Switch(targets),
+                opcode if opcode.is_branch() => Operand::Target(targets[0]),
+                opcode => {
+                    Instruction::new(offset, pending.prefixes.clone(), opcode.clone()).operand
+                }
+            };
+            instructions.push(Instruction {
+                offset,
+                prefixes: pending.prefixes.clone(),
+                opcode: pending.opcode.clone(),
+                operand,
+            });
+        }
+
+        let mut clauses = Vec::with_capacity(body.clauses.len());
+        for clause in &body.clauses {
+            let try_offset = point(&clause.try_start)?;
+            let try_end = point(&clause.try_end)?;
+            let handler_offset = point(&clause.handler_start)?;
+            let handler_end = point(&clause.handler_end)?;
+            if try_end <= try_offset || handler_end <= handler_offset {
+                return Err(self.error("empty exception handling block"));
+            }
+            clauses.push(ExceptionClause {
+                kind: match &clause.kind {
+                    PendingClauseKind::Catch(token) => ExceptionClauseKind::Catch(*token),
+                    PendingClauseKind::Filter(start) => ExceptionClauseKind::Filter(point(start)?),
+                    PendingClauseKind::Finally => ExceptionClauseKind::Finally,
+                    PendingClauseKind::Fault => ExceptionClauseKind::Fault,
+                },
+                try_offset,
+                try_length: try_end - try_offset,
+                handler_offset,
+                handler_length: handler_end - handler_offset,
+            });
+        }
+
+        let local_var_sig_token = (!body.locals.is_empty()).then(|| {
+            self.stand_alone_sig(StandAloneSigDecl::LocalVars(LocalVarSignature {
+                locals: body.locals,
+            }))
+        });
+        let (code, exception_clauses) = encode_instructions(&instructions, &clauses)?;
+        let header = MethodHeader {
+            max_stack: body.max_stack,
+            code_size: code.len() as u32,
+            local_var_sig_token,
+            init_locals: body.init_locals,
+            exception_clauses,
+        };
+        Ok(Some((header, decode_instructions(&code)?)))
+    }
+}
@@ -0,0 +1,304 @@
+//! Splits ILAsm source into lexemes
+
+use crate::{Result, error::Error};
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum LexemeKind {
+    /// Identifier, keyword or opcode name. Dots between identifiers are part of the name, as in
+    /// `System.Object` or `ldc.i4.s`.
+    Name {
+        value: String,
+        quoted: bool,
+    },
+    /// Directive such as `.class`, including the dot. Also used for `.ctor` and `.cctor`.
+    Directive(String),
+    String(String),
+    Integer(i64),
+    Float(f64),
+    /// Hexadecimal bytes in parentheses, eg. the value of a custom attribute
+    Bytes(Vec<u8>),
+    Punct(&'static str),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Lexeme {
+    pub kind: LexemeKind,
+    pub line: usize,
+}
+
+#[rustfmt::skip]
+const PUNCTUATION: &[&str] = &[
+    "...", "::", "!!", ":", "!", "{", "}", "(", ")", "[", "]", "<", ">", ",", "=", "*", "&", "/",
+    "+", "-", ".",
+];
+
+struct Lexer {
+    chars: Vec<char>,
+    position: usize,
+    line: usize,
+    lexemes: Vec<Lexeme>,
+    /// Position just past the last name, a dot right after it continues a dotted name
+    name_end: Option<usize>,
+}
+
+/// Splits `source` into lexemes, skipping whitespace and comments
+pub(super) fn tokenize(source: &str) -> Result<Vec<Lexeme>> {
+    let mut lexer = Lexer {
+        chars: source.chars().collect(),
+        position: 0,
+        line: 1,
+        lexemes: Vec::new(),
+        name_end: None,
+    };
+    loop {
+        lexer.skip_whitespace()?;
+        let line = lexer.line;
+        let Some(kind) = lexer.next_lexeme()? else {
+            return Ok(lexer.lexemes);
+        };
+        lexer.lexemes.push(Lexeme { kind, line });
+    }
+}
+
+fn is_identifier_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || "_$@`?".contains(c)
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "_$@`?".contains(c)
+}
+
+impl Lexer {
+    fn peek(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.position + offset).copied()
+    }
+
+    fn error(&self, message: &str) -> Error {
+        Error::InvalidILAsm {
+            line: self.line,
+            message: message.to_string(),
+        }
+    }
+
+    fn skip_whitespace(&mut self) -> Result<()> {
+        while let Some(c) = self.peek(0) {
+            match (c, self.peek(1)) {
+                ('\n', _) => {
+                    self.line += 1;
+                    self.position += 1;
+                }
+                (c, _) if c.is_whitespace() => self.position += 1,
+                ('/', Some('/')) => {
+                    while self.peek(0).is_some_and(|c| c != '\n') {
+                        self.position += 1;
+                    }
+                }
+                ('/', Some('*')) => {
+                    self.position += 2;
+                    loop {
+                        match (self.peek(0), self.peek(1)) {
+                            (Some('*'), Some('/')) => break,
+                            (Some('\n'), _) => self.line += 1,
+                            (None, _) => return Err(self.error("unterminated comment")),
+                            _ => {}
+                        }
+                        self.position += 1;
+                    }
+                    self.position += 2;
+                }
+                _ => break,
+            }
+        }
+        Ok(())
+    }
+
+    fn next_lexeme(&mut self) -> Result<Option<LexemeKind>> {
+        let Some(c) = self.peek(0) else {
+            return Ok(None);
+        };
+
+        let after_name = self.name_end == Some(self.position);
+        self.name_end = None;
+        let kind = match c {
+            '"' => LexemeKind::String(self.quoted('"')?),
+            '\'' => {
+                let value = self.quoted('\'')?;
+                self.name_end = Some(self.position);
+                LexemeKind::Name {
+                    value,
+                    quoted: true,
+                }
+            }
+            '(' if self.starts_bytes() => LexemeKind::Bytes(self.bytes()?),
+            '0'..='9' => self.number()?,
+            '-' if self.peek(1).is_some_and(|c| c.is_ascii_digit()) => self.number()?,
+            '.' if !after_name && self.peek(1).is_some_and(is_identifier_start) => {
+                self.position += 1;
+                LexemeKind::Directive(format!(".{}", self.identifier()))
+            }
+            c if is_identifier_start(c) => {
+                let value = self.identifier();
+                self.name_end = Some(self.position);
+                LexemeKind::Name {
+                    value,
+                    quoted: false,
+                }
+            }
+            _ => {
+                let rest = &self.chars[self.position..];
+                let punct = PUNCTUATION
+                    .iter()
+                    .find(|p| rest.iter().copied().take(p.len()).eq(p.chars()))
+                    .ok_or_else(|| self.error(&format!("unexpected character '{c}'")))?;
+                self.position += punct.len();
+                LexemeKind::Punct(punct)
+            }
+        };
+        Ok(Some(kind))
+    }
+
+    /// A dotted identifier. A trailing dot followed by whitespace is kept, for prefixes such as
+    /// `tail.`
+    fn identifier(&mut self) -> String {
+        let start = self.position;
+        loop {
+            while self.peek(0).is_some_and(is_identifier_char) {
+                self.position += 1;
+            }
+            match (self.peek(0), self.peek(1)) {
+                (Some('.'), Some(c)) if is_identifier_char(c) => self.position += 1,
+                (Some('.'), None) => {
+                    self.position += 1;
+                    break;
+                }
+                (Some('.'), Some(c)) if c.is_whitespace() => {
+                    self.position += 1;
+                    break;
+                }
+                _ => break,
+            }
+        }
+        self.chars[start..self.position].iter().collect()
+    }
+
+    fn quoted(&mut self, quote: char) -> Result<String> {
+        self.position += 1;
+        let mut value = String::new();
+        loop {
+            let c = self
+                .peek(0)
+                .ok_or_else(|| self.error("unterminated string"))?;
+            self.position += 1;
+            match c {
+                c if c == quote => return Ok(value),
+                '\n' => return Err(self.error("unterminated string")),
+                '\\' => {
+                    let escaped = self
+                        .peek(0)
+                        .ok_or_else(|| self.error("unterminated string"))?;
+                    self.position += 1;
+                    value.push(match escaped {
+                        'n' => '\n',
+                        't' => '\t',
+                        'r' => '\r',
+                        '0' => '\0',
+                        c => c,
+                    });
+                }
+                c => value.push(c),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<LexemeKind> {
+        let start = self.position;
+        if self.peek(0) == Some('-') {
+            self.position += 1;
+        }
+
+        if self.peek(0) == Some('0') && matches!(self.peek(1), Some('x' | 'X')) {
+            self.position += 2;
+            let digits_start = self.position;
+            while self.peek(0).is_some_and(|c| c.is_ascii_hexdigit()) {
+                self.position += 1;
+            }
+            let digits = self.chars[digits_start..self.position]
+                .iter()
+                .collect::<String>();
+            let value = u64::from_str_radix(&digits, 16)
+                .map_err(|_| self.error(&format!("invalid hexadecimal number 0x{digits}")))?
+                as i64;
+            return Ok(LexemeKind::Integer(if self.chars[start] == '-' {
+                value.wrapping_neg()
+            } else {
+                value
+            }));
+        }
+
+        let digits = |lexer: &mut Self| {
+            while lexer.peek(0).is_some_and(|c| c.is_ascii_digit()) {
+                lexer.position += 1;
+            }
+        };
+        digits(self);
+        let mut is_float = false;
+        // `0...` is a lower array bound followed by an ellipsis rather than a float
+        if self.peek(0) == Some('.') && self.peek(1).is_some_and(|c| c.is_ascii_digit()) {
+            self.position += 1;
+            digits(self);
+            is_float = true;
+        }
+        if matches!(self.peek(0), Some('e' | 'E'))
+            && (self.peek(1).is_some_and(|c| c.is_ascii_digit())
+                || (matches!(self.peek(1), Some('+' | '-'))
+                    && self.peek(2).is_some_and(|c| c.is_ascii_digit())))
+        {
+            self.position += 2;
+            digits(self);
+            is_float = true;
+        }
+
+        let text = self.chars[start..self.position].iter().collect::<String>();
+        let invalid = || self.error(&format!("invalid number {text}"));
+        if is_float {
+            return text.parse().map(LexemeKind::Float).map_err(|_| invalid());
+        }
+        text.parse::<i64>()
+            .or_else(|_| text.parse::<u64>().map(|v| v as i64))
+            .map(LexemeKind::Integer)
+            .map_err(|_| invalid())
+    }
+
+    /// Whether the parenthesis at the current position opens a byte list, which follows `=` in
+    /// declarations, `bytearray` and the float opcodes
+    fn starts_bytes(&self) -> bool {
+        match self.lexemes.last().map(|l| &l.kind) {
+            Some(LexemeKind::Punct("=")) => true,
+            Some(LexemeKind::Name {
+                value,
+                quoted: false,
+            }) => matches!(value.as_str(), "bytearray" | "ldc.r4" | "ldc.r8"),
+            _ => false,
+        }
+    }
+
+    fn bytes(&mut self) -> Result<Vec<u8>> {
+        self.position += 1;
+        let mut bytes = Vec::new();
+        loop {
+            self.skip_whitespace()?;
+            match (self.peek(0), self.peek(1)) {
+                (Some(')'), _) => {
+                    self.position += 1;
+                    return Ok(bytes);
+                }
+                (Some(high), Some(low)) if high.is_ascii_hexdigit() && low.is_ascii_hexdigit() => {
+                    let digits = format!("{high}{low}");
+                    bytes.push(u8::from_str_radix(&digits, 16).unwrap());
+                    self.position += 2;
+                }
+                _ => return Err(self.error("expected a hexadecimal byte")),
+            }
+        }
+    }
+}
@@ -0,0 +1,351 @@
+//! Assembly of ILAsm source into metadata and method bodies (ECMA-335 Partition II)
+//!
+//! The result is an [`AssembledModule`] laid out like the metadata tables it describes. Tokens in
+//! method bodies, coded indices in signatures and handles all refer to the 1-based rows of its
+//! tables, so references between declarations are resolved the same way as in a loaded image.
+//!
+//! Types, fields and methods may be referenced before they are declared. The source is parsed
+//! again with the declarations found by the previous pass until they no longer change, which takes
+//! three passes: one to find the types, one for the member signatures and a final one that binds
+//! references to them. Source whose declarations still change after four passes is rejected.
+
+mod body;
+mod lexer;
+mod parser;
+mod types;
+
+use std::collections::HashMap;
+
+use crate::{
+    Result,
+    constant::ConstantValue,
+    error::Error,
+    identity::AssemblyIdentity,
+    image::MethodHeader,
+    meta::{FieldHandle, MethodDefHandle, Token, TypeDefHandle},
+    opcodes::Instruction,
+    signature::{
+        Element, LocalVarSignature, MemberRefSignature, PropertySignature,
+        StandaloneMethodSignature,
+    },
+    tables::{
+        AssemblyHashAlgorithm, FieldAttributes, MemberRefParent, MethodAttributes, MethodDefOrRef,
+        MethodImplAttributes, ParamAttributes, ResolutionScope, TypeAttributes, TypeDefOrRef,
+    },
+};
+
+use parser::Parser;
+
+/// Metadata and method bodies of a module assembled from ILAsm source
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssembledModule {
+    /// Name given by `.module`, empty if the source has none
+    pub name: String,
+    pub assembly: Option<AssemblyDecl>,
+    /// `.assembly extern` declarations. Assemblies that are only named in a type reference are
+    /// added with version 0.0.0.0.
+    pub assembly_refs: Vec<AssemblyIdentity>,
+    pub module_refs: Vec<String>,
+    /// Custom attributes of the module itself
+    pub custom_attributes: Vec<CustomAttributeDecl>,
+    /// Runtime flags of the CLI header, `ILONLY` unless `.corflags` says otherwise
+    pub corflags: u32,
+    pub entry_point: Option<MethodDefHandle>,
+    /// Row 1 is `<Module>`, which holds the global fields and methods
+    pub type_defs: Vec<TypeDecl>,
+    pub type_refs: Vec<TypeRefDecl>,
+    pub type_specs: Vec<Element>,
+    pub member_refs: Vec<MemberRefDecl>,
+    pub method_specs: Vec<MethodSpecDecl>,
+    pub stand_alone_sigs: Vec<StandAloneSigDecl>,
+    /// Strings loaded by `ldstr`. Their tokens are offsets into the #US heap the strings would be
+    /// written to in this order.
+    pub user_strings: Vec<(Token, String)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssemblyDecl {
+    pub identity: AssemblyIdentity,
+    pub hash_algorithm: AssemblyHashAlgorithm,
+    pub custom_attributes: Vec<CustomAttributeDecl>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeDecl {
+    pub flags: TypeAttributes,
+    pub namespace: String,
+    pub name: String,
+    pub extends: Option<TypeDefOrRef>,
+    pub interfaces: Vec<TypeDefOrRef>,
+    pub enclosing: Option<TypeDefHandle>,
+    pub generic_params: Vec<GenericParamDecl>,
+    /// `.pack`, only set together with `class_size`
+    pub packing_size: Option<u16>,
+    /// `.size`
+    pub class_size: Option<u32>,
+    pub custom_attributes: Vec<CustomAttributeDecl>,
+    pub fields: Vec<FieldDecl>,
+    pub methods: Vec<MethodDecl>,
+    pub properties: Vec<PropertyDecl>,
+    pub events: Vec<EventDecl>,
+}
+
+impl TypeDecl {
+    fn new(namespace: String, name: String) -> Self {
+        Self {
+            flags: TypeAttributes(0),
+            namespace,
+            name,
+            extends: None,
+            interfaces: Vec::new(),
+            enclosing: None,
+            generic_params: Vec::new(),
+            packing_size: None,
+            class_size: None,
+            custom_attributes: Vec::new(),
+            fields: Vec::new(),
+            methods: Vec::new(),
+            properties: Vec::new(),
+            events: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenericParamDecl {
+    pub number: u16,
+    /// Variance and special constraints (ECMA-335 II.23.1.7)
+    pub flags: u16,
+    pub name: String,
+    pub constraints: Vec<TypeDefOrRef>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldDecl {
+    pub flags: FieldAttributes,
+    pub name: String,
+    pub field_type: Element,
+    /// Offset given in brackets for types with explicit layout
+    pub offset: Option<u32>,
+    pub default_value: Option<ConstantValue>,
+    /// Bytes of the `.data` declaration the field is placed `at`
+    pub initial_data: Option<Vec<u8>>,
+    pub custom_attributes: Vec<CustomAttributeDecl>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MethodDecl {
+    pub flags: MethodAttributes,
+    pub impl_flags: MethodImplAttributes,
+    pub name: String,
+    pub signature: StandaloneMethodSignature,
+    pub generic_params: Vec<GenericParamDecl>,
+    /// Parameters that have a name, flags, a default value or custom attributes, by sequence.
+    /// Sequence 0 is the return value.
+    pub params: Vec<ParamDecl>,
+    /// `pinvokeimpl` target, for P/Invoke methods that name one
+    pub impl_map: Option<ImplMapDecl>,
+    /// Methods this one implements through `.override`
+    pub overrides: Vec<MethodDefOrRef>,
+    pub custom_attributes: Vec<CustomAttributeDecl>,
+    /// Header and instructions, `None` for methods without IL
+    pub body: Option<(MethodHeader, Vec<Instruction>)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParamDecl {
+    pub flags: ParamAttributes,
+    pub sequence: u16,
+    pub name: Option<String>,
+    pub default_value: Option<ConstantValue>,
+    pub custom_attributes: Vec<CustomAttributeDecl>,
+}
+
+/// Unmanaged function a P/Invoke method forwards to (ECMA-335 II.22.22)
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImplMapDecl {
+    pub mapping_flags: u16,
+    pub import_name: String,
+    /// Row of the module reference
+    pub import_scope: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PropertyDecl {
+    pub flags: u16,
+    pub name: String,
+    pub signature: PropertySignature,
+    pub default_value: Option<ConstantValue>,
+    pub custom_attributes: Vec<CustomAttributeDecl>,
+    /// Accessors with their MethodSemantics flags, eg. 0x2 for `.get`
+    pub methods: Vec<(u16, MethodDefHandle)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventDecl {
+    pub flags: u16,
+    pub name: String,
+    pub event_type: Option<TypeDefOrRef>,
+    pub custom_attributes: Vec<CustomAttributeDecl>,
+    /// Accessors with their MethodSemantics flags, eg. 0x8 for `.addon`
+    pub methods: Vec<(u16, MethodDefHandle)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CustomAttributeDecl {
+    pub constructor: MethodDefOrRef,
+    /// Encoded value blob, starting with the 0x0001 prolog
+    pub value: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeRefDecl {
+    pub scope: ResolutionScope,
+    pub namespace: String,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemberRefDecl {
+    pub parent: MemberRefParent,
+    pub name: String,
+    pub signature: MemberRefSignature,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MethodSpecDecl {
+    pub method: MethodDefOrRef,
+    pub generic_args: Vec<Element>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StandAloneSigDecl {
+    /// `calli` call site
+    Method(StandaloneMethodSignature),
+    LocalVars(LocalVarSignature),
+}
+
+impl AssembledModule {
+    /// Field definitions in row order, with the type that declares them
+    pub fn fields(&self) -> impl Iterator<Item = (FieldHandle, TypeDefHandle, &FieldDecl)> {
+        self.type_defs
+            .iter()
+            .enumerate()
+            .flat_map(|(i, t)| {
+                t.fields
+                    .iter()
+                    .map(move |f| (TypeDefHandle(i as u32 + 1), f))
+            })
+            .enumerate()
+            .map(|(row, (owner, field))| (FieldHandle(row as u32 + 1), owner, field))
+    }
+
+    /// Method definitions in row order, with the type that declares them
+    pub fn methods(&self) -> impl Iterator<Item = (MethodDefHandle, TypeDefHandle, &MethodDecl)> {
+        self.type_defs
+            .iter()
+            .enumerate()
+            .flat_map(|(i, t)| {
+                t.methods
+                    .iter()
+                    .map(move |m| (TypeDefHandle(i as u32 + 1), m))
+            })
+            .enumerate()
+            .map(|(row, (owner, method))| (MethodDefHandle(row as u32 + 1), owner, method))
+    }
+
+    pub fn field(&self, handle: FieldHandle) -> Option<&FieldDecl> {
+        self.fields().nth(handle.index()).map(|(_, _, field)| field)
+    }
+
+    pub fn method(&self, handle: MethodDefHandle) -> Option<&MethodDecl> {
+        self.methods()
+            .nth(handle.index())
+            .map(|(_, _, method)| method)
+    }
+
+    pub fn user_string(&self, token: Token) -> Option<&str> {
+        self.user_strings
+            .iter()
+            .find(|(t, _)| *t == token)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Types, fields and methods declared by the source, as found by one pass over it
+#[derive(Debug, Default, PartialEq)]
+struct Declarations {
+    /// Keyed by the row of the enclosing type (0 for top level types), namespace and name
+    types: HashMap<(u32, String, String), TypeDefHandle>,
+    /// Keyed by the row of the declaring type and the name
+    fields: HashMap<(u32, String), Vec<(Element, FieldHandle)>>,
+    methods: HashMap<(u32, String), Vec<(StandaloneMethodSignature, MethodDefHandle)>>,
+}
+
+impl Declarations {
+    fn new(module: &AssembledModule) -> Self {
+        let mut declarations = Self::default();
+        for (i, type_decl) in module.type_defs.iter().enumerate() {
+            let enclosing = type_decl.enclosing.map_or(0, |e| e.0);
+            declarations.types.insert(
+                (
+                    enclosing,
+                    type_decl.namespace.clone(),
+                    type_decl.name.clone(),
+                ),
+                TypeDefHandle(i as u32 + 1),
+            );
+        }
+        for (handle, owner, field) in module.fields() {
+            declarations
+                .fields
+                .entry((owner.0, field.name.clone()))
+                .or_default()
+                .push((field.field_type.clone(), handle));
+        }
+        for (handle, owner, method) in module.methods() {
+            declarations
+                .methods
+                .entry((owner.0, method.name.clone()))
+                .or_default()
+                .push((method.signature.clone(), handle));
+        }
+        declarations
+    }
+}
+
+/// How many times [`assemble`] parses the source before giving up on its declarations settling
+const MAX_PASSES: usize = 4;
+
+/// Assembles ILAsm source, such as the output of the [`Disassembler`](crate::disassembler::Disassembler).
+///
+/// Covers assembly and module declarations, classes with their fields, methods, properties and
+/// events, and method bodies with labels, `.locals` and exception handling blocks. Branches are
+/// encoded in their short form whenever the target is in range, regardless of the form written.
+pub fn assemble(source: &str) -> Result<AssembledModule> {
+    let lexemes = lexer::tokenize(source)?;
+    let mut declarations = Declarations::default();
+    for _ in 0..MAX_PASSES {
+        let (module, unresolved) = Parser::new(&lexemes, &declarations).parse()?;
+        let found = Declarations::new(&module);
+        if found == declarations {
+            return match unresolved {
+                Some(error) => Err(error),
+                None => Ok(module),
+            };
+        }
+        declarations = found;
+    }
+    Err(error(
+        1,
+        format!("declarations did not settle after {MAX_PASSES} passes"),
+    ))
+}
+
+/// Error for the given line of the source
+fn error(line: usize, message: impl Into<String>) -> Error {
+    Error::InvalidILAsm {
+        line,
+        message: message.into(),
+    }
+}
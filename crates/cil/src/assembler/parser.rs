@@ -0,0 +1,1213 @@
+//! Declarations: the manifest, classes and their members
+
+use std::collections::HashMap;
+
+use super::{
+    AssembledModule, AssemblyDecl, CustomAttributeDecl, Declarations, EventDecl, FieldDecl,
+    GenericParamDecl, ImplMapDecl, MethodDecl, ParamDecl, PropertyDecl, TypeDecl, error,
+    lexer::{Lexeme, LexemeKind},
+};
+use crate::{
+    Result,
+    constant::ConstantValue,
+    error::Error,
+    identity::{AssemblyIdentity, AssemblyVersion, PublicKeyOrToken},
+    meta::{MethodDefHandle, Token, TypeDefHandle},
+    signature::{Element, PropertySignature, StandaloneMethodSignature},
+    tables::{
+        AssemblyFlags, AssemblyHashAlgorithm, FieldAttributes, MemberRefParent, MethodAttributes,
+        MethodDefOrRef, MethodImplAttributes, ParamAttributes, ResolutionScope, TypeAttributes,
+    },
+};
+
+/// A parameter of a method declaration or reference
+pub(super) struct Parameter {
+    pub flags: u16,
+    pub element: Element,
+    pub name: Option<String>,
+}
+
+pub(super) struct Parser<'a> {
+    lexemes: &'a [Lexeme],
+    position: usize,
+    pub(super) declarations: &'a Declarations,
+    pub(super) module: AssembledModule,
+    /// First reference that could not be resolved. Only an error if it is still unresolved once
+    /// the declarations are complete.
+    pub(super) unresolved: Option<Error>,
+    /// Names of the generic parameters of the current class and method, for `!T` and `!!T`
+    pub(super) type_generics: Vec<String>,
+    pub(super) method_generics: Vec<String>,
+    /// Set while the generic parameters of a method are not known yet
+    pub(super) lenient_generics: bool,
+    /// Namespace of the enclosing `.namespace` blocks
+    namespace: String,
+    /// Rows of the classes being declared, innermost last
+    classes: Vec<u32>,
+    /// Indices of the fields placed `at` a data label, with the label
+    data_references: Vec<(usize, usize, String, usize)>,
+    data: HashMap<String, Vec<u8>>,
+    /// Type and method index of the `.entrypoint`
+    entry_point: Option<(usize, usize)>,
+    pub(super) type_ref_rows: HashMap<(ResolutionScope, String, String), u32>,
+    pub(super) member_ref_rows: HashMap<(MemberRefParent, String), Vec<u32>>,
+    pub(super) user_string_tokens: HashMap<String, Token>,
+    pub(super) user_string_heap_size: u32,
+}
+
+/// Where the next `.custom` directive in a class or at the top level applies
+#[derive(Clone, Copy)]
+enum CustomTarget {
+    /// The class, or the module at the top level
+    Owner,
+    Field(usize),
+}
+
+impl<'a> Parser<'a> {
+    pub(super) fn new(lexemes: &'a [Lexeme], declarations: &'a Declarations) -> Self {
+        Self {
+            lexemes,
+            position: 0,
+            declarations,
+            module: AssembledModule {
+                name: String::new(),
+                assembly: None,
+                assembly_refs: Vec::new(),
+                module_refs: Vec::new(),
+                custom_attributes: Vec::new(),
+                corflags: 0x1,
+                entry_point: None,
+                type_defs: vec![TypeDecl::new(String::new(), "<Module>".to_string())],
+                type_refs: Vec::new(),
+                type_specs: Vec::new(),
+                member_refs: Vec::new(),
+                method_specs: Vec::new(),
+                stand_alone_sigs: Vec::new(),
+                user_strings: Vec::new(),
+            },
+            unresolved: None,
+            type_generics: Vec::new(),
+            method_generics: Vec::new(),
+            lenient_generics: false,
+            namespace: String::new(),
+            classes: Vec::new(),
+            data_references: Vec::new(),
+            data: HashMap::new(),
+            entry_point: None,
+            type_ref_rows: HashMap::new(),
+            member_ref_rows: HashMap::new(),
+            user_string_tokens: HashMap::new(),
+            // The heap starts with an empty entry
+            user_string_heap_size: 1,
+        }
+    }
+
+    /// Parses the whole source, returning the module and the first unresolved reference
+    pub(super) fn parse(mut self) -> Result<(AssembledModule, Option<Error>)> {
+        let mut target = CustomTarget::Owner;
+        while self.peek().is_some() {
+            target = self.declaration(1, target)?;
+        }
+
+        for (type_index, field_index, label, line) in std::mem::take(&mut self.data_references) {
+            let data = self
+                .data
+                .get(&label)
+                .ok_or_else(|| error(line, format!("undefined data label {label}")))?;
+            self.module.type_defs[type_index].fields[field_index].initial_data = Some(data.clone());
+        }
+        if let Some((type_index, method_index)) = self.entry_point {
+            let row = self.module.type_defs[..type_index]
+                .iter()
+                .map(|t| t.methods.len())
+                .sum::<usize>()
+                + method_index
+                + 1;
+            self.module.entry_point = Some(MethodDefHandle(row as u32));
+        }
+        Ok((self.module, self.unresolved))
+    }
+
+    // Lexemes
+
+    pub(super) fn peek(&self) -> Option<&'a LexemeKind> {
+        self.peek_at(0)
+    }
+
+    pub(super) fn peek_at(&self, offset: usize) -> Option<&'a LexemeKind> {
+        self.lexemes.get(self.position + offset).map(|l| &l.kind)
+    }
+
+    pub(super) fn position(&self) -> usize {
+        self.position
+    }
+
+    pub(super) fn set_position(&mut self, position: usize) {
+        self.position = position;
+    }
+
+    /// Line of the next lexeme, or of the last one at the end of the source
+    pub(super) fn line(&self) -> usize {
+        self.lexemes
+            .get(self.position)
+            .or(self.lexemes.last())
+            .map_or(1, |l| l.line)
+    }
+
+    pub(super) fn error(&self, message: impl Into<String>) -> Error {
+        error(self.line(), message)
+    }
+
+    /// Error for an unexpected lexeme, `expected` describes what should have been there
+    pub(super) fn unexpected(&self, expected: &str) -> Error {
+        let found = match self.peek() {
+            None => "end of file".to_string(),
+            Some(LexemeKind::Name { value, .. }) => format!("'{value}'"),
+            Some(LexemeKind::Directive(directive)) => directive.clone(),
+            Some(LexemeKind::String(value)) => format!("\"{value}\""),
+            Some(LexemeKind::Integer(value)) => value.to_string(),
+            Some(LexemeKind::Float(value)) => value.to_string(),
+            Some(LexemeKind::Bytes(_)) => "bytes".to_string(),
+            Some(LexemeKind::Punct(punct)) => format!("'{punct}'"),
+        };
+        self.error(format!("expected {expected}, found {found}"))
+    }
+
+    /// Records a reference that could not be resolved
+    pub(super) fn unresolved(&mut self, message: impl Into<String>) {
+        if self.unresolved.is_none() {
+            self.unresolved = Some(self.error(message));
+        }
+    }
+
+    pub(super) fn advance(&mut self) {
+        self.position += 1;
+    }
+
+    pub(super) fn is_punct(&self, punct: &str) -> bool {
+        matches!(self.peek(), Some(LexemeKind::Punct(p)) if *p == punct)
+    }
+
+    pub(super) fn eat_punct(&mut self, punct: &str) -> bool {
+        let found = self.is_punct(punct);
+        if found {
+            self.advance();
+        }
+        found
+    }
+
+    pub(super) fn expect_punct(&mut self, punct: &str) -> Result<()> {
+        if !self.eat_punct(punct) {
+            return Err(self.unexpected(&format!("'{punct}'")));
+        }
+        Ok(())
+    }
+
+    /// Whether the next lexeme is the given keyword. Quoted names are never keywords.
+    pub(super) fn is_keyword(&self, keyword: &str) -> bool {
+        self.keyword_at(0) == Some(keyword)
+    }
+
+    pub(super) fn keyword_at(&self, offset: usize) -> Option<&'a str> {
+        match self.peek_at(offset) {
+            Some(LexemeKind::Name {
+                value,
+                quoted: false,
+            }) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub(super) fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let found = self.is_keyword(keyword);
+        if found {
+            self.advance();
+        }
+        found
+    }
+
+    pub(super) fn expect_keyword(&mut self, keyword: &str) -> Result<()> {
+        if !self.eat_keyword(keyword) {
+            return Err(self.unexpected(&format!("'{keyword}'")));
+        }
+        Ok(())
+    }
+
+    pub(super) fn is_directive(&self, directive: &str) -> bool {
+        matches!(self.peek(), Some(LexemeKind::Directive(d)) if d == directive)
+    }
+
+    pub(super) fn eat_directive(&mut self, directive: &str) -> bool {
+        let found = self.is_directive(directive);
+        if found {
+            self.advance();
+        }
+        found
+    }
+
+    pub(super) fn integer(&mut self) -> Result<i64> {
+        match self.peek() {
+            Some(LexemeKind::Integer(value)) => {
+                self.advance();
+                Ok(*value)
+            }
+            _ => Err(self.unexpected("an integer")),
+        }
+    }
+
+    /// An integer that has to fit into `T`
+    pub(super) fn integer_as<T: TryFrom<i64>>(&mut self) -> Result<T> {
+        let value = self.integer()?;
+        T::try_from(value).map_err(|_| {
+            self.position -= 1;
+            self.error(format!("{value} is out of range"))
+        })
+    }
+
+    /// A dotted name, whose parts may be quoted, eg. `System.'<>c'`
+    pub(super) fn dotted_name(&mut self) -> Result<String> {
+        let mut name = match self.peek() {
+            Some(LexemeKind::Name { value, .. }) => value.clone(),
+            _ => return Err(self.unexpected("a name")),
+        };
+        self.advance();
+        while self.is_punct(".")
+            && let Some(LexemeKind::Name { value, .. }) = self.peek_at(1)
+        {
+            name.push('.');
+            name.push_str(value);
+            self.position += 2;
+        }
+        Ok(name)
+    }
+
+    /// Name of a method or field, which may also be `.ctor` or `.cctor`
+    pub(super) fn member_name(&mut self) -> Result<String> {
+        match self.peek() {
+            Some(LexemeKind::Directive(name)) if name == ".ctor" || name == ".cctor" => {
+                self.advance();
+                Ok(name.clone())
+            }
+            _ => self.dotted_name(),
+        }
+    }
+
+    pub(super) fn bytes(&mut self) -> Result<Vec<u8>> {
+        match self.peek() {
+            Some(LexemeKind::Bytes(bytes)) => {
+                self.advance();
+                Ok(bytes.clone())
+            }
+            _ => Err(self.unexpected("bytes in parentheses")),
+        }
+    }
+
+    /// A quoted string, or `bytearray` with its UTF-16 code units
+    pub(super) fn string(&mut self) -> Result<String> {
+        match self.peek() {
+            Some(LexemeKind::String(value)) => {
+                self.advance();
+                Ok(value.clone())
+            }
+            _ if self.eat_keyword("bytearray") => {
+                let bytes = self.bytes()?;
+                if !bytes.len().is_multiple_of(2) {
+                    return Err(self.error("UTF-16 string with an odd number of bytes"));
+                }
+                let units = bytes
+                    .chunks_exact(2)
+                    .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                    .collect::<Vec<_>>();
+                Ok(String::from_utf16_lossy(&units))
+            }
+            _ => Err(self.unexpected("a string")),
+        }
+    }
+
+    // Top level
+
+    /// Parses a declaration at the top level or in a class, returning where a following `.custom`
+    /// applies. `owner` is the row of the class, 1 for global members.
+    fn declaration(&mut self, owner: u32, target: CustomTarget) -> Result<CustomTarget> {
+        let owner_index = owner as usize - 1;
+        let Some(LexemeKind::Directive(directive)) = self.peek() else {
+            return Err(self.unexpected("a declaration"));
+        };
+        self.advance();
+
+        match directive.as_str() {
+            ".custom" => {
+                let attribute = self.custom_attribute()?;
+                let type_decl = &mut self.module.type_defs[owner_index];
+                match target {
+                    CustomTarget::Field(index) => {
+                        type_decl.fields[index].custom_attributes.push(attribute)
+                    }
+                    CustomTarget::Owner if owner == 1 => {
+                        self.module.custom_attributes.push(attribute)
+                    }
+                    CustomTarget::Owner => type_decl.custom_attributes.push(attribute),
+                }
+                return Ok(target);
+            }
+            ".field" => {
+                let field = self.field(owner_index)?;
+                let fields = &mut self.module.type_defs[owner_index].fields;
+                fields.push(field);
+                return Ok(CustomTarget::Field(fields.len() - 1));
+            }
+            ".method" => self.method(owner_index)?,
+            ".class" => self.class()?,
+            ".namespace" if owner == 1 => {
+                let name = self.dotted_name()?;
+                let namespace = if self.namespace.is_empty() {
+                    name
+                } else {
+                    format!("{}.{name}", self.namespace)
+                };
+                let outer = std::mem::replace(&mut self.namespace, namespace);
+                self.expect_punct("{")?;
+                let mut target = CustomTarget::Owner;
+                while !self.eat_punct("}") {
+                    target = self.declaration(1, target)?;
+                }
+                self.namespace = outer;
+            }
+            ".assembly" if owner == 1 => {
+                if self.eat_keyword("extern") {
+                    self.assembly_ref()?;
+                } else {
+                    self.assembly()?;
+                }
+            }
+            ".module" if owner == 1 => {
+                if self.eat_keyword("extern") {
+                    let name = self.dotted_name()?;
+                    self.module_ref(&name);
+                } else {
+                    self.module.name = self.dotted_name()?;
+                }
+            }
+            ".corflags" if owner == 1 => self.module.corflags = self.integer_as()?,
+            ".data" if owner == 1 => self.data()?,
+            ".property" if owner != 1 => self.property(owner_index)?,
+            ".event" if owner != 1 => self.event(owner_index)?,
+            ".pack" if owner != 1 => {
+                self.module.type_defs[owner_index].packing_size = Some(self.integer_as()?)
+            }
+            ".size" if owner != 1 => {
+                self.module.type_defs[owner_index].class_size = Some(self.integer_as()?)
+            }
+            _ => {
+                self.position -= 1;
+                return Err(self.unexpected("a declaration"));
+            }
+        }
+        Ok(CustomTarget::Owner)
+    }
+
+    fn version(&mut self) -> Result<AssemblyVersion> {
+        let mut parts = [0u16; 4];
+        for (i, part) in parts.iter_mut().enumerate() {
+            if i > 0 {
+                self.expect_punct(":")?;
+            }
+            *part = self.integer_as()?;
+        }
+        Ok(AssemblyVersion {
+            major: parts[0],
+            minor: parts[1],
+            build: parts[2],
+            revision: parts[3],
+        })
+    }
+
+    fn assembly(&mut self) -> Result<()> {
+        let name = self.dotted_name()?;
+        let mut assembly = AssemblyDecl {
+            identity: AssemblyIdentity {
+                name,
+                version: AssemblyVersion::default(),
+                culture: None,
+                public_key: PublicKeyOrToken::None,
+                flags: AssemblyFlags(0),
+            },
+            hash_algorithm: AssemblyHashAlgorithm::SHA1,
+            custom_attributes: Vec::new(),
+        };
+
+        self.expect_punct("{")?;
+        while !self.eat_punct("}") {
+            let Some(LexemeKind::Directive(directive)) = self.peek() else {
+                return Err(self.unexpected("an assembly declaration"));
+            };
+            self.advance();
+            match directive.as_str() {
+                ".custom" => {
+                    let attribute = self.custom_attribute()?;
+                    assembly.custom_attributes.push(attribute);
+                }
+                ".publickey" => {
+                    self.expect_punct("=")?;
+                    assembly.identity.public_key = PublicKeyOrToken::PublicKey(self.bytes()?);
+                    assembly.identity.flags.0 |= 0x0001;
+                }
+                ".hash" => {
+                    self.expect_keyword("algorithm")?;
+                    assembly.hash_algorithm = match self.integer()? {
+                        0x0000 => AssemblyHashAlgorithm::None,
+                        0x8003 => AssemblyHashAlgorithm::MD5,
+                        0x8004 => AssemblyHashAlgorithm::SHA1,
+                        0x800C => AssemblyHashAlgorithm::SHA256,
+                        0x800D => AssemblyHashAlgorithm::SHA384,
+                        0x800E => AssemblyHashAlgorithm::SHA512,
+                        other => {
+                            return Err(self.error(format!("unknown hash algorithm {other:#x}")));
+                        }
+                    };
+                }
+                ".ver" => assembly.identity.version = self.version()?,
+                ".culture" | ".locale" => assembly.identity.culture = Some(self.string()?),
+                _ => {
+                    self.position -= 1;
+                    return Err(self.unexpected("an assembly declaration"));
+                }
+            }
+        }
+
+        self.module.assembly = Some(assembly);
+        Ok(())
+    }
+
+    fn assembly_ref(&mut self) -> Result<()> {
+        let mut flags = AssemblyFlags(0);
+        if self.eat_keyword("retargetable") {
+            flags.0 |= 0x0100;
+        }
+        let name = self.dotted_name()?;
+        let mut identity = AssemblyIdentity {
+            name,
+            version: AssemblyVersion::default(),
+            culture: None,
+            public_key: PublicKeyOrToken::None,
+            flags,
+        };
+
+        self.expect_punct("{")?;
+        while !self.eat_punct("}") {
+            let Some(LexemeKind::Directive(directive)) = self.peek() else {
+                return Err(self.unexpected("an assembly reference declaration"));
+            };
+            self.advance();
+            match directive.as_str() {
+                ".publickey" => {
+                    self.expect_punct("=")?;
+                    identity.public_key = PublicKeyOrToken::PublicKey(self.bytes()?);
+                    identity.flags.0 |= 0x0001;
+                }
+                ".publickeytoken" => {
+                    self.expect_punct("=")?;
+                    let token = self.bytes()?;
+                    identity.public_key = PublicKeyOrToken::Token(
+                        token
+                            .try_into()
+                            .map_err(|_| self.error("public key token must be 8 bytes long"))?,
+                    );
+                }
+                ".ver" => identity.version = self.version()?,
+                ".culture" | ".locale" => identity.culture = Some(self.string()?),
+                _ => {
+                    self.position -= 1;
+                    return Err(self.unexpected("an assembly reference declaration"));
+                }
+            }
+        }
+
+        // Replaces the reference added when a type scope named the assembly first
+        match self
+            .module
+            .assembly_refs
+            .iter_mut()
+            .find(|a| a.name == identity.name)
+        {
+            Some(existing) => *existing = identity,
+            None => self.module.assembly_refs.push(identity),
+        }
+        Ok(())
+    }
+
+    /// Row of a module reference, which is added if necessary
+    pub(super) fn module_ref(&mut self, name: &str) -> u32 {
+        match self.module.module_refs.iter().position(|m| m == name) {
+            Some(index) => index as u32 + 1,
+            None => {
+                self.module.module_refs.push(name.to_string());
+                self.module.module_refs.len() as u32
+            }
+        }
+    }
+
+    fn data(&mut self) -> Result<()> {
+        self.eat_keyword("cil");
+        let line = self.line();
+        let label = self.dotted_name()?;
+        self.expect_punct("=")?;
+        if !matches!(self.peek(), Some(LexemeKind::Bytes(_))) {
+            self.expect_keyword("bytearray")?;
+        }
+        let bytes = self.bytes()?;
+        if self.data.insert(label.clone(), bytes).is_some() {
+            return Err(error(line, format!("duplicate data label {label}")));
+        }
+        Ok(())
+    }
+
+    /// `.custom` followed by the constructor and the value blob
+    pub(super) fn custom_attribute(&mut self) -> Result<CustomAttributeDecl> {
+        let constructor = self.method_def_or_ref()?;
+        let value = if self.eat_punct("=") {
+            self.bytes()?
+        } else {
+            Vec::new()
+        };
+        Ok(CustomAttributeDecl { constructor, value })
+    }
+
+    // Classes
+
+    fn class(&mut self) -> Result<()> {
+        let mut flags = 0u32;
+        while let Some(keyword) = self.keyword_at(0) {
+            let (mask, value) = match keyword {
+                "private" => (0x7, 0x0),
+                "public" => (0x7, 0x1),
+                "nested" => {
+                    self.advance();
+                    let visibility = match self.keyword_at(0) {
+                        Some("public") => 0x2,
+                        Some("private") => 0x3,
+                        Some("family") => 0x4,
+                        Some("assembly") => 0x5,
+                        Some("famandassem") => 0x6,
+                        Some("famorassem") => 0x7,
+                        _ => return Err(self.unexpected("a nested type visibility")),
+                    };
+                    (0x7, visibility)
+                }
+                "interface" => (0x20, 0x20),
+                "abstract" => (0x80, 0x80),
+                "auto" => (0x18, 0x0),
+                "sequential" => (0x18, 0x8),
+                "explicit" => (0x18, 0x10),
+                "ansi" => (0x30000, 0x0),
+                "unicode" => (0x30000, 0x10000),
+                "autochar" => (0x30000, 0x20000),
+                "sealed" => (0x100, 0x100),
+                "serializable" => (0x2000, 0x2000),
+                "import" => (0x1000, 0x1000),
+                "specialname" => (0x400, 0x400),
+                "rtspecialname" => (0x800, 0x800),
+                "beforefieldinit" => (0x100000, 0x100000),
+                _ => break,
+            };
+            flags = (flags & !mask) | value;
+            self.advance();
+        }
+
+        let enclosing = self.classes.last().copied();
+        let (namespace, name) = match enclosing {
+            Some(_) => (String::new(), self.dotted_name()?),
+            None => {
+                let (namespace, name) = self.qualified_name()?;
+                match (self.namespace.is_empty(), namespace.is_empty()) {
+                    (true, _) => (namespace, name),
+                    (false, true) => (self.namespace.clone(), name),
+                    (false, false) => (format!("{}.{namespace}", self.namespace), name),
+                }
+            }
+        };
+
+        if self.module.type_defs.iter().any(|t| {
+            t.enclosing.map(|e| e.0) == enclosing && t.namespace == namespace && t.name == name
+        }) {
+            return Err(self.error(format!("duplicate class {name}")));
+        }
+
+        let mut type_decl = TypeDecl::new(namespace, name);
+        type_decl.flags = TypeAttributes(flags);
+        type_decl.enclosing = enclosing.map(TypeDefHandle);
+        self.module.type_defs.push(type_decl);
+        let row = self.module.type_defs.len() as u32;
+        let index = row as usize - 1;
+
+        let outer_generics = std::mem::take(&mut self.type_generics);
+        if self.is_punct("<") {
+            let generic_params = self.generic_params(true)?;
+            self.module.type_defs[index].generic_params = generic_params;
+        }
+        if self.eat_keyword("extends") {
+            let base = self.type_spec()?;
+            self.module.type_defs[index].extends = Some(base);
+        }
+        if self.eat_keyword("implements") {
+            loop {
+                let interface = self.type_spec()?;
+                self.module.type_defs[index].interfaces.push(interface);
+                if !self.eat_punct(",") {
+                    break;
+                }
+            }
+        }
+
+        self.classes.push(row);
+        self.expect_punct("{")?;
+        let mut target = CustomTarget::Owner;
+        while !self.eat_punct("}") {
+            target = self.declaration(row, target)?;
+        }
+        self.classes.pop();
+        self.type_generics = outer_generics;
+        Ok(())
+    }
+
+    /// Generic parameters of a class or method, eg. `<+class .ctor (IFoo) T>`. Their names are
+    /// brought into scope as they are declared.
+    pub(super) fn generic_params(&mut self, of_type: bool) -> Result<Vec<GenericParamDecl>> {
+        self.expect_punct("<")?;
+        let mut params = Vec::new();
+        loop {
+            let mut flags = 0;
+            if self.eat_punct("+") {
+                flags |= 0x1;
+            } else if self.eat_punct("-") {
+                flags |= 0x2;
+            }
+            loop {
+                if self.eat_keyword("class") {
+                    flags |= 0x4;
+                } else if self.eat_keyword("valuetype") {
+                    flags |= 0x8;
+                } else if self.eat_directive(".ctor") {
+                    flags |= 0x10;
+                } else {
+                    break;
+                }
+            }
+
+            let mut constraints = Vec::new();
+            if self.eat_punct("(") {
+                loop {
+                    constraints.push(self.type_spec()?);
+                    if !self.eat_punct(",") {
+                        break;
+                    }
+                }
+                self.expect_punct(")")?;
+            }
+
+            let name = self.dotted_name()?;
+            if of_type {
+                self.type_generics.push(name.clone());
+            } else {
+                self.method_generics.push(name.clone());
+            }
+            params.push(GenericParamDecl {
+                number: params.len() as u16,
+                flags,
+                name,
+                constraints,
+            });
+            if !self.eat_punct(",") {
+                break;
+            }
+        }
+        self.expect_punct(">")?;
+        Ok(params)
+    }
+
+    // Members
+
+    fn field(&mut self, owner_index: usize) -> Result<FieldDecl> {
+        let offset = if self.eat_punct("[") {
+            let offset = self.integer_as()?;
+            self.expect_punct("]")?;
+            Some(offset)
+        } else {
+            None
+        };
+
+        let mut flags = 0u16;
+        while let Some(keyword) = self.keyword_at(0) {
+            let (mask, value) = match member_access(keyword) {
+                Some(access) => (0x7, access),
+                None => match keyword {
+                    "static" => (0x10, 0x10),
+                    "initonly" => (0x20, 0x20),
+                    "literal" => (0x40, 0x40),
+                    "notserialized" => (0x80, 0x80),
+                    "specialname" => (0x200, 0x200),
+                    "rtspecialname" => (0x400, 0x400),
+                    _ => break,
+                },
+            };
+            flags = (flags & !mask) | value;
+            self.advance();
+        }
+
+        let field_type = self.parse_type()?;
+        let name = self.dotted_name()?;
+        if self.eat_keyword("at") {
+            let line = self.line();
+            let label = self.dotted_name()?;
+            flags |= 0x0100;
+            let field_index = self.module.type_defs[owner_index].fields.len();
+            self.data_references
+                .push((owner_index, field_index, label, line));
+        }
+        let default_value = if self.eat_punct("=") {
+            flags |= 0x8000;
+            Some(self.constant()?)
+        } else {
+            None
+        };
+
+        Ok(FieldDecl {
+            flags: FieldAttributes(flags),
+            name,
+            field_type,
+            offset,
+            default_value,
+            initial_data: None,
+            custom_attributes: Vec::new(),
+        })
+    }
+
+    /// Default value of a field, parameter or property, eg. `int32(5)` or `"text"`
+    pub(super) fn constant(&mut self) -> Result<ConstantValue> {
+        if matches!(self.peek(), Some(LexemeKind::String(_))) || self.is_keyword("bytearray") {
+            return self.string().map(ConstantValue::String);
+        }
+        if self.eat_keyword("nullref") {
+            return Ok(ConstantValue::Null);
+        }
+
+        let Some(kind) = self.keyword_at(0) else {
+            return Err(self.unexpected("a constant"));
+        };
+        self.advance();
+        self.expect_punct("(")?;
+        let value = match kind {
+            "bool" => {
+                let value = match self.keyword_at(0) {
+                    Some("true") => true,
+                    Some("false") => false,
+                    _ => return Err(self.unexpected("true or false")),
+                };
+                self.advance();
+                ConstantValue::Boolean(value)
+            }
+            "char" => ConstantValue::Char(self.integer_as()?),
+            "int8" => ConstantValue::I1(self.integer()? as i8),
+            "uint8" => ConstantValue::U1(self.integer()? as u8),
+            "int16" => ConstantValue::I2(self.integer()? as i16),
+            "uint16" => ConstantValue::U2(self.integer()? as u16),
+            "int32" => ConstantValue::I4(self.integer()? as i32),
+            "uint32" => ConstantValue::U4(self.integer()? as u32),
+            "int64" => ConstantValue::I8(self.integer()?),
+            "uint64" => ConstantValue::U8(self.integer()? as u64),
+            // An integer gives the bit pattern of the float
+            "float32" => ConstantValue::R4(match self.peek() {
+                Some(LexemeKind::Float(value)) => {
+                    self.advance();
+                    *value as f32
+                }
+                _ => f32::from_bits(self.integer()? as u32),
+            }),
+            "float64" => ConstantValue::R8(match self.peek() {
+                Some(LexemeKind::Float(value)) => {
+                    self.advance();
+                    *value
+                }
+                _ => f64::from_bits(self.integer()? as u64),
+            }),
+            _ => {
+                self.position -= 2;
+                return Err(self.unexpected("a constant"));
+            }
+        };
+        self.expect_punct(")")?;
+        Ok(value)
+    }
+
+    fn method(&mut self, owner_index: usize) -> Result<()> {
+        let mut flags = 0u16;
+        let mut impl_map = None;
+        while let Some(keyword) = self.keyword_at(0) {
+            if keyword == "pinvokeimpl" {
+                self.advance();
+                flags |= 0x2000;
+                impl_map = self.pinvoke_impl()?;
+                continue;
+            }
+            let (mask, value) = match member_access(keyword) {
+                Some(access) => (0x7, access),
+                None => match keyword {
+                    "static" => (0x10, 0x10),
+                    "final" => (0x20, 0x20),
+                    "virtual" => (0x40, 0x40),
+                    "hidebysig" => (0x80, 0x80),
+                    "newslot" => (0x100, 0x100),
+                    "strict" => (0x200, 0x200),
+                    "abstract" => (0x400, 0x400),
+                    "specialname" => (0x800, 0x800),
+                    "rtspecialname" => (0x1000, 0x1000),
+                    "unmanagedexp" => (0x8, 0x8),
+                    _ => break,
+                },
+            };
+            flags = (flags & !mask) | value;
+            self.advance();
+        }
+
+        // The return type may use generic parameters that are declared after the name, so it is
+        // skipped at first and parsed again once they are known
+        let header = self.calling_convention()?;
+        let return_type_position = self.position;
+        self.method_generics.clear();
+        self.lenient_generics = true;
+        let skipped = self.parse_type();
+        self.lenient_generics = false;
+        skipped?;
+
+        let name = self.member_name()?;
+        let generic_params = if self.is_punct("<") {
+            self.generic_params(false)?
+        } else {
+            Vec::new()
+        };
+        let parameters_position = self.position;
+        self.position = return_type_position;
+        let return_type = self.parse_type()?;
+        self.position = parameters_position;
+
+        let (parameters, sentinel) = self.parameters(true)?;
+
+        let mut impl_flags = 0u16;
+        while let Some(keyword) = self.keyword_at(0) {
+            let (mask, value) = match keyword {
+                "cil" => (0x3, 0x0),
+                "native" => (0x3, 0x1),
+                "optil" => (0x3, 0x2),
+                "runtime" => (0x3, 0x3),
+                "managed" => (0x4, 0x0),
+                "unmanaged" => (0x4, 0x4),
+                "forwardref" => (0x10, 0x10),
+                "preservesig" => (0x80, 0x80),
+                "internalcall" => (0x1000, 0x1000),
+                "synchronized" => (0x20, 0x20),
+                "noinlining" => (0x8, 0x8),
+                "nooptimization" => (0x40, 0x40),
+                "aggressiveinlining" => (0x100, 0x100),
+                _ => break,
+            };
+            impl_flags = (impl_flags & !mask) | value;
+            self.advance();
+        }
+
+        let mut signature_header = header;
+        if !generic_params.is_empty() {
+            signature_header.0 |= 0x10;
+        }
+        let params = parameters
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| p.flags != 0 || p.name.is_some())
+            .map(|(i, p)| ParamDecl {
+                flags: ParamAttributes(p.flags),
+                sequence: i as u16 + 1,
+                name: p.name.clone(),
+                default_value: None,
+                custom_attributes: Vec::new(),
+            })
+            .collect();
+        let mut method = MethodDecl {
+            flags: MethodAttributes(flags),
+            impl_flags: MethodImplAttributes(impl_flags),
+            name,
+            signature: StandaloneMethodSignature {
+                header: signature_header,
+                generic_param_count: generic_params.len() as u32,
+                return_type,
+                parameters: parameters.iter().map(|p| p.element.clone()).collect(),
+                sentinel,
+            },
+            generic_params,
+            params,
+            impl_map,
+            overrides: Vec::new(),
+            custom_attributes: Vec::new(),
+            body: None,
+        };
+        if method.flags.is_pinvoke_impl()
+            && let Some(impl_map) = &mut method.impl_map
+            && impl_map.import_name.is_empty()
+        {
+            impl_map.import_name = method.name.clone();
+        }
+
+        let names = parameters.into_iter().map(|p| p.name).collect::<Vec<_>>();
+        let is_entry_point = self.method_body(&mut method, &names)?;
+        self.method_generics.clear();
+
+        let methods = &mut self.module.type_defs[owner_index].methods;
+        methods.push(method);
+        if is_entry_point {
+            self.entry_point = Some((owner_index, methods.len() - 1));
+        }
+        Ok(())
+    }
+
+    /// `pinvokeimpl(...)` after the keyword, `None` if it doesn't name a module
+    fn pinvoke_impl(&mut self) -> Result<Option<ImplMapDecl>> {
+        self.expect_punct("(")?;
+        if self.eat_punct(")") {
+            return Ok(None);
+        }
+
+        let module = self.string()?;
+        let import_scope = self.module_ref(&module);
+        // Filled in with the method name unless `as` gives another one
+        let import_name = if self.eat_keyword("as") {
+            self.string()?
+        } else {
+            String::new()
+        };
+
+        let mut mapping_flags = 0u16;
+        while !self.eat_punct(")") {
+            let Some(keyword) = self.keyword_at(0) else {
+                return Err(self.unexpected("a P/Invoke attribute"));
+            };
+            self.advance();
+            let (mask, value) = match keyword {
+                "nomangle" => (0x0001, 0x0001),
+                "ansi" => (0x0006, 0x0002),
+                "unicode" => (0x0006, 0x0004),
+                "autochar" => (0x0006, 0x0006),
+                "lasterr" => (0x0040, 0x0040),
+                "winapi" => (0x0700, 0x0100),
+                "cdecl" => (0x0700, 0x0200),
+                "stdcall" => (0x0700, 0x0300),
+                "thiscall" => (0x0700, 0x0400),
+                "fastcall" => (0x0700, 0x0500),
+                "bestfit" | "charmaperror" => {
+                    self.expect_punct(":")?;
+                    let on = match self.keyword_at(0) {
+                        Some("on") => true,
+                        Some("off") => false,
+                        _ => return Err(self.unexpected("on or off")),
+                    };
+                    self.advance();
+                    match (keyword, on) {
+                        ("bestfit", true) => (0x0030, 0x0010),
+                        ("bestfit", false) => (0x0030, 0x0020),
+                        (_, true) => (0x3000, 0x1000),
+                        (_, false) => (0x3000, 0x2000),
+                    }
+                }
+                _ => {
+                    self.position -= 1;
+                    return Err(self.unexpected("a P/Invoke attribute"));
+                }
+            };
+            mapping_flags = (mapping_flags & !mask) | value;
+        }
+
+        Ok(Some(ImplMapDecl {
+            mapping_flags,
+            import_name,
+            import_scope,
+        }))
+    }
+
+    /// Parameter list in parentheses, with the index of the `...` sentinel if there is one.
+    /// Declarations may give parameters `[in]`, `[out]` and `[opt]` flags.
+    pub(super) fn parameters(
+        &mut self,
+        declaration: bool,
+    ) -> Result<(Vec<Parameter>, Option<usize>)> {
+        self.expect_punct("(")?;
+        let mut parameters = Vec::new();
+        let mut sentinel = None;
+        if self.eat_punct(")") {
+            return Ok((parameters, sentinel));
+        }
+
+        loop {
+            if self.eat_punct("...") {
+                sentinel = Some(parameters.len());
+            } else {
+                let mut flags = 0;
+                while declaration && self.eat_punct("[") {
+                    flags |= match self.keyword_at(0) {
+                        Some("in") => 0x0001,
+                        Some("out") => 0x0002,
+                        Some("opt") => 0x0010,
+                        _ => return Err(self.unexpected("in, out or opt")),
+                    };
+                    self.advance();
+                    self.expect_punct("]")?;
+                }
+                let element = self.parse_type()?;
+                let name = match self.peek() {
+                    Some(LexemeKind::Name { .. }) => Some(self.dotted_name()?),
+                    _ => None,
+                };
+                parameters.push(Parameter {
+                    flags,
+                    element,
+                    name,
+                });
+            }
+            if !self.eat_punct(",") {
+                break;
+            }
+        }
+        self.expect_punct(")")?;
+        Ok((parameters, sentinel))
+    }
+
+    fn property(&mut self, owner_index: usize) -> Result<()> {
+        let mut flags = 0u16;
+        loop {
+            if self.eat_keyword("specialname") {
+                flags |= 0x0200;
+            } else if self.eat_keyword("rtspecialname") {
+                flags |= 0x0400;
+            } else {
+                break;
+            }
+        }
+        let header = self.calling_convention()?;
+        let property_type = self.parse_type()?;
+        let name = self.dotted_name()?;
+        let (parameters, _) = self.parameters(false)?;
+        let default_value = if self.eat_punct("=") {
+            flags |= 0x1000;
+            Some(self.constant()?)
+        } else {
+            None
+        };
+
+        let mut property = PropertyDecl {
+            flags,
+            name,
+            signature: PropertySignature {
+                has_this: header.has_this(),
+                property_type,
+                parameters: parameters.into_iter().map(|p| p.element).collect(),
+            },
+            default_value,
+            custom_attributes: Vec::new(),
+            methods: Vec::new(),
+        };
+        self.accessors(&mut property.custom_attributes, &mut property.methods)?;
+        self.module.type_defs[owner_index].properties.push(property);
+        Ok(())
+    }
+
+    fn event(&mut self, owner_index: usize) -> Result<()> {
+        let mut flags = 0u16;
+        loop {
+            if self.eat_keyword("specialname") {
+                flags |= 0x0200;
+            } else if self.eat_keyword("rtspecialname") {
+                flags |= 0x0400;
+            } else {
+                break;
+            }
+        }
+        // The type is optional, a lone name is followed by the body
+        let event_type =
+            if matches!(self.peek(), Some(LexemeKind::Name { .. })) && self.is_body_after_name() {
+                None
+            } else {
+                Some(self.type_spec()?)
+            };
+        let name = self.dotted_name()?;
+
+        let mut event = EventDecl {
+            flags,
+            name,
+            event_type,
+            custom_attributes: Vec::new(),
+            methods: Vec::new(),
+        };
+        self.accessors(&mut event.custom_attributes, &mut event.methods)?;
+        self.module.type_defs[owner_index].events.push(event);
+        Ok(())
+    }
+
+    /// Whether the dotted name at the current position is directly followed by `{`
+    fn is_body_after_name(&self) -> bool {
+        let mut offset = 1;
+        while self.peek_at(offset) == Some(&LexemeKind::Punct("."))
+            && matches!(self.peek_at(offset + 1), Some(LexemeKind::Name { .. }))
+        {
+            offset += 2;
+        }
+        self.peek_at(offset) == Some(&LexemeKind::Punct("{"))
+    }
+
+    /// Body of a property or event with its custom attributes and accessor methods
+    fn accessors(
+        &mut self,
+        custom_attributes: &mut Vec<CustomAttributeDecl>,
+        methods: &mut Vec<(u16, MethodDefHandle)>,
+    ) -> Result<()> {
+        self.expect_punct("{")?;
+        while !self.eat_punct("}") {
+            let Some(LexemeKind::Directive(directive)) = self.peek() else {
+                return Err(self.unexpected("an accessor"));
+            };
+            let semantics = match directive.as_str() {
+                ".custom" => {
+                    self.advance();
+                    custom_attributes.push(self.custom_attribute()?);
+                    continue;
+                }
+                ".set" => 0x0001,
+                ".get" => 0x0002,
+                ".other" => 0x0004,
+                ".addon" => 0x0008,
+                ".removeon" => 0x0010,
+                ".fire" => 0x0020,
+                _ => return Err(self.unexpected("an accessor")),
+            };
+            self.advance();
+            let method = match self.method_def_or_ref()? {
+                MethodDefOrRef::MethodDef(row) => MethodDefHandle(row),
+                MethodDefOrRef::MemberRef(_) => {
+                    self.unresolved("accessor is not a method of this module");
+                    MethodDefHandle(0)
+                }
+            };
+            methods.push((semantics, method));
+        }
+        Ok(())
+    }
+}
+
+/// Access of a field or method (ECMA-335 II.23.1.10)
+fn member_access(keyword: &str) -> Option<u16> {
+    Some(match keyword {
+        "privatescope" | "compilercontrolled" => 0,
+        "private" => 1,
+        "famandassem" => 2,
+        "assembly" => 3,
+        "family" => 4,
+        "famorassem" => 5,
+        "public" => 6,
+        _ => return None,
+    })
+}
@@ -0,0 +1,694 @@
+//! Types, signatures and references to types and members
+
+use super::{
+    Declarations, MemberRefDecl, MethodSpecDecl, TypeRefDecl, lexer::LexemeKind, parser::Parser,
+};
+use crate::{
+    Result,
+    identity::{AssemblyIdentity, AssemblyVersion, PublicKeyOrToken},
+    meta::{
+        FieldHandle, MemberRefHandle, MethodDefHandle, MethodSpecHandle, Token, TokenKind,
+        TypeDefHandle, TypeRefHandle, TypeSpecHandle,
+    },
+    signature::{
+        ArrayShape, Element, FieldSignature, MemberRefSignature, StandaloneMethodSigHeader,
+        StandaloneMethodSignature,
+    },
+    tables::{AssemblyFlags, MemberRefParent, MethodDefOrRef, ResolutionScope, TypeDefOrRef},
+    util::PackedU32,
+};
+
+/// Keywords that start a type rather than a class name
+#[rustfmt::skip]
+const TYPE_KEYWORDS: &[&str] = &[
+    "void", "bool", "char", "int8", "int16", "int32", "int64", "uint8", "uint16", "uint32",
+    "uint64", "float32", "float64", "string", "object", "typedref", "native", "unsigned", "class",
+    "valuetype", "value", "method",
+];
+
+impl<'a> Parser<'a> {
+    /// Calling convention of a method signature, eg. `instance` or `unmanaged cdecl`
+    pub(super) fn calling_convention(&mut self) -> Result<StandaloneMethodSigHeader> {
+        let mut header = StandaloneMethodSigHeader::default();
+        loop {
+            if self.eat_keyword("instance") {
+                header.0 |= 0x20;
+            } else if self.eat_keyword("explicit") {
+                header.0 |= 0x40;
+            } else if self.eat_keyword("default") {
+                header.0 &= !0x0F;
+            } else if self.eat_keyword("vararg") {
+                header.0 = (header.0 & !0x0F) | 0x5;
+            } else if self.eat_keyword("unmanaged") {
+                let call_type = match self.keyword_at(0) {
+                    Some("cdecl") => 0x1,
+                    Some("stdcall") => 0x2,
+                    Some("thiscall") => 0x3,
+                    Some("fastcall") => 0x4,
+                    _ => 0x9,
+                };
+                if call_type != 0x9 {
+                    self.advance();
+                }
+                header.0 = (header.0 & !0x0F) | call_type;
+            } else {
+                return Ok(header);
+            }
+        }
+    }
+
+    fn is_type_start(&self) -> bool {
+        self.keyword_at(0)
+            .is_some_and(|k| TYPE_KEYWORDS.contains(&k))
+            || self.is_punct("!")
+            || self.is_punct("!!")
+    }
+
+    /// A type as written in signatures, eg. `class [System.Runtime]System.Object[]`
+    pub(super) fn parse_type(&mut self) -> Result<Element> {
+        let mut element = self.type_head()?;
+        loop {
+            // `[` also starts the scope of a class name following the type
+            if self.is_punct("[")
+                && matches!(
+                    self.peek_at(1),
+                    Some(LexemeKind::Punct("]" | "..." | ",") | LexemeKind::Integer(_))
+                )
+            {
+                self.advance();
+                element = if self.eat_punct("]") {
+                    Element::SzArray(Box::new(element))
+                } else {
+                    Element::Array(Box::new(element), self.array_shape()?)
+                };
+            } else if self.is_punct("*") && self.peek_at(1) != Some(&LexemeKind::Punct("(")) {
+                self.advance();
+                element = Element::Ptr(Box::new(element));
+            } else if self.eat_punct("&") {
+                element = Element::ByRef(Box::new(element));
+            } else if self.eat_keyword("pinned") {
+                element = Element::Pinned(Box::new(element));
+            } else if self.is_keyword("modreq") || self.is_keyword("modopt") {
+                let required = self.is_keyword("modreq");
+                self.advance();
+                self.expect_punct("(")?;
+                let modifier = self.class_ref()?;
+                self.expect_punct(")")?;
+                element = if required {
+                    Element::CModRequired(modifier, Box::new(element))
+                } else {
+                    Element::CModOptional(modifier, Box::new(element))
+                };
+            } else if self.is_punct("<")
+                && matches!(element, Element::Class(_) | Element::ValueType(_))
+            {
+                let generic_args = self.generic_args()?;
+                element = Element::GenericInst {
+                    generic_type: Box::new(element),
+                    generic_args,
+                };
+            } else {
+                return Ok(element);
+            }
+        }
+    }
+
+    /// Dimensions of a general array after the opening bracket, eg. `0...,0...]`
+    fn array_shape(&mut self) -> Result<ArrayShape> {
+        let mut dimensions: Vec<(Option<i32>, Option<u32>)> = Vec::new();
+        loop {
+            let dimension = match self.peek() {
+                Some(LexemeKind::Integer(_)) => {
+                    let first = self.integer_as::<i32>()?;
+                    if self.eat_punct("...") {
+                        match self.peek() {
+                            Some(LexemeKind::Integer(_)) => {
+                                let last = self.integer_as::<i32>()?;
+                                let size = u32::try_from(last as i64 - first as i64 + 1).map_err(
+                                    |_| self.error("array upper bound below lower bound"),
+                                )?;
+                                (Some(first), Some(size))
+                            }
+                            _ => (Some(first), None),
+                        }
+                    } else {
+                        let size =
+                            u32::try_from(first).map_err(|_| self.error("negative array size"))?;
+                        (Some(0), Some(size))
+                    }
+                }
+                _ => {
+                    self.eat_punct("...");
+                    (None, None)
+                }
+            };
+            dimensions.push(dimension);
+            if !self.eat_punct(",") {
+                break;
+            }
+        }
+        self.expect_punct("]")?;
+
+        Ok(ArrayShape {
+            rank: dimensions.len() as u32,
+            sizes: dimensions.iter().map_while(|d| d.1).collect(),
+            lower_bounds: dimensions.iter().map_while(|d| d.0).collect(),
+        })
+    }
+
+    /// Generic arguments in angle brackets
+    pub(super) fn generic_args(&mut self) -> Result<Vec<Element>> {
+        self.expect_punct("<")?;
+        let mut args = vec![self.parse_type()?];
+        while self.eat_punct(",") {
+            args.push(self.parse_type()?);
+        }
+        self.expect_punct(">")?;
+        Ok(args)
+    }
+
+    fn type_head(&mut self) -> Result<Element> {
+        if self.is_punct("!") || self.is_punct("!!") {
+            return self.generic_param_ref();
+        }
+
+        let Some(keyword) = self.keyword_at(0) else {
+            return Err(self.unexpected("a type"));
+        };
+        self.advance();
+        Ok(match keyword {
+            "void" => Element::Void,
+            "bool" => Element::Boolean,
+            "char" => Element::Char,
+            "int8" => Element::I1,
+            "int16" => Element::I2,
+            "int32" => Element::I4,
+            "int64" => Element::I8,
+            "uint8" => Element::U1,
+            "uint16" => Element::U2,
+            "uint32" => Element::U4,
+            "uint64" => Element::U8,
+            "float32" => Element::R4,
+            "float64" => Element::R8,
+            "string" => Element::String,
+            "object" => Element::Object,
+            "typedref" => Element::TypedByRef,
+            "native" => {
+                let unsigned = self.eat_keyword("unsigned") || self.eat_keyword("uint");
+                if !unsigned {
+                    self.expect_keyword("int")?;
+                } else if self.keyword_at(0) == Some("int") {
+                    self.advance();
+                }
+                if unsigned {
+                    Element::UIntPtr
+                } else {
+                    Element::IntPtr
+                }
+            }
+            "unsigned" => {
+                let element = match self.keyword_at(0) {
+                    Some("int8") => Element::U1,
+                    Some("int16") => Element::U2,
+                    Some("int32") => Element::U4,
+                    Some("int64") => Element::U8,
+                    _ => return Err(self.unexpected("an integer type")),
+                };
+                self.advance();
+                element
+            }
+            "class" => Element::Class(self.class_ref()?),
+            "valuetype" => Element::ValueType(self.class_ref()?),
+            "value" => {
+                self.expect_keyword("class")?;
+                Element::ValueType(self.class_ref()?)
+            }
+            "method" => {
+                let header = self.calling_convention()?;
+                let return_type = self.parse_type()?;
+                self.expect_punct("*")?;
+                let (parameters, sentinel) = self.parameters(false)?;
+                Element::FnPtr(Box::new(StandaloneMethodSignature {
+                    header,
+                    generic_param_count: 0,
+                    return_type,
+                    parameters: parameters.into_iter().map(|p| p.element).collect(),
+                    sentinel,
+                }))
+            }
+            _ => {
+                self.set_position(self.position() - 1);
+                return Err(self.unexpected("a type"));
+            }
+        })
+    }
+
+    /// `!n` or `!!n`, where `n` is the number or name of a generic parameter
+    fn generic_param_ref(&mut self) -> Result<Element> {
+        let of_method = self.is_punct("!!");
+        self.advance();
+        let number = match self.peek() {
+            Some(LexemeKind::Integer(_)) => self.integer_as::<u32>()?,
+            Some(LexemeKind::Name { value, .. }) => {
+                let names = if of_method {
+                    &self.method_generics
+                } else {
+                    &self.type_generics
+                };
+                let number = match names.iter().rposition(|n| n == value) {
+                    Some(number) => number as u32,
+                    // Method generic parameters are declared after the return type
+                    None if of_method && self.lenient_generics => 0,
+                    None => {
+                        return Err(self.error(format!("undefined generic parameter {value}")));
+                    }
+                };
+                self.advance();
+                number
+            }
+            _ => return Err(self.unexpected("a generic parameter")),
+        };
+        Ok(if of_method {
+            Element::MVar(PackedU32(number))
+        } else {
+            Element::Var(PackedU32(number))
+        })
+    }
+
+    /// A name as written in a class declaration or reference, split into namespace and name.
+    /// A quoted last part is the whole name, eg. `System.'<>c'`.
+    pub(super) fn qualified_name(&mut self) -> Result<(String, String)> {
+        let mut parts = Vec::new();
+        loop {
+            match self.peek() {
+                Some(LexemeKind::Name { value, quoted }) => parts.push((value.clone(), *quoted)),
+                _ => return Err(self.unexpected("a name")),
+            }
+            self.advance();
+            if !(self.is_punct(".") && matches!(self.peek_at(1), Some(LexemeKind::Name { .. }))) {
+                break;
+            }
+            self.advance();
+        }
+
+        let (last, quoted) = parts.pop().unwrap();
+        let mut namespace = parts
+            .into_iter()
+            .map(|(part, _)| part)
+            .collect::<Vec<_>>()
+            .join(".");
+        let name = match last.rsplit_once('.') {
+            Some((prefix, name)) if !quoted => {
+                if !namespace.is_empty() {
+                    namespace.push('.');
+                }
+                namespace.push_str(prefix);
+                name.to_string()
+            }
+            _ => last,
+        };
+        Ok((namespace, name))
+    }
+
+    /// A class name with an optional resolution scope, eg. `[System.Runtime]System.Object` or
+    /// `Outer/Inner`. Names without a scope refer to classes of this module.
+    pub(super) fn class_ref(&mut self) -> Result<TypeDefOrRef> {
+        let mut scope = None;
+        if self.eat_punct("[") {
+            scope = Some(if self.eat_directive(".module") {
+                let name = self.dotted_name()?;
+                ResolutionScope::ModuleRef(self.module_ref(&name))
+            } else {
+                let name = self.dotted_name()?;
+                ResolutionScope::AssemblyRef(self.assembly_ref_row(&name))
+            });
+            self.expect_punct("]")?;
+        }
+
+        let (namespace, name) = self.qualified_name()?;
+        let mut nested = Vec::new();
+        while self.eat_punct("/") {
+            nested.push(self.dotted_name()?);
+        }
+
+        let Some(scope) = scope else {
+            return Ok(TypeDefOrRef::TypeDef(
+                self.local_type(&namespace, &name, &nested)
+                    .map_or(0, |t| t.0),
+            ));
+        };
+        let mut row = self.type_ref_row(scope, namespace, name);
+        for name in nested {
+            row = self.type_ref_row(ResolutionScope::TypeRef(row), String::new(), name);
+        }
+        Ok(TypeDefOrRef::TypeRef(row))
+    }
+
+    /// Looks up a class of this module, recording it as unresolved if it isn't declared (yet)
+    fn local_type(
+        &mut self,
+        namespace: &str,
+        name: &str,
+        nested: &[String],
+    ) -> Option<TypeDefHandle> {
+        let declarations: &'a Declarations = self.declarations;
+        let types = &declarations.types;
+        let mut handle = types
+            .get(&(0, namespace.to_string(), name.to_string()))
+            .copied();
+        for nested_name in nested {
+            handle = handle.and_then(|h| {
+                types
+                    .get(&(h.0, String::new(), nested_name.clone()))
+                    .copied()
+            });
+        }
+        if handle.is_none() {
+            let mut full_name = if namespace.is_empty() {
+                name.to_string()
+            } else {
+                format!("{namespace}.{name}")
+            };
+            for nested_name in nested {
+                full_name.push('/');
+                full_name.push_str(nested_name);
+            }
+            self.unresolved(format!("undefined class {full_name}"));
+        }
+        handle
+    }
+
+    /// Row of an assembly reference, which is added with version 0.0.0.0 if it isn't declared
+    fn assembly_ref_row(&mut self, name: &str) -> u32 {
+        let refs = &mut self.module.assembly_refs;
+        match refs.iter().position(|a| a.name == name) {
+            Some(index) => index as u32 + 1,
+            None => {
+                refs.push(AssemblyIdentity {
+                    name: name.to_string(),
+                    version: AssemblyVersion::default(),
+                    culture: None,
+                    public_key: PublicKeyOrToken::None,
+                    flags: AssemblyFlags(0),
+                });
+                refs.len() as u32
+            }
+        }
+    }
+
+    fn type_ref_row(&mut self, scope: ResolutionScope, namespace: String, name: String) -> u32 {
+        let key = (scope, namespace, name);
+        if let Some(&row) = self.type_ref_rows.get(&key) {
+            return row;
+        }
+        let (scope, namespace, name) = key.clone();
+        self.module.type_refs.push(TypeRefDecl {
+            scope,
+            namespace,
+            name,
+        });
+        let row = self.module.type_refs.len() as u32;
+        self.type_ref_rows.insert(key, row);
+        row
+    }
+
+    /// A type used as a class, such as a base type or the operand of `box`. Types that aren't
+    /// plain classes become TypeSpec rows.
+    pub(super) fn type_spec(&mut self) -> Result<TypeDefOrRef> {
+        if !self.is_type_start() {
+            return self.class_ref();
+        }
+        Ok(match self.parse_type()? {
+            Element::Class(token) | Element::ValueType(token) => token,
+            element => TypeDefOrRef::TypeSpec(self.type_spec_row(element)),
+        })
+    }
+
+    fn type_spec_row(&mut self, element: Element) -> u32 {
+        let specs = &mut self.module.type_specs;
+        match specs.iter().position(|s| *s == element) {
+            Some(index) => index as u32 + 1,
+            None => {
+                specs.push(element);
+                specs.len() as u32
+            }
+        }
+    }
+
+    pub(super) fn type_token(&mut self) -> Result<Token> {
+        Ok(type_token(self.type_spec()?))
+    }
+
+    /// Whether a class name followed by `::` starts at the current position
+    fn is_class_name_then_member(&self) -> bool {
+        let mut offset = 0;
+        loop {
+            if !matches!(self.peek_at(offset), Some(LexemeKind::Name { .. })) {
+                return false;
+            }
+            offset += 1;
+            match self.peek_at(offset) {
+                Some(LexemeKind::Punct("." | "/")) => offset += 1,
+                Some(LexemeKind::Punct("::")) => return true,
+                _ => return false,
+            }
+        }
+    }
+
+    /// The parent in a member reference followed by `::`, `None` for global members
+    fn member_parent(&mut self) -> Result<Option<MemberRefParent>> {
+        let start = self.position();
+        if self.is_punct("[")
+            && matches!(self.peek_at(1), Some(LexemeKind::Directive(d)) if d == ".module")
+        {
+            self.position_advance(2);
+            let name = self.dotted_name()?;
+            self.expect_punct("]")?;
+            if self.eat_punct("::") {
+                return Ok(Some(MemberRefParent::ModuleRef(self.module_ref(&name))));
+            }
+            self.set_position(start);
+        }
+
+        if !(self.is_punct("[") || self.is_type_start() || self.is_class_name_then_member()) {
+            return Ok(None);
+        }
+        let parent = match self.type_spec()? {
+            TypeDefOrRef::TypeDef(row) => MemberRefParent::TypeDef(row),
+            TypeDefOrRef::TypeRef(row) => MemberRefParent::TypeRef(row),
+            TypeDefOrRef::TypeSpec(row) => MemberRefParent::TypeSpec(row),
+        };
+        self.expect_punct("::")?;
+        Ok(Some(parent))
+    }
+
+    fn position_advance(&mut self, count: usize) {
+        self.set_position(self.position() + count);
+    }
+
+    /// A method reference, eg. `instance void [System.Runtime]System.Object::.ctor()`, with the
+    /// generic arguments of a generic method instantiation
+    fn method_ref(&mut self) -> Result<(MethodDefOrRef, Option<Vec<Element>>)> {
+        let mut header = self.calling_convention()?;
+        let return_type = self.parse_type()?;
+        let parent = self.member_parent()?;
+        let name = self.member_name()?;
+
+        let mut generic_param_count = 0;
+        let mut generic_args = None;
+        if self.is_punct("<") {
+            if self.peek_at(1) == Some(&LexemeKind::Punct("[")) {
+                self.position_advance(2);
+                generic_param_count = self.integer_as()?;
+                self.expect_punct("]")?;
+                self.expect_punct(">")?;
+            } else {
+                let args = self.generic_args()?;
+                generic_param_count = args.len() as u32;
+                generic_args = Some(args);
+            }
+        }
+        if generic_param_count > 0 {
+            header.0 |= 0x10;
+        }
+
+        let (parameters, sentinel) = self.parameters(false)?;
+        let signature = StandaloneMethodSignature {
+            header,
+            generic_param_count,
+            return_type,
+            parameters: parameters.into_iter().map(|p| p.element).collect(),
+            sentinel,
+        };
+
+        let owner = match parent {
+            None => Some(1),
+            Some(MemberRefParent::TypeDef(row)) => Some(row),
+            Some(_) => None,
+        };
+        let method = match owner {
+            // The class itself is unresolved
+            Some(0) => MethodDefOrRef::MethodDef(0),
+            Some(owner) => {
+                // Vararg call sites reference the definition through a MemberRef
+                let mut definition = signature.clone();
+                if let Some(sentinel) = definition.sentinel.take() {
+                    definition.parameters.truncate(sentinel);
+                }
+                let row = self
+                    .declarations
+                    .methods
+                    .get(&(owner, name.clone()))
+                    .and_then(|methods| methods.iter().find(|(s, _)| *s == definition))
+                    .map(|(_, handle)| handle.0);
+                match row {
+                    Some(row) if signature.sentinel.is_some() => {
+                        MethodDefOrRef::MemberRef(self.member_ref_row(
+                            MemberRefParent::MethodDef(row),
+                            name,
+                            MemberRefSignature::Method(signature),
+                        ))
+                    }
+                    Some(row) => MethodDefOrRef::MethodDef(row),
+                    None => {
+                        self.unresolved(format!("undefined method {name}"));
+                        MethodDefOrRef::MethodDef(0)
+                    }
+                }
+            }
+            None => MethodDefOrRef::MemberRef(self.member_ref_row(
+                parent.unwrap(),
+                name,
+                MemberRefSignature::Method(signature),
+            )),
+        };
+        Ok((method, generic_args))
+    }
+
+    /// A method reference that can't be a generic method instantiation, as used by `.custom`
+    pub(super) fn method_def_or_ref(&mut self) -> Result<MethodDefOrRef> {
+        let (method, generic_args) = self.method_ref()?;
+        if generic_args.is_some() {
+            return Err(self.error("generic method instantiation is not allowed here"));
+        }
+        Ok(method)
+    }
+
+    /// Token of a method reference, a MethodSpec for generic method instantiations
+    pub(super) fn method_token(&mut self) -> Result<Token> {
+        let (method, generic_args) = self.method_ref()?;
+        let Some(generic_args) = generic_args else {
+            return Ok(method_token(method));
+        };
+
+        let specs = &mut self.module.method_specs;
+        let row = match specs
+            .iter()
+            .position(|s| s.method == method && s.generic_args == generic_args)
+        {
+            Some(index) => index as u32 + 1,
+            None => {
+                specs.push(MethodSpecDecl {
+                    method,
+                    generic_args,
+                });
+                specs.len() as u32
+            }
+        };
+        Ok(MethodSpecHandle(row).token())
+    }
+
+    /// Token of a field reference, eg. `int32 Namespace.Type::field`
+    pub(super) fn field_token(&mut self) -> Result<Token> {
+        let field_type = self.parse_type()?;
+        let parent = self.member_parent()?;
+        let name = self.dotted_name()?;
+
+        let owner = match parent {
+            None => 1,
+            Some(MemberRefParent::TypeDef(row)) => row,
+            Some(parent) => {
+                let row = self.member_ref_row(
+                    parent,
+                    name,
+                    MemberRefSignature::Field(FieldSignature { field_type }),
+                );
+                return Ok(MemberRefHandle(row).token());
+            }
+        };
+        if owner == 0 {
+            return Ok(FieldHandle(0).token());
+        }
+        let row = self
+            .declarations
+            .fields
+            .get(&(owner, name.clone()))
+            .and_then(|fields| fields.iter().find(|(t, _)| *t == field_type))
+            .map(|(_, handle)| handle.0);
+        if row.is_none() {
+            self.unresolved(format!("undefined field {name}"));
+        }
+        Ok(FieldHandle(row.unwrap_or(0)).token())
+    }
+
+    fn member_ref_row(
+        &mut self,
+        parent: MemberRefParent,
+        name: String,
+        signature: MemberRefSignature,
+    ) -> u32 {
+        let rows = self
+            .member_ref_rows
+            .entry((parent, name.clone()))
+            .or_default();
+        let member_refs = &mut self.module.member_refs;
+        if let Some(&row) = rows
+            .iter()
+            .find(|&&row| member_refs[row as usize - 1].signature == signature)
+        {
+            return row;
+        }
+        member_refs.push(MemberRefDecl {
+            parent,
+            name,
+            signature,
+        });
+        let row = member_refs.len() as u32;
+        rows.push(row);
+        row
+    }
+
+    /// Token of a string loaded by `ldstr`, its offset in the #US heap
+    pub(super) fn user_string_token(&mut self, value: String) -> Token {
+        if let Some(&token) = self.user_string_tokens.get(&value) {
+            return token;
+        }
+        let token = Token(((TokenKind::UserString as u32) << 24) | self.user_string_heap_size);
+        // Compressed length, UTF-16 code units and a trailing flag byte
+        let size = value.encode_utf16().count() as u32 * 2 + 1;
+        let length_size = match size {
+            0..0x80 => 1,
+            0x80..0x4000 => 2,
+            _ => 4,
+        };
+        self.user_string_heap_size += length_size + size;
+        self.user_string_tokens.insert(value.clone(), token);
+        self.module.user_strings.push((token, value));
+        token
+    }
+}
+
+pub(super) fn type_token(token: TypeDefOrRef) -> Token {
+    match token {
+        TypeDefOrRef::TypeDef(row) => TypeDefHandle(row).token(),
+        TypeDefOrRef::TypeRef(row) => TypeRefHandle(row).token(),
+        TypeDefOrRef::TypeSpec(row) => TypeSpecHandle(row).token(),
+    }
+}
+
+fn method_token(method: MethodDefOrRef) -> Token {
+    match method {
+        MethodDefOrRef::MethodDef(row) => MethodDefHandle(row).token(),
+        MethodDefOrRef::MemberRef(row) => MemberRefHandle(row).token(),
+    }
+}
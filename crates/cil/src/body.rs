@@ -0,0 +1,187 @@
+//! Method bodies: headers, instructions and exception handling sections (ECMA-335 II.25.4)
+
+use std::io::Cursor;
+
+use binrw::BinReaderExt;
+
+use crate::{
+    Result,
+    error::Error,
+    image::MethodHeader,
+    meta::Token,
+    opcodes::{self, Instruction},
+};
+
+pub(crate) const TINY_FORMAT: u8 = 0x2;
+pub(crate) const FAT_FORMAT: u16 = 0x3;
+pub(crate) const MORE_SECTS: u16 = 0x8;
+pub(crate) const INIT_LOCALS: u16 = 0x10;
+
+pub(crate) const SECTION_EH_TABLE: u8 = 0x1;
+pub(crate) const SECTION_FAT_FORMAT: u8 = 0x40;
+pub(crate) const SECTION_MORE_SECTS: u8 = 0x80;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExceptionClauseKind {
+    /// Handles exceptions assignable to a TypeDef, TypeRef or TypeSpec
+    Catch(Token),
+    /// Handles exceptions accepted by the filter block starting at the given offset
+    Filter(u32),
+    Finally,
+    Fault,
+}
+
+impl ExceptionClauseKind {
+    pub(crate) fn flags(&self) -> u32 {
+        match self {
+            Self::Catch(_) => 0x0,
+            Self::Filter(_) => 0x1,
+            Self::Finally => 0x2,
+            Self::Fault => 0x4,
+        }
+    }
+
+    /// Class token or filter offset, as stored in the last column of a clause
+    pub(crate) fn data(&self) -> u32 {
+        match self {
+            Self::Catch(token) => token.0,
+            Self::Filter(offset) => *offset,
+            Self::Finally | Self::Fault => 0,
+        }
+    }
+}
+
+/// A protected block with its handler. Offsets are relative to the start of the method body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExceptionClause {
+    pub kind: ExceptionClauseKind,
+    pub try_offset: u32,
+    pub try_length: u32,
+    pub handler_offset: u32,
+    pub handler_length: u32,
+}
+
+impl ExceptionClause {
+    pub fn try_end(&self) -> u32 {
+        self.try_offset + self.try_length
+    }
+
+    pub fn handler_end(&self) -> u32 {
+        self.handler_offset + self.handler_length
+    }
+
+    fn read(reader: &mut Cursor<&[u8]>, fat: bool) -> Result<Self> {
+        let (flags, try_offset, try_length, handler_offset, handler_length) = if fat {
+            (
+                reader.read_le::<u32>()?,
+                reader.read_le::<u32>()?,
+                reader.read_le::<u32>()?,
+                reader.read_le::<u32>()?,
+                reader.read_le::<u32>()?,
+            )
+        } else {
+            (
+                reader.read_le::<u16>()? as u32,
+                reader.read_le::<u16>()? as u32,
+                reader.read_le::<u8>()? as u32,
+                reader.read_le::<u16>()? as u32,
+                reader.read_le::<u8>()? as u32,
+            )
+        };
+        let data: u32 = reader.read_le()?;
+
+        let kind = match flags {
+            0x0 => ExceptionClauseKind::Catch(Token(data)),
+            0x1 => ExceptionClauseKind::Filter(data),
+            0x2 => ExceptionClauseKind::Finally,
+            0x4 => ExceptionClauseKind::Fault,
+            _ => return Err(Error::InvalidExceptionClause(flags)),
+        };
+
+        Ok(Self {
+            kind,
+            try_offset,
+            try_length,
+            handler_offset,
+            handler_length,
+        })
+    }
+}
+
+/// Decodes the method body at the start of `data`, which may continue past the end of the body
+pub fn decode_method_body(data: &[u8]) -> Result<(MethodHeader, Vec<Instruction>)> {
+    let mut reader = Cursor::new(data);
+    let first: u8 = reader.read_le()?;
+
+    let (mut header, mut more_sections) = if first & 0x3 == FAT_FORMAT as u8 {
+        reader.set_position(0);
+        let flags_and_size: u16 = reader.read_le()?;
+        let max_stack: u16 = reader.read_le()?;
+        let code_size: u32 = reader.read_le()?;
+        let local_var_sig_token: Token = reader.read_le()?;
+
+        // Header size is given in 4-byte units
+        reader.set_position((flags_and_size >> 12) as u64 * 4);
+
+        let flags = flags_and_size & 0xFFF;
+        let header = MethodHeader {
+            max_stack,
+            code_size,
+            local_var_sig_token: (local_var_sig_token.0 != 0).then_some(local_var_sig_token),
+            init_locals: flags & INIT_LOCALS != 0,
+            exception_clauses: Vec::new(),
+        };
+        (header, flags & MORE_SECTS != 0)
+    } else {
+        let header = MethodHeader {
+            code_size: (first >> 2) as u32,
+            max_stack: 8,
+            local_var_sig_token: None,
+            init_locals: false,
+            exception_clauses: Vec::new(),
+        };
+        (header, false)
+    };
+
+    let code_start = reader.position() as usize;
+    let code = code_start
+        .checked_add(header.code_size as usize)
+        .and_then(|code_end| data.get(code_start..code_end))
+        .ok_or_else(|| binrw::Error::AssertFail {
+            pos: code_start as u64,
+            message: format!("Code size {:#x} exceeds the method body", header.code_size),
+        })?;
+    reader.set_position((code_start + code.len()) as u64);
+    let instructions = opcodes::decode_instructions(code)?;
+
+    while more_sections {
+        // Sections are aligned to 4 bytes, as is the fat header
+        let section_start = reader.position().next_multiple_of(4);
+        reader.set_position(section_start);
+
+        let kind: u8 = reader.read_le()?;
+        let fat = kind & SECTION_FAT_FORMAT != 0;
+        let data_size = if fat {
+            let size: [u8; 3] = reader.read_le()?;
+            u32::from_le_bytes([size[0], size[1], size[2], 0])
+        } else {
+            let size: u8 = reader.read_le()?;
+            let _reserved: u16 = reader.read_le()?;
+            size as u32
+        };
+
+        if kind & SECTION_EH_TABLE != 0 {
+            let clause_size = if fat { 24 } else { 12 };
+            for _ in 0..data_size.saturating_sub(4) / clause_size {
+                header
+                    .exception_clauses
+                    .push(ExceptionClause::read(&mut reader, fat)?);
+            }
+        }
+
+        reader.set_position(section_start + data_size as u64);
+        more_sections = kind & SECTION_MORE_SECTS != 0;
+    }
+
+    Ok((header, instructions))
+}
@@ -0,0 +1,71 @@
+//! Default values of fields, parameters and properties, as stored in the Constant table (ECMA-335 II.22.9)
+
+use crate::{Result, error::Error, image::CilImage, tables::HasConstant};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConstantValue {
+    Boolean(bool),
+    Char(u16),
+    I1(i8),
+    U1(u8),
+    I2(i16),
+    U2(u16),
+    I4(i32),
+    U4(u32),
+    I8(i64),
+    U8(u64),
+    R4(f32),
+    R8(f64),
+    String(String),
+    /// `null` for reference types
+    Null,
+}
+
+impl ConstantValue {
+    /// Decodes a constant blob, `kind` is the element type stored in the Constant row
+    pub fn parse(kind: u8, blob: &[u8]) -> Result<Self> {
+        fn bytes<const N: usize>(kind: u8, blob: &[u8]) -> Result<[u8; N]> {
+            blob.get(..N)
+                .and_then(|b| b.try_into().ok())
+                .ok_or(Error::InvalidConstant(kind))
+        }
+
+        Ok(match kind {
+            0x02 => Self::Boolean(bytes::<1>(kind, blob)?[0] != 0),
+            0x03 => Self::Char(u16::from_le_bytes(bytes(kind, blob)?)),
+            0x04 => Self::I1(i8::from_le_bytes(bytes(kind, blob)?)),
+            0x05 => Self::U1(u8::from_le_bytes(bytes(kind, blob)?)),
+            0x06 => Self::I2(i16::from_le_bytes(bytes(kind, blob)?)),
+            0x07 => Self::U2(u16::from_le_bytes(bytes(kind, blob)?)),
+            0x08 => Self::I4(i32::from_le_bytes(bytes(kind, blob)?)),
+            0x09 => Self::U4(u32::from_le_bytes(bytes(kind, blob)?)),
+            0x0A => Self::I8(i64::from_le_bytes(bytes(kind, blob)?)),
+            0x0B => Self::U8(u64::from_le_bytes(bytes(kind, blob)?)),
+            0x0C => Self::R4(f32::from_le_bytes(bytes(kind, blob)?)),
+            0x0D => Self::R8(f64::from_le_bytes(bytes(kind, blob)?)),
+            0x0E => {
+                if !blob.len().is_multiple_of(2) {
+                    return Err(Error::InvalidConstant(kind));
+                }
+                let chars = blob
+                    .chunks_exact(2)
+                    .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                    .collect::<Vec<_>>();
+                Self::String(String::from_utf16_lossy(&chars))
+            }
+            // ELEMENT_TYPE_CLASS, the value is always a 4 byte zero
+            0x12 => Self::Null,
+            _ => return Err(Error::InvalidConstant(kind)),
+        })
+    }
+}
+
+impl CilImage {
+    /// Default value of a field, parameter or property, if it has one
+    pub fn constant_value(&self, parent: HasConstant) -> Result<Option<ConstantValue>> {
+        let Some(constant) = self.constant_of(parent) else {
+            return Ok(None);
+        };
+        ConstantValue::parse(constant.kind, self.blob(constant.value_blob_index)?).map(Some)
+    }
+}
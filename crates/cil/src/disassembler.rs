@@ -0,0 +1,1125 @@
+//! ILAsm text for a whole image, in the style of ILDasm (ECMA-335 Partition II)
+//!
+//! Tokens are written as the types and members they refer to, custom attributes as their raw blobs
+//! and field initializers as `.data` declarations, so the output can be assembled again.
+
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashSet},
+};
+
+use crate::{
+    Result,
+    body::{ExceptionClause, ExceptionClauseKind},
+    constant::ConstantValue,
+    error::Error,
+    format::{TypeNameFormatter, TypeNameStyle, ilasm_name},
+    identity::{AssemblyVersion, PublicKeyOrToken},
+    image::CilImage,
+    meta::{
+        AssemblyRefHandle, EventHandle, FieldHandle, MemberRefHandle, MethodDefHandle,
+        MethodSpecHandle, PropertyHandle, StandAloneSigHandle, Token, TypeDefHandle,
+    },
+    opcodes::{Instruction, Operand, RawOpcode, TokenReference},
+    signature::{Element, MemberRefSignature},
+    tables::{
+        ClassSemantics, CodeType, CustomAttributeType, HasConstant, HasCustomAttribute,
+        HasSemantics, Layout, MemberAccess, MemberForwarded, MemberRefParent, MethodDefOrRef,
+        NativeStringFormat, TypeDefOrRef, TypeOrMethodDef, Unmanaged, Visibility, VtableLayout,
+    },
+};
+
+/// Writes the declarations of an image as ILAsm source
+pub struct Disassembler<'img> {
+    image: &'img CilImage,
+    names: TypeNameFormatter<'img>,
+    out: String,
+    indent: usize,
+    /// Initial data of fields with an RVA, written as `.data` after the classes
+    data: BTreeMap<u32, Cow<'img, [u8]>>,
+}
+
+/// A `.try` block, handler or filter of a method body, with the text that opens it
+struct Block {
+    start: u32,
+    end: u32,
+    header: String,
+}
+
+impl<'img> Disassembler<'img> {
+    pub fn new(image: &'img CilImage) -> Self {
+        Self {
+            image,
+            names: TypeNameFormatter::new(image, TypeNameStyle::ILAsm),
+            out: String::new(),
+            indent: 0,
+            data: BTreeMap::new(),
+        }
+    }
+
+    /// Disassembles the manifest, every type and the field data of the image
+    pub fn disassemble(mut self) -> Result<String> {
+        self.assembly_refs()?;
+        self.assembly()?;
+        self.module()?;
+
+        // Members of `<Module>` are global
+        if !self.image.type_defs.is_empty() {
+            self.members(TypeDefHandle(1))?;
+        }
+        for index in 2..=self.image.type_defs.len() as u32 {
+            let handle = TypeDefHandle(index);
+            if self.image.enclosing_type_of(handle).is_none() {
+                self.class(handle)?;
+            }
+        }
+
+        for (rva, data) in std::mem::take(&mut self.data) {
+            self.bytes(&format!(".data I_{rva:08X} = bytearray "), &data);
+        }
+
+        Ok(self.out)
+    }
+
+    fn line(&mut self, text: &str) {
+        for _ in 0..self.indent {
+            self.out.push_str("  ");
+        }
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    fn open(&mut self) {
+        self.line("{");
+        self.indent += 1;
+    }
+
+    fn close(&mut self) {
+        self.indent -= 1;
+        self.line("}");
+    }
+
+    /// Writes `prefix` followed by the bytes in parentheses, 16 to a line
+    fn bytes(&mut self, prefix: &str, bytes: &[u8]) {
+        if bytes.len() <= 16 {
+            self.line(&format!("{prefix}({})", hex(bytes)));
+            return;
+        }
+
+        self.line(&format!("{prefix}("));
+        self.indent += 2;
+        for chunk in bytes.chunks(16) {
+            self.line(&hex(chunk));
+        }
+        self.indent -= 2;
+        self.line(")");
+    }
+
+    fn assembly_refs(&mut self) -> Result<()> {
+        for index in 1..=self.image.assembly_refs.len() as u32 {
+            let identity = self.image.assembly_ref_identity(AssemblyRefHandle(index))?;
+            let retargetable = if identity.is_retargetable() {
+                "retargetable "
+            } else {
+                ""
+            };
+            self.line(&format!(
+                ".assembly extern {retargetable}{}",
+                ilasm_name(&identity.name)
+            ));
+            self.open();
+            match &identity.public_key {
+                PublicKeyOrToken::None => {}
+                PublicKeyOrToken::PublicKey(key) => self.bytes(".publickey = ", key),
+                PublicKeyOrToken::Token(token) => self.bytes(".publickeytoken = ", token),
+            }
+            if let Some(culture) = &identity.culture {
+                self.line(&format!(".culture {}", string_literal(culture)));
+            }
+            self.line(&format!(".ver {}", version(&identity.version)));
+            self.close();
+        }
+        Ok(())
+    }
+
+    fn assembly(&mut self) -> Result<()> {
+        let Some(identity) = self.image.assembly_identity()? else {
+            return Ok(());
+        };
+
+        self.line(&format!(".assembly {}", ilasm_name(&identity.name)));
+        self.open();
+        self.custom_attributes(HasCustomAttribute::Assembly(1))?;
+        if let PublicKeyOrToken::PublicKey(key) = &identity.public_key {
+            self.bytes(".publickey = ", key);
+        }
+        let hash_algorithm = self.image.assemblies[0].hash_algorithm as u32;
+        self.line(&format!(".hash algorithm {hash_algorithm:#010x}"));
+        if let Some(culture) = &identity.culture {
+            self.line(&format!(".culture {}", string_literal(culture)));
+        }
+        self.line(&format!(".ver {}", version(&identity.version)));
+        self.close();
+        Ok(())
+    }
+
+    fn module(&mut self) -> Result<()> {
+        for module_ref in &self.image.module_refs {
+            self.line(&format!(".module extern {}", ilasm_name(&module_ref.name)));
+        }
+
+        if let Some(module) = self.image.modules.first() {
+            self.line(&format!(".module {}", ilasm_name(&module.name)));
+            if let Some(mvid) = self.image.mvid() {
+                self.line(&format!("// MVID: {mvid}"));
+            }
+            self.custom_attributes(HasCustomAttribute::Module(1))?;
+        }
+        self.line(&format!(
+            ".corflags {:#010x}",
+            self.image.header.flags.bits()
+        ));
+        Ok(())
+    }
+
+    fn class(&mut self, handle: TypeDefHandle) -> Result<()> {
+        let image = self.image;
+        let type_def = &image.type_defs[handle.index()];
+        let flags = type_def.flags;
+
+        let mut header = String::from(".class ");
+        header.push_str(match flags.visibility() {
+            Visibility::NotPublic => "private ",
+            Visibility::Public => "public ",
+            Visibility::NestedPublic => "nested public ",
+            Visibility::NestedPrivate => "nested private ",
+            Visibility::NestedFamily => "nested family ",
+            Visibility::NestedAssembly => "nested assembly ",
+            Visibility::NestedFamilyAndAssembly => "nested famandassem ",
+            Visibility::NestedFamilyOrAssembly => "nested famorassem ",
+        });
+        if let ClassSemantics::Interface = flags.class_semantics() {
+            header.push_str("interface ");
+        }
+        if flags.is_abstract() {
+            header.push_str("abstract ");
+        }
+        header.push_str(match flags.layout() {
+            Layout::Auto => "auto ",
+            Layout::Sequential => "sequential ",
+            Layout::Explicit => "explicit ",
+        });
+        header.push_str(match flags.native_string_format() {
+            NativeStringFormat::Ansi => "ansi ",
+            NativeStringFormat::Unicode => "unicode ",
+            NativeStringFormat::Auto => "autochar ",
+            NativeStringFormat::Custom => "",
+        });
+        for (set, keyword) in [
+            (flags.is_sealed(), "sealed "),
+            (flags.is_serializable(), "serializable "),
+            (flags.is_import(), "import "),
+            (flags.is_special_name(), "specialname "),
+            (flags.is_rt_special_name(), "rtspecialname "),
+            (flags.is_before_field_init(), "beforefieldinit "),
+        ] {
+            if set {
+                header.push_str(keyword);
+            }
+        }
+
+        if image.enclosing_type_of(handle).is_none() && !type_def.type_namespace.is_empty() {
+            header.push_str(&ilasm_name(&type_def.type_namespace));
+            header.push('.');
+        }
+        header.push_str(&ilasm_name(&type_def.type_name));
+        header.push_str(&self.generic_params(TypeOrMethodDef::TypeDef(handle.0)));
+        self.line(&header);
+
+        self.indent += 3;
+        if let Ok(base) = TypeDefOrRef::try_from(type_def.extends as u32)
+            && type_def.extends != 0
+        {
+            self.line(&format!("extends {}", self.names.format_type(base)));
+        }
+        let interfaces = image
+            .interface_impls_of(handle)
+            .filter_map(|ii| TypeDefOrRef::try_from(ii.interface as u32).ok())
+            .map(|interface| self.names.format_type(interface))
+            .collect::<Vec<_>>();
+        if !interfaces.is_empty() {
+            self.line(&format!("implements {}", interfaces.join(",\n           ")));
+        }
+        self.indent -= 3;
+
+        self.open();
+        self.custom_attributes(HasCustomAttribute::TypeDef(handle.0))?;
+        if let Some(class_layout) = image.class_layout_of(handle) {
+            self.line(&format!(".pack {}", class_layout.packing_size));
+            self.line(&format!(".size {}", class_layout.class_size));
+        }
+        self.members(handle)?;
+        for nested in image.nested_types_of(handle).collect::<Vec<_>>() {
+            self.class(nested)?;
+        }
+        self.close();
+        Ok(())
+    }
+
+    fn members(&mut self, handle: TypeDefHandle) -> Result<()> {
+        for field in self.image.fields_of(handle) {
+            self.field(field)?;
+        }
+        for method in self.image.methods_of(handle) {
+            self.method(handle, method)?;
+        }
+        for event in self.image.events_of(handle) {
+            self.event(event)?;
+        }
+        for property in self.image.properties_of(handle) {
+            self.property(property)?;
+        }
+        Ok(())
+    }
+
+    /// Generic parameters of a type or method with their constraints, eg. `<class .ctor (IFoo) T>`
+    fn generic_params(&self, owner: TypeOrMethodDef) -> String {
+        let image = self.image;
+        let mut params = image
+            .generic_params
+            .iter()
+            .enumerate()
+            .filter(|(_, gp)| gp.owner as u32 == owner.encode())
+            .collect::<Vec<_>>();
+        if params.is_empty() {
+            return String::new();
+        }
+        params.sort_by_key(|(_, gp)| gp.number);
+
+        let params = params
+            .into_iter()
+            .map(|(row, gp)| {
+                let mut s = String::new();
+                match gp.flags & 0x0003 {
+                    1 => s.push('+'),
+                    2 => s.push('-'),
+                    _ => {}
+                }
+                for (mask, keyword) in [
+                    (0x0004, "class "),
+                    (0x0008, "valuetype "),
+                    (0x0010, ".ctor "),
+                ] {
+                    if gp.flags & mask != 0 {
+                        s.push_str(keyword);
+                    }
+                }
+                let constraints = image
+                    .generic_param_constraints
+                    .iter()
+                    .filter(|c| c.owner as usize == row + 1)
+                    .filter_map(|c| TypeDefOrRef::try_from(c.constraint as u32).ok())
+                    .map(|c| self.names.format_type(c))
+                    .collect::<Vec<_>>();
+                if !constraints.is_empty() {
+                    s.push_str(&format!("({}) ", constraints.join(", ")));
+                }
+                s.push_str(&ilasm_name(&gp.name));
+                s
+            })
+            .collect::<Vec<_>>();
+        format!("<{}>", params.join(", "))
+    }
+
+    fn custom_attributes(&mut self, parent: HasCustomAttribute) -> Result<()> {
+        let image = self.image;
+        for custom_attribute in image.custom_attributes_of(parent) {
+            let constructor =
+                match CustomAttributeType::try_from(custom_attribute.type_index as u32) {
+                    Ok(CustomAttributeType::MethodDef(index)) => MethodDefOrRef::MethodDef(index),
+                    Ok(CustomAttributeType::MemberRef(index)) => MethodDefOrRef::MemberRef(index),
+                    Err(_) => {
+                        return Err(Error::InvalidBlobIndex(custom_attribute.type_index as u32));
+                    }
+                };
+            let constructor = self.method_ref(constructor, None)?;
+            let value = image.blob(custom_attribute.value_blob_index)?;
+            self.bytes(&format!(".custom {constructor} = "), value);
+        }
+        Ok(())
+    }
+
+    fn field(&mut self, handle: FieldHandle) -> Result<()> {
+        let image = self.image;
+        let field = &image.fields[handle.index()];
+        let flags = field.flags;
+
+        let mut s = String::from(".field ");
+        if let Some(offset) = image.field_offset(handle) {
+            s.push_str(&format!("[{offset}] "));
+        }
+        s.push_str(access(flags.access()));
+        for (set, keyword) in [
+            (flags.is_static(), "static "),
+            (flags.is_init_only(), "initonly "),
+            (flags.is_literal(), "literal "),
+            (flags.is_not_serialized(), "notserialized "),
+            (flags.is_special_name(), "specialname "),
+            (flags.is_runtime_special_name(), "rtspecialname "),
+        ] {
+            if set {
+                s.push_str(keyword);
+            }
+        }
+        s.push_str(&self.names.format(&image.field_type(handle)?));
+        s.push(' ');
+        s.push_str(&ilasm_name(&field.name));
+
+        if let Some(rva) = image.field_rva(handle) {
+            if let Some(data) = image.initial_data(handle)? {
+                self.data.insert(rva, data);
+            }
+            s.push_str(&format!(" at I_{rva:08X}"));
+        }
+        if let Some(value) = image.constant_value(HasConstant::Field(handle.0))? {
+            s.push_str(&format!(" = {}", constant(&value)));
+        }
+        self.line(&s);
+        self.custom_attributes(HasCustomAttribute::Field(handle.0))
+    }
+
+    fn method(&mut self, owner: TypeDefHandle, handle: MethodDefHandle) -> Result<()> {
+        let image = self.image;
+        let (method, header, instructions) = &image.method_defs[handle.index()];
+        let flags = method.flags;
+        let signature = image.method_signature(handle)?;
+        let parameters = image.method_parameters(handle)?;
+
+        let mut s = String::from(".method ");
+        s.push_str(access(flags.access()));
+        for (set, keyword) in [
+            (flags.hide_by_sig(), "hidebysig "),
+            (
+                matches!(flags.vtable_layout(), VtableLayout::NewSlot),
+                "newslot ",
+            ),
+            (flags.is_special_name(), "specialname "),
+            (flags.is_runtime_special_name(), "rtspecialname "),
+            (flags.is_abstract(), "abstract "),
+            (flags.is_virtual(), "virtual "),
+            (flags.is_final(), "final "),
+            (flags.is_strict(), "strict "),
+            (flags.is_static(), "static "),
+        ] {
+            if set {
+                s.push_str(keyword);
+            }
+        }
+        if flags.is_pinvoke_impl() {
+            s.push_str(&self.pinvoke_impl(handle, &method.name));
+        }
+        if flags.is_unmanaged_export() {
+            s.push_str("unmanagedexp ");
+        }
+
+        s.push_str(&self.names.ilasm_calling_convention(&signature));
+        s.push_str(&self.names.format(&signature.return_type));
+        s.push(' ');
+        s.push_str(&ilasm_name(&method.name));
+        s.push_str(&self.generic_params(TypeOrMethodDef::MethodDef(handle.0)));
+
+        let mut params = Vec::with_capacity(parameters.parameters.len());
+        for (i, parameter) in parameters.parameters.iter().enumerate() {
+            let mut p = String::new();
+            if signature.sentinel == Some(i) {
+                params.push("...".to_string());
+            }
+            for (set, keyword) in [
+                (parameter.is_in(), "[in] "),
+                (parameter.is_out(), "[out] "),
+                (parameter.is_optional(), "[opt] "),
+            ] {
+                if set {
+                    p.push_str(keyword);
+                }
+            }
+            p.push_str(&self.names.format(&parameter.param_type));
+            if let Some(name) = &parameter.name {
+                p.push(' ');
+                p.push_str(&ilasm_name(name));
+            }
+            params.push(p);
+        }
+        s.push_str(&format!("({})", params.join(", ")));
+
+        let impl_flags = method.impl_flags;
+        s.push_str(match impl_flags.code_type() {
+            CodeType::IL => " cil",
+            CodeType::Native => " native",
+            CodeType::OptIL => " optil",
+            CodeType::Runtime => " runtime",
+        });
+        s.push_str(match impl_flags.unmanaged() {
+            Unmanaged::Managed => " managed",
+            Unmanaged::Unmanaged => " unmanaged",
+        });
+        for (set, keyword) in [
+            (impl_flags.is_forward_ref(), " forwardref"),
+            (impl_flags.preserve_sig(), " preservesig"),
+            (impl_flags.is_internal_call(), " internalcall"),
+            (impl_flags.is_synchronized(), " synchronized"),
+            (impl_flags.no_inlining(), " noinlining"),
+            (impl_flags.no_optimization(), " nooptimization"),
+            (impl_flags.aggressive_inlining(), " aggressiveinlining"),
+        ] {
+            if set {
+                s.push_str(keyword);
+            }
+        }
+        self.line(&s);
+
+        self.open();
+        self.custom_attributes(HasCustomAttribute::MethodDef(handle.0))?;
+        for parameter in std::iter::once(&parameters.return_value).chain(&parameters.parameters) {
+            let Some(param) = parameter.handle else {
+                continue;
+            };
+            let has_attributes = image
+                .custom_attributes_of(HasCustomAttribute::Param(param.0))
+                .next()
+                .is_some();
+            if parameter.default_value.is_none() && !has_attributes {
+                continue;
+            }
+            match &parameter.default_value {
+                Some(value) => self.line(&format!(
+                    ".param [{}] = {}",
+                    parameter.sequence,
+                    constant(value)
+                )),
+                None => self.line(&format!(".param [{}]", parameter.sequence)),
+            }
+            self.custom_attributes(HasCustomAttribute::Param(param.0))?;
+        }
+
+        for method_impl in image.method_impls_of(owner) {
+            if MethodDefOrRef::try_from(method_impl.method_body as u32)
+                == Ok(MethodDefOrRef::MethodDef(handle.0))
+                && let Ok(declaration) =
+                    MethodDefOrRef::try_from(method_impl.method_declaration as u32)
+            {
+                let declaration = self.method_ref(declaration, None)?;
+                self.line(&format!(".override method {declaration}"));
+            }
+        }
+
+        if image.header.entry_point_token == handle.token().0 {
+            self.line(".entrypoint");
+        }
+        if method.rva != 0 && !flags.is_abstract() {
+            self.line(&format!(".maxstack {}", header.max_stack));
+            if let Some(token) = header.local_var_sig_token {
+                let signature = StandAloneSigHandle::try_from(token)
+                    .map_err(Error::InvalidToken)
+                    .and_then(|handle| image.local_var_signature(handle))?;
+                let locals = signature
+                    .locals
+                    .iter()
+                    .enumerate()
+                    .map(|(i, local)| format!("{} V_{i}", self.names.format(local)))
+                    .collect::<Vec<_>>();
+                let init = if header.init_locals { "init " } else { "" };
+                self.line(&format!(".locals {init}({})", locals.join(", ")));
+            }
+            self.instructions(instructions, &header.exception_clauses)?;
+        }
+        self.close();
+        Ok(())
+    }
+
+    /// `pinvokeimpl(...)` clause of a P/Invoke method, see ECMA-335 II.15.5.2
+    fn pinvoke_impl(&self, handle: MethodDefHandle, name: &str) -> String {
+        let Some(impl_map) = self.image.impl_map_of(MemberForwarded::MethodDef(handle.0)) else {
+            return "pinvokeimpl() ".to_string();
+        };
+
+        let module = self
+            .image
+            .module_refs
+            .get((impl_map.import_scope as usize).wrapping_sub(1))
+            .map_or("", |m| m.name.as_str());
+        let mut s = format!("pinvokeimpl({}", string_literal(module));
+        if impl_map.import_name != name {
+            s.push_str(&format!(" as {}", string_literal(&impl_map.import_name)));
+        }
+
+        let flags = impl_map.mapping_flags;
+        let mut keywords = Vec::new();
+        if flags & 0x0001 != 0 {
+            keywords.push("nomangle");
+        }
+        keywords.extend(match flags & 0x0006 {
+            0x0002 => Some("ansi"),
+            0x0004 => Some("unicode"),
+            0x0006 => Some("autochar"),
+            _ => None,
+        });
+        if flags & 0x0040 != 0 {
+            keywords.push("lasterr");
+        }
+        keywords.extend(match flags & 0x0700 {
+            0x0100 => Some("winapi"),
+            0x0200 => Some("cdecl"),
+            0x0300 => Some("stdcall"),
+            0x0400 => Some("thiscall"),
+            0x0500 => Some("fastcall"),
+            _ => None,
+        });
+        keywords.extend(match flags & 0x0030 {
+            0x0010 => Some("bestfit:on"),
+            0x0020 => Some("bestfit:off"),
+            _ => None,
+        });
+        keywords.extend(match flags & 0x3000 {
+            0x1000 => Some("charmaperror:on"),
+            0x2000 => Some("charmaperror:off"),
+            _ => None,
+        });
+        for keyword in keywords {
+            s.push(' ');
+            s.push_str(keyword);
+        }
+        s.push_str(") ");
+        s
+    }
+
+    fn instructions(
+        &mut self,
+        instructions: &[Instruction],
+        clauses: &[ExceptionClause],
+    ) -> Result<()> {
+        let code_end = instructions.last().map_or(0, Instruction::next_offset);
+        let blocks = self.blocks(instructions, clauses, code_end)?;
+
+        let mut open: Vec<u32> = Vec::new();
+        let mut next_block = 0;
+        for instruction in instructions {
+            self.enter_blocks(instruction.offset, &blocks, &mut open, &mut next_block);
+            let text = self.instruction(instruction)?;
+            self.line(&text);
+        }
+        self.enter_blocks(code_end, &blocks, &mut open, &mut next_block);
+
+        if blocks.is_empty() && !clauses.is_empty() {
+            // Clauses that don't nest are written with labels instead
+            if clauses
+                .iter()
+                .any(|c| c.try_end() == code_end || c.handler_end() == code_end)
+            {
+                self.line(&format!("IL_{code_end:04x}:"));
+            }
+            for clause in clauses {
+                let handler = match clause.kind {
+                    ExceptionClauseKind::Catch(token) => format!("catch {}", self.token(token)?),
+                    ExceptionClauseKind::Filter(offset) => format!("filter IL_{offset:04x}"),
+                    ExceptionClauseKind::Finally => "finally".to_string(),
+                    ExceptionClauseKind::Fault => "fault".to_string(),
+                };
+                self.line(&format!(
+                    ".try IL_{:04x} to IL_{:04x} {handler} handler IL_{:04x} to IL_{:04x}",
+                    clause.try_offset,
+                    clause.try_end(),
+                    clause.handler_offset,
+                    clause.handler_end()
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Closes the blocks that end at `offset` and opens the ones that start there
+    fn enter_blocks(
+        &mut self,
+        offset: u32,
+        blocks: &[Block],
+        open: &mut Vec<u32>,
+        next_block: &mut usize,
+    ) {
+        while open.last().is_some_and(|&end| end <= offset) {
+            open.pop();
+            self.close();
+        }
+        while let Some(block) = blocks.get(*next_block)
+            && block.start == offset
+        {
+            if !block.header.is_empty() {
+                self.line(&block.header);
+            }
+            self.open();
+            open.push(block.end);
+            *next_block += 1;
+        }
+    }
+
+    /// Splits the exception clauses into `.try`, handler and filter blocks, sorted so that outer
+    /// blocks open first. Returns no blocks if the clauses can't be written as nested blocks, ie.
+    /// if a handler doesn't directly follow its `.try` block or two blocks partially overlap.
+    fn blocks(
+        &self,
+        instructions: &[Instruction],
+        clauses: &[ExceptionClause],
+        code_end: u32,
+    ) -> Result<Vec<Block>> {
+        // Clauses protecting the same range share a single `.try` block
+        let mut groups: Vec<Vec<&ExceptionClause>> = Vec::new();
+        for clause in clauses {
+            match groups.iter_mut().find(|g| {
+                g[0].try_offset == clause.try_offset && g[0].try_length == clause.try_length
+            }) {
+                Some(group) => group.push(clause),
+                None => groups.push(vec![clause]),
+            }
+        }
+
+        let mut blocks = Vec::new();
+        for group in &mut groups {
+            group.sort_by_key(|c| match c.kind {
+                ExceptionClauseKind::Filter(offset) => offset,
+                _ => c.handler_offset,
+            });
+
+            let try_block = Block {
+                start: group[0].try_offset,
+                end: group[0].try_end(),
+                header: ".try".to_string(),
+            };
+            let mut cursor = try_block.end;
+            blocks.push(try_block);
+            for clause in group.iter() {
+                let header = match clause.kind {
+                    ExceptionClauseKind::Catch(token) => format!("catch {}", self.token(token)?),
+                    ExceptionClauseKind::Filter(offset) => {
+                        if offset != cursor {
+                            return Ok(Vec::new());
+                        }
+                        blocks.push(Block {
+                            start: offset,
+                            end: clause.handler_offset,
+                            header: "filter".to_string(),
+                        });
+                        cursor = clause.handler_offset;
+                        String::new()
+                    }
+                    ExceptionClauseKind::Finally => "finally".to_string(),
+                    ExceptionClauseKind::Fault => "fault".to_string(),
+                };
+                if clause.handler_offset != cursor {
+                    return Ok(Vec::new());
+                }
+                blocks.push(Block {
+                    start: clause.handler_offset,
+                    end: clause.handler_end(),
+                    header,
+                });
+                cursor = clause.handler_end();
+            }
+        }
+
+        let boundaries = instructions
+            .iter()
+            .map(|i| i.offset)
+            .chain(std::iter::once(code_end))
+            .collect::<HashSet<_>>();
+        for (i, a) in blocks.iter().enumerate() {
+            if a.start >= a.end || !boundaries.contains(&a.start) || !boundaries.contains(&a.end) {
+                return Ok(Vec::new());
+            }
+            for b in &blocks[i + 1..] {
+                let disjoint = a.end <= b.start || b.end <= a.start;
+                let a_in_b = b.start <= a.start && a.end <= b.end;
+                let b_in_a = a.start <= b.start && b.end <= a.end;
+                let same = a.start == b.start && a.end == b.end;
+                if same || !(disjoint || a_in_b || b_in_a) {
+                    return Ok(Vec::new());
+                }
+            }
+        }
+
+        blocks.sort_by_key(|b| (b.start, std::cmp::Reverse(b.end)));
+        Ok(blocks)
+    }
+
+    fn instruction(&self, instruction: &Instruction) -> Result<String> {
+        let mut s = format!("IL_{:04x}:  ", instruction.offset);
+        for prefix in instruction.prefixes.opcodes() {
+            s.push_str(prefix.asm_name());
+            match prefix {
+                RawOpcode::Constrained { this_type } => {
+                    s.push_str(&format!(" {}", self.token(this_type)?))
+                }
+                RawOpcode::Unaligned { alignment } => s.push_str(&format!(" {alignment}")),
+                RawOpcode::No { checks } => s.push_str(&format!(" {:#x}", checks.bits())),
+                _ => {}
+            }
+            s.push(' ');
+        }
+        s.push_str(instruction.opcode.asm_name());
+
+        let short_form = instruction.opcode.size() == 1;
+        match &instruction.operand {
+            Operand::None => {}
+            Operand::Target(target) => s.push_str(&format!(" IL_{target:04x}")),
+            Operand::Switch(targets) => {
+                let targets = targets
+                    .iter()
+                    .map(|t| format!("IL_{t:04x}"))
+                    .collect::<Vec<_>>();
+                s.push_str(&format!(" ({})", targets.join(", ")));
+            }
+            Operand::Local(_) | Operand::Argument(_) | Operand::I4(_) if short_form => {}
+            Operand::Local(index) => s.push_str(&format!(" V_{index}")),
+            Operand::Argument(index) => s.push_str(&format!(" {index}")),
+            Operand::I4(value) => s.push_str(&format!(" {value}")),
+            Operand::I8(value) => s.push_str(&format!(" {value}")),
+            Operand::R4(value) if value.is_finite() => s.push_str(&format!(" {value:?}")),
+            Operand::R4(value) => s.push_str(&format!(" ({})", hex(&value.to_le_bytes()))),
+            Operand::R8(value) if value.is_finite() => s.push_str(&format!(" {value:?}")),
+            Operand::R8(value) => s.push_str(&format!(" ({})", hex(&value.to_le_bytes()))),
+            Operand::Token(token) => {
+                s.push(' ');
+                if let RawOpcode::LdToken { .. } = instruction.opcode {
+                    match self.image.resolve_token(*token)? {
+                        TokenReference::Method(_) | TokenReference::MethodSpec(_) => {
+                            s.push_str("method ")
+                        }
+                        TokenReference::Field(_) | TokenReference::FieldRef(_) => {
+                            s.push_str("field ")
+                        }
+                        _ => {}
+                    }
+                }
+                s.push_str(&self.token(*token)?);
+            }
+        }
+        Ok(s)
+    }
+
+    /// The type, member, signature or string a token refers to, as written in an operand
+    fn token(&self, token: Token) -> Result<String> {
+        Ok(match self.image.resolve_token(token)? {
+            TokenReference::Type(ty) => self.names.format_type(ty),
+            TokenReference::Method(method) => self.method_ref(method, None)?,
+            TokenReference::MethodSpec(handle) => self.method_spec_ref(handle)?,
+            TokenReference::Field(handle) => self.field_ref(handle)?,
+            TokenReference::FieldRef(handle) => self.field_member_ref(handle)?,
+            TokenReference::Signature(handle) => {
+                let signature = self.image.call_site_signature(handle)?;
+                self.names.format_method_signature("", &signature)
+            }
+            TokenReference::String(string) => string_literal(&string.value),
+        })
+    }
+
+    /// Type a member reference belongs to, `None` for global members of this module
+    fn member_ref_parent(&self, class_index: u16) -> Result<Option<String>> {
+        let image = self.image;
+        Ok(match MemberRefParent::try_from(class_index as u32) {
+            Ok(MemberRefParent::TypeDef(1)) => None,
+            Ok(MemberRefParent::TypeDef(index)) => {
+                Some(self.names.format_type(TypeDefOrRef::TypeDef(index)))
+            }
+            Ok(MemberRefParent::TypeRef(index)) => {
+                Some(self.names.format_type(TypeDefOrRef::TypeRef(index)))
+            }
+            Ok(MemberRefParent::TypeSpec(index)) => {
+                Some(self.names.format_type(TypeDefOrRef::TypeSpec(index)))
+            }
+            Ok(MemberRefParent::ModuleRef(index)) => image
+                .module_refs
+                .get((index as usize).wrapping_sub(1))
+                .map(|m| format!("[.module {}]", ilasm_name(&m.name))),
+            // Vararg call sites reference the method definition they call
+            Ok(MemberRefParent::MethodDef(index)) => image
+                .declaring_type_of(MethodDefHandle(index))
+                .filter(|t| t.0 != 1)
+                .map(|t| self.names.format_type(TypeDefOrRef::TypeDef(t.0))),
+            Err(_) => return Err(Error::InvalidBlobIndex(class_index as u32)),
+        })
+    }
+
+    /// A method reference such as `instance void [System.Runtime]System.Object::.ctor()`, with
+    /// `generic_args` given for generic method instantiations
+    fn method_ref(
+        &self,
+        method: MethodDefOrRef,
+        generic_args: Option<&[Element]>,
+    ) -> Result<String> {
+        let image = self.image;
+        let (parent, name, signature) = match method {
+            MethodDefOrRef::MethodDef(index) => {
+                let handle = MethodDefHandle(index);
+                let (method, _, _) = image
+                    .method_defs
+                    .get(handle.index())
+                    .ok_or(Error::InvalidToken(handle.token()))?;
+                let parent = image
+                    .declaring_type_of(handle)
+                    .filter(|t| t.0 != 1)
+                    .map(|t| self.names.format_type(TypeDefOrRef::TypeDef(t.0)));
+                (parent, &method.name, image.method_signature(handle)?)
+            }
+            MethodDefOrRef::MemberRef(index) => {
+                let handle = MemberRefHandle(index);
+                let member_ref = image
+                    .member_refs
+                    .get(handle.index())
+                    .ok_or(Error::InvalidToken(handle.token()))?;
+                let MemberRefSignature::Method(signature) = image.member_ref_signature(handle)?
+                else {
+                    return Err(Error::InvalidToken(handle.token()));
+                };
+                (
+                    self.member_ref_parent(member_ref.class_index)?,
+                    &member_ref.name,
+                    signature,
+                )
+            }
+        };
+
+        let generics = match generic_args {
+            Some(args) => {
+                let args = args
+                    .iter()
+                    .map(|a| self.names.format(a))
+                    .collect::<Vec<_>>();
+                format!("<{}>", args.join(", "))
+            }
+            None if signature.generic_param_count > 0 => {
+                format!("<[{}]>", signature.generic_param_count)
+            }
+            None => String::new(),
+        };
+        let name = match parent {
+            Some(parent) => format!("{parent}::{}{generics}", ilasm_name(name)),
+            None => format!("{}{generics}", ilasm_name(name)),
+        };
+        Ok(self.names.format_method_signature(&name, &signature))
+    }
+
+    fn method_spec_ref(&self, handle: MethodSpecHandle) -> Result<String> {
+        let method_spec = self
+            .image
+            .method_specs
+            .get(handle.index())
+            .ok_or(Error::InvalidToken(handle.token()))?;
+        let method = MethodDefOrRef::try_from(method_spec.method as u32)
+            .map_err(|_| Error::InvalidToken(handle.token()))?;
+        let generic_args = self.image.method_spec_signature(handle)?.generic_args;
+        self.method_ref(method, Some(&generic_args))
+    }
+
+    /// A field definition reference such as `int32 Namespace.Type::field`
+    fn field_ref(&self, handle: FieldHandle) -> Result<String> {
+        let image = self.image;
+        let field = image
+            .fields
+            .get(handle.index())
+            .ok_or(Error::InvalidToken(handle.token()))?;
+        let field_type = self.names.format(&image.field_type(handle)?);
+        Ok(
+            match image.declaring_type_of_field(handle).filter(|t| t.0 != 1) {
+                Some(parent) => format!(
+                    "{field_type} {}::{}",
+                    self.names.format_type(TypeDefOrRef::TypeDef(parent.0)),
+                    ilasm_name(&field.name)
+                ),
+                None => format!("{field_type} {}", ilasm_name(&field.name)),
+            },
+        )
+    }
+
+    fn field_member_ref(&self, handle: MemberRefHandle) -> Result<String> {
+        let image = self.image;
+        let member_ref = image
+            .member_refs
+            .get(handle.index())
+            .ok_or(Error::InvalidToken(handle.token()))?;
+        let MemberRefSignature::Field(signature) = image.member_ref_signature(handle)? else {
+            return Err(Error::InvalidToken(handle.token()));
+        };
+        let field_type = self.names.format(&signature.field_type);
+        Ok(match self.member_ref_parent(member_ref.class_index)? {
+            Some(parent) => format!("{field_type} {parent}::{}", ilasm_name(&member_ref.name)),
+            None => format!("{field_type} {}", ilasm_name(&member_ref.name)),
+        })
+    }
+
+    fn property(&mut self, handle: PropertyHandle) -> Result<()> {
+        let image = self.image;
+        let property = &image.properties[handle.index()];
+        let signature = image.property_signature(handle)?;
+
+        let mut s = String::from(".property ");
+        if property.flags & 0x0200 != 0 {
+            s.push_str("specialname ");
+        }
+        if property.flags & 0x0400 != 0 {
+            s.push_str("rtspecialname ");
+        }
+        if signature.has_this {
+            s.push_str("instance ");
+        }
+        let parameters = signature
+            .parameters
+            .iter()
+            .map(|p| self.names.format(p))
+            .collect::<Vec<_>>();
+        s.push_str(&format!(
+            "{} {}({})",
+            self.names.format(&signature.property_type),
+            ilasm_name(&property.name),
+            parameters.join(", ")
+        ));
+        if let Some(value) = image.constant_value(HasConstant::Property(handle.0))? {
+            s.push_str(&format!(" = {}", constant(&value)));
+        }
+        self.line(&s);
+
+        self.open();
+        self.custom_attributes(HasCustomAttribute::Property(handle.0))?;
+        self.accessors(HasSemantics::Property(handle.0))?;
+        self.close();
+        Ok(())
+    }
+
+    fn event(&mut self, handle: EventHandle) -> Result<()> {
+        let image = self.image;
+        let event = &image.events[handle.index()];
+
+        let mut s = String::from(".event ");
+        if event.flags & 0x0200 != 0 {
+            s.push_str("specialname ");
+        }
+        if event.flags & 0x0400 != 0 {
+            s.push_str("rtspecialname ");
+        }
+        if let Ok(event_type) = TypeDefOrRef::try_from(event.event_type as u32)
+            && event.event_type != 0
+        {
+            s.push_str(&self.names.format_type(event_type));
+            s.push(' ');
+        }
+        s.push_str(&ilasm_name(&event.name));
+        self.line(&s);
+
+        self.open();
+        self.custom_attributes(HasCustomAttribute::Event(handle.0))?;
+        self.accessors(HasSemantics::Event(handle.0))?;
+        self.close();
+        Ok(())
+    }
+
+    /// `.get`, `.set`, `.addon` and other methods of a property or event
+    fn accessors(&mut self, association: HasSemantics) -> Result<()> {
+        for semantics in self.image.method_semantics_of(association) {
+            let directive = match semantics.semantics {
+                0x0001 => ".set",
+                0x0002 => ".get",
+                0x0008 => ".addon",
+                0x0010 => ".removeon",
+                0x0020 => ".fire",
+                _ => ".other",
+            };
+            let method =
+                self.method_ref(MethodDefOrRef::MethodDef(semantics.method as u32), None)?;
+            self.line(&format!("{directive} {method}"));
+        }
+        Ok(())
+    }
+}
+
+impl CilImage {
+    /// Disassembles the image to ILAsm source, see [`Disassembler`]
+    pub fn disassemble(&self) -> Result<String> {
+        Disassembler::new(self).disassemble()
+    }
+}
+
+fn access(access: MemberAccess) -> &'static str {
+    match access {
+        MemberAccess::CompilerControlled => "privatescope ",
+        MemberAccess::Private => "private ",
+        MemberAccess::FamilyAndAssembly => "famandassem ",
+        MemberAccess::Assembly => "assembly ",
+        MemberAccess::Family => "family ",
+        MemberAccess::FamilyOrAssembly => "famorassem ",
+        MemberAccess::Public => "public ",
+    }
+}
+
+fn version(version: &AssemblyVersion) -> String {
+    format!(
+        "{}:{}:{}:{}",
+        version.major, version.minor, version.build, version.revision
+    )
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{b:02X}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// A quoted string, or the UTF-16 bytes of strings with characters that can't be written as is
+fn string_literal(value: &str) -> String {
+    if !value
+        .chars()
+        .all(|c| matches!(c, ' '..='~' | '\t' | '\n' | '\r'))
+    {
+        let bytes = value
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect::<Vec<_>>();
+        return format!("bytearray ({})", hex(&bytes));
+    }
+
+    let mut s = String::with_capacity(value.len() + 2);
+    s.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => s.push_str("\\\""),
+            '\\' => s.push_str("\\\\"),
+            '\t' => s.push_str("\\t"),
+            '\n' => s.push_str("\\n"),
+            '\r' => s.push_str("\\r"),
+            _ => s.push(c),
+        }
+    }
+    s.push('"');
+    s
+}
+
+/// Default value of a field, parameter or property, eg. `int32(5)`. Floats that aren't finite are
+/// written as their bit pattern.
+fn constant(value: &ConstantValue) -> String {
+    match value {
+        ConstantValue::Boolean(v) => format!("bool({v})"),
+        ConstantValue::Char(v) => format!("char({v:#06x})"),
+        ConstantValue::I1(v) => format!("int8({v})"),
+        ConstantValue::U1(v) => format!("uint8({v})"),
+        ConstantValue::I2(v) => format!("int16({v})"),
+        ConstantValue::U2(v) => format!("uint16({v})"),
+        ConstantValue::I4(v) => format!("int32({v})"),
+        ConstantValue::U4(v) => format!("uint32({v})"),
+        ConstantValue::I8(v) => format!("int64({v})"),
+        ConstantValue::U8(v) => format!("uint64({v})"),
+        ConstantValue::R4(v) if v.is_finite() => format!("float32({v:?})"),
+        ConstantValue::R4(v) => format!("float32({:#010x})", v.to_bits()),
+        ConstantValue::R8(v) if v.is_finite() => format!("float64({v:?})"),
+        ConstantValue::R8(v) => format!("float64({:#018x})", v.to_bits()),
+        ConstantValue::String(v) => string_literal(v),
+        ConstantValue::Null => "nullref".to_string(),
+    }
+}
@@ -0,0 +1,294 @@
+//! Dominator and post-dominator trees and dominance frontiers of a [`ControlFlowGraph`]
+//!
+//! The trees are computed with the iterative algorithm of Cooper, Harvey and Kennedy, "A Simple,
+//! Fast Dominance Algorithm". Every edge counts, including exceptional ones, so handlers are
+//! dominated by the entry of the region they protect.
+
+use crate::flow::{BlockId, ControlFlowGraph};
+
+/// Adjacency lists of the graph a tree is computed over
+struct Graph {
+    successors: Vec<Vec<usize>>,
+    predecessors: Vec<Vec<usize>>,
+    root: usize,
+}
+
+impl Graph {
+    fn forward(cfg: &ControlFlowGraph) -> Self {
+        Self {
+            successors: cfg
+                .blocks
+                .iter()
+                .map(|b| b.successors.iter().map(|e| e.target.0).collect())
+                .collect(),
+            predecessors: cfg
+                .blocks
+                .iter()
+                .map(|b| b.predecessors.iter().map(|e| e.source.0).collect())
+                .collect(),
+            root: 0,
+        }
+    }
+
+    /// The reversed graph, rooted at a virtual exit that follows every block without successors
+    fn reverse(cfg: &ControlFlowGraph) -> Self {
+        let exit = cfg.len();
+        let mut graph = Self {
+            successors: vec![Vec::new(); exit + 1],
+            predecessors: vec![Vec::new(); exit + 1],
+            root: exit,
+        };
+        for (b, block) in cfg.blocks.iter().enumerate() {
+            for edge in &block.successors {
+                graph.successors[edge.target.0].push(b);
+                graph.predecessors[b].push(edge.target.0);
+            }
+            if block.successors.is_empty() {
+                graph.successors[exit].push(b);
+                graph.predecessors[b].push(exit);
+            }
+        }
+        graph
+    }
+
+    fn len(&self) -> usize {
+        self.successors.len()
+    }
+
+    /// Nodes reachable from the root in postorder
+    fn postorder(&self) -> Vec<usize> {
+        let mut order = Vec::with_capacity(self.len());
+        if self.len() == 0 {
+            return order;
+        }
+
+        let mut visited = vec![false; self.len()];
+        visited[self.root] = true;
+        let mut stack = vec![(self.root, 0)];
+        while let Some(&(node, next)) = stack.last() {
+            match self.successors[node].get(next) {
+                Some(&successor) => {
+                    stack.last_mut().unwrap().1 += 1;
+                    if !std::mem::replace(&mut visited[successor], true) {
+                        stack.push((successor, 0));
+                    }
+                }
+                None => {
+                    order.push(node);
+                    stack.pop();
+                }
+            }
+        }
+        order
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DominatorTree {
+    root: BlockId,
+    immediate_dominators: Vec<Option<BlockId>>,
+    children: Vec<Vec<BlockId>>,
+    /// Preorder and postorder number of each node in the tree, for constant time dominance
+    /// queries. `None` for unreachable nodes.
+    intervals: Vec<Option<(u32, u32)>>,
+    /// Whether this is a post-dominator tree
+    post: bool,
+}
+
+impl DominatorTree {
+    /// Dominators of the blocks reachable from the entry block
+    pub fn dominators(cfg: &ControlFlowGraph) -> Self {
+        Self::new(&Graph::forward(cfg), false)
+    }
+
+    /// Post-dominators, rooted at a virtual exit block `BlockId(cfg.len())` that follows every
+    /// block without successors. Blocks that can't reach an exit, such as those of an infinite
+    /// loop, have no post-dominator.
+    pub fn post_dominators(cfg: &ControlFlowGraph) -> Self {
+        Self::new(&Graph::reverse(cfg), true)
+    }
+
+    fn new(graph: &Graph, post: bool) -> Self {
+        let postorder = graph.postorder();
+        let mut number = vec![usize::MAX; graph.len()];
+        for (i, &node) in postorder.iter().enumerate() {
+            number[node] = i;
+        }
+
+        let mut idom: Vec<Option<usize>> = vec![None; graph.len()];
+        if let Some(root) = idom.get_mut(graph.root) {
+            *root = Some(graph.root);
+        }
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &node in postorder.iter().rev().skip(1) {
+                let mut new_idom = None;
+                for &predecessor in &graph.predecessors[node] {
+                    if idom[predecessor].is_none() {
+                        continue;
+                    }
+                    new_idom = Some(match new_idom {
+                        None => predecessor,
+                        Some(other) => intersect(&idom, &number, predecessor, other),
+                    });
+                }
+                if new_idom != idom[node] {
+                    idom[node] = new_idom;
+                    changed = true;
+                }
+            }
+        }
+
+        let mut tree = Self {
+            root: BlockId(graph.root),
+            immediate_dominators: vec![None; graph.len()],
+            children: vec![Vec::new(); graph.len()],
+            intervals: vec![None; graph.len()],
+            post,
+        };
+        for (node, dominator) in idom.into_iter().enumerate() {
+            if let Some(dominator) = dominator
+                && dominator != node
+            {
+                tree.immediate_dominators[node] = Some(BlockId(dominator));
+                tree.children[dominator].push(BlockId(node));
+            }
+        }
+        tree.number();
+        tree
+    }
+
+    fn number(&mut self) {
+        if self.children.is_empty() {
+            return;
+        }
+        let mut counter = 0;
+        let mut stack = vec![(self.root, 0)];
+        self.intervals[self.root.0] = Some((0, 0));
+        while let Some(&(node, next)) = stack.last() {
+            match self.children[node.0].get(next) {
+                Some(&child) => {
+                    stack.last_mut().unwrap().1 += 1;
+                    counter += 1;
+                    self.intervals[child.0] = Some((counter, 0));
+                    stack.push((child, 0));
+                }
+                None => {
+                    counter += 1;
+                    if let Some((_, post)) = &mut self.intervals[node.0] {
+                        *post = counter;
+                    }
+                    stack.pop();
+                }
+            }
+        }
+    }
+
+    /// The entry block, or the virtual exit of a post-dominator tree
+    pub fn root(&self) -> BlockId {
+        self.root
+    }
+
+    pub fn is_post_dominator_tree(&self) -> bool {
+        self.post
+    }
+
+    /// `None` for the root and for blocks that aren't reachable from it
+    pub fn immediate_dominator(&self, block: BlockId) -> Option<BlockId> {
+        self.immediate_dominators.get(block.0).copied().flatten()
+    }
+
+    /// Blocks immediately dominated by `block`
+    pub fn children(&self, block: BlockId) -> &[BlockId] {
+        self.children.get(block.0).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn is_reachable(&self, block: BlockId) -> bool {
+        self.intervals.get(block.0).is_some_and(Option::is_some)
+    }
+
+    /// Whether every path from the root to `b` goes through `a`. Blocks dominate themselves.
+    pub fn dominates(&self, a: BlockId, b: BlockId) -> bool {
+        match (
+            self.intervals.get(a.0).copied().flatten(),
+            self.intervals.get(b.0).copied().flatten(),
+        ) {
+            (Some((a_pre, a_post)), Some((b_pre, b_post))) => a_pre <= b_pre && b_post <= a_post,
+            _ => false,
+        }
+    }
+
+    pub fn strictly_dominates(&self, a: BlockId, b: BlockId) -> bool {
+        a != b && self.dominates(a, b)
+    }
+
+    /// Dominators of `block` from the block itself up to the root, empty for unreachable blocks
+    pub fn dominators_of(&self, block: BlockId) -> impl Iterator<Item = BlockId> + '_ {
+        let start = self.is_reachable(block).then_some(block);
+        std::iter::successors(start, |&b| self.immediate_dominator(b))
+    }
+}
+
+/// Nearest common dominator of `a` and `b`, walking up the partially built tree
+fn intersect(idom: &[Option<usize>], number: &[usize], mut a: usize, mut b: usize) -> usize {
+    while a != b {
+        while number[a] < number[b] {
+            a = idom[a].unwrap();
+        }
+        while number[b] < number[a] {
+            b = idom[b].unwrap();
+        }
+    }
+    a
+}
+
+/// Blocks where the dominance of each block ends. The frontiers of a post-dominator tree give the
+/// blocks each block is control dependent on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DominanceFrontiers {
+    frontiers: Vec<Vec<BlockId>>,
+}
+
+impl DominanceFrontiers {
+    pub fn new(cfg: &ControlFlowGraph, tree: &DominatorTree) -> Self {
+        let graph = if tree.post {
+            Graph::reverse(cfg)
+        } else {
+            Graph::forward(cfg)
+        };
+
+        let mut frontiers = vec![Vec::new(); graph.len()];
+        for (node, predecessors) in graph.predecessors.iter().enumerate() {
+            let block = BlockId(node);
+            // The root is also entered from outside the graph, so a single edge back to it
+            // already makes it a join point
+            let entries = predecessors.len() + usize::from(node == graph.root);
+            if entries < 2 || !tree.is_reachable(block) {
+                continue;
+            }
+            let idom = tree.immediate_dominator(block);
+            for &predecessor in predecessors {
+                let mut runner = BlockId(predecessor);
+                if !tree.is_reachable(runner) {
+                    continue;
+                }
+                while Some(runner) != idom {
+                    let frontier: &mut Vec<BlockId> = &mut frontiers[runner.0];
+                    if !frontier.contains(&block) {
+                        frontier.push(block);
+                    }
+                    match tree.immediate_dominator(runner) {
+                        Some(dominator) => runner = dominator,
+                        None => break,
+                    }
+                }
+            }
+        }
+        Self { frontiers }
+    }
+
+    pub fn of(&self, block: BlockId) -> &[BlockId] {
+        self.frontiers.get(block.0).map_or(&[], Vec::as_slice)
+    }
+}
@@ -0,0 +1,347 @@
+//! Encoding of instructions back into method bodies (ECMA-335 II.25.4)
+
+use std::collections::HashMap;
+
+use crate::{
+    Result,
+    body::{
+        ExceptionClause, ExceptionClauseKind, FAT_FORMAT, INIT_LOCALS, MORE_SECTS,
+        SECTION_EH_TABLE, SECTION_FAT_FORMAT, TINY_FORMAT,
+    },
+    error::Error,
+    image::{CilImage, MethodHeader},
+    opcodes::{FlowControl, Instruction, RawOpcode},
+    signature::StandaloneMethodSignature,
+};
+
+/// Short and long forms of every branch opcode
+macro_rules! branch_forms {
+    ($($short:ident / $long:ident),* $(,)?) => {
+        /// The branch opcode with the given form and offset, relative to the end of the instruction.
+        /// `None` if `opcode` is not a branch that has a short form.
+        fn branch_with_offset(opcode: &RawOpcode, long: bool, offset: i32) -> Option<RawOpcode> {
+            Some(match opcode {
+                $(RawOpcode::$short { .. } | RawOpcode::$long { .. } => {
+                    if long {
+                        RawOpcode::$long { offset }
+                    } else {
+                        RawOpcode::$short { offset: offset as i8 }
+                    }
+                })*
+                _ => return None,
+            })
+        }
+    };
+}
+
+branch_forms! {
+    Br_S / Br,
+    Br_False_S / Br_False,
+    Br_True_S / Br_True,
+    Beq_S / Beq,
+    Bge_S / Bge,
+    Bgt_S / Bgt,
+    Ble_S / Ble,
+    Blt_S / Blt,
+    Bne_Un_S / Bne_Un,
+    Bge_Un_S / Bge_Un,
+    Bgt_Un_S / Bgt_Un,
+    Ble_Un_S / Ble_Un,
+    Blt_Un_S / Blt_Un,
+    Leave_S / Leave,
+}
+
+/// Maps the offsets used by the input instructions to instruction indices. The offset just past the
+/// last instruction maps to the number of instructions.
+struct Labels(HashMap<u32, usize>);
+
+impl Labels {
+    fn new(instructions: &[Instruction]) -> Self {
+        let mut labels: HashMap<u32, usize> = instructions
+            .iter()
+            .enumerate()
+            .map(|(i, instruction)| (instruction.offset, i))
+            .collect();
+        if let Some(last) = instructions.last() {
+            labels
+                .entry(last.next_offset())
+                .or_insert(instructions.len());
+        }
+        Self(labels)
+    }
+
+    fn index(&self, offset: u32) -> Result<usize> {
+        self.0
+            .get(&offset)
+            .copied()
+            .ok_or(Error::InvalidBranchTarget(offset))
+    }
+
+    /// Indices of the instructions a branch can jump to, none for a `switch` without targets
+    fn targets(&self, instruction: &Instruction) -> Result<Vec<usize>> {
+        instruction
+            .branch_targets()
+            .iter()
+            .map(|&target| self.index(target))
+            .collect()
+    }
+}
+
+/// Encodes instructions, using the short form of every branch whose target is in range.
+///
+/// Branch targets and exception clauses refer to the `offset`s of the input instructions, which
+/// only have to be unique. Returns the code together with the clauses moved to the new offsets.
+pub fn encode_instructions(
+    instructions: &[Instruction],
+    exception_clauses: &[ExceptionClause],
+) -> Result<(Vec<u8>, Vec<ExceptionClause>)> {
+    let labels = Labels::new(instructions);
+    let targets = instructions
+        .iter()
+        .map(|instruction| labels.targets(instruction))
+        .collect::<Result<Vec<_>>>()?;
+
+    // Start with every branch in its short form and widen the ones that don't reach their target.
+    // Widening only moves instructions further apart, so this converges.
+    let mut long = vec![false; instructions.len()];
+    let offsets = loop {
+        let offsets = layout(instructions, &long);
+        let mut changed = false;
+        for (i, instruction) in instructions.iter().enumerate() {
+            if long[i] || branch_with_offset(&instruction.opcode, false, 0).is_none() {
+                continue;
+            }
+
+            let relative = offsets[targets[i][0]] as i64 - offsets[i + 1] as i64;
+            if i8::try_from(relative).is_err() {
+                long[i] = true;
+                changed = true;
+            }
+        }
+
+        if !changed {
+            break offsets;
+        }
+    };
+
+    let mut code = Vec::with_capacity(offsets[instructions.len()] as usize);
+    for (i, instruction) in instructions.iter().enumerate() {
+        for prefix in instruction.prefixes.opcodes() {
+            prefix.encode(&mut code);
+        }
+
+        let relative = |target: usize| (offsets[target] as i64 - offsets[i + 1] as i64) as i32;
+        let opcode = match (&instruction.opcode, targets[i].as_slice()) {
+            (RawOpcode::Switch { .. }, targets) => RawOpcode::Switch {
+                targets: targets.iter().map(|&t| relative(t)).collect(),
+            },
+            (opcode, &[target]) => branch_with_offset(opcode, long[i], relative(target))
+                .unwrap_or_else(|| opcode.clone()),
+            (opcode, _) => opcode.clone(),
+        };
+        opcode.encode(&mut code);
+    }
+
+    let moved = |offset: u32| labels.index(offset).map(|i| offsets[i]);
+    let exception_clauses = exception_clauses
+        .iter()
+        .map(|clause| {
+            let try_offset = moved(clause.try_offset)?;
+            let handler_offset = moved(clause.handler_offset)?;
+            Ok(ExceptionClause {
+                kind: match clause.kind {
+                    ExceptionClauseKind::Filter(offset) => {
+                        ExceptionClauseKind::Filter(moved(offset)?)
+                    }
+                    kind => kind,
+                },
+                try_offset,
+                try_length: moved(clause.try_end())? - try_offset,
+                handler_offset,
+                handler_length: moved(clause.handler_end())? - handler_offset,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok((code, exception_clauses))
+}
+
+/// Encoded offset of every instruction, followed by the total code size
+fn layout(instructions: &[Instruction], long: &[bool]) -> Vec<u32> {
+    let mut offsets = Vec::with_capacity(instructions.len() + 1);
+    let mut offset = 0;
+    for (instruction, &long) in instructions.iter().zip(long) {
+        offsets.push(offset);
+        let opcode_size = match branch_with_offset(&instruction.opcode, long, 0) {
+            Some(branch) => branch.size(),
+            None => instruction.opcode.size(),
+        };
+        offset += (instruction.prefixes.size() + opcode_size) as u32;
+    }
+    offsets.push(offset);
+    offsets
+}
+
+impl CilImage {
+    /// Deepest the evaluation stack gets, following every path from the start of the method and
+    /// from each exception handler. `signature` is the signature of the method the instructions
+    /// belong to.
+    pub fn max_stack(
+        &self,
+        signature: &StandaloneMethodSignature,
+        instructions: &[Instruction],
+        exception_clauses: &[ExceptionClause],
+    ) -> Result<u16> {
+        let labels = Labels::new(instructions);
+
+        let mut worklist = vec![(0, 0u16)];
+        for clause in exception_clauses {
+            // Catch and filter blocks start with the exception object on the stack
+            match clause.kind {
+                ExceptionClauseKind::Catch(_) => {
+                    worklist.push((labels.index(clause.handler_offset)?, 1));
+                }
+                ExceptionClauseKind::Filter(offset) => {
+                    worklist.push((labels.index(offset)?, 1));
+                    worklist.push((labels.index(clause.handler_offset)?, 1));
+                }
+                ExceptionClauseKind::Finally | ExceptionClauseKind::Fault => {
+                    worklist.push((labels.index(clause.handler_offset)?, 0));
+                }
+            }
+        }
+
+        let mut visited = vec![false; instructions.len()];
+        let mut max_stack = 0;
+        while let Some((i, height)) = worklist.pop() {
+            let Some(instruction) = instructions.get(i) else {
+                continue;
+            };
+            if std::mem::replace(&mut visited[i], true) {
+                continue;
+            }
+
+            let effect = instruction.stack_effect(self, signature)?;
+            let height = height
+                .checked_sub(effect.pops)
+                .ok_or(Error::StackUnderflow(instruction.offset))?
+                + effect.pushes;
+            max_stack = max_stack.max(height);
+
+            // `leave` empties the evaluation stack
+            let target_height = match instruction.opcode {
+                RawOpcode::Leave { .. } | RawOpcode::Leave_S { .. } => 0,
+                _ => height,
+            };
+            for target in labels.targets(instruction)? {
+                worklist.push((target, target_height));
+            }
+            if !instruction.opcode.ends_block()
+                || instruction.opcode.flow_control() == FlowControl::CondBranch
+            {
+                worklist.push((i + 1, height));
+            }
+        }
+
+        Ok(max_stack)
+    }
+
+    /// Encodes a method body with its header and exception handling sections.
+    ///
+    /// `max_stack` is computed from the instructions and `code_size` from their encoding, the
+    /// values in `header` are ignored. A tiny header is used whenever the body allows it.
+    pub fn encode_method_body(
+        &self,
+        signature: &StandaloneMethodSignature,
+        header: &MethodHeader,
+        instructions: &[Instruction],
+    ) -> Result<Vec<u8>> {
+        let max_stack = self.max_stack(signature, instructions, &header.exception_clauses)?;
+        encode_method_body(
+            &MethodHeader {
+                max_stack,
+                ..header.clone()
+            },
+            instructions,
+        )
+    }
+}
+
+/// Encodes a method body like [`CilImage::encode_method_body`], but keeps the `max_stack` given in
+/// `header` instead of computing it
+pub fn encode_method_body(header: &MethodHeader, instructions: &[Instruction]) -> Result<Vec<u8>> {
+    let max_stack = header.max_stack;
+    let (code, exception_clauses) = encode_instructions(instructions, &header.exception_clauses)?;
+
+    let mut body = Vec::new();
+    let is_tiny = code.len() < 64
+        && max_stack <= 8
+        && header.local_var_sig_token.is_none()
+        && exception_clauses.is_empty();
+    if is_tiny {
+        body.push(((code.len() as u8) << 2) | TINY_FORMAT);
+        body.extend_from_slice(&code);
+        return Ok(body);
+    }
+
+    let mut flags = FAT_FORMAT;
+    if !exception_clauses.is_empty() {
+        flags |= MORE_SECTS;
+    }
+    if header.init_locals {
+        flags |= INIT_LOCALS;
+    }
+    // The fat header is 3 4-byte units long
+    body.extend_from_slice(&(flags | (3 << 12)).to_le_bytes());
+    body.extend_from_slice(&max_stack.to_le_bytes());
+    body.extend_from_slice(&(code.len() as u32).to_le_bytes());
+    body.extend_from_slice(&header.local_var_sig_token.map_or(0, |t| t.0).to_le_bytes());
+    body.extend_from_slice(&code);
+
+    if !exception_clauses.is_empty() {
+        body.resize(body.len().next_multiple_of(4), 0);
+        encode_exception_section(&exception_clauses, &mut body);
+    }
+
+    Ok(body)
+}
+
+/// Appends an EH table, using the small format if every clause fits into it
+fn encode_exception_section(clauses: &[ExceptionClause], out: &mut Vec<u8>) {
+    let small_size = 4 + 12 * clauses.len();
+    let fits_small = small_size <= u8::MAX as usize
+        && clauses.iter().all(|c| {
+            c.try_offset <= u16::MAX as u32
+                && c.try_length <= u8::MAX as u32
+                && c.handler_offset <= u16::MAX as u32
+                && c.handler_length <= u8::MAX as u32
+        });
+
+    if fits_small {
+        out.extend_from_slice(&[SECTION_EH_TABLE, small_size as u8, 0, 0]);
+        for clause in clauses {
+            out.extend_from_slice(&(clause.kind.flags() as u16).to_le_bytes());
+            out.extend_from_slice(&(clause.try_offset as u16).to_le_bytes());
+            out.push(clause.try_length as u8);
+            out.extend_from_slice(&(clause.handler_offset as u16).to_le_bytes());
+            out.push(clause.handler_length as u8);
+            out.extend_from_slice(&clause.kind.data().to_le_bytes());
+        }
+    } else {
+        let fat_size = (4 + 24 * clauses.len()) as u32;
+        out.push(SECTION_EH_TABLE | SECTION_FAT_FORMAT);
+        out.extend_from_slice(&fat_size.to_le_bytes()[..3]);
+        for clause in clauses {
+            for value in [
+                clause.kind.flags(),
+                clause.try_offset,
+                clause.try_length,
+                clause.handler_offset,
+                clause.handler_length,
+                clause.kind.data(),
+            ] {
+                out.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+    }
+}
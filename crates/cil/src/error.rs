@@ -1,5 +1,7 @@
 use thiserror::Error;
 
+use crate::{meta::Token, signature::SignatureKind};
+
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("Loaded PE binary is not a valid CIL image")]
@@ -13,6 +15,51 @@ pub enum Error {
 
     #[error("Unsupported CIL table {0}")]
     UnsupportedTable(&'static str),
+
+    #[error("{0:?} does not refer to a valid row")]
+    InvalidToken(Token),
+
+    #[error("Invalid blob heap index {0:#x}")]
+    InvalidBlobIndex(u32),
+
+    #[error("Expected a {expected:?} signature, found calling convention {found:#04x}")]
+    UnexpectedSignatureKind { expected: SignatureKind, found: u8 },
+
+    #[error("Invalid type name at position {position}: {message}")]
+    InvalidTypeName { position: usize, message: String },
+
+    #[error("Type {0} could not be resolved")]
+    UnresolvedType(String),
+
+    #[error("Type {name} is forwarded to assembly {assembly}")]
+    ForwardedType { name: String, assembly: String },
+
+    #[error("RVA {0:#x} does not point into any section")]
+    InvalidRva(u32),
+
+    #[error("Size of field {0:?} could not be determined")]
+    UnknownFieldSize(Token),
+
+    #[error("Value type {0:?} contains itself")]
+    RecursiveLayout(Token),
+
+    #[error("Invalid assembly name \"{name}\": {message}")]
+    InvalidAssemblyName { name: String, message: String },
+
+    #[error("Invalid constant of element type {0:#04x}")]
+    InvalidConstant(u8),
+
+    #[error("Invalid exception clause kind {0:#x}")]
+    InvalidExceptionClause(u32),
+
+    #[error("IL_{0:04x} is not the start of an instruction")]
+    InvalidBranchTarget(u32),
+
+    #[error("Stack underflow at IL_{0:04x}")]
+    StackUnderflow(u32),
+
+    #[error("Invalid ILAsm source on line {line}: {message}")]
+    InvalidILAsm { line: usize, message: String },
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
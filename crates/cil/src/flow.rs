@@ -0,0 +1,284 @@
+//! Control-flow graphs of method bodies (ECMA-335 I.12.4)
+//!
+//! Blocks are split at branch targets, after instructions that end a block and at the boundaries
+//! of exception handling regions, so that each block lies either entirely inside or entirely
+//! outside every protected region, handler and filter.
+
+use std::{collections::BTreeSet, ops::Range};
+
+use crate::{
+    Result,
+    body::{ExceptionClause, ExceptionClauseKind},
+    error::Error,
+    image::CilImage,
+    meta::MethodDefHandle,
+    opcodes::{FlowControl, Instruction, RawOpcode},
+};
+
+/// Index of a block in [`ControlFlowGraph::blocks`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BlockId(pub usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EdgeKind {
+    /// Execution continues with the next block. Also the default case of a `switch`.
+    Fallthrough,
+    /// Unconditional `br`
+    Branch,
+    /// Conditional branch that is taken
+    ConditionalTrue,
+    /// Conditional branch that is not taken
+    ConditionalFalse,
+    /// Target `n` of a `switch`
+    SwitchCase(u32),
+    /// From a block in a protected region to the handler or filter of a clause protecting it
+    Exceptional,
+    /// `leave` out of a protected region or handler
+    Leave,
+    /// From the end of a `finally` handler to the target of a `leave` that exits its protected
+    /// region
+    EndFinally,
+    /// From the end of a filter to the handler it guards
+    EndFilter,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Edge {
+    pub source: BlockId,
+    pub target: BlockId,
+    pub kind: EdgeKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BasicBlock {
+    /// Offset of the first instruction
+    pub start: u32,
+    /// Offset just past the last instruction
+    pub end: u32,
+    /// Indices of the instructions in the method body
+    pub instructions: Range<usize>,
+    pub successors: Vec<Edge>,
+    pub predecessors: Vec<Edge>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ControlFlowGraph {
+    /// Blocks in offset order. The first one is the entry point, unless the body is empty.
+    pub blocks: Vec<BasicBlock>,
+    pub exception_clauses: Vec<ExceptionClause>,
+}
+
+impl ControlFlowGraph {
+    /// Builds the graph of a method body. Branch targets and clause offsets have to be the offsets
+    /// of instructions, or the end of the body.
+    pub fn new(
+        instructions: &[Instruction],
+        exception_clauses: &[ExceptionClause],
+    ) -> Result<Self> {
+        let end = instructions.last().map_or(0, Instruction::next_offset);
+        let index_of = |offset: u32| {
+            if offset == end {
+                return Ok(instructions.len());
+            }
+            instructions
+                .binary_search_by_key(&offset, |i| i.offset)
+                .map_err(|_| Error::InvalidBranchTarget(offset))
+        };
+
+        let mut leaders = BTreeSet::from([0]);
+        for (i, instruction) in instructions.iter().enumerate() {
+            for &target in instruction.branch_targets() {
+                leaders.insert(index_of(target)?);
+            }
+            if instruction.opcode.ends_block() {
+                leaders.insert(i + 1);
+            }
+        }
+        for clause in exception_clauses {
+            leaders.insert(index_of(clause.try_offset)?);
+            leaders.insert(index_of(clause.try_end())?);
+            leaders.insert(index_of(clause.handler_offset)?);
+            leaders.insert(index_of(clause.handler_end())?);
+            if let ExceptionClauseKind::Filter(offset) = clause.kind {
+                leaders.insert(index_of(offset)?);
+            }
+        }
+        leaders.retain(|&i| i < instructions.len());
+
+        let leaders = leaders.into_iter().collect::<Vec<_>>();
+        let mut blocks = leaders
+            .iter()
+            .enumerate()
+            .map(|(b, &first)| {
+                let last = leaders.get(b + 1).copied().unwrap_or(instructions.len());
+                BasicBlock {
+                    start: instructions[first].offset,
+                    end: instructions[last - 1].next_offset(),
+                    instructions: first..last,
+                    successors: Vec::new(),
+                    predecessors: Vec::new(),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let block_at = |offset: u32| {
+            leaders
+                .binary_search(&index_of(offset)?)
+                .map(BlockId)
+                .map_err(|_| Error::InvalidBranchTarget(offset))
+        };
+
+        let mut edges = Vec::new();
+        for (b, block) in blocks.iter().enumerate() {
+            let source = BlockId(b);
+            let mut edge = |target: BlockId, kind: EdgeKind| {
+                edges.push(Edge {
+                    source,
+                    target,
+                    kind,
+                })
+            };
+            let next = (b + 1 < leaders.len()).then_some(BlockId(b + 1));
+
+            let last = &instructions[block.instructions.end - 1];
+            match (&last.opcode, last.opcode.flow_control()) {
+                (RawOpcode::Leave { .. } | RawOpcode::Leave_S { .. }, _) => {
+                    edge(block_at(last.branch_targets()[0])?, EdgeKind::Leave)
+                }
+                (RawOpcode::Switch { .. }, _) => {
+                    for (n, &target) in last.branch_targets().iter().enumerate() {
+                        edge(block_at(target)?, EdgeKind::SwitchCase(n as u32));
+                    }
+                    if let Some(next) = next {
+                        edge(next, EdgeKind::Fallthrough);
+                    }
+                }
+                (_, FlowControl::Branch) => {
+                    edge(block_at(last.branch_targets()[0])?, EdgeKind::Branch)
+                }
+                (_, FlowControl::CondBranch) => {
+                    edge(
+                        block_at(last.branch_targets()[0])?,
+                        EdgeKind::ConditionalTrue,
+                    );
+                    if let Some(next) = next {
+                        edge(next, EdgeKind::ConditionalFalse);
+                    }
+                }
+                (RawOpcode::EndFilter {}, _) => {
+                    for clause in exception_clauses {
+                        if let ExceptionClauseKind::Filter(offset) = clause.kind
+                            && (offset..clause.handler_offset).contains(&block.start)
+                        {
+                            edge(block_at(clause.handler_offset)?, EdgeKind::EndFilter);
+                        }
+                    }
+                }
+                (RawOpcode::EndFaultOrFinally {}, _) => {
+                    for clause in exception_clauses {
+                        if clause.kind != ExceptionClauseKind::Finally
+                            || !(clause.handler_offset..clause.handler_end()).contains(&block.start)
+                        {
+                            continue;
+                        }
+                        for target in leave_targets(instructions, clause) {
+                            edge(block_at(target)?, EdgeKind::EndFinally);
+                        }
+                    }
+                }
+                // `jmp` leaves the method like `ret`
+                (RawOpcode::Jmp { .. }, _) | (_, FlowControl::Return | FlowControl::Throw) => {}
+                _ => {
+                    if let Some(next) = next {
+                        edge(next, EdgeKind::Fallthrough);
+                    }
+                }
+            }
+
+            for clause in exception_clauses {
+                if (clause.try_offset..clause.try_end()).contains(&block.start) {
+                    let handler = match clause.kind {
+                        ExceptionClauseKind::Filter(offset) => offset,
+                        _ => clause.handler_offset,
+                    };
+                    edge(block_at(handler)?, EdgeKind::Exceptional);
+                }
+            }
+        }
+
+        for edge in edges {
+            blocks[edge.source.0].successors.push(edge);
+            blocks[edge.target.0].predecessors.push(edge);
+        }
+        Ok(Self {
+            blocks,
+            exception_clauses: exception_clauses.to_vec(),
+        })
+    }
+
+    pub fn entry(&self) -> BlockId {
+        BlockId(0)
+    }
+
+    pub fn block(&self, id: BlockId) -> &BasicBlock {
+        &self.blocks[id.0]
+    }
+
+    /// Block starting at `offset`
+    pub fn block_at(&self, offset: u32) -> Option<BlockId> {
+        self.blocks
+            .binary_search_by_key(&offset, |b| b.start)
+            .ok()
+            .map(BlockId)
+    }
+
+    /// Block containing the instruction at `offset`
+    pub fn block_containing(&self, offset: u32) -> Option<BlockId> {
+        let b = self.blocks.partition_point(|b| b.start <= offset);
+        (b > 0 && offset < self.blocks[b - 1].end).then(|| BlockId(b - 1))
+    }
+
+    /// Entry of the handler of a clause, or of its filter for filter clauses
+    pub fn handler_entry(&self, clause: &ExceptionClause) -> Option<BlockId> {
+        match clause.kind {
+            ExceptionClauseKind::Filter(offset) => self.block_at(offset),
+            _ => self.block_at(clause.handler_offset),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+}
+
+/// Distinct targets of the `leave` instructions that exit the protected region of `clause`
+fn leave_targets(instructions: &[Instruction], clause: &ExceptionClause) -> Vec<u32> {
+    let protected = clause.try_offset..clause.try_end();
+    let mut targets = Vec::new();
+    for instruction in instructions {
+        if let RawOpcode::Leave { .. } | RawOpcode::Leave_S { .. } = instruction.opcode
+            && protected.contains(&instruction.offset)
+            && let Some(&target) = instruction.branch_targets().first()
+            && !protected.contains(&target)
+            && !targets.contains(&target)
+        {
+            targets.push(target);
+        }
+    }
+    targets
+}
+
+impl CilImage {
+    /// Control-flow graph of the body of a method definition, empty for methods without IL
+    pub fn control_flow_graph(&self, handle: MethodDefHandle) -> Result<ControlFlowGraph> {
+        let (_, header, instructions) = self
+            .method_defs
+            .get(handle.index())
+            .ok_or(Error::InvalidToken(handle.token()))?;
+        ControlFlowGraph::new(instructions, &header.exception_clauses)
+    }
+}
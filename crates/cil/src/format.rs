@@ -0,0 +1,743 @@
+//! Type name formatting in C#, ILAsm and reflection (assembly-qualified) syntax
+
+use crate::{
+    image::CilImage,
+    meta::{MethodDefHandle, TypeDefHandle, TypeRefHandle, TypeSpecHandle},
+    signature::{Element, MethodCallType, StandaloneMethodSignature},
+    tables::{ResolutionScope, TypeDefOrRef, TypeOrMethodDef},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TypeNameStyle {
+    /// C# syntax, eg. `List<int>`, `int[,]`, `ref readonly T` or `delegate*<int, void>`
+    CSharp,
+    /// ILAsm syntax, eg. `class [mscorlib]System.String` or `!!0`
+    ILAsm,
+    /// Reflection syntax as used by `Type.GetType`, eg. ``System.Collections.Generic.List`1[[System.Int32, System.Private.CoreLib]]``
+    Reflection,
+}
+
+/// Formats signature types and type references as text in one of the supported [`TypeNameStyle`]s
+pub struct TypeNameFormatter<'img> {
+    image: &'img CilImage,
+    style: TypeNameStyle,
+    namespaces: bool,
+    assembly_qualified: bool,
+    type_context: Option<TypeDefHandle>,
+    method_context: Option<MethodDefHandle>,
+    core_library: String,
+}
+
+#[derive(Clone, Copy)]
+enum GenericParamKind {
+    Type,
+    Method,
+}
+
+/// Where a named type is defined, relative to the image being formatted
+enum TypeScope {
+    Local,
+    Assembly(String),
+    Module(String),
+}
+
+/// A TypeDef or TypeRef with its chain of enclosing types, outermost first
+struct NamedType {
+    namespace: String,
+    names: Vec<String>,
+    scope: TypeScope,
+}
+
+impl<'img> TypeNameFormatter<'img> {
+    pub fn new(image: &'img CilImage, style: TypeNameStyle) -> Self {
+        Self {
+            image,
+            style,
+            namespaces: style != TypeNameStyle::CSharp,
+            assembly_qualified: false,
+            type_context: None,
+            method_context: None,
+            core_library: core_library_name(image),
+        }
+    }
+
+    /// Whether C# names include their namespace. ILAsm and reflection names are always qualified.
+    pub fn with_namespaces(mut self, namespaces: bool) -> Self {
+        self.namespaces = namespaces;
+        self
+    }
+
+    /// Whether reflection names are suffixed with the name of their defining assembly
+    pub fn assembly_qualified(mut self, assembly_qualified: bool) -> Self {
+        self.assembly_qualified = assembly_qualified;
+        self
+    }
+
+    /// Sets the type and method whose generic parameter names are used for `Var` and `MVar` elements
+    pub fn with_generic_context(
+        mut self,
+        type_def: Option<TypeDefHandle>,
+        method: Option<MethodDefHandle>,
+    ) -> Self {
+        self.type_context = type_def;
+        self.method_context = method;
+        self
+    }
+
+    pub fn format(&self, element: &Element) -> String {
+        let name = self.element(element);
+        if self.style == TypeNameStyle::Reflection && self.assembly_qualified {
+            match self.assembly_of(element) {
+                Some(assembly) => format!("{name}, {assembly}"),
+                None => name,
+            }
+        } else {
+            name
+        }
+    }
+
+    /// Formats a type token, as used by eg. `box` or `castclass`
+    pub fn format_type(&self, token: TypeDefOrRef) -> String {
+        match token {
+            TypeDefOrRef::TypeSpec(index) => match self.image.type_spec(TypeSpecHandle(index)) {
+                Ok(element) => self.format(&element),
+                Err(_) => format!("<invalid typespec {index}>"),
+            },
+            _ if self.style == TypeNameStyle::ILAsm => self.named(token, &[]),
+            _ => self.format(&Element::Class(token)),
+        }
+    }
+
+    /// Formats a method signature together with the name of the method
+    pub fn format_method_signature(
+        &self,
+        name: &str,
+        signature: &StandaloneMethodSignature,
+    ) -> String {
+        let mut parameters = signature
+            .parameters
+            .iter()
+            .map(|p| self.element(p))
+            .collect::<Vec<_>>();
+        if let Some(sentinel) = signature.sentinel {
+            parameters.insert(sentinel, "...".to_string());
+        }
+
+        let generics = if signature.generic_param_count > 0 && self.style != TypeNameStyle::ILAsm {
+            let args = (0..signature.generic_param_count)
+                .map(|i| self.generic_param(GenericParamKind::Method, i))
+                .collect::<Vec<_>>();
+            match self.style {
+                TypeNameStyle::CSharp => format!("<{}>", args.join(", ")),
+                _ => format!("[{}]", args.join(",")),
+            }
+        } else {
+            String::new()
+        };
+
+        let prefix = if self.style == TypeNameStyle::ILAsm {
+            self.ilasm_calling_convention(signature)
+        } else {
+            String::new()
+        };
+
+        format!(
+            "{prefix}{} {name}{generics}({})",
+            self.element(&signature.return_type),
+            parameters.join(", ")
+        )
+    }
+
+    fn element(&self, element: &Element) -> String {
+        if let Some(primitive) = self.primitive(element) {
+            return primitive.to_string();
+        }
+
+        match self.style {
+            TypeNameStyle::CSharp => self.csharp(element),
+            TypeNameStyle::ILAsm => self.ilasm(element),
+            TypeNameStyle::Reflection => self.reflection(element),
+        }
+    }
+
+    fn csharp(&self, element: &Element) -> String {
+        match element {
+            Element::Ptr(inner) => format!("{}*", self.element(inner)),
+            Element::ByRef(inner) => format!("ref {}", self.element(inner)),
+            Element::CModRequired(modifier, inner) => match inner.as_ref() {
+                Element::ByRef(referent) if self.is_interop_attribute(*modifier, "InAttribute") => {
+                    format!("ref readonly {}", self.element(referent))
+                }
+                Element::ByRef(referent)
+                    if self.is_interop_attribute(*modifier, "OutAttribute") =>
+                {
+                    format!("out {}", self.element(referent))
+                }
+                _ => self.element(inner),
+            },
+            Element::CModOptional(_, inner) | Element::Pinned(inner) => self.element(inner),
+            Element::SzArray(_) | Element::Array(..) => {
+                // C# lists the outermost rank first, eg. `int[][,]` is an array of `int[,]`
+                let mut ranks = String::new();
+                let mut current = element;
+                loop {
+                    let (inner, rank) = match current {
+                        Element::SzArray(inner) => (inner, "[]".to_string()),
+                        Element::Array(inner, shape) if shape.rank == 1 => {
+                            (inner, "[*]".to_string())
+                        }
+                        Element::Array(inner, shape) => {
+                            (inner, format!("[{}]", ",".repeat(shape.rank as usize - 1)))
+                        }
+                        _ => break,
+                    };
+                    ranks.push_str(&rank);
+                    current = inner;
+                }
+                format!("{}{ranks}", self.element(current))
+            }
+            Element::GenericInst {
+                generic_type,
+                generic_args,
+            } => match generic_type.as_ref() {
+                Element::Class(token) | Element::ValueType(token) => {
+                    let full_name = self.full_name(*token).unwrap_or_default();
+                    if generic_args.len() == 1 && full_name == "System.Nullable`1" {
+                        format!("{}?", self.element(&generic_args[0]))
+                    } else if (2..8).contains(&generic_args.len())
+                        && full_name.starts_with("System.ValueTuple`")
+                    {
+                        let items = generic_args
+                            .iter()
+                            .map(|a| self.element(a))
+                            .collect::<Vec<_>>();
+                        format!("({})", items.join(", "))
+                    } else {
+                        self.named(*token, generic_args)
+                    }
+                }
+                other => self.element(other),
+            },
+            Element::Class(token) | Element::ValueType(token) => self.named(*token, &[]),
+            Element::Var(index) => self.generic_param(GenericParamKind::Type, index.0),
+            Element::MVar(index) => self.generic_param(GenericParamKind::Method, index.0),
+            Element::FnPtr(signature) => {
+                let mut types = signature
+                    .parameters
+                    .iter()
+                    .map(|p| self.element(p))
+                    .collect::<Vec<_>>();
+                types.push(self.element(&signature.return_type));
+
+                let convention = match signature.header.call_type() {
+                    MethodCallType::Default | MethodCallType::Vararg => String::new(),
+                    MethodCallType::C => " unmanaged[Cdecl]".to_string(),
+                    MethodCallType::StdCall => " unmanaged[Stdcall]".to_string(),
+                    MethodCallType::ThisCall => " unmanaged[Thiscall]".to_string(),
+                    MethodCallType::FastCall => " unmanaged[Fastcall]".to_string(),
+                    MethodCallType::Unmanaged => {
+                        let conventions = self.unmanaged_conventions(&signature.return_type);
+                        if conventions.is_empty() {
+                            " unmanaged".to_string()
+                        } else {
+                            format!(" unmanaged[{}]", conventions.join(", "))
+                        }
+                    }
+                };
+
+                format!("delegate*{convention}<{}>", types.join(", "))
+            }
+            other => self.fallback(other),
+        }
+    }
+
+    fn ilasm(&self, element: &Element) -> String {
+        match element {
+            Element::Ptr(inner) => format!("{}*", self.element(inner)),
+            Element::ByRef(inner) => format!("{}&", self.element(inner)),
+            Element::Pinned(inner) => format!("{} pinned", self.element(inner)),
+            Element::CModRequired(modifier, inner) => {
+                format!(
+                    "{} modreq({})",
+                    self.element(inner),
+                    self.named(*modifier, &[])
+                )
+            }
+            Element::CModOptional(modifier, inner) => {
+                format!(
+                    "{} modopt({})",
+                    self.element(inner),
+                    self.named(*modifier, &[])
+                )
+            }
+            Element::SzArray(inner) => format!("{}[]", self.element(inner)),
+            Element::Array(inner, shape) => {
+                let dimensions = shape.dimensions();
+                if shape.rank == 1 && dimensions.is_empty() {
+                    format!("{}[...]", self.element(inner))
+                } else {
+                    format!("{}[{dimensions}]", self.element(inner))
+                }
+            }
+            Element::GenericInst {
+                generic_type,
+                generic_args,
+            } => {
+                let args = generic_args
+                    .iter()
+                    .map(|a| self.element(a))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                format!("{}<{args}>", self.element(generic_type))
+            }
+            Element::Class(token) => format!("class {}", self.named(*token, &[])),
+            Element::ValueType(token) => format!("valuetype {}", self.named(*token, &[])),
+            Element::Var(index) => format!("!{}", index.0),
+            Element::MVar(index) => format!("!!{}", index.0),
+            Element::FnPtr(signature) => {
+                let mut parameters = signature
+                    .parameters
+                    .iter()
+                    .map(|p| self.element(p))
+                    .collect::<Vec<_>>();
+                if let Some(sentinel) = signature.sentinel {
+                    parameters.insert(sentinel, "...".to_string());
+                }
+                format!(
+                    "method {}{} *({})",
+                    self.ilasm_calling_convention(signature),
+                    self.element(&signature.return_type),
+                    parameters.join(", ")
+                )
+            }
+            other => self.fallback(other),
+        }
+    }
+
+    fn reflection(&self, element: &Element) -> String {
+        match element {
+            Element::Ptr(inner) => format!("{}*", self.element(inner)),
+            Element::ByRef(inner) => format!("{}&", self.element(inner)),
+            Element::CModRequired(_, inner)
+            | Element::CModOptional(_, inner)
+            | Element::Pinned(inner) => self.element(inner),
+            Element::SzArray(inner) => format!("{}[]", self.element(inner)),
+            Element::Array(inner, shape) if shape.rank == 1 => {
+                format!("{}[*]", self.element(inner))
+            }
+            Element::Array(inner, shape) => format!(
+                "{}[{}]",
+                self.element(inner),
+                ",".repeat(shape.rank as usize - 1)
+            ),
+            Element::GenericInst {
+                generic_type,
+                generic_args,
+            } => {
+                let args = generic_args
+                    .iter()
+                    .map(|a| match self.assembly_of(a) {
+                        Some(assembly) => format!("[{}, {assembly}]", self.element(a)),
+                        None => format!("[{}]", self.element(a)),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(",");
+                format!("{}[{args}]", self.element(generic_type))
+            }
+            Element::Class(token) | Element::ValueType(token) => self.named(*token, &[]),
+            Element::Var(index) => self.generic_param(GenericParamKind::Type, index.0),
+            Element::MVar(index) => self.generic_param(GenericParamKind::Method, index.0),
+            // Function pointers have no reflection name, the runtime treats them as native ints
+            Element::FnPtr(_) => "System.IntPtr".to_string(),
+            other => self.fallback(other),
+        }
+    }
+
+    fn fallback(&self, element: &Element) -> String {
+        match element {
+            Element::Internal(handle) => format!("<internal:{handle:#x}>"),
+            _ => "<end>".to_string(),
+        }
+    }
+
+    fn primitive(&self, element: &Element) -> Option<&'static str> {
+        let names = match element {
+            Element::Void => ("void", "void", "System.Void"),
+            Element::Boolean => ("bool", "bool", "System.Boolean"),
+            Element::Char => ("char", "char", "System.Char"),
+            Element::I1 => ("sbyte", "int8", "System.SByte"),
+            Element::U1 => ("byte", "uint8", "System.Byte"),
+            Element::I2 => ("short", "int16", "System.Int16"),
+            Element::U2 => ("ushort", "uint16", "System.UInt16"),
+            Element::I4 => ("int", "int32", "System.Int32"),
+            Element::U4 => ("uint", "uint32", "System.UInt32"),
+            Element::I8 => ("long", "int64", "System.Int64"),
+            Element::U8 => ("ulong", "uint64", "System.UInt64"),
+            Element::R4 => ("float", "float32", "System.Single"),
+            Element::R8 => ("double", "float64", "System.Double"),
+            Element::String => ("string", "string", "System.String"),
+            Element::Object => ("object", "object", "System.Object"),
+            Element::IntPtr => ("nint", "native int", "System.IntPtr"),
+            Element::UIntPtr => ("nuint", "native uint", "System.UIntPtr"),
+            Element::TypedByRef => ("TypedReference", "typedref", "System.TypedReference"),
+            _ => return None,
+        };
+
+        Some(match self.style {
+            TypeNameStyle::CSharp => names.0,
+            TypeNameStyle::ILAsm => names.1,
+            TypeNameStyle::Reflection => names.2,
+        })
+    }
+
+    /// Formats a TypeDef or TypeRef name, distributing `generic_args` over the nesting chain
+    fn named(&self, token: TypeDefOrRef, generic_args: &[Element]) -> String {
+        if let TypeDefOrRef::TypeSpec(_) = token {
+            return self.format_type(token);
+        }
+
+        let Some(ty) = self.named_type(token) else {
+            return format!("<unk:{token:?}>");
+        };
+
+        if self.style == TypeNameStyle::CSharp
+            && ty.names.len() == 1
+            && ty.namespace == "System"
+            && let Some(keyword) = csharp_keyword(&ty.names[0])
+        {
+            return keyword.to_string();
+        }
+
+        let separator = match self.style {
+            TypeNameStyle::CSharp => ".",
+            TypeNameStyle::ILAsm => "/",
+            TypeNameStyle::Reflection => "+",
+        };
+
+        let mut s = String::new();
+        if self.style == TypeNameStyle::ILAsm {
+            match &ty.scope {
+                TypeScope::Local => {}
+                TypeScope::Assembly(name) => s.push_str(&format!("[{}]", ilasm_name(name))),
+                TypeScope::Module(name) => s.push_str(&format!("[.module {}]", ilasm_name(name))),
+            }
+        }
+        if self.namespaces && !ty.namespace.is_empty() {
+            if self.style == TypeNameStyle::ILAsm {
+                s.push_str(&ilasm_name(&ty.namespace));
+            } else {
+                s.push_str(&ty.namespace);
+            }
+            s.push('.');
+        }
+
+        let mut remaining_args = generic_args;
+        for (i, name) in ty.names.iter().enumerate() {
+            if i > 0 {
+                s.push_str(separator);
+            }
+
+            if self.style == TypeNameStyle::ILAsm {
+                s.push_str(&ilasm_name(name));
+                continue;
+            }
+            if self.style == TypeNameStyle::Reflection {
+                s.push_str(name);
+                continue;
+            }
+
+            let (base, arity) = split_arity(name);
+            s.push_str(base);
+            // Any arguments left over after the last component belong to it
+            let count = if i == ty.names.len() - 1 {
+                remaining_args.len()
+            } else {
+                arity.min(remaining_args.len())
+            };
+            if count > 0 {
+                let args = remaining_args[..count]
+                    .iter()
+                    .map(|a| self.element(a))
+                    .collect::<Vec<_>>();
+                s.push_str(&format!("<{}>", args.join(", ")));
+                remaining_args = &remaining_args[count..];
+            }
+        }
+
+        s
+    }
+
+    fn named_type(&self, token: TypeDefOrRef) -> Option<NamedType> {
+        let image = self.image;
+        match token {
+            TypeDefOrRef::TypeDef(index) => {
+                let name = image.type_def_name(TypeDefHandle(index))?;
+                Some(NamedType {
+                    namespace: name.namespace,
+                    names: name.enclosing.into_iter().chain([name.name]).collect(),
+                    scope: TypeScope::Local,
+                })
+            }
+            TypeDefOrRef::TypeRef(index) => {
+                let chain = image.type_ref_chain(TypeRefHandle(index))?;
+                let outermost = chain.last()?;
+                let scope = match ResolutionScope::try_from(outermost.resolution_scope as u32) {
+                    Ok(ResolutionScope::AssemblyRef(assembly)) => image
+                        .assembly_refs
+                        .get((assembly as usize).checked_sub(1)?)
+                        .map_or(TypeScope::Local, |a| TypeScope::Assembly(a.name.clone())),
+                    Ok(ResolutionScope::ModuleRef(module)) => image
+                        .module_refs
+                        .get((module as usize).checked_sub(1)?)
+                        .map_or(TypeScope::Local, |m| TypeScope::Module(m.name.clone())),
+                    _ => TypeScope::Local,
+                };
+
+                Some(NamedType {
+                    namespace: outermost.namespace.clone(),
+                    names: chain.iter().rev().map(|r| r.name.clone()).collect(),
+                    scope,
+                })
+            }
+            TypeDefOrRef::TypeSpec(_) => None,
+        }
+    }
+
+    /// Namespace-qualified reflection name, used to recognize well-known types
+    fn full_name(&self, token: TypeDefOrRef) -> Option<String> {
+        let ty = self.named_type(token)?;
+        let mut s = ty.names.join("+");
+        if !ty.namespace.is_empty() {
+            s = format!("{}.{s}", ty.namespace);
+        }
+        Some(s)
+    }
+
+    fn is_interop_attribute(&self, token: TypeDefOrRef, name: &str) -> bool {
+        self.full_name(token)
+            .is_some_and(|n| n == format!("System.Runtime.InteropServices.{name}"))
+    }
+
+    /// Calling conventions of an unmanaged function pointer, taken from the `CallConv*` modopts on its return type
+    fn unmanaged_conventions(&self, return_type: &Element) -> Vec<String> {
+        let mut conventions = Vec::new();
+        let mut current = return_type;
+        while let Element::CModOptional(modifier, inner) = current {
+            if let Some(name) = self.full_name(*modifier)
+                && let Some(convention) =
+                    name.strip_prefix("System.Runtime.CompilerServices.CallConv")
+            {
+                conventions.push(convention.to_string());
+            }
+            current = inner;
+        }
+        conventions
+    }
+
+    pub(crate) fn ilasm_calling_convention(&self, signature: &StandaloneMethodSignature) -> String {
+        let mut s = String::new();
+        if signature.header.has_this() {
+            s.push_str("instance ");
+        }
+        if signature.header.explicit_this() {
+            s.push_str("explicit ");
+        }
+        s.push_str(match signature.header.call_type() {
+            MethodCallType::Default => "",
+            MethodCallType::Vararg => "vararg ",
+            MethodCallType::C => "unmanaged cdecl ",
+            MethodCallType::StdCall => "unmanaged stdcall ",
+            MethodCallType::ThisCall => "unmanaged thiscall ",
+            MethodCallType::FastCall => "unmanaged fastcall ",
+            MethodCallType::Unmanaged => "unmanaged ",
+        });
+        s
+    }
+
+    /// Name of generic parameter `number` of the type or method in the generic context
+    fn generic_param(&self, kind: GenericParamKind, number: u32) -> String {
+        let owner = match kind {
+            GenericParamKind::Type => self.type_context.map(|t| TypeOrMethodDef::TypeDef(t.0)),
+            GenericParamKind::Method => {
+                self.method_context.map(|m| TypeOrMethodDef::MethodDef(m.0))
+            }
+        };
+
+        let name = owner.and_then(|owner| {
+            self.image
+                .generic_params_of(owner)
+                .find(|gp| gp.number as u32 == number)
+                .map(|gp| gp.name.clone())
+        });
+
+        match (name, self.style, kind) {
+            (Some(name), TypeNameStyle::ILAsm, GenericParamKind::Type) => {
+                format!("!{}", ilasm_name(&name))
+            }
+            (Some(name), TypeNameStyle::ILAsm, GenericParamKind::Method) => {
+                format!("!!{}", ilasm_name(&name))
+            }
+            (Some(name), ..) => name,
+            (None, TypeNameStyle::CSharp, GenericParamKind::Type) => format!("T{number}"),
+            (None, TypeNameStyle::CSharp, GenericParamKind::Method) => format!("TMethod{number}"),
+            (None, _, GenericParamKind::Type) => format!("!{number}"),
+            (None, _, GenericParamKind::Method) => format!("!!{number}"),
+        }
+    }
+
+    /// Name of the assembly defining the element type of `element`, generic parameters have none
+    fn assembly_of(&self, element: &Element) -> Option<String> {
+        match element {
+            Element::Ptr(inner)
+            | Element::ByRef(inner)
+            | Element::Pinned(inner)
+            | Element::SzArray(inner)
+            | Element::Array(inner, _)
+            | Element::CModRequired(_, inner)
+            | Element::CModOptional(_, inner) => self.assembly_of(inner),
+            Element::GenericInst { generic_type, .. } => self.assembly_of(generic_type),
+            Element::Class(token) | Element::ValueType(token) => match self.named_type(*token) {
+                Some(NamedType {
+                    scope: TypeScope::Assembly(name),
+                    ..
+                }) => Some(name),
+                _ => Some(self.local_assembly_name()),
+            },
+            Element::Var(_) | Element::MVar(_) => None,
+            _ => Some(self.core_library.clone()),
+        }
+    }
+
+    fn local_assembly_name(&self) -> String {
+        if let Some(assembly) = self.image.assemblies.first() {
+            assembly.name.clone()
+        } else {
+            self.image
+                .modules
+                .first()
+                .map_or_else(String::new, |m| m.name.clone())
+        }
+    }
+}
+
+/// Name of the assembly that `System.Object` is resolved from
+fn core_library_name(image: &CilImage) -> String {
+    image
+        .type_refs
+        .iter()
+        .find(|tr| tr.namespace == "System" && tr.name == "Object")
+        .and_then(
+            |tr| match ResolutionScope::try_from(tr.resolution_scope as u32) {
+                Ok(ResolutionScope::AssemblyRef(index)) => image
+                    .assembly_refs
+                    .get((index as usize).checked_sub(1)?)
+                    .map(|a| a.name.clone()),
+                _ => None,
+            },
+        )
+        .or_else(|| {
+            // The core library itself defines System.Object
+            image
+                .type_defs
+                .iter()
+                .any(|td| td.type_namespace == "System" && td.type_name == "Object")
+                .then(|| image.assemblies.first().map(|a| a.name.clone()))
+                .flatten()
+        })
+        .unwrap_or_else(|| "mscorlib".to_string())
+}
+
+/// ILAsm keywords that can't be used as identifiers without quoting them
+#[rustfmt::skip]
+const ILASM_KEYWORDS: &[&str] = &[
+    "abstract", "add", "algorithm", "alignment", "and", "ansi", "any", "arglist", "array", "as",
+    "assembly", "assert", "at", "auto", "autochar", "beforefieldinit", "beq", "bge", "bgt", "ble",
+    "blt", "bne", "bool", "box", "br", "break", "brfalse", "brinst", "brnull", "brtrue", "brzero",
+    "bytearray", "call", "calli", "callvirt", "castclass", "catch", "cdecl", "ceq", "cf", "cgt",
+    "char", "cil", "ckfinite", "class", "clt", "const", "conv", "cpblk", "cpobj", "default",
+    "demand", "div", "dup", "endfault", "endfilter", "endfinally", "explicit", "extends", "extern",
+    "false", "famandassem", "family", "famorassem", "fastcall", "fault", "field", "filter", "final",
+    "finally", "float32", "float64", "forwardref", "handler", "hidebysig", "import", "in",
+    "initblk", "initobj", "initonly", "instance", "int", "int16", "int32", "int64", "int8",
+    "interface", "internalcall", "isinst", "jmp", "lasterr", "ldarg", "ldarga", "ldc", "ldelem",
+    "ldelema", "ldfld", "ldflda", "ldftn", "ldind", "ldlen", "ldloc", "ldloca", "ldnull", "ldobj",
+    "ldsfld", "ldsflda", "ldstr", "ldtoken", "ldvirtftn", "leave", "literal", "localloc", "managed",
+    "marshal", "method", "mkrefany", "modopt", "modreq", "mul", "native", "neg", "nested", "newarr",
+    "newobj", "newslot", "noinlining", "nomangle", "nop", "not", "notserialized", "null", "nullref",
+    "object", "opt", "optil", "or", "out", "permitonly", "pinned", "pinvokeimpl", "pop",
+    "preservesig", "private", "privatescope", "public", "record", "refany", "refanytype",
+    "refanyval", "rem", "request", "ret", "rethrow", "retval", "rtspecialname", "runtime", "sealed",
+    "sequential", "serializable", "shl", "shr", "sizeof", "specialname", "starg", "static",
+    "stdcall", "stelem", "stfld", "stind", "stloc", "stobj", "storage", "stsfld", "string",
+    "struct", "sub", "switch", "synchronized", "syschar", "sysstring", "tbstr", "thiscall", "throw",
+    "to", "true", "typedref", "uint", "uint16", "uint32", "uint64", "uint8", "unbox", "unicode",
+    "unmanaged", "unmanagedexp", "unsigned", "value", "valuetype", "vararg", "variant", "vector",
+    "virtual", "void", "wchar", "winapi", "with", "xor",
+];
+
+/// Quotes a name for ILAsm unless it is a dotted name made of valid identifiers, eg. `System.Object`
+/// is left as is but `<Module>` and `My.value.Ns` become `'<Module>'` and `'My.value.Ns'`
+pub fn ilasm_name(name: &str) -> String {
+    let is_identifier = |segment: &str| {
+        let mut chars = segment.chars();
+        chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || "_$@`?".contains(c))
+            && chars.all(|c| c.is_ascii_alphanumeric() || "_$@`?".contains(c))
+    };
+
+    // Constructors are the only names that may start with a dot
+    let valid = name == ".ctor"
+        || name == ".cctor"
+        || name
+            .split('.')
+            .all(|segment| is_identifier(segment) && !ILASM_KEYWORDS.contains(&segment));
+    if valid {
+        return name.to_string();
+    }
+
+    let mut quoted = String::with_capacity(name.len() + 2);
+    quoted.push('\'');
+    for c in name.chars() {
+        match c {
+            '\'' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('\'');
+    quoted
+}
+
+/// Splits the generic arity suffix off a metadata type name, eg. ``List`1`` becomes `("List", 1)`
+fn split_arity(name: &str) -> (&str, usize) {
+    name.rsplit_once('`')
+        .and_then(|(base, arity)| Some((base, arity.parse().ok()?)))
+        .unwrap_or((name, 0))
+}
+
+fn csharp_keyword(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "Void" => "void",
+        "Boolean" => "bool",
+        "Char" => "char",
+        "SByte" => "sbyte",
+        "Byte" => "byte",
+        "Int16" => "short",
+        "UInt16" => "ushort",
+        "Int32" => "int",
+        "UInt32" => "uint",
+        "Int64" => "long",
+        "UInt64" => "ulong",
+        "Single" => "float",
+        "Double" => "double",
+        "String" => "string",
+        "Object" => "object",
+        "IntPtr" => "nint",
+        "UIntPtr" => "nuint",
+        "Decimal" => "decimal",
+        _ => return None,
+    })
+}
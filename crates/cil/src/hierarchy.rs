@@ -0,0 +1,856 @@
+//! Inheritance and interface implementation queries (ECMA-335 II.10.1, II.12), optionally following
+//! base types and interfaces into referenced assemblies
+
+use std::{cell::RefCell, collections::HashMap, fmt::Debug};
+
+use crate::{
+    Result,
+    identity::AssemblyIdentity,
+    image::{CilImage, TypeName},
+    meta::{AssemblyRefHandle, ExportedTypeHandle, TypeDefHandle, TypeRefHandle, TypeSpecHandle},
+    signature::{Element, GenericContext, SignatureComparer},
+    tables::{ClassSemantics, Implementation, ResolutionScope, TypeDefOrRef},
+};
+
+/// How many type forwarders are followed before giving up on a type reference
+const MAX_FORWARDING_DEPTH: usize = 8;
+
+/// A type definition and the image it belongs to
+pub type ResolvedTypeDef<'a> = (&'a CilImage, TypeDefHandle);
+
+/// Provides the images of referenced assemblies
+pub trait AssemblyResolver {
+    /// Image of a referenced assembly, `None` if it isn't available
+    fn resolve(&self, assembly: &AssemblyIdentity) -> Option<&CilImage>;
+}
+
+/// Matches assemblies by simple name, ignoring versions and public keys
+impl AssemblyResolver for Vec<CilImage> {
+    fn resolve(&self, assembly: &AssemblyIdentity) -> Option<&CilImage> {
+        self.iter().find(|image| {
+            image
+                .assemblies
+                .first()
+                .is_some_and(|a| a.name.eq_ignore_ascii_case(&assembly.name))
+        })
+    }
+}
+
+/// A type together with the image its tokens belong to
+#[derive(Clone)]
+pub struct ScopedType<'a> {
+    pub image: &'a CilImage,
+    pub element: Element,
+    /// What the `Var` placeholders in `element` stand for, when a generic type from one image is
+    /// instantiated with arguments from another. Empty otherwise, arguments from the same image are
+    /// substituted into `element` directly.
+    pub type_args: Vec<ScopedType<'a>>,
+}
+
+impl<'a> ScopedType<'a> {
+    pub fn new(image: &'a CilImage, element: Element) -> Self {
+        Self {
+            image,
+            element,
+            type_args: vec![],
+        }
+    }
+
+    /// `element` in the scope of `image`, where `Var`s refer to `type_args`
+    pub(crate) fn with_args(
+        image: &'a CilImage,
+        element: &Element,
+        type_args: &[ScopedType<'a>],
+    ) -> Self {
+        if let Element::Var(index) = element
+            && let Some(arg) = type_args.get(index.0 as usize)
+        {
+            return arg.clone();
+        }
+
+        Self {
+            image,
+            element: element.clone(),
+            type_args: if element.is_open() {
+                type_args.to_vec()
+            } else {
+                vec![]
+            },
+        }
+    }
+}
+
+impl Debug for ScopedType<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.element.debug_print(self.image))?;
+        if !self.type_args.is_empty() {
+            f.debug_list().entries(&self.type_args).finish()?;
+        }
+        Ok(())
+    }
+}
+
+/// Answers inheritance questions about the types defined in an image.
+///
+/// Without a resolver, base types and interfaces defined in other assemblies are still reported, but
+/// their own bases and interfaces are unknown. Generic variance is not taken into account.
+pub struct TypeHierarchy<'a> {
+    pub(crate) image: &'a CilImage,
+    resolver: Option<&'a dyn AssemblyResolver>,
+    /// TypeRefs that have been resolved, keyed by image address
+    resolved: RefCell<HashMap<(usize, u32), Option<ResolvedTypeDef<'a>>>>,
+}
+
+impl<'a> TypeHierarchy<'a> {
+    pub fn new(image: &'a CilImage) -> Self {
+        Self {
+            image,
+            resolver: None,
+            resolved: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Follow type references into the assemblies `resolver` provides
+    pub fn with_resolver(mut self, resolver: &'a dyn AssemblyResolver) -> Self {
+        self.resolver = Some(resolver);
+        self
+    }
+
+    /// Finds the definition of a TypeDef, TypeRef or TypeSpec from `image`, following type forwarders.
+    /// TypeSpecs resolve to the definition of their generic type.
+    pub fn resolve(&self, image: &'a CilImage, token: TypeDefOrRef) -> Option<ResolvedTypeDef<'a>> {
+        match token {
+            TypeDefOrRef::TypeDef(index) => {
+                let handle = TypeDefHandle(index);
+                image.type_defs.get(handle.index()).map(|_| (image, handle))
+            }
+            TypeDefOrRef::TypeRef(index) => {
+                let key = (image as *const CilImage as usize, index);
+                if let Some(resolved) = self.resolved.borrow().get(&key) {
+                    return *resolved;
+                }
+                let resolved = self.resolve_type_ref(image, TypeRefHandle(index));
+                self.resolved.borrow_mut().insert(key, resolved);
+                resolved
+            }
+            TypeDefOrRef::TypeSpec(index) => match image.type_spec(TypeSpecHandle(index)).ok()? {
+                Element::GenericInst { generic_type, .. } => match *generic_type {
+                    Element::Class(token) | Element::ValueType(token) => self.resolve(image, token),
+                    _ => None,
+                },
+                Element::Class(token) | Element::ValueType(token) => self.resolve(image, token),
+                _ => None,
+            },
+        }
+    }
+
+    /// Direct base type of a type definition, `None` for `System.Object`, interfaces and `<Module>`
+    pub fn base_type_of(&self, handle: TypeDefHandle) -> Result<Option<ScopedType<'a>>> {
+        self.base_of(&self.type_def(self.image, handle))
+    }
+
+    /// Base types of a type definition, starting with the direct base type. Stops at the first base
+    /// type that can't be resolved.
+    pub fn base_types_of(&self, handle: TypeDefHandle) -> Result<Vec<ScopedType<'a>>> {
+        let mut bases: Vec<ScopedType<'a>> = vec![];
+        let mut current = self.base_type_of(handle)?;
+        while let Some(base) = current {
+            // Guard against malformed images with circular inheritance
+            if bases.iter().any(|b| self.same_type(b, &base)) {
+                break;
+            }
+            current = self.base_of(&base)?;
+            bases.push(base);
+        }
+        Ok(bases)
+    }
+
+    /// Interfaces listed in the InterfaceImpl table for a type definition
+    pub fn interfaces_of(&self, handle: TypeDefHandle) -> Result<Vec<ScopedType<'a>>> {
+        self.direct_interfaces(&self.type_def(self.image, handle))
+    }
+
+    /// Every interface a type implements, including the ones implemented by its base types and the
+    /// ones inherited by other interfaces
+    pub fn all_interfaces_of(&self, handle: TypeDefHandle) -> Result<Vec<ScopedType<'a>>> {
+        self.all_interfaces(&self.type_def(self.image, handle))
+    }
+
+    /// Type definitions in the image that derive from `base`, directly or indirectly.
+    ///
+    /// `base` is a type from the image. A generic type definition such as `List<T>` matches all of its
+    /// instantiations, a generic instance only matches itself.
+    pub fn derived_types_of(&self, base: &Element) -> Result<Vec<TypeDefHandle>> {
+        let base = ScopedType::new(self.image, base.clone());
+        let mut derived = vec![];
+        for handle in self.type_def_handles() {
+            if self
+                .base_types_of(handle)?
+                .iter()
+                .any(|b| self.matches(b, &base))
+            {
+                derived.push(handle);
+            }
+        }
+        Ok(derived)
+    }
+
+    /// Classes and value types in the image that implement `interface`, directly, through a base type
+    /// or through another interface. Matches generic definitions like [`Self::derived_types_of`].
+    pub fn implementors_of(&self, interface: &Element) -> Result<Vec<TypeDefHandle>> {
+        let interface = ScopedType::new(self.image, interface.clone());
+        let mut implementors = vec![];
+        for handle in self.type_def_handles() {
+            if matches!(
+                self.image.type_defs[handle.index()].flags.class_semantics(),
+                ClassSemantics::Interface
+            ) {
+                continue;
+            }
+            if self
+                .all_interfaces_of(handle)?
+                .iter()
+                .any(|i| self.matches(i, &interface))
+            {
+                implementors.push(handle);
+            }
+        }
+        Ok(implementors)
+    }
+
+    /// Whether a value of type `source` can be stored in a location of type `target`, both types from
+    /// the image. Follows `Type.IsAssignableFrom`: base types, interfaces (also for value types, which
+    /// are boxed), `System.Object` and array covariance.
+    ///
+    /// Primitive types such as `int32` or `string` stand for their `System` types. Their bases and
+    /// interfaces come from the core library when the resolver provides it, otherwise from the
+    /// members of the kernel profile listed in ECMA-335 IV.
+    pub fn is_assignable_to(&self, source: &Element, target: &Element) -> Result<bool> {
+        self.scoped_is_assignable_to(
+            &ScopedType::new(self.image, source.clone()),
+            &ScopedType::new(self.image, target.clone()),
+        )
+    }
+
+    fn scoped_is_assignable_to(
+        &self,
+        source: &ScopedType<'a>,
+        target: &ScopedType<'a>,
+    ) -> Result<bool> {
+        if self.same_type(source, target) {
+            return Ok(true);
+        }
+        // `string` and `class System.String` are the same type
+        if let Some(name) =
+            primitive_name(&source.element).or_else(|| primitive_name(&target.element))
+            && self.is_system_type(source, name)
+            && self.is_system_type(target, name)
+        {
+            return Ok(true);
+        }
+
+        let is_pointer_like = matches!(
+            source.element,
+            Element::Ptr(_) | Element::FnPtr(_) | Element::ByRef(_) | Element::TypedByRef
+        );
+        if is_pointer_like {
+            return Ok(false);
+        }
+        if self.is_system_type(target, "Object") {
+            return Ok(true);
+        }
+
+        if let Some(name) = primitive_name(&source.element) {
+            if let Some(definition) = self.core_type(source.image, name) {
+                return self.scoped_is_assignable_to(&definition, target);
+            }
+
+            let is_value_type = !matches!(source.element, Element::String | Element::Object);
+            if is_value_type && self.is_system_type(target, "ValueType") {
+                return Ok(true);
+            }
+            return Ok(builtin_interfaces(&source.element)
+                .into_iter()
+                .any(|(name, arg)| {
+                    self.builtin_type_matches(target, name, arg.as_ref(), source.image)
+                }));
+        }
+
+        match &source.element {
+            Element::SzArray(source_element) | Element::Array(source_element, _) => {
+                if self.is_system_type(target, "Array") {
+                    return Ok(true);
+                }
+                let source_element =
+                    ScopedType::with_args(source.image, source_element, &source.type_args);
+
+                let target_element = match (&source.element, &target.element) {
+                    (Element::SzArray(_), Element::SzArray(t)) => Some(t),
+                    (Element::Array(_, s), Element::Array(t, ts)) if s.rank == ts.rank => Some(t),
+                    _ => None,
+                };
+                if let Some(target_element) = target_element {
+                    let target_element =
+                        ScopedType::with_args(target.image, target_element, &target.type_args);
+                    return self.array_element_assignable(&source_element, &target_element);
+                }
+
+                // `T[]` implements `IList<T>` and the other generic collection interfaces
+                if matches!(source.element, Element::SzArray(_))
+                    && let Some([target_element]) = ARRAY_GENERIC_INTERFACES
+                        .iter()
+                        .find_map(|name| self.named_type_args(target, name))
+                        .as_deref()
+                {
+                    return self.array_element_assignable(&source_element, target_element);
+                }
+
+                // The remaining interfaces are the ones of `System.Array`
+                if let Some(array) = self.core_type(source.image, "Array") {
+                    return self.scoped_is_assignable_to(&array, target);
+                }
+                Ok(ARRAY_INTERFACES
+                    .iter()
+                    .any(|name| self.builtin_type_matches(target, name, None, source.image)))
+            }
+            Element::Class(_)
+            | Element::ValueType(_)
+            | Element::GenericInst { .. }
+            | Element::Var(_) => {
+                let mut current = self.base_of(source)?;
+                let mut depth = 0;
+                while let Some(base) = current {
+                    if self.same_type(&base, target) {
+                        return Ok(true);
+                    }
+                    depth += 1;
+                    if depth > self.image.type_defs.len() + 64 {
+                        break;
+                    }
+                    current = self.base_of(&base)?;
+                }
+
+                Ok(self
+                    .all_interfaces(source)?
+                    .iter()
+                    .any(|i| self.same_type(i, target)))
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Whether an array of `source` elements can be stored as an array of `target` elements. Arrays are
+    /// covariant in reference types only.
+    fn array_element_assignable(
+        &self,
+        source: &ScopedType<'a>,
+        target: &ScopedType<'a>,
+    ) -> Result<bool> {
+        if !is_reference_type(&source.element) {
+            return Ok(self.same_type(source, target));
+        }
+        self.scoped_is_assignable_to(source, target)
+    }
+
+    /// Definition of `System.<name>` in the core library, found through the references of `image`
+    fn core_type(&self, image: &'a CilImage, name: &str) -> Option<ScopedType<'a>> {
+        let type_name = TypeName {
+            namespace: "System".to_string(),
+            enclosing: vec![],
+            name: name.to_string(),
+        };
+
+        // The image may be the core library itself
+        let found = self.find_type_def(image, &type_name, 0).or_else(|| {
+            (1..=image.type_refs.len() as u32)
+                .find(|&i| image.type_ref_name(TypeRefHandle(i)).as_ref() == Some(&type_name))
+                .and_then(|i| self.resolve(image, TypeDefOrRef::TypeRef(i)))
+        });
+        let (image, handle) = found.or_else(|| {
+            (1..=image.assembly_refs.len() as u32).find_map(|i| {
+                let assembly = self.resolve_assembly(image, AssemblyRefHandle(i))?;
+                self.find_type_def(assembly, &type_name, 0)
+            })
+        })?;
+
+        Some(self.type_def(image, handle))
+    }
+
+    /// Generic arguments of `ty` if it is the type called `full_name` in any assembly, eg.
+    /// ``System.IComparable`1``
+    fn named_type_args(&self, ty: &ScopedType<'a>, full_name: &str) -> Option<Vec<ScopedType<'a>>> {
+        let (token, args) = match &ty.element {
+            Element::Class(token) | Element::ValueType(token) => (*token, &[][..]),
+            Element::GenericInst {
+                generic_type,
+                generic_args,
+            } => match generic_type.as_ref() {
+                Element::Class(token) | Element::ValueType(token) => (*token, &generic_args[..]),
+                _ => return None,
+            },
+            _ => return None,
+        };
+
+        (token.name_with_namespace(ty.image)? == full_name).then(|| {
+            args.iter()
+                .map(|a| ScopedType::with_args(ty.image, a, &ty.type_args))
+                .collect()
+        })
+    }
+
+    /// Whether `ty` is the type called `full_name`, instantiated with `arg` if it is generic
+    fn builtin_type_matches(
+        &self,
+        ty: &ScopedType<'a>,
+        full_name: &str,
+        arg: Option<&Element>,
+        image: &'a CilImage,
+    ) -> bool {
+        match (self.named_type_args(ty, full_name).as_deref(), arg) {
+            (Some([]), None) => true,
+            (Some([ty_arg]), Some(arg)) => {
+                self.same_type(ty_arg, &ScopedType::new(image, arg.clone()))
+            }
+            _ => false,
+        }
+    }
+
+    pub(crate) fn type_def_handles(&self) -> impl Iterator<Item = TypeDefHandle> + use<> {
+        (1..=self.image.type_defs.len() as u32).map(TypeDefHandle)
+    }
+
+    pub(crate) fn type_def(&self, image: &'a CilImage, handle: TypeDefHandle) -> ScopedType<'a> {
+        let token = TypeDefOrRef::TypeDef(handle.0);
+        ScopedType::new(
+            image,
+            if image.is_value_type(handle) {
+                Element::ValueType(token)
+            } else {
+                Element::Class(token)
+            },
+        )
+    }
+
+    /// The definition of a named or instantiated type, and its generic arguments
+    pub(crate) fn definition(
+        &self,
+        ty: &ScopedType<'a>,
+    ) -> Result<Option<(&'a CilImage, TypeDefHandle, Vec<ScopedType<'a>>)>> {
+        Ok(match &ty.element {
+            Element::Class(TypeDefOrRef::TypeSpec(index))
+            | Element::ValueType(TypeDefOrRef::TypeSpec(index)) => {
+                let element = ty.image.type_spec(TypeSpecHandle(*index))?;
+                return self.definition(&ScopedType::with_args(ty.image, &element, &ty.type_args));
+            }
+            Element::Class(token) | Element::ValueType(token) => self
+                .resolve(ty.image, *token)
+                .map(|(image, handle)| (image, handle, vec![])),
+            Element::GenericInst {
+                generic_type,
+                generic_args,
+            } => match generic_type.as_ref() {
+                Element::Class(token) | Element::ValueType(token) => {
+                    self.resolve(ty.image, *token).map(|(image, handle)| {
+                        let args = generic_args
+                            .iter()
+                            .map(|a| ScopedType::with_args(ty.image, a, &ty.type_args))
+                            .collect();
+                        (image, handle, args)
+                    })
+                }
+                _ => None,
+            },
+            _ => None,
+        })
+    }
+
+    /// `element` from the definition in `image`, with its type parameters bound to `args`
+    pub(crate) fn instantiate(
+        &self,
+        image: &'a CilImage,
+        element: Element,
+        args: &[ScopedType<'a>],
+    ) -> ScopedType<'a> {
+        if args
+            .iter()
+            .all(|a| std::ptr::eq(a.image, image) && a.type_args.is_empty())
+        {
+            let context =
+                GenericContext::new(args.iter().map(|a| a.element.clone()).collect(), vec![]);
+            ScopedType::new(image, element.substitute(&context))
+        } else {
+            ScopedType::with_args(image, &element, args)
+        }
+    }
+
+    pub(crate) fn type_element(&self, image: &CilImage, token: TypeDefOrRef) -> Result<Element> {
+        Ok(match token {
+            TypeDefOrRef::TypeSpec(index) => image.type_spec(TypeSpecHandle(index))?,
+            TypeDefOrRef::TypeDef(index) if image.is_value_type(TypeDefHandle(index)) => {
+                Element::ValueType(token)
+            }
+            _ => Element::Class(token),
+        })
+    }
+
+    pub(crate) fn base_of(&self, ty: &ScopedType<'a>) -> Result<Option<ScopedType<'a>>> {
+        let Some((image, handle, args)) = self.definition(ty)? else {
+            return Ok(None);
+        };
+
+        let extends = image.type_defs[handle.index()].extends as u32;
+        let base = match TypeDefOrRef::try_from(extends) {
+            Ok(TypeDefOrRef::TypeDef(0) | TypeDefOrRef::TypeRef(0) | TypeDefOrRef::TypeSpec(0))
+            | Err(_) => return Ok(None),
+            Ok(base) => base,
+        };
+
+        let element = self.type_element(image, base)?;
+        Ok(Some(self.instantiate(image, element, &args)))
+    }
+
+    fn direct_interfaces(&self, ty: &ScopedType<'a>) -> Result<Vec<ScopedType<'a>>> {
+        let Some((image, handle, args)) = self.definition(ty)? else {
+            return Ok(vec![]);
+        };
+
+        let mut interfaces = vec![];
+        for interface_impl in image.interface_impls_of(handle) {
+            let Ok(interface) = TypeDefOrRef::try_from(interface_impl.interface as u32) else {
+                continue;
+            };
+            let element = self.type_element(image, interface)?;
+            interfaces.push(self.instantiate(image, element, &args));
+        }
+        Ok(interfaces)
+    }
+
+    pub(crate) fn all_interfaces(&self, ty: &ScopedType<'a>) -> Result<Vec<ScopedType<'a>>> {
+        let mut interfaces: Vec<ScopedType<'a>> = vec![];
+        let push_unique = |interfaces: &mut Vec<ScopedType<'a>>, found: Vec<ScopedType<'a>>| {
+            for interface in found {
+                if !interfaces.iter().any(|i| self.same_type(i, &interface)) {
+                    interfaces.push(interface);
+                }
+            }
+        };
+
+        let mut current = Some(ty.clone());
+        let mut visited: Vec<ScopedType<'a>> = vec![];
+        while let Some(t) = current {
+            if visited.iter().any(|v| self.same_type(v, &t)) {
+                break;
+            }
+            push_unique(&mut interfaces, self.direct_interfaces(&t)?);
+            current = self.base_of(&t)?;
+            visited.push(t);
+        }
+
+        // Interfaces inherited by interfaces, the list grows while it is being walked
+        let mut i = 0;
+        while i < interfaces.len() {
+            let inherited = self.direct_interfaces(&interfaces[i].clone())?;
+            push_unique(&mut interfaces, inherited);
+            i += 1;
+        }
+
+        Ok(interfaces)
+    }
+
+    /// Whether `candidate` is `target`, or an instantiation of `target` if it's a generic definition
+    fn matches(&self, candidate: &ScopedType<'a>, target: &ScopedType<'a>) -> bool {
+        if let Element::GenericInst { generic_type, .. } = &candidate.element
+            && matches!(target.element, Element::Class(_) | Element::ValueType(_))
+            && self.same_type(
+                &ScopedType::with_args(candidate.image, generic_type, &candidate.type_args),
+                target,
+            )
+        {
+            return true;
+        }
+        self.same_type(candidate, target)
+    }
+
+    pub(crate) fn same_type(&self, a: &ScopedType<'a>, b: &ScopedType<'a>) -> bool {
+        let a_part = |e: &Element| ScopedType::with_args(a.image, e, &a.type_args);
+        let b_part = |e: &Element| ScopedType::with_args(b.image, e, &b.type_args);
+
+        match (&a.element, &b.element) {
+            (Element::Var(x), _) if (x.0 as usize) < a.type_args.len() => {
+                self.same_type(&a.type_args[x.0 as usize], b)
+            }
+            (_, Element::Var(y)) if (y.0 as usize) < b.type_args.len() => {
+                self.same_type(a, &b.type_args[y.0 as usize])
+            }
+            (
+                Element::Class(x) | Element::ValueType(x),
+                Element::Class(y) | Element::ValueType(y),
+            ) => self.same_definition(a.image, *x, b.image, *y),
+            (
+                Element::GenericInst {
+                    generic_type: x,
+                    generic_args: x_args,
+                },
+                Element::GenericInst {
+                    generic_type: y,
+                    generic_args: y_args,
+                },
+            ) => {
+                self.same_type(&a_part(x), &b_part(y))
+                    && x_args.len() == y_args.len()
+                    && x_args
+                        .iter()
+                        .zip(y_args)
+                        .all(|(x, y)| self.same_type(&a_part(x), &b_part(y)))
+            }
+            (Element::SzArray(x), Element::SzArray(y))
+            | (Element::Ptr(x), Element::Ptr(y))
+            | (Element::ByRef(x), Element::ByRef(y)) => self.same_type(&a_part(x), &b_part(y)),
+            (Element::Array(x, x_shape), Element::Array(y, y_shape)) => {
+                x_shape.rank == y_shape.rank && self.same_type(&a_part(x), &b_part(y))
+            }
+            (x, y) => SignatureComparer::new(a.image, b.image)
+                .ignore_assembly_versions(true)
+                .types_equal(x, y),
+        }
+    }
+
+    fn same_definition(
+        &self,
+        a_image: &'a CilImage,
+        a: TypeDefOrRef,
+        b_image: &'a CilImage,
+        b: TypeDefOrRef,
+    ) -> bool {
+        match (self.resolve(a_image, a), self.resolve(b_image, b)) {
+            (Some((a_image, a)), Some((b_image, b))) => std::ptr::eq(a_image, b_image) && a == b,
+            _ => SignatureComparer::new(a_image, b_image)
+                .ignore_assembly_versions(true)
+                .type_refs_equal(a, b),
+        }
+    }
+
+    /// Whether `ty` is `System.<name>`, referenced by name or by its element type
+    fn is_system_type(&self, ty: &ScopedType<'a>, name: &str) -> bool {
+        match &ty.element {
+            Element::Class(token) | Element::ValueType(token) => {
+                token.typename(ty.image).is_some_and(|n| {
+                    n.namespace == "System" && n.enclosing.is_empty() && n.name == name
+                })
+            }
+            element => primitive_name(element) == Some(name),
+        }
+    }
+
+    fn resolve_type_ref(
+        &self,
+        image: &'a CilImage,
+        handle: TypeRefHandle,
+    ) -> Option<ResolvedTypeDef<'a>> {
+        let name = image.type_ref_name(handle)?;
+
+        // The outermost TypeRef of a nested type holds the scope
+        let mut type_ref = image.type_refs.get(handle.index())?;
+        let mut depth = 0;
+        let scope = loop {
+            match ResolutionScope::try_from(type_ref.resolution_scope as u32).ok()? {
+                ResolutionScope::TypeRef(enclosing) if depth < image.type_refs.len() => {
+                    type_ref = image.type_refs.get(TypeRefHandle(enclosing).index())?;
+                    depth += 1;
+                }
+                scope => break scope,
+            }
+        };
+
+        let target = match scope {
+            ResolutionScope::AssemblyRef(index) => {
+                self.resolve_assembly(image, AssemblyRefHandle(index))?
+            }
+            // Multi-module assemblies aren't supported, so ModuleRefs are assumed to refer to this module
+            _ => image,
+        };
+        self.find_type_def(target, &name, 0)
+    }
+
+    fn resolve_assembly(
+        &self,
+        image: &'a CilImage,
+        handle: AssemblyRefHandle,
+    ) -> Option<&'a CilImage> {
+        let identity = image.assembly_ref_identity(handle).ok()?;
+        self.resolver?.resolve(&identity)
+    }
+
+    /// Type definition with the given name in `image`, following type forwarders
+    pub(crate) fn find_type_def(
+        &self,
+        image: &'a CilImage,
+        name: &TypeName,
+        depth: usize,
+    ) -> Option<ResolvedTypeDef<'a>> {
+        let found = image
+            .type_defs
+            .iter()
+            .enumerate()
+            .filter(|(_, td)| td.type_name == name.name)
+            .map(|(i, _)| TypeDefHandle(i as u32 + 1))
+            .find(|&handle| image.type_def_name(handle).as_ref() == Some(name));
+        if let Some(handle) = found {
+            return Some((image, handle));
+        }
+
+        // The type may have been forwarded to another assembly
+        if depth >= MAX_FORWARDING_DEPTH {
+            return None;
+        }
+        let exported = (1..=image.exported_types.len() as u32)
+            .map(ExportedTypeHandle)
+            .find(|&handle| exported_type_name(image, handle).as_ref() == Some(name))?;
+        let assembly = exported_type_assembly(image, exported)?;
+        let target = self.resolve_assembly(image, assembly)?;
+        self.find_type_def(target, name, depth + 1)
+    }
+}
+
+/// Generic interfaces the runtime implements for single-dimensional arrays, for the element type
+const ARRAY_GENERIC_INTERFACES: &[&str] = &[
+    "System.Collections.Generic.IList`1",
+    "System.Collections.Generic.ICollection`1",
+    "System.Collections.Generic.IEnumerable`1",
+    "System.Collections.Generic.IReadOnlyList`1",
+    "System.Collections.Generic.IReadOnlyCollection`1",
+];
+
+/// Interfaces of `System.Array`, used when the core library isn't available
+const ARRAY_INTERFACES: &[&str] = &[
+    "System.ICloneable",
+    "System.Collections.IList",
+    "System.Collections.ICollection",
+    "System.Collections.IEnumerable",
+];
+
+/// Name of the `System` type a primitive element type stands for
+fn primitive_name(element: &Element) -> Option<&'static str> {
+    Some(match element {
+        Element::Boolean => "Boolean",
+        Element::Char => "Char",
+        Element::I1 => "SByte",
+        Element::U1 => "Byte",
+        Element::I2 => "Int16",
+        Element::U2 => "UInt16",
+        Element::I4 => "Int32",
+        Element::U4 => "UInt32",
+        Element::I8 => "Int64",
+        Element::U8 => "UInt64",
+        Element::R4 => "Single",
+        Element::R8 => "Double",
+        Element::String => "String",
+        Element::Object => "Object",
+        Element::IntPtr => "IntPtr",
+        Element::UIntPtr => "UIntPtr",
+        _ => return None,
+    })
+}
+
+/// Interfaces of the primitive types in the kernel profile (ECMA-335 IV.5.3), with their generic
+/// argument. Used when the core library isn't available.
+fn builtin_interfaces(element: &Element) -> Vec<(&'static str, Option<Element>)> {
+    let own = |name| (name, Some(element.clone()));
+    match element {
+        Element::String => vec![
+            ("System.IComparable", None),
+            ("System.ICloneable", None),
+            ("System.IConvertible", None),
+            ("System.Collections.IEnumerable", None),
+            own("System.IComparable`1"),
+            own("System.IEquatable`1"),
+            (
+                "System.Collections.Generic.IEnumerable`1",
+                Some(Element::Char),
+            ),
+        ],
+        Element::Boolean | Element::Char => vec![
+            ("System.IComparable", None),
+            ("System.IConvertible", None),
+            own("System.IComparable`1"),
+            own("System.IEquatable`1"),
+        ],
+        Element::I1
+        | Element::U1
+        | Element::I2
+        | Element::U2
+        | Element::I4
+        | Element::U4
+        | Element::I8
+        | Element::U8
+        | Element::R4
+        | Element::R8 => vec![
+            ("System.IComparable", None),
+            ("System.IConvertible", None),
+            ("System.IFormattable", None),
+            own("System.IComparable`1"),
+            own("System.IEquatable`1"),
+        ],
+        Element::IntPtr | Element::UIntPtr => vec![own("System.IEquatable`1")],
+        _ => vec![],
+    }
+}
+
+fn is_reference_type(element: &Element) -> bool {
+    match element {
+        Element::Class(_)
+        | Element::String
+        | Element::Object
+        | Element::SzArray(_)
+        | Element::Array(..) => true,
+        Element::GenericInst { generic_type, .. } => matches!(**generic_type, Element::Class(_)),
+        _ => false,
+    }
+}
+
+fn exported_type_name(image: &CilImage, handle: ExportedTypeHandle) -> Option<TypeName> {
+    let mut exported = image.exported_types.get(handle.index())?;
+    let mut name = TypeName {
+        namespace: String::new(),
+        enclosing: vec![],
+        name: exported.type_name.clone(),
+    };
+
+    for _ in 0..image.exported_types.len() {
+        match Implementation::try_from(exported.implementation as u32).ok()? {
+            Implementation::ExportedType(enclosing) => {
+                exported = image
+                    .exported_types
+                    .get(ExportedTypeHandle(enclosing).index())?;
+                name.enclosing.insert(0, exported.type_name.clone());
+            }
+            _ => {
+                name.namespace = exported.type_namespace.clone();
+                return Some(name);
+            }
+        }
+    }
+
+    None
+}
+
+/// Assembly an exported type is forwarded to. Types in other modules of the same assembly aren't
+/// supported.
+fn exported_type_assembly(
+    image: &CilImage,
+    handle: ExportedTypeHandle,
+) -> Option<AssemblyRefHandle> {
+    let mut exported = image.exported_types.get(handle.index())?;
+    for _ in 0..image.exported_types.len() {
+        match Implementation::try_from(exported.implementation as u32).ok()? {
+            Implementation::AssemblyRef(index) => return Some(AssemblyRefHandle(index)),
+            Implementation::ExportedType(enclosing) => {
+                exported = image
+                    .exported_types
+                    .get(ExportedTypeHandle(enclosing).index())?;
+            }
+            Implementation::File(_) => return None,
+        }
+    }
+    None
+}
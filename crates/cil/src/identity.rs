@@ -0,0 +1,309 @@
+//! Assembly identities and their display names, eg. `mscorlib, Version=4.0.0.0, Culture=neutral, PublicKeyToken=b77a5c561934e089`
+
+use std::{fmt::Display, str::FromStr};
+
+use crate::{
+    Result,
+    error::Error,
+    image::CilImage,
+    meta::AssemblyRefHandle,
+    sha1::sha1,
+    tables::{AssemblyContentType, AssemblyFlags},
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct AssemblyVersion {
+    pub major: u16,
+    pub minor: u16,
+    pub build: u16,
+    pub revision: u16,
+}
+
+impl Display for AssemblyVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}.{}.{}.{}",
+            self.major, self.minor, self.build, self.revision
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PublicKeyOrToken {
+    None,
+    PublicKey(Vec<u8>),
+    Token([u8; 8]),
+}
+
+/// Name, version, culture and public key that identify an assembly
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssemblyIdentity {
+    pub name: String,
+    pub version: AssemblyVersion,
+    /// `None` for culture neutral assemblies
+    pub culture: Option<String>,
+    pub public_key: PublicKeyOrToken,
+    pub flags: AssemblyFlags,
+}
+
+impl AssemblyIdentity {
+    /// The public key token, computed from the public key if necessary
+    pub fn public_key_token(&self) -> Option<[u8; 8]> {
+        match &self.public_key {
+            PublicKeyOrToken::None => None,
+            PublicKeyOrToken::PublicKey(key) => Some(public_key_token(key)),
+            PublicKeyOrToken::Token(token) => Some(*token),
+        }
+    }
+
+    pub fn is_retargetable(&self) -> bool {
+        self.flags.is_retargetable()
+    }
+
+    pub fn content_type(&self) -> AssemblyContentType {
+        self.flags.content_type()
+    }
+
+    /// Parses a display name. Version components that are left out are 0, unknown attributes are ignored.
+    pub fn parse(display_name: &str) -> Result<Self> {
+        let invalid = |message: &str| Error::InvalidAssemblyName {
+            name: display_name.to_string(),
+            message: message.to_string(),
+        };
+
+        let mut parts = split_unescaped(display_name).into_iter();
+        let name = parts.next().map(|n| unescape(n.trim())).unwrap_or_default();
+        if name.is_empty() {
+            return Err(invalid("missing assembly name"));
+        }
+
+        let mut identity = Self {
+            name,
+            version: AssemblyVersion::default(),
+            culture: None,
+            public_key: PublicKeyOrToken::None,
+            flags: AssemblyFlags(0),
+        };
+
+        for part in parts {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| invalid("expected a key=value pair"))?;
+            let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+
+            match key.trim().to_ascii_lowercase().as_str() {
+                "version" => {
+                    let mut components = [0u16; 4];
+                    let values = value.split('.').collect::<Vec<_>>();
+                    if values.len() < 2 || values.len() > 4 {
+                        return Err(invalid("version needs 2 to 4 components"));
+                    }
+                    for (component, value) in components.iter_mut().zip(values) {
+                        *component = value
+                            .parse()
+                            .map_err(|_| invalid("invalid version component"))?;
+                    }
+                    let [major, minor, build, revision] = components;
+                    identity.version = AssemblyVersion {
+                        major,
+                        minor,
+                        build,
+                        revision,
+                    };
+                }
+                "culture" => {
+                    identity.culture =
+                        (!value.eq_ignore_ascii_case("neutral")).then(|| value.to_string());
+                }
+                "publickeytoken" => {
+                    identity.public_key = if value.eq_ignore_ascii_case("null") {
+                        PublicKeyOrToken::None
+                    } else {
+                        let token = parse_hex(value)
+                            .and_then(|t| <[u8; 8]>::try_from(t).ok())
+                            .ok_or_else(|| invalid("public key token must be 8 hex bytes"))?;
+                        PublicKeyOrToken::Token(token)
+                    };
+                }
+                "publickey" => {
+                    let key = parse_hex(value).ok_or_else(|| invalid("invalid public key"))?;
+                    identity.public_key = PublicKeyOrToken::PublicKey(key);
+                    identity.flags.0 |= 0x0001;
+                }
+                "retargetable" if value.eq_ignore_ascii_case("yes") => {
+                    identity.flags.0 |= 0x0100;
+                }
+                "contenttype" if value.eq_ignore_ascii_case("windowsruntime") => {
+                    identity.flags.0 |= u32::from(AssemblyContentType::WindowsRuntime) << 9;
+                }
+                _ => {}
+            }
+        }
+
+        Ok(identity)
+    }
+}
+
+impl FromStr for AssemblyIdentity {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s)
+    }
+}
+
+impl Display for AssemblyIdentity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}, Version={}, Culture={}, PublicKeyToken=",
+            escape(&self.name),
+            self.version,
+            self.culture.as_deref().unwrap_or("neutral")
+        )?;
+
+        match self.public_key_token() {
+            Some(token) => {
+                for b in token {
+                    write!(f, "{b:02x}")?;
+                }
+            }
+            None => write!(f, "null")?,
+        }
+
+        if self.is_retargetable() {
+            write!(f, ", Retargetable=Yes")?;
+        }
+        if self.content_type() == AssemblyContentType::WindowsRuntime {
+            write!(f, ", ContentType=WindowsRuntime")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Public key token of a public key: the last 8 bytes of its SHA-1 hash, in reverse order
+pub fn public_key_token(public_key: &[u8]) -> [u8; 8] {
+    let hash = sha1(public_key);
+    let mut token = [0u8; 8];
+    for (t, h) in token.iter_mut().zip(hash.iter().rev()) {
+        *t = *h;
+    }
+    token
+}
+
+impl CilImage {
+    /// Identity of the assembly defined by this image, `None` for modules without an assembly manifest
+    pub fn assembly_identity(&self) -> Result<Option<AssemblyIdentity>> {
+        let Some(assembly) = self.assemblies.first() else {
+            return Ok(None);
+        };
+
+        let public_key = self.blob(assembly.public_key_blob_index)?;
+        Ok(Some(AssemblyIdentity {
+            name: assembly.name.clone(),
+            version: AssemblyVersion {
+                major: assembly.major_version,
+                minor: assembly.minor_version,
+                build: assembly.build_number,
+                revision: assembly.revision_number,
+            },
+            culture: (!assembly.culture.is_empty()).then(|| assembly.culture.clone()),
+            public_key: if public_key.is_empty() {
+                PublicKeyOrToken::None
+            } else {
+                PublicKeyOrToken::PublicKey(public_key.to_vec())
+            },
+            flags: assembly.flags,
+        }))
+    }
+
+    pub fn assembly_ref_identity(&self, handle: AssemblyRefHandle) -> Result<AssemblyIdentity> {
+        let assembly_ref = self
+            .assembly_refs
+            .get(handle.index())
+            .ok_or(Error::InvalidToken(handle.token()))?;
+
+        let key = self.blob(assembly_ref.public_key_or_token_blob_index)?;
+        let public_key = if key.is_empty() {
+            PublicKeyOrToken::None
+        } else if assembly_ref.flags.has_public_key() {
+            PublicKeyOrToken::PublicKey(key.to_vec())
+        } else {
+            PublicKeyOrToken::Token(key.try_into().map_err(|_| {
+                Error::InvalidBlobIndex(assembly_ref.public_key_or_token_blob_index as u32)
+            })?)
+        };
+
+        Ok(AssemblyIdentity {
+            name: assembly_ref.name.clone(),
+            version: AssemblyVersion {
+                major: assembly_ref.major_version,
+                minor: assembly_ref.minor_version,
+                build: assembly_ref.build_number,
+                revision: assembly_ref.revision_number,
+            },
+            culture: (!assembly_ref.culture.is_empty()).then(|| assembly_ref.culture.clone()),
+            public_key,
+            flags: assembly_ref.flags,
+        })
+    }
+}
+
+/// Characters that have to be escaped in an assembly name
+const SPECIAL_CHARACTERS: &[char] = &[',', '=', '"', '\'', '\\'];
+
+fn escape(name: &str) -> String {
+    let mut s = String::with_capacity(name.len());
+    for c in name.chars() {
+        if SPECIAL_CHARACTERS.contains(&c) {
+            s.push('\\');
+        }
+        s.push(c);
+    }
+    s
+}
+
+fn unescape(name: &str) -> String {
+    let mut s = String::with_capacity(name.len());
+    let mut chars = name.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            s.extend(chars.next());
+        } else {
+            s.push(c);
+        }
+    }
+    s
+}
+
+/// Splits a display name at commas that aren't escaped
+fn split_unescaped(s: &str) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut start = 0;
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            ',' => {
+                parts.push(&s[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+fn parse_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) || !s.is_ascii() {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).ok())
+        .collect()
+}
@@ -1,48 +1,89 @@
 use binrw::BinReaderExt;
 use std::{
+    borrow::Cow,
     fmt::Display,
     io::{Cursor, Read, Seek, SeekFrom},
     path::Path,
 };
 
-use object::{Object, ObjectSection, pe::IMAGE_DIRECTORY_ENTRY_COM_DESCRIPTOR, read::pe::PeFile32};
+use object::{
+    FileKind, Object, ObjectSection,
+    pe::{IMAGE_DIRECTORY_ENTRY_COM_DESCRIPTOR, ImageNtHeaders32, ImageNtHeaders64},
+    read::pe::{ImageNtHeaders, ImageOptionalHeader, PeFile},
+};
 
 use crate::{
     Result,
     error::Error,
-    meta::{Guid, PhysicalMetadata, Token, TokenKind},
-    opcodes::RawOpcode,
-    signature::StandaloneMethodSignature,
-    tables::{self, MemberRefParent, TypeDefOrRef, member},
+    meta::{
+        EventHandle, FieldHandle, Guid, GuidIndex, MemberRefHandle, MethodDefHandle,
+        MethodSpecHandle, ParamHandle, PhysicalMetadata, PropertyHandle, StandAloneSigHandle,
+        Token, TokenKind, TypeDefHandle, TypeRefHandle, TypeSpecHandle,
+    },
+    opcodes::Instruction,
+    signature::{
+        Element, FieldSignature, GenericContext, LocalVarSignature, MemberRefSignature,
+        MethodSpecSignature, PropertySignature, SignatureKind, StandaloneMethodSignature,
+        TypeSpecSignature,
+    },
+    tables::{self, Layout, MemberRefParent, MethodDefOrRef, ResolutionScope, TypeDefOrRef},
 };
 use crate::{
+    body::{self, ExceptionClause},
     header::CliHeader,
+    index::RowIndices,
+    layout::LayoutEngine,
     strings::{BlobHeap, StringHeap, UserStringHeap},
 };
 
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct TypeName {
     pub namespace: String,
+    /// Names of the enclosing types, outermost first. Empty for types that aren't nested.
+    pub enclosing: Vec<String>,
     pub name: String,
 }
 
 impl TypeName {
     pub fn path_cxx(&self) -> String {
-        self.to_string().replace(".", "::")
+        self.namespace
+            .split('.')
+            .filter(|s| !s.is_empty())
+            .chain(self.enclosing.iter().map(String::as_str))
+            .chain(std::iter::once(self.name.as_str()))
+            .collect::<Vec<_>>()
+            .join("::")
     }
 }
 
 impl Display for TypeName {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if self.namespace.is_empty() {
-            write!(f, "{}", self.name)
-        } else {
-            write!(f, "{}.{}", self.namespace, self.name)
+        if !self.namespace.is_empty() {
+            write!(f, "{}.", self.namespace)?;
+        }
+        for enclosing in &self.enclosing {
+            write!(f, "{enclosing}/")?;
         }
+        write!(f, "{}", self.name)
     }
 }
 
+/// Layout information of a type definition, from its flags and the ClassLayout and FieldLayout tables
+#[derive(Debug, Clone)]
+pub struct TypeLayout {
+    pub kind: Layout,
+    /// Field alignment in bytes, 0 means the platform default. `None` without a ClassLayout row.
+    pub packing_size: Option<u16>,
+    /// Minimum size of the type in bytes. `None` without a ClassLayout row.
+    pub class_size: Option<u32>,
+    /// Explicit field offsets, only present for types with explicit layout
+    pub field_offsets: Vec<(FieldHandle, u32)>,
+}
+
 pub struct CilImage {
     pub code_base: u32,
+    /// Size of a pointer in bytes, 4 for PE32 images and 8 for PE32+ images
+    pub pointer_size: u32,
     pub header: CliHeader,
 
     pub guids: Vec<Guid>,
@@ -55,22 +96,27 @@ pub struct CilImage {
     pub type_refs: Vec<tables::TypeRef>,
     pub type_defs: Vec<tables::TypeDef>,
     pub fields: Vec<tables::Field>,
-    pub method_defs: Vec<(tables::Method, MethodHeader, Vec<(u32, RawOpcode)>)>,
+    pub method_defs: Vec<MethodDef>,
     pub params: Vec<tables::Param>,
     pub member_refs: Vec<tables::MemberRef>,
     pub custom_attributes: Vec<tables::CustomAttribute>,
+    pub field_marshals: Vec<tables::FieldMarshal>,
     pub stand_alone_sigs: Vec<tables::StandAloneSig>,
     pub assemblies: Vec<tables::Assembly>,
     pub assembly_refs: Vec<tables::AssemblyRef>,
+    pub exported_types: Vec<tables::ExportedType>,
     pub interface_impls: Vec<tables::InterfaceImpl>,
     pub constants: Vec<tables::Constant>,
     pub decl_security: Vec<tables::DeclSecurity>,
     pub class_layouts: Vec<tables::ClassLayout>,
     pub field_layouts: Vec<tables::FieldLayout>,
+    pub event_maps: Vec<tables::EventMap>,
+    pub events: Vec<tables::Event>,
     pub property_maps: Vec<tables::PropertyMap>,
     pub properties: Vec<tables::Property>,
     pub method_semantics: Vec<tables::MethodSemantics>,
     pub method_impls: Vec<tables::MethodImpl>,
+    pub module_refs: Vec<tables::ModuleRef>,
     pub type_specs: Vec<tables::TypeSpec>,
     pub impl_maps: Vec<tables::ImplMap>,
     pub nested_classes: Vec<tables::NestedClass>,
@@ -78,26 +124,49 @@ pub struct CilImage {
     pub method_specs: Vec<tables::MethodSpec>,
     pub generic_param_constraints: Vec<tables::GenericParamConstraint>,
     pub field_rvas: Vec<tables::FieldRva>,
+
+    /// Contents of the image file, for data referenced by RVA such as FieldRVA initializers
+    data: Vec<u8>,
+    sections: Vec<SectionData>,
+    /// Bit vector of the tables that are sorted by their key column. Keyed lookups binary search
+    /// these tables and build an index for the others the first time they are queried.
+    pub sorted_tables: u64,
+    pub(crate) row_indices: RowIndices,
+}
+
+struct SectionData {
+    rva: u32,
+    /// Size of the section once loaded, past the raw data it is filled with zeroes
+    virtual_size: u32,
+    /// Range of the raw data in the image file
+    file_range: std::ops::Range<usize>,
 }
 
 impl CilImage {
     pub fn load<A: AsRef<Path>>(path: A) -> Result<Self> {
-        let data = std::fs::read(path)?;
-        Self::read(&data)
+        Self::from_data(std::fs::read(path)?)
     }
 
     pub fn read(data: &[u8]) -> Result<Self> {
-        let obj = PeFile32::parse(data).map_err(|_| Error::InvalidCilImage)?;
+        Self::from_data(data.to_vec())
+    }
+
+    fn from_data(data: Vec<u8>) -> Result<Self> {
+        match FileKind::parse(&*data).map_err(|_| Error::InvalidCilImage)? {
+            FileKind::Pe32 => Self::read_pe::<ImageNtHeaders32>(data, 4),
+            FileKind::Pe64 => Self::read_pe::<ImageNtHeaders64>(data, 8),
+            _ => Err(Error::InvalidCilImage),
+        }
+    }
+
+    fn read_pe<Pe: ImageNtHeaders>(data: Vec<u8>, pointer_size: u32) -> Result<Self> {
+        let obj = PeFile::<Pe>::parse(&*data).map_err(|_| Error::InvalidCilImage)?;
         let dir = obj
             .data_directories()
             .get(IMAGE_DIRECTORY_ENTRY_COM_DESCRIPTOR)
             .ok_or(Error::InvalidCilImage)?;
 
-        let code_base = obj
-            .nt_headers()
-            .optional_header
-            .base_of_code
-            .get(object::LittleEndian);
+        let code_base = obj.nt_headers().optional_header().base_of_code();
 
         let cli_header_offset = dir.virtual_address.get(object::LittleEndian) - code_base;
         let code_section = obj
@@ -173,6 +242,7 @@ impl CilImage {
 
         let mut r = Self {
             code_base,
+            pointer_size,
             header: cli_header,
             guids,
             strings,
@@ -187,18 +257,23 @@ impl CilImage {
             params: vec![],
             member_refs: vec![],
             custom_attributes: vec![],
+            field_marshals: vec![],
             stand_alone_sigs: vec![],
             assemblies: vec![],
             assembly_refs: vec![],
+            exported_types: vec![],
             interface_impls: vec![],
             constants: vec![],
             decl_security: vec![],
             class_layouts: vec![],
             field_layouts: vec![],
+            event_maps: vec![],
+            events: vec![],
             property_maps: vec![],
             properties: vec![],
             method_semantics: vec![],
             method_impls: vec![],
+            module_refs: vec![],
             type_specs: vec![],
             impl_maps: vec![],
             nested_classes: vec![],
@@ -206,6 +281,24 @@ impl CilImage {
             method_specs: vec![],
             generic_param_constraints: vec![],
             field_rvas: vec![],
+
+            data: vec![],
+            sections: obj
+                .section_table()
+                .iter()
+                .filter_map(|section| {
+                    let (offset, size) = section.pe_file_range();
+                    Some(SectionData {
+                        rva: section.virtual_address.get(object::LittleEndian),
+                        virtual_size: section.virtual_size.get(object::LittleEndian),
+                        file_range: offset as usize
+                            ..(offset as usize).checked_add(size as usize)?,
+                    })
+                })
+                .filter(|section| section.file_range.end <= data.len())
+                .collect(),
+            sorted_tables: 0,
+            row_indices: RowIndices::default(),
         };
 
         let meta_streamheader = physical_metadata
@@ -218,6 +311,7 @@ impl CilImage {
         c.read_exact(&mut meta_data)?;
         let mut meta_stream = Cursor::new(meta_data);
         let logical_metadata: crate::meta::LogicalMetadataTables = meta_stream.read_le().unwrap();
+        r.sorted_tables = logical_metadata.sorted;
         let mut table = 0;
         for bit in 0..u64::BITS - 1 {
             if logical_metadata.valid & (1 << bit) != 0 {
@@ -252,7 +346,7 @@ impl CilImage {
                                 .read_le_args((&r.strings,))
                                 .expect("Failed to read Method table");
                             let (header, opcodes) =
-                                parse_cil_bytecode(&method, r.code_base as u64, &mut c)?;
+                                parse_cil_bytecode(&method, r.code_base as u64, c.get_ref())?;
                             // parse_cil_method(&method, code_base as u64, &mut c, &userstring_heap)
                             //     .expect("Failed to parse CIL method");
                             r.method_defs.push((method, header, opcodes));
@@ -288,7 +382,10 @@ impl CilImage {
                             r.custom_attributes.push(custom_attribute);
                         }
                         0x0D => {
-                            return Result::Err(Error::UnsupportedTable("FieldMarshal"));
+                            let field_marshal: tables::FieldMarshal = meta_stream
+                                .read_le()
+                                .expect("Failed to read FieldMarshal table");
+                            r.field_marshals.push(field_marshal);
                         }
                         0x0E => {
                             let decl_security: tables::DeclSecurity = meta_stream
@@ -315,10 +412,16 @@ impl CilImage {
                             r.stand_alone_sigs.push(stand_alone_sig);
                         }
                         0x12 => {
-                            return Result::Err(Error::UnsupportedTable("EventMap"));
+                            let event_map: tables::EventMap = meta_stream
+                                .read_le()
+                                .expect("Failed to read EventMap table");
+                            r.event_maps.push(event_map);
                         }
                         0x14 => {
-                            return Result::Err(Error::UnsupportedTable("Event"));
+                            let event: tables::Event = meta_stream
+                                .read_le_args((&r.strings,))
+                                .expect("Failed to read Event table");
+                            r.events.push(event);
                         }
                         0x15 => {
                             let property_map: tables::PropertyMap = meta_stream
@@ -345,7 +448,10 @@ impl CilImage {
                             r.method_impls.push(method_impl);
                         }
                         0x1A => {
-                            return Result::Err(Error::UnsupportedTable("ModuleRef"));
+                            let module_ref: tables::ModuleRef = meta_stream
+                                .read_le_args((&r.strings,))
+                                .expect("Failed to read ModuleRef table");
+                            r.module_refs.push(module_ref);
                         }
                         0x1B => {
                             let type_spec: tables::TypeSpec = meta_stream
@@ -393,7 +499,10 @@ impl CilImage {
                             return Result::Err(Error::UnsupportedTable("File"));
                         }
                         0x27 => {
-                            return Result::Err(Error::UnsupportedTable("ExportedType"));
+                            let exported_type: tables::ExportedType = meta_stream
+                                .read_le_args((&r.strings,))
+                                .expect("Failed to read ExportedType table");
+                            r.exported_types.push(exported_type);
                         }
                         0x28 => {
                             return Result::Err(Error::UnsupportedTable("ManifestResource"));
@@ -430,13 +539,457 @@ impl CilImage {
             }
         }
 
+        r.data = data;
         Ok(r)
     }
 
-    fn parse_method_signature(&self, index: u16) -> Result<Option<StandaloneMethodSignature>> {
-        let blob = self.blobs.get(index as u32).expect("Failed to get blob");
-        let mut reader = Cursor::new(blob);
-        Ok(Some(reader.read_le()?))
+    fn parse_method_signature(&self, index: u16) -> Result<StandaloneMethodSignature> {
+        StandaloneMethodSignature::parse(self.blob(index)?, self.pointer_size)
+    }
+
+    pub(crate) fn blob(&self, index: u16) -> Result<&[u8]> {
+        self.blobs
+            .get(index as u32)
+            .ok_or(Error::InvalidBlobIndex(index as u32))
+    }
+
+    /// GUID at a 1-based #GUID heap index, `None` for index 0
+    pub fn guid(&self, index: GuidIndex) -> Option<&Guid> {
+        self.guids.get((index.0 as usize).checked_sub(1)?)
+    }
+
+    /// Every GUID in the heap with its index. Stops at the last GUID a 16-bit [`GuidIndex`] can address.
+    pub fn guid_entries(&self) -> impl Iterator<Item = (GuidIndex, &Guid)> {
+        self.guids
+            .iter()
+            .enumerate()
+            .map_while(|(i, guid)| Some((GuidIndex(u16::try_from(i + 1).ok()?), guid)))
+    }
+
+    /// Module version ID, which identifies this particular build of the module
+    pub fn mvid(&self) -> Option<&Guid> {
+        self.guid(self.modules.first()?.mvid)
+    }
+
+    /// Type that a nested type definition is declared in, `None` for top-level types
+    pub fn enclosing_type_of(&self, handle: TypeDefHandle) -> Option<TypeDefHandle> {
+        self.nested_class_of(handle)
+            .map(|nc| TypeDefHandle(nc.enclosing_class as u32))
+    }
+
+    /// Types declared directly inside a type definition
+    pub fn nested_types_of(
+        &self,
+        handle: TypeDefHandle,
+    ) -> impl Iterator<Item = TypeDefHandle> + '_ {
+        self.nested_classes
+            .iter()
+            .filter(move |nc| nc.enclosing_class as u32 == handle.0)
+            .map(|nc| TypeDefHandle(nc.nested_class as u32))
+    }
+
+    /// Full name of a type definition, including the types it is nested in
+    pub fn type_def_name(&self, handle: TypeDefHandle) -> Option<TypeName> {
+        let type_def = self.type_defs.get(handle.index())?;
+        let mut name = TypeName {
+            namespace: type_def.type_namespace.clone(),
+            enclosing: vec![],
+            name: type_def.type_name.clone(),
+        };
+
+        let mut current = handle;
+        while let Some(enclosing) = self.enclosing_type_of(current) {
+            // Guard against cycles in malformed NestedClass tables
+            if name.enclosing.len() > self.nested_classes.len() {
+                break;
+            }
+            let outer = self.type_defs.get(enclosing.index())?;
+            name.enclosing.insert(0, outer.type_name.clone());
+            // Nested types have no namespace of their own
+            name.namespace = outer.type_namespace.clone();
+            current = enclosing;
+        }
+
+        Some(name)
+    }
+
+    /// Full name of a type reference, following ResolutionScope=TypeRef for nested types
+    pub fn type_ref_name(&self, handle: TypeRefHandle) -> Option<TypeName> {
+        let chain = self.type_ref_chain(handle)?;
+        let outermost = chain.last()?;
+        Some(TypeName {
+            // Nested types have no namespace of their own
+            namespace: outermost.namespace.clone(),
+            enclosing: chain[1..].iter().rev().map(|r| r.name.clone()).collect(),
+            name: chain[0].name.clone(),
+        })
+    }
+
+    /// A type reference followed by the references it is nested in, innermost first. The resolution
+    /// scope of the last one says where the type lives.
+    pub(crate) fn type_ref_chain(&self, handle: TypeRefHandle) -> Option<Vec<&tables::TypeRef>> {
+        let mut chain = vec![self.type_refs.get(handle.index())?];
+        while let Ok(ResolutionScope::TypeRef(enclosing)) =
+            ResolutionScope::try_from(chain.last()?.resolution_scope as u32)
+        {
+            // Guard against cycles in malformed ResolutionScope columns
+            if chain.len() > self.type_refs.len() {
+                break;
+            }
+            chain.push(self.type_refs.get((enclosing as usize).checked_sub(1)?)?);
+        }
+
+        Some(chain)
+    }
+
+    /// Methods owned by a type definition, ie. the run starting at its MethodList up to the next type's
+    pub fn methods_of(
+        &self,
+        handle: TypeDefHandle,
+    ) -> impl Iterator<Item = MethodDefHandle> + use<> {
+        let end = self.method_defs.len() as u32 + 1;
+        let start = self
+            .type_defs
+            .get(handle.index())
+            .map_or(end, |td| (td.method_list as u32).min(end));
+        let next = self
+            .type_defs
+            .get(handle.index() + 1)
+            .map_or(end, |td| (td.method_list as u32).clamp(start, end));
+        (start..next).map(MethodDefHandle)
+    }
+
+    /// Type definition whose method list contains a method
+    pub fn declaring_type_of(&self, handle: MethodDefHandle) -> Option<TypeDefHandle> {
+        self.method_defs.get(handle.index())?;
+        // Method lists are ascending, the owner is the last type whose list starts at or before the method
+        let owners = self
+            .type_defs
+            .partition_point(|td| td.method_list as u32 <= handle.0);
+        owners.checked_sub(1).map(|i| TypeDefHandle(i as u32 + 1))
+    }
+
+    /// Type definition whose field list contains a field
+    pub fn declaring_type_of_field(&self, handle: FieldHandle) -> Option<TypeDefHandle> {
+        self.fields.get(handle.index())?;
+        let owners = self
+            .type_defs
+            .partition_point(|td| td.field_list as u32 <= handle.0);
+        owners.checked_sub(1).map(|i| TypeDefHandle(i as u32 + 1))
+    }
+
+    /// Fields owned by a type definition, ie. the run starting at its FieldList up to the next type's
+    pub fn fields_of(&self, handle: TypeDefHandle) -> impl Iterator<Item = FieldHandle> + use<> {
+        let end = self.fields.len() as u32 + 1;
+        let start = self
+            .type_defs
+            .get(handle.index())
+            .map_or(end, |td| (td.field_list as u32).min(end));
+        let next = self
+            .type_defs
+            .get(handle.index() + 1)
+            .map_or(end, |td| (td.field_list as u32).clamp(start, end));
+        (start..next).map(FieldHandle)
+    }
+
+    /// Param rows owned by a method definition, ie. the run starting at its ParamList up to the next method's
+    pub fn params_of(&self, handle: MethodDefHandle) -> impl Iterator<Item = ParamHandle> + use<> {
+        let end = self.params.len() as u32 + 1;
+        let start = self
+            .method_defs
+            .get(handle.index())
+            .map_or(end, |(m, _, _)| (m.param_list as u32).min(end));
+        let next = self
+            .method_defs
+            .get(handle.index() + 1)
+            .map_or(end, |(m, _, _)| (m.param_list as u32).clamp(start, end));
+        (start..next).map(ParamHandle)
+    }
+
+    /// Properties owned by a type definition, ie. the run starting at the PropertyList of its
+    /// PropertyMap row up to the next row's
+    pub fn properties_of(
+        &self,
+        handle: TypeDefHandle,
+    ) -> impl Iterator<Item = PropertyHandle> + use<> {
+        let end = self.properties.len() as u32 + 1;
+        let Some(row) = self
+            .property_maps
+            .iter()
+            .position(|pm| pm.parent as u32 == handle.0)
+        else {
+            return (end..end).map(PropertyHandle);
+        };
+        let start = (self.property_maps[row].property_list as u32).min(end);
+        let next = self
+            .property_maps
+            .get(row + 1)
+            .map_or(end, |pm| (pm.property_list as u32).clamp(start, end));
+        (start..next).map(PropertyHandle)
+    }
+
+    /// Events owned by a type definition, ie. the run starting at the EventList of its EventMap row
+    /// up to the next row's
+    pub fn events_of(&self, handle: TypeDefHandle) -> impl Iterator<Item = EventHandle> + use<> {
+        let end = self.events.len() as u32 + 1;
+        let Some(row) = self
+            .event_maps
+            .iter()
+            .position(|em| em.parent as u32 == handle.0)
+        else {
+            return (end..end).map(EventHandle);
+        };
+        let start = (self.event_maps[row].event_list as u32).min(end);
+        let next = self
+            .event_maps
+            .get(row + 1)
+            .map_or(end, |em| (em.event_list as u32).clamp(start, end));
+        (start..next).map(EventHandle)
+    }
+
+    pub fn method_signature(&self, handle: MethodDefHandle) -> Result<StandaloneMethodSignature> {
+        let (method, _, _) = self
+            .method_defs
+            .get(handle.index())
+            .ok_or(Error::InvalidToken(handle.token()))?;
+        self.parse_method_signature(method.signature_blob_index)
+    }
+
+    pub fn member_ref_signature(&self, handle: MemberRefHandle) -> Result<MemberRefSignature> {
+        let member_ref = self
+            .member_refs
+            .get(handle.index())
+            .ok_or(Error::InvalidToken(handle.token()))?;
+        MemberRefSignature::parse(
+            self.blob(member_ref.signature_blob_index)?,
+            self.pointer_size,
+        )
+    }
+
+    pub fn field_signature(&self, handle: FieldHandle) -> Result<FieldSignature> {
+        let field = self
+            .fields
+            .get(handle.index())
+            .ok_or(Error::InvalidToken(handle.token()))?;
+        FieldSignature::parse(self.blob(field.signature_blob_index)?, self.pointer_size)
+    }
+
+    /// Decodes the type of a field definition
+    pub fn field_type(&self, handle: FieldHandle) -> Result<Element> {
+        Ok(self.field_signature(handle)?.field_type)
+    }
+
+    pub fn property_signature(&self, handle: PropertyHandle) -> Result<PropertySignature> {
+        let property = self
+            .properties
+            .get(handle.index())
+            .ok_or(Error::InvalidToken(handle.token()))?;
+        PropertySignature::parse(self.blob(property.type_blob_index)?, self.pointer_size)
+    }
+
+    /// Decodes the local variable signature referenced by a method header
+    pub fn local_var_signature(&self, handle: StandAloneSigHandle) -> Result<LocalVarSignature> {
+        let sig = self
+            .stand_alone_sigs
+            .get(handle.index())
+            .ok_or(Error::InvalidToken(handle.token()))?;
+        LocalVarSignature::parse(self.blob(sig.signature_blob_index)?, self.pointer_size)
+    }
+
+    /// Decodes a standalone method signature, as referenced by `calli`
+    pub fn call_site_signature(
+        &self,
+        handle: StandAloneSigHandle,
+    ) -> Result<StandaloneMethodSignature> {
+        let sig = self
+            .stand_alone_sigs
+            .get(handle.index())
+            .ok_or(Error::InvalidToken(handle.token()))?;
+        self.parse_method_signature(sig.signature_blob_index)
+    }
+
+    pub fn method_spec_signature(&self, handle: MethodSpecHandle) -> Result<MethodSpecSignature> {
+        let method_spec = self
+            .method_specs
+            .get(handle.index())
+            .ok_or(Error::InvalidToken(handle.token()))?;
+        MethodSpecSignature::parse(
+            self.blob(method_spec.instantiation_blob_index)?,
+            self.pointer_size,
+        )
+    }
+
+    /// Decodes the type described by a TypeSpec row
+    pub fn type_spec(&self, handle: TypeSpecHandle) -> Result<Element> {
+        let type_spec = self
+            .type_specs
+            .get(handle.index())
+            .ok_or(Error::InvalidToken(handle.token()))?;
+        Ok(TypeSpecSignature::parse(
+            self.blob(type_spec.signature_blob_index)?,
+            self.pointer_size,
+        )?
+        .element)
+    }
+
+    /// Generic context for members accessed through `parent`. Only TypeSpec parents that instantiate a
+    /// generic type provide arguments, for any other parent the context is empty.
+    pub fn member_ref_parent_context(&self, parent: MemberRefParent) -> Result<GenericContext> {
+        match parent {
+            MemberRefParent::TypeSpec(index) => Ok(GenericContext::from_type(
+                &self.type_spec(TypeSpecHandle(index))?,
+            )),
+            _ => Ok(GenericContext::default()),
+        }
+    }
+
+    /// Signature of a MemberRef with the generic arguments of its parent applied,
+    /// eg. `List<string>::Add` takes a `string` rather than `!0`
+    pub fn instantiated_member_ref_signature(
+        &self,
+        handle: MemberRefHandle,
+    ) -> Result<MemberRefSignature> {
+        let member_ref = self
+            .member_refs
+            .get(handle.index())
+            .ok_or(Error::InvalidToken(handle.token()))?;
+        let parent = MemberRefParent::try_from(member_ref.class_index as u32)
+            .map_err(|_| Error::InvalidToken(handle.token()))?;
+        let context = self.member_ref_parent_context(parent)?;
+        Ok(self.member_ref_signature(handle)?.substitute(&context))
+    }
+
+    /// Signature of a generic method instantiation, with the method arguments and, for methods
+    /// referenced through a TypeSpec, the type arguments of the parent applied
+    pub fn instantiated_method_spec_signature(
+        &self,
+        handle: MethodSpecHandle,
+    ) -> Result<StandaloneMethodSignature> {
+        let method_spec = self
+            .method_specs
+            .get(handle.index())
+            .ok_or(Error::InvalidToken(handle.token()))?;
+        let method_args = self.method_spec_signature(handle)?.generic_args;
+
+        match MethodDefOrRef::try_from(method_spec.method as u32) {
+            Ok(MethodDefOrRef::MethodDef(index)) => {
+                let context = GenericContext::new(vec![], method_args);
+                Ok(self
+                    .method_signature(MethodDefHandle(index))?
+                    .substitute(&context))
+            }
+            Ok(MethodDefOrRef::MemberRef(index)) => {
+                let handle = MemberRefHandle(index);
+                let member_ref = self
+                    .member_refs
+                    .get(handle.index())
+                    .ok_or(Error::InvalidToken(handle.token()))?;
+                let parent = MemberRefParent::try_from(member_ref.class_index as u32)
+                    .map_err(|_| Error::InvalidToken(handle.token()))?;
+                // Both kinds of parameters are substituted at once, as the type arguments of the
+                // parent may themselves refer to the method parameters of the caller, eg. `!!0`
+                let context = self
+                    .member_ref_parent_context(parent)?
+                    .with_method_args(method_args);
+                match self.member_ref_signature(handle)? {
+                    MemberRefSignature::Method(signature) => Ok(signature.substitute(&context)),
+                    MemberRefSignature::Field(_) => Err(Error::UnexpectedSignatureKind {
+                        expected: SignatureKind::StandaloneMethod,
+                        found: 0x06,
+                    }),
+                }
+            }
+            Err(_) => Err(Error::InvalidToken(handle.token())),
+        }
+    }
+
+    /// Returns `size` bytes of section data starting at `rva`. Past its raw data, a section reads as
+    /// zeroes up to its virtual size, only those reads are copied.
+    pub fn data_at_rva(&self, rva: u32, size: usize) -> Option<Cow<'_, [u8]>> {
+        self.sections.iter().find_map(|section| {
+            let start = rva.checked_sub(section.rva)? as usize;
+            let end = start.checked_add(size)?;
+            let raw = &self.data[section.file_range.clone()];
+            if end <= raw.len() {
+                return Some(Cow::Borrowed(&raw[start..end]));
+            }
+
+            // The loader fills the rest of the section up to its virtual size with zeroes
+            if end > (section.virtual_size as usize).max(raw.len()) {
+                return None;
+            }
+            let mut data = raw.get(start..).unwrap_or_default().to_vec();
+            data.resize(size, 0);
+            Some(Cow::Owned(data))
+        })
+    }
+
+    pub fn type_layout(&self, handle: TypeDefHandle) -> Result<TypeLayout> {
+        let type_def = self
+            .type_defs
+            .get(handle.index())
+            .ok_or(Error::InvalidToken(handle.token()))?;
+        let class_layout = self.class_layout_of(handle);
+
+        Ok(TypeLayout {
+            kind: type_def.flags.layout(),
+            packing_size: class_layout.map(|cl| cl.packing_size),
+            class_size: class_layout.map(|cl| cl.class_size),
+            field_offsets: self
+                .fields_of(handle)
+                .filter_map(|f| Some((f, self.field_offset(f)?)))
+                .collect(),
+        })
+    }
+
+    /// Explicit offset of a field in a type with explicit layout
+    pub fn field_offset(&self, handle: FieldHandle) -> Option<u32> {
+        self.field_layout_of(handle).map(|fl| fl.offset)
+    }
+
+    /// RVA of the initial data of a field, see [`Self::initial_data`]
+    pub fn field_rva(&self, handle: FieldHandle) -> Option<u32> {
+        self.field_rva_of(handle).map(|fr| fr.rva)
+    }
+
+    /// Data a field with an RVA is mapped to, eg. the contents of `static readonly byte[] Table = {...}`,
+    /// which the compiler stores in a `<PrivateImplementationDetails>` field of a fixed size struct.
+    ///
+    /// The length of the slice is the size of the field's type. Returns `None` for fields without a
+    /// FieldRVA row.
+    pub fn initial_data(&self, handle: FieldHandle) -> Result<Option<Cow<'_, [u8]>>> {
+        let Some(rva) = self.field_rva(handle) else {
+            return Ok(None);
+        };
+
+        let size = LayoutEngine::new(self)
+            .size_of(&self.field_type(handle)?)?
+            .known()
+            .ok_or(Error::UnknownFieldSize(handle.token()))?
+            .size;
+        self.data_at_rva(rva, size as usize)
+            .map(Some)
+            .ok_or(Error::InvalidRva(rva))
+    }
+
+    /// Whether a type definition is a value type, ie. it extends `System.ValueType` or `System.Enum`
+    pub fn is_value_type(&self, handle: TypeDefHandle) -> bool {
+        let Some(type_def) = self.type_defs.get(handle.index()) else {
+            return false;
+        };
+        // System.Enum itself extends System.ValueType, but is a reference type
+        if type_def.extends == 0
+            || (type_def.type_namespace == "System" && type_def.type_name == "Enum")
+        {
+            return false;
+        }
+
+        TypeDefOrRef::try_from(type_def.extends as u32)
+            .ok()
+            .and_then(|base| base.typename(self))
+            .is_some_and(|base| {
+                base.namespace == "System" && (base.name == "ValueType" || base.name == "Enum")
+            })
     }
 
     pub fn class_name(&self, index: u16) -> Result<Option<TypeName>> {
@@ -453,8 +1006,7 @@ impl CilImage {
                 let member_ref = self.member_refs.get(token.index() as usize - 1).unwrap();
                 let signature = self
                     .parse_method_signature(member_ref.signature_blob_index)
-                    .expect("Failed to parse method signature")
-                    .unwrap_or_default();
+                    .expect("Failed to parse method signature");
                 let class_name = self.class_name(member_ref.class_index).unwrap().unwrap();
                 Some((class_name, member_ref.name.clone(), signature))
             }
@@ -462,11 +1014,11 @@ impl CilImage {
                 let (method_def, _, _) = self.method_defs.get(token.index() as usize - 1).unwrap();
                 let signature = self
                     .parse_method_signature(method_def.signature_blob_index)
-                    .expect("Failed to parse method signature")
-                    .unwrap_or_default();
+                    .expect("Failed to parse method signature");
                 Some((
                     TypeName {
                         namespace: "".to_string(),
+                        enclosing: vec![],
                         name: "this".to_string(),
                     },
                     method_def.name.clone(),
@@ -480,71 +1032,38 @@ impl CilImage {
     }
 }
 
+/// Method row, header and decoded body
+pub type MethodDef = (tables::Method, MethodHeader, Vec<Instruction>);
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct MethodHeader {
     pub max_stack: u16,
     pub code_size: u32,
     pub local_var_sig_token: Option<Token>,
+    /// Whether local variables are zero-initialized
+    pub init_locals: bool,
+    pub exception_clauses: Vec<ExceptionClause>,
 }
 
 fn parse_cil_bytecode(
     def: &tables::Method,
     code_base: u64,
-    code: &mut Cursor<&[u8]>,
-) -> Result<(MethodHeader, Vec<(u32, RawOpcode)>)> {
-    if def.flags.is_abstract() {
-        println!(".method abstract {}() {{}}", def.name);
+    code: &[u8],
+) -> Result<(MethodHeader, Vec<Instruction>)> {
+    // Abstract, runtime-implemented and P/Invoke methods have no body
+    if def.flags.is_abstract() || def.rva == 0 {
         return Ok((
             MethodHeader {
                 max_stack: 0,
                 code_size: 0,
                 local_var_sig_token: None,
+                init_locals: false,
+                exception_clauses: Vec::new(),
             },
             Vec::new(),
         ));
     }
 
-    let header_start = def.rva as u64 - code_base;
-    code.set_position(header_start);
-
-    let header: u8 = code.read_le()?;
-    let is_fat = header & 0x3 == 3;
-
-    let header = if is_fat {
-        code.set_position(header_start);
-        let b: u16 = code.read_le()?;
-        let _flags = b & 0xFFF;
-        let size = b >> 12;
-
-        let max_stack: u16 = code.read_le()?;
-        let code_size: u32 = code.read_le()?;
-        let local_var_sig_token: Token = code.read_le()?;
-
-        code.set_position(header_start + (size * 4) as u64);
-
-        MethodHeader {
-            max_stack,
-            code_size,
-            local_var_sig_token: Some(local_var_sig_token),
-        }
-    } else {
-        let size = header >> 2;
-        MethodHeader {
-            code_size: size as u32,
-            max_stack: 8,
-            local_var_sig_token: None,
-        }
-    };
-
-    let mut data = vec![0u8; header.code_size as usize];
-    code.read_exact(&mut data)?;
-
-    let mut opcodes = Vec::new();
-    let mut cil_cursor = Cursor::new(data);
-    while cil_cursor.position() < cil_cursor.get_ref().len() as u64 {
-        let offset = cil_cursor.position() as u32;
-        let opcode: RawOpcode = cil_cursor.read_le()?;
-        opcodes.push((offset, opcode));
-    }
-
-    Ok((header, opcodes))
+    let header_start = (def.rva as u64 - code_base) as usize;
+    body::decode_method_body(code.get(header_start..).ok_or(Error::InvalidRva(def.rva))?)
 }
@@ -0,0 +1,243 @@
+//! Lookup of rows by their key column, for the tables ECMA-335 II.22 requires to be sorted.
+//!
+//! Tables the image marks as sorted are binary searched, for any other table an index is built the
+//! first time it is queried.
+
+use std::{collections::HashMap, ops::Range, sync::OnceLock};
+
+use crate::{
+    image::CilImage,
+    meta::{FieldHandle, TypeDefHandle},
+    tables::{
+        self, HasConstant, HasCustomAttribute, HasDeclSecurity, HasFieldMarshal, HasSemantics,
+        MemberForwarded, TypeOrMethodDef,
+    },
+};
+
+const INTERFACE_IMPL: usize = 0x09;
+const CONSTANT: usize = 0x0B;
+const CUSTOM_ATTRIBUTE: usize = 0x0C;
+const FIELD_MARSHAL: usize = 0x0D;
+const DECL_SECURITY: usize = 0x0E;
+const CLASS_LAYOUT: usize = 0x0F;
+const FIELD_LAYOUT: usize = 0x10;
+const METHOD_SEMANTICS: usize = 0x18;
+const METHOD_IMPL: usize = 0x19;
+const IMPL_MAP: usize = 0x1C;
+const FIELD_RVA: usize = 0x1D;
+const NESTED_CLASS: usize = 0x29;
+const GENERIC_PARAM: usize = 0x2A;
+
+/// Key to row index maps for unsorted tables, built on demand
+pub(crate) struct RowIndices {
+    tables: [OnceLock<HashMap<u32, Vec<u32>>>; 64],
+}
+
+impl Default for RowIndices {
+    fn default() -> Self {
+        Self {
+            tables: std::array::from_fn(|_| OnceLock::new()),
+        }
+    }
+}
+
+/// Zero-based indices of the rows matching a key
+pub(crate) enum Rows<'a> {
+    Sorted(Range<usize>),
+    Indexed(std::slice::Iter<'a, u32>),
+}
+
+impl Iterator for Rows<'_> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        match self {
+            Rows::Sorted(range) => range.next(),
+            Rows::Indexed(rows) => rows.next().map(|&i| i as usize),
+        }
+    }
+}
+
+impl CilImage {
+    /// Finds the rows of `table` whose key column equals `value`
+    pub(crate) fn rows_by_key<T>(
+        &self,
+        table: usize,
+        rows: &[T],
+        key: impl Fn(&T) -> u32,
+        value: u32,
+    ) -> Rows<'_> {
+        if self.sorted_tables & (1 << table) != 0 {
+            let start = rows.partition_point(|r| key(r) < value);
+            let end = start + rows[start..].partition_point(|r| key(r) == value);
+            Rows::Sorted(start..end)
+        } else {
+            let index = self.row_indices.tables[table].get_or_init(|| {
+                let mut index: HashMap<u32, Vec<u32>> = HashMap::new();
+                for (i, row) in rows.iter().enumerate() {
+                    index.entry(key(row)).or_default().push(i as u32);
+                }
+                index
+            });
+            Rows::Indexed(index.get(&value).map_or([].iter(), |rows| rows.iter()))
+        }
+    }
+
+    pub fn constant_of(&self, parent: HasConstant) -> Option<&tables::Constant> {
+        self.rows_by_key(
+            CONSTANT,
+            &self.constants,
+            |c| c.parent as u32,
+            parent.encode(),
+        )
+        .next()
+        .map(|i| &self.constants[i])
+    }
+
+    pub fn custom_attributes_of(
+        &self,
+        parent: HasCustomAttribute,
+    ) -> impl Iterator<Item = &tables::CustomAttribute> {
+        self.rows_by_key(
+            CUSTOM_ATTRIBUTE,
+            &self.custom_attributes,
+            |ca| ca.parent_index as u32,
+            parent.encode(),
+        )
+        .map(|i| &self.custom_attributes[i])
+    }
+
+    pub fn field_marshal_of(&self, parent: HasFieldMarshal) -> Option<&tables::FieldMarshal> {
+        self.rows_by_key(
+            FIELD_MARSHAL,
+            &self.field_marshals,
+            |fm| fm.parent as u32,
+            parent.encode(),
+        )
+        .next()
+        .map(|i| &self.field_marshals[i])
+    }
+
+    pub fn decl_security_of(
+        &self,
+        parent: HasDeclSecurity,
+    ) -> impl Iterator<Item = &tables::DeclSecurity> {
+        self.rows_by_key(
+            DECL_SECURITY,
+            &self.decl_security,
+            |ds| ds.parent as u32,
+            parent.encode(),
+        )
+        .map(|i| &self.decl_security[i])
+    }
+
+    pub fn class_layout_of(&self, handle: TypeDefHandle) -> Option<&tables::ClassLayout> {
+        self.rows_by_key(
+            CLASS_LAYOUT,
+            &self.class_layouts,
+            |cl| cl.parent as u32,
+            handle.0,
+        )
+        .next()
+        .map(|i| &self.class_layouts[i])
+    }
+
+    pub fn field_layout_of(&self, handle: FieldHandle) -> Option<&tables::FieldLayout> {
+        self.rows_by_key(
+            FIELD_LAYOUT,
+            &self.field_layouts,
+            |fl| fl.field as u32,
+            handle.0,
+        )
+        .next()
+        .map(|i| &self.field_layouts[i])
+    }
+
+    pub fn field_rva_of(&self, handle: FieldHandle) -> Option<&tables::FieldRva> {
+        self.rows_by_key(
+            FIELD_RVA,
+            &self.field_rvas,
+            |fr| fr.field_index as u32,
+            handle.0,
+        )
+        .next()
+        .map(|i| &self.field_rvas[i])
+    }
+
+    pub fn impl_map_of(&self, member: MemberForwarded) -> Option<&tables::ImplMap> {
+        self.rows_by_key(
+            IMPL_MAP,
+            &self.impl_maps,
+            |im| im.member_forwarded as u32,
+            member.encode(),
+        )
+        .next()
+        .map(|i| &self.impl_maps[i])
+    }
+
+    /// NestedClass row of a nested type, see [`Self::enclosing_type_of`]
+    pub fn nested_class_of(&self, handle: TypeDefHandle) -> Option<&tables::NestedClass> {
+        self.rows_by_key(
+            NESTED_CLASS,
+            &self.nested_classes,
+            |nc| nc.nested_class as u32,
+            handle.0,
+        )
+        .next()
+        .map(|i| &self.nested_classes[i])
+    }
+
+    /// Getters, setters and other methods of a property or event
+    pub fn method_semantics_of(
+        &self,
+        association: HasSemantics,
+    ) -> impl Iterator<Item = &tables::MethodSemantics> {
+        self.rows_by_key(
+            METHOD_SEMANTICS,
+            &self.method_semantics,
+            |ms| ms.association as u32,
+            association.encode(),
+        )
+        .map(|i| &self.method_semantics[i])
+    }
+
+    pub fn interface_impls_of(
+        &self,
+        handle: TypeDefHandle,
+    ) -> impl Iterator<Item = &tables::InterfaceImpl> {
+        self.rows_by_key(
+            INTERFACE_IMPL,
+            &self.interface_impls,
+            |ii| ii.class as u32,
+            handle.0,
+        )
+        .map(|i| &self.interface_impls[i])
+    }
+
+    pub fn method_impls_of(
+        &self,
+        handle: TypeDefHandle,
+    ) -> impl Iterator<Item = &tables::MethodImpl> {
+        self.rows_by_key(
+            METHOD_IMPL,
+            &self.method_impls,
+            |mi| mi.class as u32,
+            handle.0,
+        )
+        .map(|i| &self.method_impls[i])
+    }
+
+    /// Generic parameters of a type or method, ordered by number
+    pub fn generic_params_of(
+        &self,
+        owner: TypeOrMethodDef,
+    ) -> impl Iterator<Item = &tables::GenericParam> {
+        self.rows_by_key(
+            GENERIC_PARAM,
+            &self.generic_params,
+            |gp| gp.owner as u32,
+            owner.encode(),
+        )
+        .map(|i| &self.generic_params[i])
+    }
+}
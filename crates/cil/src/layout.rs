@@ -0,0 +1,303 @@
+//! Size, alignment and field offsets of value types, following the rules the runtime uses for
+//! sequential, explicit and auto layout (ECMA-335 II.10.7)
+
+use std::cell::RefCell;
+
+use crate::{
+    Result,
+    error::Error,
+    image::{CilImage, TypeLayout},
+    meta::{FieldHandle, TypeDefHandle, TypeRefHandle},
+    signature::{Element, GenericContext},
+    tables::{Layout, TypeDefOrRef},
+};
+
+/// Size and alignment of a type, in bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TypeSize {
+    pub size: u32,
+    pub alignment: u32,
+}
+
+impl TypeSize {
+    const fn new(size: u32) -> Self {
+        Self {
+            size,
+            alignment: size,
+        }
+    }
+}
+
+/// Computed layout of a value type definition
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValueTypeLayout {
+    pub size: u32,
+    pub alignment: u32,
+    /// Instance fields in declaration order
+    pub fields: Vec<FieldPlacement>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldPlacement {
+    pub field: FieldHandle,
+    pub offset: u32,
+    pub size: TypeSize,
+}
+
+/// Outcome of a layout computation
+#[derive(Debug, Clone, PartialEq)]
+pub enum LayoutResult<T> {
+    Known(T),
+    /// The layout depends on this type, whose size can't be determined from the image. This is either
+    /// a value type defined in another assembly or a generic parameter without an argument.
+    Unknown(Element),
+}
+
+impl<T> LayoutResult<T> {
+    pub fn known(self) -> Option<T> {
+        match self {
+            Self::Known(v) => Some(v),
+            Self::Unknown(_) => None,
+        }
+    }
+
+    pub fn is_known(&self) -> bool {
+        matches!(self, Self::Known(_))
+    }
+}
+
+/// Computes value type layouts for a single image.
+///
+/// Auto layout is computed like sequential layout, in declaration order. The runtime only reorders
+/// fields of auto layout structs that contain object references, so this is exact for blittable types.
+pub struct LayoutEngine<'img> {
+    image: &'img CilImage,
+    pointer_size: u32,
+    /// Types whose layout is currently being computed, to detect structs that contain themselves
+    in_progress: RefCell<Vec<TypeDefHandle>>,
+}
+
+impl<'img> LayoutEngine<'img> {
+    /// Creates an engine for the target of the image, see [`CilImage::pointer_size`]
+    pub fn new(image: &'img CilImage) -> Self {
+        Self {
+            image,
+            pointer_size: image.pointer_size,
+            in_progress: RefCell::new(vec![]),
+        }
+    }
+
+    /// Size of pointers, native integers and object references
+    pub fn with_pointer_size(mut self, pointer_size: u32) -> Self {
+        self.pointer_size = pointer_size;
+        self
+    }
+
+    /// Size and alignment of a value of type `element`, as stored in a field, local or array element.
+    /// Reference types are pointer-sized.
+    pub fn size_of(&self, element: &Element) -> Result<LayoutResult<TypeSize>> {
+        let pointer = TypeSize::new(self.pointer_size);
+        Ok(LayoutResult::Known(match element {
+            Element::Boolean | Element::I1 | Element::U1 => TypeSize::new(1),
+            Element::Char | Element::I2 | Element::U2 => TypeSize::new(2),
+            Element::I4 | Element::U4 | Element::R4 => TypeSize::new(4),
+            Element::I8 | Element::U8 | Element::R8 => TypeSize::new(8),
+            Element::IntPtr
+            | Element::UIntPtr
+            | Element::Ptr(_)
+            | Element::FnPtr(_)
+            | Element::ByRef(_)
+            | Element::String
+            | Element::Object
+            | Element::Class(_)
+            | Element::SzArray(_)
+            | Element::Array(..) => pointer,
+            // Value pointer and type handle
+            Element::TypedByRef => TypeSize {
+                size: self.pointer_size * 2,
+                alignment: self.pointer_size,
+            },
+            Element::CModRequired(_, inner)
+            | Element::CModOptional(_, inner)
+            | Element::Pinned(inner) => return self.size_of(inner),
+            Element::ValueType(token) => return self.size_of_named(*token, element, None),
+            Element::GenericInst {
+                generic_type,
+                generic_args,
+            } => match generic_type.as_ref() {
+                Element::ValueType(token) => {
+                    let context = GenericContext::new(generic_args.clone(), vec![]);
+                    return self.size_of_named(*token, element, Some(&context));
+                }
+                _ => pointer,
+            },
+            _ => return Ok(LayoutResult::Unknown(element.clone())),
+        }))
+    }
+
+    /// Field offsets, size and alignment of a value type definition
+    pub fn value_type_layout(
+        &self,
+        handle: TypeDefHandle,
+    ) -> Result<LayoutResult<ValueTypeLayout>> {
+        self.layout_with_context(handle, &GenericContext::default())
+    }
+
+    /// Layout of an instantiation of a generic value type, eg. `KeyValuePair<int, long>`
+    pub fn generic_value_type_layout(
+        &self,
+        handle: TypeDefHandle,
+        context: &GenericContext,
+    ) -> Result<LayoutResult<ValueTypeLayout>> {
+        self.layout_with_context(handle, context)
+    }
+
+    fn size_of_named(
+        &self,
+        token: TypeDefOrRef,
+        element: &Element,
+        context: Option<&GenericContext>,
+    ) -> Result<LayoutResult<TypeSize>> {
+        match token {
+            TypeDefOrRef::TypeDef(index) => {
+                let layout = self.layout_with_context(
+                    TypeDefHandle(index),
+                    context.unwrap_or(&Default::default()),
+                )?;
+                Ok(match layout {
+                    LayoutResult::Known(layout) => LayoutResult::Known(TypeSize {
+                        size: layout.size,
+                        alignment: layout.alignment,
+                    }),
+                    LayoutResult::Unknown(element) => LayoutResult::Unknown(element),
+                })
+            }
+            TypeDefOrRef::TypeRef(index) => {
+                Ok(self.well_known_size(TypeRefHandle(index)).map_or_else(
+                    || LayoutResult::Unknown(element.clone()),
+                    LayoutResult::Known,
+                ))
+            }
+            TypeDefOrRef::TypeSpec(_) => Ok(LayoutResult::Unknown(element.clone())),
+        }
+    }
+
+    /// Sizes of core library value types that may be referenced by TypeRef instead of their element type
+    fn well_known_size(&self, handle: TypeRefHandle) -> Option<TypeSize> {
+        let name = self.image.type_ref_name(handle)?;
+        if name.namespace != "System" || !name.enclosing.is_empty() {
+            return None;
+        }
+
+        Some(match name.name.as_str() {
+            "Boolean" | "SByte" | "Byte" => TypeSize::new(1),
+            "Char" | "Int16" | "UInt16" => TypeSize::new(2),
+            "Int32" | "UInt32" | "Single" => TypeSize::new(4),
+            "Int64" | "UInt64" | "Double" => TypeSize::new(8),
+            "IntPtr" | "UIntPtr" => TypeSize::new(self.pointer_size),
+            _ => return None,
+        })
+    }
+
+    fn layout_with_context(
+        &self,
+        handle: TypeDefHandle,
+        context: &GenericContext,
+    ) -> Result<LayoutResult<ValueTypeLayout>> {
+        let type_layout = self.image.type_layout(handle)?;
+
+        if self.in_progress.borrow().contains(&handle) {
+            return Err(Error::RecursiveLayout(handle.token()));
+        }
+        self.in_progress.borrow_mut().push(handle);
+        let fields = self.place_fields(handle, &type_layout, context);
+        self.in_progress.borrow_mut().pop();
+
+        let fields = match fields? {
+            LayoutResult::Known(fields) => fields,
+            LayoutResult::Unknown(element) => return Ok(LayoutResult::Unknown(element)),
+        };
+
+        let packing = type_layout.packing_size.unwrap_or(0) as u32;
+        let alignment = fields
+            .iter()
+            .map(|f| effective_alignment(f.size.alignment, packing))
+            .max()
+            .unwrap_or(1);
+        let end = fields
+            .iter()
+            .map(|f| f.offset + f.size.size)
+            .max()
+            .unwrap_or(0);
+
+        // Empty structs still occupy a byte, and ClassLayout can only grow a type
+        let size = align_up(end, alignment)
+            .max(type_layout.class_size.unwrap_or(0))
+            .max(1);
+
+        Ok(LayoutResult::Known(ValueTypeLayout {
+            size,
+            alignment,
+            fields,
+        }))
+    }
+
+    fn place_fields(
+        &self,
+        handle: TypeDefHandle,
+        type_layout: &TypeLayout,
+        context: &GenericContext,
+    ) -> Result<LayoutResult<Vec<FieldPlacement>>> {
+        let packing = type_layout.packing_size.unwrap_or(0) as u32;
+        let mut fields = vec![];
+        let mut offset = 0;
+
+        for field in self.image.fields_of(handle) {
+            let flags = self.image.fields[field.index()].flags;
+            if flags.is_static() || flags.is_literal() {
+                continue;
+            }
+
+            let field_type = self.image.field_type(field)?.substitute(context);
+            let size = match self.size_of(&field_type)? {
+                LayoutResult::Known(size) => size,
+                LayoutResult::Unknown(element) => return Ok(LayoutResult::Unknown(element)),
+            };
+
+            let field_offset = match type_layout.kind {
+                Layout::Explicit => type_layout
+                    .field_offsets
+                    .iter()
+                    .find(|(f, _)| *f == field)
+                    .map_or(0, |(_, o)| *o),
+                Layout::Sequential | Layout::Auto => {
+                    let field_offset =
+                        align_up(offset, effective_alignment(size.alignment, packing));
+                    offset = field_offset + size.size;
+                    field_offset
+                }
+            };
+
+            fields.push(FieldPlacement {
+                field,
+                offset: field_offset,
+                size,
+            });
+        }
+
+        Ok(LayoutResult::Known(fields))
+    }
+}
+
+/// Alignment of a field, limited by the packing of the containing type (0 means no limit)
+fn effective_alignment(alignment: u32, packing: u32) -> u32 {
+    if packing == 0 {
+        alignment.max(1)
+    } else {
+        alignment.clamp(1, packing)
+    }
+}
+
+fn align_up(value: u32, alignment: u32) -> u32 {
+    value.div_ceil(alignment) * alignment
+}
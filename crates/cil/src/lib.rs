@@ -1,12 +1,31 @@
+pub mod assembler;
+pub mod body;
+pub mod constant;
+pub mod disassembler;
+pub mod dominance;
+pub mod encoder;
 pub mod error;
+pub mod flow;
+pub mod format;
 pub mod header;
+pub mod hierarchy;
+pub mod identity;
 pub mod image;
+mod index;
+pub mod layout;
+pub mod loops;
+pub mod marshal;
 pub mod meta;
 pub mod opcodes;
+pub mod parameters;
+mod sha1;
 pub mod signature;
 pub mod strings;
 pub mod tables;
+pub mod type_name;
 mod util;
+pub mod verifier;
+pub mod vtable;
 
 pub use error::Result;
 
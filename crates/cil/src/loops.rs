@@ -0,0 +1,327 @@
+//! Natural loops and irreducible regions of a [`ControlFlowGraph`]
+//!
+//! A back edge is an edge whose target dominates its source. The natural loop of a header is the
+//! header together with every block that reaches one of its back edges without going through the
+//! header. Cycles that can be entered at more than one block have no such header and are reported
+//! as irreducible regions instead.
+
+use crate::{
+    dominance::DominatorTree,
+    flow::{BlockId, ControlFlowGraph, Edge},
+};
+
+/// Index of a loop in [`LoopForest::loops`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LoopId(pub usize);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Loop {
+    /// The only block of the loop that is entered from outside it
+    pub header: BlockId,
+    /// Sources of the back edges to the header
+    pub latches: Vec<BlockId>,
+    /// Every block of the loop in offset order, including the header and the blocks of nested
+    /// loops
+    pub blocks: Vec<BlockId>,
+    pub parent: Option<LoopId>,
+    pub children: Vec<LoopId>,
+    /// 1 for outermost loops
+    pub depth: u32,
+}
+
+impl Loop {
+    pub fn contains(&self, block: BlockId) -> bool {
+        self.blocks.binary_search(&block).is_ok()
+    }
+
+    /// Edges leaving the loop
+    pub fn exits(&self, cfg: &ControlFlowGraph) -> Vec<Edge> {
+        self.blocks
+            .iter()
+            .flat_map(|&b| &cfg.block(b).successors)
+            .filter(|e| !self.contains(e.target))
+            .copied()
+            .collect()
+    }
+}
+
+/// A strongly connected region with more than one entry
+#[derive(Debug, Clone, PartialEq)]
+pub struct IrreducibleRegion {
+    /// Blocks of the region that are entered from outside it
+    pub entries: Vec<BlockId>,
+    /// Every block of the region in offset order
+    pub blocks: Vec<BlockId>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoopForest {
+    /// Loops ordered so that every loop comes after the loop containing it
+    pub loops: Vec<Loop>,
+    pub back_edges: Vec<Edge>,
+    pub irreducible_regions: Vec<IrreducibleRegion>,
+    /// Innermost loop containing each block
+    innermost: Vec<Option<LoopId>>,
+}
+
+impl LoopForest {
+    pub fn new(cfg: &ControlFlowGraph, dominators: &DominatorTree) -> Self {
+        let back_edges = cfg
+            .blocks
+            .iter()
+            .flat_map(|b| &b.successors)
+            .filter(|e| dominators.dominates(e.target, e.source))
+            .copied()
+            .collect::<Vec<_>>();
+
+        let mut headers = back_edges.iter().map(|e| e.target).collect::<Vec<_>>();
+        headers.sort();
+        headers.dedup();
+
+        let mut loops = headers
+            .into_iter()
+            .map(|header| {
+                let latches = back_edges
+                    .iter()
+                    .filter(|e| e.target == header)
+                    .map(|e| e.source)
+                    .collect::<Vec<_>>();
+                Loop {
+                    header,
+                    blocks: natural_loop(cfg, dominators, header, &latches),
+                    latches,
+                    parent: None,
+                    children: Vec::new(),
+                    depth: 0,
+                }
+            })
+            .collect::<Vec<_>>();
+        // Natural loops with different headers are either disjoint or nested, so the larger one
+        // always contains the smaller one
+        loops.sort_by(|a, b| {
+            b.blocks
+                .len()
+                .cmp(&a.blocks.len())
+                .then(a.header.cmp(&b.header))
+        });
+
+        let mut innermost = vec![None; cfg.len()];
+        for l in 0..loops.len() {
+            if let Some(parent) = innermost[loops[l].header.0] {
+                let LoopId(p) = parent;
+                loops[l].parent = Some(parent);
+                loops[l].depth = loops[p].depth + 1;
+                loops[p].children.push(LoopId(l));
+            } else {
+                loops[l].depth = 1;
+            }
+            for &block in &loops[l].blocks {
+                innermost[block.0] = Some(LoopId(l));
+            }
+        }
+
+        let reachable = (0..cfg.len())
+            .map(|b| dominators.is_reachable(BlockId(b)))
+            .collect::<Vec<_>>();
+        let nodes = (0..cfg.len()).filter(|&b| reachable[b]).collect::<Vec<_>>();
+        let mut irreducible_regions = Vec::new();
+        find_irreducible(cfg, &nodes, &reachable, &mut irreducible_regions);
+        irreducible_regions.sort_by_key(|r| r.blocks[0]);
+
+        Self {
+            loops,
+            back_edges,
+            irreducible_regions,
+            innermost,
+        }
+    }
+
+    pub fn get(&self, id: LoopId) -> &Loop {
+        &self.loops[id.0]
+    }
+
+    /// Innermost loop containing `block`
+    pub fn innermost_loop(&self, block: BlockId) -> Option<LoopId> {
+        self.innermost.get(block.0).copied().flatten()
+    }
+
+    /// Number of loops containing `block`
+    pub fn depth(&self, block: BlockId) -> u32 {
+        self.innermost_loop(block)
+            .map_or(0, |l| self.loops[l.0].depth)
+    }
+
+    /// Whether `block` is the header of a loop
+    pub fn is_header(&self, block: BlockId) -> bool {
+        self.innermost_loop(block)
+            .is_some_and(|l| self.loops[l.0].header == block)
+    }
+
+    /// Loops that aren't nested in another loop
+    pub fn outermost(&self) -> impl Iterator<Item = LoopId> + '_ {
+        (0..self.loops.len())
+            .map(LoopId)
+            .filter(|&l| self.loops[l.0].parent.is_none())
+    }
+
+    pub fn is_reducible(&self) -> bool {
+        self.irreducible_regions.is_empty()
+    }
+}
+
+/// Blocks of the natural loop of `header`, in offset order
+fn natural_loop(
+    cfg: &ControlFlowGraph,
+    dominators: &DominatorTree,
+    header: BlockId,
+    latches: &[BlockId],
+) -> Vec<BlockId> {
+    let mut in_loop = vec![false; cfg.len()];
+    in_loop[header.0] = true;
+    let mut worklist = Vec::new();
+    for &latch in latches {
+        if !std::mem::replace(&mut in_loop[latch.0], true) {
+            worklist.push(latch);
+        }
+    }
+    while let Some(block) = worklist.pop() {
+        for edge in &cfg.block(block).predecessors {
+            if dominators.is_reachable(edge.source)
+                && !std::mem::replace(&mut in_loop[edge.source.0], true)
+            {
+                worklist.push(edge.source);
+            }
+        }
+    }
+    (0..cfg.len())
+        .filter(|&b| in_loop[b])
+        .map(BlockId)
+        .collect()
+}
+
+/// Splits the subgraph of `nodes` into strongly connected components. A cyclic component with a
+/// single entry is a loop, and is searched again without its header so that irreducible cycles
+/// nested in it are found too. Edges from blocks that aren't `reachable` don't make entries.
+fn find_irreducible(
+    cfg: &ControlFlowGraph,
+    nodes: &[usize],
+    reachable: &[bool],
+    regions: &mut Vec<IrreducibleRegion>,
+) {
+    let mut in_subgraph = vec![false; cfg.len()];
+    for &node in nodes {
+        in_subgraph[node] = true;
+    }
+
+    for component in strongly_connected_components(cfg, nodes, &in_subgraph) {
+        let mut in_component = vec![false; cfg.len()];
+        for &node in &component {
+            in_component[node] = true;
+        }
+        let cyclic = component.len() > 1
+            || cfg.blocks[component[0]]
+                .successors
+                .iter()
+                .any(|e| e.target.0 == component[0]);
+        if !cyclic {
+            continue;
+        }
+
+        let entries = component
+            .iter()
+            .copied()
+            .filter(|&node| {
+                node == cfg.entry().0
+                    || cfg.blocks[node]
+                        .predecessors
+                        .iter()
+                        .any(|e| reachable[e.source.0] && !in_component[e.source.0])
+            })
+            .collect::<Vec<_>>();
+        match entries.as_slice() {
+            [header] => {
+                let body = component
+                    .iter()
+                    .copied()
+                    .filter(|node| node != header)
+                    .collect::<Vec<_>>();
+                find_irreducible(cfg, &body, reachable, regions);
+            }
+            _ => {
+                let mut blocks = component.into_iter().map(BlockId).collect::<Vec<_>>();
+                blocks.sort();
+                regions.push(IrreducibleRegion {
+                    entries: entries.into_iter().map(BlockId).collect(),
+                    blocks,
+                });
+            }
+        }
+    }
+}
+
+/// Tarjan's algorithm over the blocks in `nodes`, ignoring edges that leave the subgraph
+fn strongly_connected_components(
+    cfg: &ControlFlowGraph,
+    nodes: &[usize],
+    in_subgraph: &[bool],
+) -> Vec<Vec<usize>> {
+    const UNVISITED: usize = usize::MAX;
+    let mut index = vec![UNVISITED; cfg.len()];
+    let mut low_link = vec![0; cfg.len()];
+    let mut on_stack = vec![false; cfg.len()];
+    let mut stack = Vec::new();
+    let mut components = Vec::new();
+    let mut counter = 0;
+
+    for &root in nodes {
+        if index[root] != UNVISITED {
+            continue;
+        }
+        let mut call_stack = vec![(root, 0)];
+        index[root] = counter;
+        low_link[root] = counter;
+        counter += 1;
+        stack.push(root);
+        on_stack[root] = true;
+
+        while let Some(&(node, next)) = call_stack.last() {
+            let successors = &cfg.blocks[node].successors;
+            if let Some(edge) = successors.get(next) {
+                call_stack.last_mut().unwrap().1 += 1;
+                let successor = edge.target.0;
+                if !in_subgraph[successor] {
+                    continue;
+                }
+                if index[successor] == UNVISITED {
+                    index[successor] = counter;
+                    low_link[successor] = counter;
+                    counter += 1;
+                    stack.push(successor);
+                    on_stack[successor] = true;
+                    call_stack.push((successor, 0));
+                } else if on_stack[successor] {
+                    low_link[node] = low_link[node].min(index[successor]);
+                }
+                continue;
+            }
+
+            call_stack.pop();
+            if let Some(&(parent, _)) = call_stack.last() {
+                low_link[parent] = low_link[parent].min(low_link[node]);
+            }
+            if low_link[node] == index[node] {
+                let mut component = Vec::new();
+                loop {
+                    let member = stack.pop().unwrap();
+                    on_stack[member] = false;
+                    component.push(member);
+                    if member == node {
+                        break;
+                    }
+                }
+                components.push(component);
+            }
+        }
+    }
+    components
+}
@@ -0,0 +1,201 @@
+//! Marshalling descriptors of fields and parameters (ECMA-335 II.23.4), as stored in the FieldMarshal table
+
+use std::io::{Cursor, Read};
+
+use int_enum::IntEnum;
+
+use crate::{Result, image::CilImage, tables::HasFieldMarshal, util::ReadExt};
+
+/// `NATIVE_TYPE_*` constants, including the ones added by the runtime after ECMA-335 was published
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, IntEnum)]
+pub enum NativeType {
+    Boolean = 0x02,
+    I1 = 0x03,
+    U1 = 0x04,
+    I2 = 0x05,
+    U2 = 0x06,
+    I4 = 0x07,
+    U4 = 0x08,
+    I8 = 0x09,
+    U8 = 0x0A,
+    R4 = 0x0B,
+    R8 = 0x0C,
+    Currency = 0x0F,
+    BStr = 0x13,
+    LPStr = 0x14,
+    LPWStr = 0x15,
+    LPTStr = 0x16,
+    FixedSysString = 0x17,
+    IUnknown = 0x19,
+    IDispatch = 0x1A,
+    Struct = 0x1B,
+    Interface = 0x1C,
+    SafeArray = 0x1D,
+    FixedArray = 0x1E,
+    Int = 0x1F,
+    UInt = 0x20,
+    ByValStr = 0x22,
+    AnsiBStr = 0x23,
+    TBStr = 0x24,
+    VariantBool = 0x25,
+    Func = 0x26,
+    AsAny = 0x28,
+    Array = 0x2A,
+    LPStruct = 0x2B,
+    CustomMarshaler = 0x2C,
+    Error = 0x2D,
+    IInspectable = 0x2E,
+    HString = 0x2F,
+    LPUtf8Str = 0x30,
+    /// Used in place of an element type to mean "not specified"
+    Max = 0x50,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MarshalDescriptor {
+    /// A native type without any parameters
+    Simple(NativeType),
+    /// `IUnknown`, `IDispatch`, `IInspectable` or an interface, optionally with the index of the
+    /// parameter that holds the interface ID
+    Interface {
+        kind: NativeType,
+        iid_parameter_index: Option<u32>,
+    },
+    /// Inline string of a fixed number of characters
+    FixedSysString { size: u32 },
+    /// Inline array of a fixed number of elements
+    FixedArray {
+        size: u32,
+        element_type: Option<NativeType>,
+    },
+    /// Pointer to a C-style array
+    Array {
+        element_type: Option<NativeType>,
+        /// Zero-based index of the parameter that holds the number of elements
+        size_parameter_index: Option<u32>,
+        /// Number of elements, or the number to add to the size parameter
+        element_count: Option<u32>,
+    },
+    SafeArray {
+        /// `VARTYPE` of the elements
+        variant_type: Option<u32>,
+        user_defined_subtype: Option<String>,
+    },
+    CustomMarshaler {
+        guid: String,
+        native_type_name: String,
+        /// Type name of the marshaler, in reflection syntax
+        marshaler: String,
+        cookie: String,
+    },
+    /// A native type this crate doesn't know about, with the entire descriptor blob
+    Unknown(Vec<u8>),
+}
+
+impl MarshalDescriptor {
+    pub fn parse(blob: &[u8]) -> Result<Self> {
+        let mut reader = Cursor::new(blob);
+        let Ok(native_type) = NativeType::try_from(read_u8(&mut reader)?) else {
+            return Ok(Self::Unknown(blob.to_vec()));
+        };
+
+        let has_more = |reader: &Cursor<&[u8]>| (reader.position() as usize) < blob.len();
+        let optional_u32 = |reader: &mut Cursor<&[u8]>| -> Result<Option<u32>> {
+            Ok(if has_more(reader) {
+                Some(reader.read_compressed_u32()?)
+            } else {
+                None
+            })
+        };
+        let optional_native_type = |reader: &mut Cursor<&[u8]>| -> Result<Option<NativeType>> {
+            Ok(if has_more(reader) {
+                NativeType::try_from(read_u8(reader)?)
+                    .ok()
+                    .filter(|t| *t != NativeType::Max)
+            } else {
+                None
+            })
+        };
+
+        Ok(match native_type {
+            NativeType::IUnknown
+            | NativeType::IDispatch
+            | NativeType::IInspectable
+            | NativeType::Interface => Self::Interface {
+                kind: native_type,
+                iid_parameter_index: optional_u32(&mut reader)?,
+            },
+            NativeType::FixedSysString => Self::FixedSysString {
+                size: reader.read_compressed_u32()?,
+            },
+            NativeType::FixedArray => Self::FixedArray {
+                size: reader.read_compressed_u32()?,
+                element_type: optional_native_type(&mut reader)?,
+            },
+            NativeType::Array => {
+                let element_type = optional_native_type(&mut reader)?;
+                let size_parameter_index = optional_u32(&mut reader)?;
+                let element_count = optional_u32(&mut reader)?;
+                // Compilers always write the parameter index when they write the element count, the
+                // trailing flags say whether it was actually specified
+                let size_parameter_specified =
+                    optional_u32(&mut reader)?.is_none_or(|f| f & 1 != 0);
+                Self::Array {
+                    element_type,
+                    size_parameter_index: size_parameter_index.filter(|_| size_parameter_specified),
+                    element_count,
+                }
+            }
+            NativeType::SafeArray => {
+                let variant_type = optional_u32(&mut reader)?;
+                let user_defined_subtype = if has_more(&reader) {
+                    read_ser_string(&mut reader)?
+                } else {
+                    None
+                };
+                Self::SafeArray {
+                    variant_type,
+                    user_defined_subtype,
+                }
+            }
+            NativeType::CustomMarshaler => Self::CustomMarshaler {
+                guid: read_ser_string(&mut reader)?.unwrap_or_default(),
+                native_type_name: read_ser_string(&mut reader)?.unwrap_or_default(),
+                marshaler: read_ser_string(&mut reader)?.unwrap_or_default(),
+                cookie: read_ser_string(&mut reader)?.unwrap_or_default(),
+            },
+            _ => Self::Simple(native_type),
+        })
+    }
+}
+
+fn read_u8(reader: &mut impl Read) -> Result<u8> {
+    let mut buf = [0u8; 1];
+    reader.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
+/// Length prefixed UTF-8 string, where a length of 0xFF means null
+fn read_ser_string(reader: &mut Cursor<&[u8]>) -> Result<Option<String>> {
+    let start = reader.position();
+    if read_u8(reader)? == 0xFF {
+        return Ok(None);
+    }
+    reader.set_position(start);
+
+    let length = reader.read_compressed_u32()? as usize;
+    let mut data = vec![0u8; length];
+    reader.read_exact(&mut data)?;
+    Ok(Some(String::from_utf8_lossy(&data).into_owned()))
+}
+
+impl CilImage {
+    /// Marshalling descriptor of a field or parameter, if it has one
+    pub fn marshal_descriptor(&self, parent: HasFieldMarshal) -> Result<Option<MarshalDescriptor>> {
+        let Some(field_marshal) = self.field_marshal_of(parent) else {
+            return Ok(None);
+        };
+        MarshalDescriptor::parse(self.blob(field_marshal.native_type_blob_index)?).map(Some)
+    }
+}
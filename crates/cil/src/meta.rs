@@ -4,7 +4,7 @@ use binrw::{NullString, binread};
 use int_enum::IntEnum;
 
 #[binread]
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
 pub struct Token(pub u32);
 
 impl Token {
@@ -39,6 +39,86 @@ impl Debug for Token {
     }
 }
 
+impl std::fmt::LowerHex for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::LowerHex::fmt(&self.0, f)
+    }
+}
+
+macro_rules! define_handles {
+    ($(
+        $(#[doc = $description:expr])?
+        $name:ident => $kind:ident
+    ),*) => {
+        $(
+            $(#[doc = $description])?
+            ///
+            /// Wraps the 1-based row number, as used by tokens and table indices.
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+            pub struct $name(pub u32);
+
+            impl $name {
+                /// Zero-based index into the corresponding `CilImage` table
+                pub fn index(&self) -> usize {
+                    self.0 as usize - 1
+                }
+
+                pub fn token(&self) -> Token {
+                    Token(((TokenKind::$kind as u32) << 24) | self.0)
+                }
+            }
+
+            impl TryFrom<Token> for $name {
+                type Error = Token;
+
+                fn try_from(token: Token) -> Result<Self, Self::Error> {
+                    if token.kind() == TokenKind::$kind && token.index() != 0 {
+                        Ok(Self(token.index()))
+                    } else {
+                        Err(token)
+                    }
+                }
+            }
+        )*
+    };
+}
+
+define_handles! {
+    /// Row in the TypeRef table
+    TypeRefHandle => TypeRef,
+    /// Row in the TypeDef table
+    TypeDefHandle => TypeDef,
+    /// Row in the Field table
+    FieldHandle => Field,
+    /// Row in the MethodDef table
+    MethodDefHandle => MethodDef,
+    /// Row in the Param table
+    ParamHandle => Param,
+    /// Row in the MemberRef table
+    MemberRefHandle => MemberRef,
+    /// Row in the StandAloneSig table
+    StandAloneSigHandle => StandAloneSig,
+    /// Row in the Event table
+    EventHandle => Event,
+    /// Row in the Property table
+    PropertyHandle => Property,
+    /// Row in the TypeSpec table
+    TypeSpecHandle => TypeSpec,
+    /// Row in the MethodSpec table
+    MethodSpecHandle => MethodSpec,
+    /// Row in the ExportedType table
+    ExportedTypeHandle => ExportedType,
+    /// Row in the AssemblyRef table
+    AssemblyRefHandle => AssemblyRef
+}
+
+/// Method or field definition that a MemberRef binds to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MemberDef {
+    Method(MethodDefHandle),
+    Field(FieldHandle),
+}
+
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, IntEnum)]
 pub enum TokenKind {
@@ -143,6 +223,7 @@ pub struct LogicalMetadataTables {
 }
 
 #[binread]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Guid {
     pub data1: u32,
     pub data2: u16,
@@ -174,6 +255,7 @@ impl Display for Guid {
 #[derive(Debug)]
 pub struct StringIndex(pub u16);
 
+/// 1-based index into the #GUID heap, 0 means no GUID
 #[binread]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct GuidIndex(pub u16);
@@ -1,14 +1,145 @@
+// Two-byte opcodes are written as `0xFE_XX`, which clippy mistakes for a literal suffix
+#![allow(clippy::mistyped_literal_suffixes)]
+
 use binrw::BinReaderExt;
-use binrw::binread;
 use bitflags::bitflags;
-use std::fmt::Display;
+use std::{fmt::Display, io::Cursor};
+
+use crate::{
+    Result,
+    error::Error,
+    image::CilImage,
+    meta::{
+        FieldHandle, MemberRefHandle, MethodDefHandle, MethodSpecHandle, StandAloneSigHandle,
+        Token, TokenKind,
+    },
+    signature::{Element, MemberRefSignature, StandaloneMethodSignature},
+    strings::UserString,
+    tables::{MethodDefOrRef, TypeDefOrRef},
+};
+
+/// Little-endian encoding of an inline operand
+trait EncodeOperand {
+    fn encode_operand(&self, out: &mut Vec<u8>);
+}
+
+macro_rules! encode_operand_le {
+    ($($ty:ty),*) => {
+        $(
+            impl EncodeOperand for $ty {
+                fn encode_operand(&self, out: &mut Vec<u8>) {
+                    out.extend_from_slice(&self.to_le_bytes());
+                }
+            }
+        )*
+    };
+}
+
+encode_operand_le!(u8, i8, u16, i32, u32, i64, f32, f64);
+
+impl EncodeOperand for Token {
+    fn encode_operand(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.0.to_le_bytes());
+    }
+}
+
+impl EncodeOperand for DisabledFaultChecks {
+    fn encode_operand(&self, out: &mut Vec<u8>) {
+        out.push(self.bits());
+    }
+}
+
+/// Operand of an instruction as written in ILAsm, before it is stored in an opcode field
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AsmOperand {
+    None,
+    Integer(i64),
+    Float(f64),
+    Token(Token),
+}
+
+/// Conversion of an ILAsm operand to the type of an opcode field
+trait FromAsmOperand: Sized {
+    fn from_asm_operand(operand: AsmOperand) -> Option<Self>;
+}
+
+macro_rules! integer_from_asm_operand {
+    ($($ty:ty),*) => {
+        $(
+            impl FromAsmOperand for $ty {
+                fn from_asm_operand(operand: AsmOperand) -> Option<Self> {
+                    match operand {
+                        AsmOperand::Integer(value) => value.try_into().ok(),
+                        _ => None,
+                    }
+                }
+            }
+        )*
+    };
+}
 
-use crate::meta::Token;
+integer_from_asm_operand!(u8, i8, u16, i64);
 
+impl FromAsmOperand for i32 {
+    fn from_asm_operand(operand: AsmOperand) -> Option<Self> {
+        match operand {
+            // Hexadecimal values such as 0xFFFFFFFF are accepted as well
+            AsmOperand::Integer(value) => i32::try_from(value)
+                .ok()
+                .or_else(|| u32::try_from(value).ok().map(|v| v as i32)),
+            _ => None,
+        }
+    }
+}
+
+impl FromAsmOperand for f32 {
+    fn from_asm_operand(operand: AsmOperand) -> Option<Self> {
+        f64::from_asm_operand(operand).map(|value| value as f32)
+    }
+}
+
+impl FromAsmOperand for f64 {
+    fn from_asm_operand(operand: AsmOperand) -> Option<Self> {
+        match operand {
+            AsmOperand::Float(value) => Some(value),
+            AsmOperand::Integer(value) => Some(value as f64),
+            _ => None,
+        }
+    }
+}
+
+impl FromAsmOperand for Token {
+    fn from_asm_operand(operand: AsmOperand) -> Option<Self> {
+        match operand {
+            AsmOperand::Token(token) => Some(token),
+            _ => None,
+        }
+    }
+}
+
+impl FromAsmOperand for DisabledFaultChecks {
+    fn from_asm_operand(operand: AsmOperand) -> Option<Self> {
+        u8::from_asm_operand(operand).map(Self::from_bits_retain)
+    }
+}
+
+macro_rules! stack_count {
+    (Var) => {
+        StackCount::Variable
+    };
+    ($count:literal) => {
+        StackCount::Fixed($count)
+    };
+}
+
+/// Each opcode is listed as `index => Name(operand fields) "asm name" [flow control, pops -> pushes,
+/// operand type]`, following ECMA-335 Partition III. `switch` has a variable length operand and is
+/// implemented by the macro itself.
 macro_rules! define_opcodes {
     ($(
         $(#[doc = $description:expr])?
-        $index:literal => $name:ident ($($fname:ident : $ftype:ident),*) $asmname:expr
+        $index:literal => $name:ident ($($fname:ident : $ftype:ident),*) $asmname:literal
+            [$flow:ident, $pop:tt -> $push:tt, $operand:ident]
     ),*) => {
         #[derive(Debug, Clone, PartialEq)]
         #[rustfmt::skip]
@@ -28,6 +159,28 @@ macro_rules! define_opcodes {
         }
 
         impl RawOpcode {
+            /// Looks an opcode up by its ILAsm name, with its operand fields set to zero. `switch`
+            /// has no targets.
+            pub fn from_asm_name(name: &str) -> Option<Self> {
+                Some(match name {
+                    $($asmname => Self::$name { $($fname: Default::default(),)* },)*
+                    "switch" => Self::Switch { targets: Vec::new() },
+                    _ => return None,
+                })
+            }
+
+            /// The same opcode with its operand field set to `operand`, `None` if the value doesn't
+            /// fit the field. Branch offsets and `switch` targets are left alone.
+            pub fn with_operand(&self, operand: AsmOperand) -> Option<Self> {
+                if self.is_branch() {
+                    return Some(self.clone());
+                }
+                Some(match self {
+                    $(Self::$name { .. } => Self::$name { $($fname: FromAsmOperand::from_asm_operand(operand)?,)* },)*
+                    Self::Switch { targets } => Self::Switch { targets: targets.clone() },
+                })
+            }
+
             pub fn asm_name(&self) -> &'static str {
                 match self {
                     $(Self::$name { .. } => $asmname,)*
@@ -35,10 +188,68 @@ macro_rules! define_opcodes {
                 }
             }
 
+            pub fn flow_control(&self) -> FlowControl {
+                match self {
+                    $(Self::$name { .. } => FlowControl::$flow,)*
+                    Self::Switch { .. } => FlowControl::CondBranch,
+                }
+            }
+
+            /// Number of values popped from the stack, see [`Instruction::stack_effect`] for the
+            /// variable ones
+            pub fn pops(&self) -> StackCount {
+                match self {
+                    $(Self::$name { .. } => stack_count!($pop),)*
+                    Self::Switch { .. } => StackCount::Fixed(1),
+                }
+            }
+
+            /// Number of values pushed onto the stack, see [`Instruction::stack_effect`] for the
+            /// variable ones
+            pub fn pushes(&self) -> StackCount {
+                match self {
+                    $(Self::$name { .. } => stack_count!($push),)*
+                    Self::Switch { .. } => StackCount::Fixed(0),
+                }
+            }
+
+            pub fn operand_type(&self) -> OperandType {
+                match self {
+                    $(Self::$name { .. } => OperandType::$operand,)*
+                    Self::Switch { .. } => OperandType::InlineSwitch,
+                }
+            }
+
+            /// Appends the opcode and its operand to `out`
+            pub fn encode(&self, out: &mut Vec<u8>) {
+                match self {
+                    $(Self::$name { $($fname,)* } => {
+                        const INDEX: u16 = $index;
+                        if INDEX > 0xFF {
+                            out.extend_from_slice(&INDEX.to_be_bytes());
+                        } else {
+                            out.push(INDEX as u8);
+                        }
+                        $(
+                            $fname.encode_operand(out);
+                        )*
+                    })*
+                    Self::Switch { targets } => {
+                        out.push(0x45);
+                        out.extend_from_slice(&(targets.len() as u32).to_le_bytes());
+                        for target in targets {
+                            out.extend_from_slice(&target.to_le_bytes());
+                        }
+                    }
+                }
+            }
+
+            /// Encoded size in bytes, including the operand
             pub fn size(&self) -> usize {
-                1 + match self {
-                    $(Self::$name { .. } => 0 $(+ std::mem::size_of::<$ftype>())*,)*
-                    Self::Switch { targets } => 4 + 4 * targets.len(),
+                match self {
+                    // Two-byte opcodes start with 0xFE
+                    $(Self::$name { .. } => (if $index > 0xFF { 2 } else { 1 }) $(+ std::mem::size_of::<$ftype>())*,)*
+                    Self::Switch { targets } => 1 + 4 + 4 * targets.len(),
                 }
             }
         }
@@ -103,483 +314,482 @@ macro_rules! define_opcodes {
 
 define_opcodes! {
     /// Do nothing (No operation).
-    0x00 => Nop() "nop",
+    0x00 => Nop() "nop" [Next, 0 -> 0, InlineNone],
     /// Inform a debugger that a breakpoint has been reached.
-    0x01 => Break() "break",
+    0x01 => Break() "break" [Break, 0 -> 0, InlineNone],
     /// Load argument 0 onto the stack.
-    0x02 => LdArg_0() "ldarg.0",
+    0x02 => LdArg_0() "ldarg.0" [Next, 0 -> 1, InlineNone],
     /// Load argument 1 onto the stack.
-    0x03 => LdArg_1() "ldarg.1",
+    0x03 => LdArg_1() "ldarg.1" [Next, 0 -> 1, InlineNone],
     /// Load argument 2 onto the stack.
-    0x04 => LdArg_2() "ldarg.2",
+    0x04 => LdArg_2() "ldarg.2" [Next, 0 -> 1, InlineNone],
     /// Load argument 3 onto the stack.
-    0x05 => LdArg_3() "ldarg.3",
+    0x05 => LdArg_3() "ldarg.3" [Next, 0 -> 1, InlineNone],
 
     /// Load local variable 0 onto stack.
-    0x06 => LdLoc_0() "ldloc.0",
+    0x06 => LdLoc_0() "ldloc.0" [Next, 0 -> 1, InlineNone],
     /// Load local variable 1 onto stack.
-    0x07 => LdLoc_1() "ldloc.1",
+    0x07 => LdLoc_1() "ldloc.1" [Next, 0 -> 1, InlineNone],
     /// Load local variable 2 onto stack.
-    0x08 => LdLoc_2() "ldloc.2",
+    0x08 => LdLoc_2() "ldloc.2" [Next, 0 -> 1, InlineNone],
     /// Load local variable 3 onto stack.
-    0x09 => LdLoc_3() "ldloc.3",
+    0x09 => LdLoc_3() "ldloc.3" [Next, 0 -> 1, InlineNone],
 
     /// Pop a value from stack into local variable 0.
-    0x0A => StLoc_0() "stloc.0",
+    0x0A => StLoc_0() "stloc.0" [Next, 1 -> 0, InlineNone],
     /// Pop a value from stack into local variable 1.
-    0x0B => StLoc_1() "stloc.1",
+    0x0B => StLoc_1() "stloc.1" [Next, 1 -> 0, InlineNone],
     /// Pop a value from stack into local variable 2.
-    0x0C => StLoc_2() "stloc.2",
+    0x0C => StLoc_2() "stloc.2" [Next, 1 -> 0, InlineNone],
     /// Pop a value from stack into local variable 3.
-    0x0D => StLoc_3() "stloc.3",
+    0x0D => StLoc_3() "stloc.3" [Next, 1 -> 0, InlineNone],
 
     /// Load argument numbered num onto the stack, short form.
-    0x0E => LdArg_S(index: u8) "ldarg.s",
+    0x0E => LdArg_S(index: u8) "ldarg.s" [Next, 0 -> 1, ShortInlineVar],
     /// Fetch the address of argument argNum, short form.
-    0x0F => LdArgA_S(index: u8) "ldarga.s",
+    0x0F => LdArgA_S(index: u8) "ldarga.s" [Next, 0 -> 1, ShortInlineVar],
     /// Store value to the argument numbered num, short form.
-    0x10 => StArg_S(index: u8) "starg.s",
+    0x10 => StArg_S(index: u8) "starg.s" [Next, 1 -> 0, ShortInlineVar],
     /// Load local variable of index indx onto stack, short form.
-    0x11 => LdLoc_S(index: u8) "ldloc.s",
+    0x11 => LdLoc_S(index: u8) "ldloc.s" [Next, 0 -> 1, ShortInlineVar],
     /// Load address of local variable with index indx, short form.
-    0x12 => LdLocA_S(index: u8) "ldloca.s",
+    0x12 => LdLocA_S(index: u8) "ldloca.s" [Next, 0 -> 1, ShortInlineVar],
     /// Pop a value from stack into local variable indx, short form.
-    0x13 => StLoc_S(index: u8) "stloc.s",
+    0x13 => StLoc_S(index: u8) "stloc.s" [Next, 1 -> 0, ShortInlineVar],
 
     /// Push a null reference on the stack.
-    0x14 => LdNull() "ldnull",
+    0x14 => LdNull() "ldnull" [Next, 0 -> 1, InlineNone],
     /// Push -1 onto the stack as int32.
-    0x15 => Ldc_I4_M1() "ldc.i4.m1",
+    0x15 => Ldc_I4_M1() "ldc.i4.m1" [Next, 0 -> 1, InlineNone],
     /// Push 0 onto the stack as int32.
-    0x16 => Ldc_I4_0() "ldc.i4.0",
+    0x16 => Ldc_I4_0() "ldc.i4.0" [Next, 0 -> 1, InlineNone],
     /// Push 1 onto the stack as int32.
-    0x17 => Ldc_I4_1() "ldc.i4.1",
+    0x17 => Ldc_I4_1() "ldc.i4.1" [Next, 0 -> 1, InlineNone],
     /// Push 2 onto the stack as int32.
-    0x18 => Ldc_I4_2() "ldc.i4.2",
+    0x18 => Ldc_I4_2() "ldc.i4.2" [Next, 0 -> 1, InlineNone],
     /// Push 3 onto the stack as int32.
-    0x19 => Ldc_I4_3() "ldc.i4.3",
+    0x19 => Ldc_I4_3() "ldc.i4.3" [Next, 0 -> 1, InlineNone],
     /// Push 4 onto the stack as int32.
-    0x1A => Ldc_I4_4() "ldc.i4.4",
+    0x1A => Ldc_I4_4() "ldc.i4.4" [Next, 0 -> 1, InlineNone],
     /// Push 5 onto the stack as int32.
-    0x1B => Ldc_I4_5() "ldc.i4.5",
+    0x1B => Ldc_I4_5() "ldc.i4.5" [Next, 0 -> 1, InlineNone],
     /// Push 6 onto the stack as int32.
-    0x1C => Ldc_I4_6() "ldc.i4.6",
+    0x1C => Ldc_I4_6() "ldc.i4.6" [Next, 0 -> 1, InlineNone],
     /// Push 7 onto the stack as int32.
-    0x1D => Ldc_I4_7() "ldc.i4.7",
+    0x1D => Ldc_I4_7() "ldc.i4.7" [Next, 0 -> 1, InlineNone],
     /// Push 8 onto the stack as int32.
-    0x1E => Ldc_I4_8() "ldc.i4.8",
+    0x1E => Ldc_I4_8() "ldc.i4.8" [Next, 0 -> 1, InlineNone],
     /// Push num onto the stack as int32, short form.
-    0x1F => Ldc_I4_S(value: i8) "ldc.i4.s",
+    0x1F => Ldc_I4_S(value: i8) "ldc.i4.s" [Next, 0 -> 1, ShortInlineI],
     /// Push num of type int32 onto the stack as int32.
-    0x20 => Ldc_I4(value: i32) "ldc.i4",
+    0x20 => Ldc_I4(value: i32) "ldc.i4" [Next, 0 -> 1, InlineI],
     /// Push num of type int64 onto the stack as int64.
-    0x21 => Ldc_I8(value: i64) "ldc.i8",
+    0x21 => Ldc_I8(value: i64) "ldc.i8" [Next, 0 -> 1, InlineI8],
     /// Push num of type float32 onto the stack as F.
-    0x22 => Ldc_R4(value: f32) "ldc.r4",
+    0x22 => Ldc_R4(value: f32) "ldc.r4" [Next, 0 -> 1, ShortInlineR],
     /// Push num of type float64 onto the stack as F.
-    0x23 => Ldc_R8(value: f64) "ldc.r8",
+    0x23 => Ldc_R8(value: f64) "ldc.r8" [Next, 0 -> 1, InlineR],
 
     /// Duplicate the value on the top of the stack.
-    0x25 => Dup() "dup",
+    0x25 => Dup() "dup" [Next, 1 -> 2, InlineNone],
     /// Pop value from the stack.
-    0x26 => Pop() "pop",
+    0x26 => Pop() "pop" [Next, 1 -> 0, InlineNone],
 
     /// Exit current method and jump to the specified method.
-    0x27 => Jmp(method: Token) "jmp",
+    0x27 => Jmp(method: Token) "jmp" [Call, 0 -> 0, InlineMethod],
     /// Call method described by method.
-    0x28 => Call(method: Token) "call",
+    0x28 => Call(method: Token) "call" [Call, Var -> Var, InlineMethod],
     /// Call method indicated on the stack with arguments described by callsitedescr.
-    0x29 => CallInd(callsitedescr: Token) "calli",
+    0x29 => CallInd(callsitedescr: Token) "calli" [Call, Var -> Var, InlineSig],
     /// Return from method, possibly with a value.
-    0x2A => Ret() "ret",
+    0x2A => Ret() "ret" [Return, Var -> 0, InlineNone],
     /// Branch to target, short form.
-    0x2B => Br_S(offset: i8) "br.s",
+    0x2B => Br_S(offset: i8) "br.s" [Branch, 0 -> 0, ShortInlineBrTarget],
     /// Branch to target if value is zero (false), short form.
-    0x2C => Br_False_S(offset: i8) "brfalse.s",
+    0x2C => Br_False_S(offset: i8) "brfalse.s" [CondBranch, 1 -> 0, ShortInlineBrTarget],
     /// Branch to target if value is non-zero (true), short form.
-    0x2D => Br_True_S(offset: i8) "brtrue.s",
+    0x2D => Br_True_S(offset: i8) "brtrue.s" [CondBranch, 1 -> 0, ShortInlineBrTarget],
     /// Branch to target if equal, short form.
-    0x2E => Beq_S(offset: i8) "beq.s",
+    0x2E => Beq_S(offset: i8) "beq.s" [CondBranch, 2 -> 0, ShortInlineBrTarget],
     /// Branch to target if greater than or equal to, short form.
-    0x2F => Bge_S(offset: i8) "bge.s",
+    0x2F => Bge_S(offset: i8) "bge.s" [CondBranch, 2 -> 0, ShortInlineBrTarget],
     /// Branch to target if greater than, short form.
-    0x30 => Bgt_S(offset: i8) "bgt.s",
+    0x30 => Bgt_S(offset: i8) "bgt.s" [CondBranch, 2 -> 0, ShortInlineBrTarget],
     /// Branch to target if less than or equal to, short form.
-    0x31 => Ble_S(offset: i8) "ble.s",
+    0x31 => Ble_S(offset: i8) "ble.s" [CondBranch, 2 -> 0, ShortInlineBrTarget],
     /// Branch to target if less than, short form.
-    0x32 => Blt_S(offset: i8) "blt.s",
+    0x32 => Blt_S(offset: i8) "blt.s" [CondBranch, 2 -> 0, ShortInlineBrTarget],
     /// Branch to target if unequal or unordered, short form.
-    0x33 => Bne_Un_S(offset: i8) "bne.un.s",
+    0x33 => Bne_Un_S(offset: i8) "bne.un.s" [CondBranch, 2 -> 0, ShortInlineBrTarget],
     /// Branch to target if greater than or equal to (unsigned or unordered), short form.
-    0x34 => Bge_Un_S(offset: i8) "bge.un.s",
+    0x34 => Bge_Un_S(offset: i8) "bge.un.s" [CondBranch, 2 -> 0, ShortInlineBrTarget],
     /// Branch to target if greater than (unsigned or unordered), short form.
-    0x35 => Bgt_Un_S(offset: i8) "bgt.un.s",
+    0x35 => Bgt_Un_S(offset: i8) "bgt.un.s" [CondBranch, 2 -> 0, ShortInlineBrTarget],
     /// Branch to target if less than or equal to (unsigned or unordered), short form.
-    0x36 => Ble_Un_S(offset: i8) "ble.un.s",
+    0x36 => Ble_Un_S(offset: i8) "ble.un.s" [CondBranch, 2 -> 0, ShortInlineBrTarget],
     /// Branch to target if less than (unsigned or unordered), short form.
-    0x37 => Blt_Un_S(offset: i8) "blt.un.s",
+    0x37 => Blt_Un_S(offset: i8) "blt.un.s" [CondBranch, 2 -> 0, ShortInlineBrTarget],
     /// Branch to target.
-    0x38 => Br(offset: i32) "br",
+    0x38 => Br(offset: i32) "br" [Branch, 0 -> 0, InlineBrTarget],
     /// Branch to target if value is zero (false).
-    0x39 => Br_False(offset: i32) "brfalse",
+    0x39 => Br_False(offset: i32) "brfalse" [CondBranch, 1 -> 0, InlineBrTarget],
     /// Branch to target if value is non-zero (true).
-    0x3A => Br_True(offset: i32) "brtrue",
+    0x3A => Br_True(offset: i32) "brtrue" [CondBranch, 1 -> 0, InlineBrTarget],
     /// Branch to target if equal.
-    0x3B => Beq(offset: i32) "beq",
+    0x3B => Beq(offset: i32) "beq" [CondBranch, 2 -> 0, InlineBrTarget],
     /// Branch to target if greater than or equal to.
-    0x3C => Bge(offset: i32) "bge",
+    0x3C => Bge(offset: i32) "bge" [CondBranch, 2 -> 0, InlineBrTarget],
     /// Branch to target if greater than.
-    0x3D => Bgt(offset: i32) "bgt",
+    0x3D => Bgt(offset: i32) "bgt" [CondBranch, 2 -> 0, InlineBrTarget],
     /// Branch to target if less than or equal to.
-    0x3E => Ble(offset: i32) "ble",
+    0x3E => Ble(offset: i32) "ble" [CondBranch, 2 -> 0, InlineBrTarget],
     /// Branch to target if less than.
-    0x3F => Blt(offset: i32) "blt",
+    0x3F => Blt(offset: i32) "blt" [CondBranch, 2 -> 0, InlineBrTarget],
     /// Branch to target if unequal or unordered.
-    0x40 => Bne_Un(offset: i32) "bne.un",
+    0x40 => Bne_Un(offset: i32) "bne.un" [CondBranch, 2 -> 0, InlineBrTarget],
     /// Branch to target if greater than or equal to (unsigned or unordered).
-    0x41 => Bge_Un(offset: i32) "bge.un",
+    0x41 => Bge_Un(offset: i32) "bge.un" [CondBranch, 2 -> 0, InlineBrTarget],
     /// Branch to target if greater than (unsigned or unordered).
-    0x42 => Bgt_Un(offset: i32) "bgt.un",
+    0x42 => Bgt_Un(offset: i32) "bgt.un" [CondBranch, 2 -> 0, InlineBrTarget],
     /// Branch to target if less than or equal to (unsigned or unordered).
-    0x43 => Ble_Un(offset: i32) "ble.un",
+    0x43 => Ble_Un(offset: i32) "ble.un" [CondBranch, 2 -> 0, InlineBrTarget],
     /// Branch to target if less than (unsigned or unordered).
-    0x44 => Blt_Un(offset: i32) "blt.un",
-    // Defined in macro
-    // 0x45 => Switch(targets: Vec<i32>) "switch",
+    0x44 => Blt_Un(offset: i32) "blt.un" [CondBranch, 2 -> 0, InlineBrTarget],
+    // 0x45 (switch) is defined by the macro
 
     /// Indirect load value of type int8 as int32 on the stack.
-    0x46 => LdInd_I1() "ldind.i1",
+    0x46 => LdInd_I1() "ldind.i1" [Next, 1 -> 1, InlineNone],
     /// Indirect load value of type unsigned int8 as int32 on the stack.
-    0x47 => LdInd_U1() "ldind.u1",
+    0x47 => LdInd_U1() "ldind.u1" [Next, 1 -> 1, InlineNone],
     /// Indirect load value of type int16 as int32 on the stack.
-    0x48 => LdInd_I2() "ldind.i2",
+    0x48 => LdInd_I2() "ldind.i2" [Next, 1 -> 1, InlineNone],
     /// Indirect load value of type unsigned int16 as int32 on the stack.
-    0x49 => LdInd_U2() "ldind.u2",
+    0x49 => LdInd_U2() "ldind.u2" [Next, 1 -> 1, InlineNone],
     /// Indirect load value of type int32 as int32 on the stack.
-    0x4A => LdInd_I4() "ldind.i4",
+    0x4A => LdInd_I4() "ldind.i4" [Next, 1 -> 1, InlineNone],
     /// Indirect load value of type unsigned int32 as int32 on the stack.
-    0x4B => LdInd_U4() "ldind.u4",
+    0x4B => LdInd_U4() "ldind.u4" [Next, 1 -> 1, InlineNone],
     /// Indirect load value of type int64 as int64 on the stack.
-    0x4C => LdInd_I8() "ldind.i8",
+    0x4C => LdInd_I8() "ldind.i8" [Next, 1 -> 1, InlineNone],
     /// Indirect load value of type native int as native int on the stack.
-    0x4D => LdInd_I() "ldind.i",
+    0x4D => LdInd_I() "ldind.i" [Next, 1 -> 1, InlineNone],
     /// Indirect load value of type float32 as F on the stack.
-    0x4E => LdInd_R4() "ldind.r4",
+    0x4E => LdInd_R4() "ldind.r4" [Next, 1 -> 1, InlineNone],
     /// Indirect load value of type float64 as F on the stack.
-    0x4F => LdInd_R8() "ldind.r8",
+    0x4F => LdInd_R8() "ldind.r8" [Next, 1 -> 1, InlineNone],
     /// Indirect load value of type object ref as O on the stack.
-    0x50 => LdInd_Ref() "ldind.ref",
+    0x50 => LdInd_Ref() "ldind.ref" [Next, 1 -> 1, InlineNone],
     /// Store value of type object ref (type O) into memory at address.
-    0x51 => StInd_Ref() "stind.ref",
+    0x51 => StInd_Ref() "stind.ref" [Next, 2 -> 0, InlineNone],
     /// Store value of type int8 into memory at address.
-    0x52 => StInd_I1() "stind.i1",
+    0x52 => StInd_I1() "stind.i1" [Next, 2 -> 0, InlineNone],
     /// Store value of type int16 into memory at address.
-    0x53 => StInd_I2() "stind.i2",
+    0x53 => StInd_I2() "stind.i2" [Next, 2 -> 0, InlineNone],
     /// Store value of type int32 into memory at address.
-    0x54 => StInd_I4() "stind.i4",
+    0x54 => StInd_I4() "stind.i4" [Next, 2 -> 0, InlineNone],
     /// Store value of type int64 into memory at address.
-    0x55 => StInd_I8() "stind.i8",
+    0x55 => StInd_I8() "stind.i8" [Next, 2 -> 0, InlineNone],
     /// Store value of type float32 into memory at address.
-    0x56 => StInd_R4() "stind.r4",
+    0x56 => StInd_R4() "stind.r4" [Next, 2 -> 0, InlineNone],
     /// Store value of type float64 into memory at address.
-    0x57 => StInd_R8() "stind.r8",
+    0x57 => StInd_R8() "stind.r8" [Next, 2 -> 0, InlineNone],
 
     /// Add two values, returning a new value.
-    0x58 => Add() "add",
+    0x58 => Add() "add" [Next, 2 -> 1, InlineNone],
     /// Subtract value2 from value1, returning a new value.
-    0x59 => Sub() "sub",
+    0x59 => Sub() "sub" [Next, 2 -> 1, InlineNone],
     /// Multiply values.
-    0x5A => Mul() "mul",
+    0x5A => Mul() "mul" [Next, 2 -> 1, InlineNone],
     /// Divide two values to return a quotient or floating-point result.
-    0x5B => Div() "div",
+    0x5B => Div() "div" [Next, 2 -> 1, InlineNone],
     /// Divide two values, unsigned, returning a quotient.
-    0x5C => DivUnsigned() "div.un",
+    0x5C => DivUnsigned() "div.un" [Next, 2 -> 1, InlineNone],
     /// Remainder when dividing one value by another.
-    0x5D => Rem() "rem",
+    0x5D => Rem() "rem" [Next, 2 -> 1, InlineNone],
     /// Remainder when dividing one unsigned value by another.
-    0x5E => RemUnsigned() "rem.un",
+    0x5E => RemUnsigned() "rem.un" [Next, 2 -> 1, InlineNone],
     /// Bitwise AND of two integral values, returns an integral value.
-    0x5F => And() "and",
+    0x5F => And() "and" [Next, 2 -> 1, InlineNone],
     /// Bitwise OR of two integer values, returns an integer.
-    0x60 => Or() "or",
+    0x60 => Or() "or" [Next, 2 -> 1, InlineNone],
     /// Bitwise XOR of integer values, returns an integer.
-    0x61 => Xor() "xor",
+    0x61 => Xor() "xor" [Next, 2 -> 1, InlineNone],
     /// Shift an integer left (shifting in zeros), return an integer.
-    0x62 => Shl() "shl",
+    0x62 => Shl() "shl" [Next, 2 -> 1, InlineNone],
     /// Shift an integer right (shift in sign), return an integer.
-    0x63 => Shr() "shr",
+    0x63 => Shr() "shr" [Next, 2 -> 1, InlineNone],
     /// Shift an integer right (shift in zero), return an integer.
-    0x64 => ShrUnsigned() "shr.un",
+    0x64 => ShrUnsigned() "shr.un" [Next, 2 -> 1, InlineNone],
     /// Negate value.
-    0x65 => Neg() "neg",
+    0x65 => Neg() "neg" [Next, 1 -> 1, InlineNone],
     /// Bitwise complement.
-    0x66 => Not() "not",
+    0x66 => Not() "not" [Next, 1 -> 1, InlineNone],
 
     /// Convert to int8, pushing int32 on stack.
-    0x67 => Conv_I1() "conv.i1",
+    0x67 => Conv_I1() "conv.i1" [Next, 1 -> 1, InlineNone],
     /// Convert to int16, pushing int32 on stack.
-    0x68 => Conv_I2() "conv.i2",
+    0x68 => Conv_I2() "conv.i2" [Next, 1 -> 1, InlineNone],
     /// Convert to int32, pushing int32 on stack.
-    0x69 => Conv_I4() "conv.i4",
+    0x69 => Conv_I4() "conv.i4" [Next, 1 -> 1, InlineNone],
     /// Convert to int64, pushing int64 on stack.
-    0x6A => Conv_I8() "conv.i8",
+    0x6A => Conv_I8() "conv.i8" [Next, 1 -> 1, InlineNone],
     /// Convert to float32, pushing F on stack.
-    0x6B => Conv_R4() "conv.r4",
+    0x6B => Conv_R4() "conv.r4" [Next, 1 -> 1, InlineNone],
     /// Convert to float64, pushing F on stack.
-    0x6C => Conv_R8() "conv.r8",
+    0x6C => Conv_R8() "conv.r8" [Next, 1 -> 1, InlineNone],
     /// Convert to unsigned int32, pushing int32 on stack.
-    0x6D => Conv_U4() "conv.u4",
+    0x6D => Conv_U4() "conv.u4" [Next, 1 -> 1, InlineNone],
     /// Convert to unsigned int64, pushing int64 on stack.
-    0x6E => Conv_U8() "conv.u8",
+    0x6E => Conv_U8() "conv.u8" [Next, 1 -> 1, InlineNone],
 
     /// Call a method associated with an object.
-    0x6F => CallVirt(method: Token) "callvirt",
+    0x6F => CallVirt(method: Token) "callvirt" [Call, Var -> Var, InlineMethod],
 
     /// Copy a value type from src to dest.
-    0x70 => CpObj(typeref: Token) "cpobj",
+    0x70 => CpObj(typeref: Token) "cpobj" [Next, 2 -> 0, InlineType],
     /// Copy the value stored at address src to the stack.
-    0x71 => LdObj(typeref: Token) "ldobj",
+    0x71 => LdObj(typeref: Token) "ldobj" [Next, 1 -> 1, InlineType],
     /// Push a string object for the literal string.
-    0x72 => LdStr(string: Token) "ldstr",
+    0x72 => LdStr(string: Token) "ldstr" [Next, 0 -> 1, InlineString],
     /// Allocate an uninitialized object or value type and call ctor.
-    0x73 => NewObj(ctor: Token) "newobj",
+    0x73 => NewObj(ctor: Token) "newobj" [Call, Var -> 1, InlineMethod],
     /// Cast obj to class.
-    0x74 => CastClass(typeref: Token) "castclass",
+    0x74 => CastClass(typeref: Token) "castclass" [Next, 1 -> 1, InlineType],
     /// Test if obj is an instance of class, returning null or an instance of that class or interface.
-    0x75 => IsInst(typeref: Token) "isinst",
+    0x75 => IsInst(typeref: Token) "isinst" [Next, 1 -> 1, InlineType],
     /// Convert unsigned integer to floating-point, pushing F on stack.
-    0x76 => Conv_R_Un() "conv.r.un",
+    0x76 => Conv_R_Un() "conv.r.un" [Next, 1 -> 1, InlineNone],
     /// Extract a value-type from obj, its boxed representation, and push a controlled-mutability managed pointer to it to the top of the stack.
-    0x79 => Unbox(typeref: Token) "unbox",
+    0x79 => Unbox(typeref: Token) "unbox" [Next, 1 -> 1, InlineType],
     /// Throw an exception.
-    0x7A => Throw() "throw",
+    0x7A => Throw() "throw" [Throw, 1 -> 0, InlineNone],
     /// Push the value of field of object (or value type) obj, onto the stack.
-    0x7B => LdFld(field: Token) "ldfld",
+    0x7B => LdFld(field: Token) "ldfld" [Next, 1 -> 1, InlineField],
     /// Push the address of field of object obj on the stack.
-    0x7C => LdFlda(field: Token) "ldflda",
+    0x7C => LdFlda(field: Token) "ldflda" [Next, 1 -> 1, InlineField],
     /// Replace the value of field of the object obj with value.
-    0x7D => SetFld(field: Token) "stfld",
+    0x7D => SetFld(field: Token) "stfld" [Next, 2 -> 0, InlineField],
     /// Push the value of the static field on the stack.
-    0x7E => GetFld(field: Token) "ldfld",
+    0x7E => LdsFld(field: Token) "ldsfld" [Next, 0 -> 1, InlineField],
     /// Push the address of the static field, field, on the stack.
-    0x7F => GetFlda(field: Token) "ldflda",
+    0x7F => LdsFlda(field: Token) "ldsflda" [Next, 0 -> 1, InlineField],
     /// Replace the value of the static field with val.
-    0x80 => StsFld(field: Token) "stsfld",
+    0x80 => StsFld(field: Token) "stsfld" [Next, 1 -> 0, InlineField],
     /// Store a value of type typeTok at an address.
-    0x81 => StObj(typeref: Token) "stobj",
+    0x81 => StObj(typeref: Token) "stobj" [Next, 2 -> 0, InlineType],
     /// Convert unsigned to an int8 (on the stack as int32) and throw an exception on overflow.
-    0x82 => Conv_Ovf_I1_Unsigned() "conv.ovf.i1.un",
+    0x82 => Conv_Ovf_I1_Unsigned() "conv.ovf.i1.un" [Next, 1 -> 1, InlineNone],
     /// Convert unsigned to an int16 (on the stack as int32) and throw an exception on overflow.
-    0x83 => Conv_Ovf_I2_Unsigned() "conv.ovf.i2.un",
+    0x83 => Conv_Ovf_I2_Unsigned() "conv.ovf.i2.un" [Next, 1 -> 1, InlineNone],
     /// Convert unsigned to an int32 (on the stack as int32) and throw an exception on overflow.
-    0x84 => Conv_Ovf_I4_Unsigned() "conv.ovf.i4.un",
+    0x84 => Conv_Ovf_I4_Unsigned() "conv.ovf.i4.un" [Next, 1 -> 1, InlineNone],
     /// Convert unsigned to an int64 (on the stack as int64) and throw an exception on overflow.
-    0x85 => Conv_Ovf_I8_Unsigned() "conv.ovf.i8.un",
+    0x85 => Conv_Ovf_I8_Unsigned() "conv.ovf.i8.un" [Next, 1 -> 1, InlineNone],
     /// Convert unsigned to an unsigned int8 (on the stack as int32) and throw an exception on overflow.
-    0x86 => Conv_Ovf_U1_Unsigned() "conv.ovf.u1.un",
+    0x86 => Conv_Ovf_U1_Unsigned() "conv.ovf.u1.un" [Next, 1 -> 1, InlineNone],
     /// Convert unsigned to an unsigned int16 (on the stack as int32) and throw an exception on overflow.
-    0x87 => Conv_Ovf_U2_Unsigned() "conv.ovf.u2.un",
+    0x87 => Conv_Ovf_U2_Unsigned() "conv.ovf.u2.un" [Next, 1 -> 1, InlineNone],
     /// Convert unsigned to an unsigned int32 (on the stack as int32) and throw an exception on overflow.
-    0x88 => Conv_Ovf_U4_Unsigned() "conv.ovf.u4.un",
+    0x88 => Conv_Ovf_U4_Unsigned() "conv.ovf.u4.un" [Next, 1 -> 1, InlineNone],
     /// Convert unsigned to an unsigned int64 (on the stack as int64) and throw an exception on overflow.
-    0x89 => Conv_Ovf_U8_Unsigned() "conv.ovf.u8.un",
+    0x89 => Conv_Ovf_U8_Unsigned() "conv.ovf.u8.un" [Next, 1 -> 1, InlineNone],
     /// Convert unsigned to a native int (on the stack as native int) and throw an exception on overflow.
-    0x8A => Conv_Ovf_I_Unsigned() "conv.ovf.i.un",
+    0x8A => Conv_Ovf_I_Unsigned() "conv.ovf.i.un" [Next, 1 -> 1, InlineNone],
     /// Convert unsigned to a native unsigned int (on the stack as native int) and throw an exception on overflow.
-    0x8B => Conv_Ovf_U_Unsigned() "conv.ovf.u.un",
+    0x8B => Conv_Ovf_U_Unsigned() "conv.ovf.u.un" [Next, 1 -> 1, InlineNone],
     /// Convert a boxable value to its boxed form.
-    0x8C => Box(typeref: Token) "box",
+    0x8C => Box(typeref: Token) "box" [Next, 1 -> 1, InlineType],
     /// Create a new array with elements of type etype.
-    0x8D => NewArr(typeref: Token) "newarr",
+    0x8D => NewArr(typeref: Token) "newarr" [Next, 1 -> 1, InlineType],
     /// Push the length (of type native unsigned int) of array on the stack.
-    0x8E => LdLen() "ldlen",
+    0x8E => LdLen() "ldlen" [Next, 1 -> 1, InlineNone],
     /// Load the address of element at index onto the top of the stack.
-    0x8F => LdElema(class: Token) "ldelema",
+    0x8F => LdElema(class: Token) "ldelema" [Next, 2 -> 1, InlineType],
     /// Load the element with type int8 at index onto the top of the stack as an int32.
-    0x90 => LdElem_I1() "ldelem.i1",
+    0x90 => LdElem_I1() "ldelem.i1" [Next, 2 -> 1, InlineNone],
     /// Load the element with type unsigned int8 at index onto the top of the stack as an int32.
-    0x91 => LdElem_U1() "ldelem.u1",
+    0x91 => LdElem_U1() "ldelem.u1" [Next, 2 -> 1, InlineNone],
     /// Load the element with type int16 at index onto the top of the stack as an int32.
-    0x92 => LdElem_I2() "ldelem.i2",
+    0x92 => LdElem_I2() "ldelem.i2" [Next, 2 -> 1, InlineNone],
     /// Load the element with type unsigned int16 at index onto the top of the stack as an int32.
-    0x93 => LdElem_U2() "ldelem.u2",
+    0x93 => LdElem_U2() "ldelem.u2" [Next, 2 -> 1, InlineNone],
     /// Load the element with type int32 at index onto the top of the stack as an int32.
-    0x94 => LdElem_I4() "ldelem.i4",
+    0x94 => LdElem_I4() "ldelem.i4" [Next, 2 -> 1, InlineNone],
     /// Load the element with type unsigned int32 at index onto the top of the stack as an int32.
-    0x95 => LdElem_U4() "ldelem.u4",
+    0x95 => LdElem_U4() "ldelem.u4" [Next, 2 -> 1, InlineNone],
     /// Load the element with type int64 at index onto the top of the stack as an int64.
-    0x96 => LdElem_I8() "ldelem.i8",
+    0x96 => LdElem_I8() "ldelem.i8" [Next, 2 -> 1, InlineNone],
     /// Load the element with type native int at index onto the top of the stack as a native int.
-    0x97 => LdElem_I() "ldelem.i",
+    0x97 => LdElem_I() "ldelem.i" [Next, 2 -> 1, InlineNone],
     /// Load the element with type float32 at index onto the top of the stack as an F.
-    0x98 => LdElem_R4() "ldelem.r4",
+    0x98 => LdElem_R4() "ldelem.r4" [Next, 2 -> 1, InlineNone],
     /// Load the element with type float64 at index onto the top of the stack as an F.
-    0x99 => LdElem_R8() "ldelem.r8",
+    0x99 => LdElem_R8() "ldelem.r8" [Next, 2 -> 1, InlineNone],
     /// Load the element at index onto the top of the stack as an O. The type of the O is the same as the element type of the array pushed on the CIL stack.
-    0x9A => LdElem_Ref() "ldelem.ref",
+    0x9A => LdElem_Ref() "ldelem.ref" [Next, 2 -> 1, InlineNone],
     /// Replace array element at index with the native int value on the stack.
-    0x9B => StElem_I() "stelem.i",
+    0x9B => StElem_I() "stelem.i" [Next, 3 -> 0, InlineNone],
     /// Replace array element at index with the int8 value on the stack.
-    0x9C => StElem_I1() "stelem.i1",
+    0x9C => StElem_I1() "stelem.i1" [Next, 3 -> 0, InlineNone],
     /// Replace array element at index with the int16 value on the stack.
-    0x9D => StElem_I2() "stelem.i2",
+    0x9D => StElem_I2() "stelem.i2" [Next, 3 -> 0, InlineNone],
     /// Replace array element at index with the int32 value on the stack.
-    0x9E => StElem_I4() "stelem.i4",
+    0x9E => StElem_I4() "stelem.i4" [Next, 3 -> 0, InlineNone],
     /// Replace array element at index with the int64 value on the stack.
-    0x9F => StElem_I8() "stelem.i8",
+    0x9F => StElem_I8() "stelem.i8" [Next, 3 -> 0, InlineNone],
     /// Replace array element at index with the float32 value on the stack.
-    0xA0 => StElem_R4() "stelem.r4",
+    0xA0 => StElem_R4() "stelem.r4" [Next, 3 -> 0, InlineNone],
     /// Replace array element at index with the float64 value on the stack.
-    0xA1 => StElem_R8() "stelem.r8",
+    0xA1 => StElem_R8() "stelem.r8" [Next, 3 -> 0, InlineNone],
     /// Replace array element at index with the ref value on the stack.
-    0xA2 => StElem_Ref() "stelem.ref",
+    0xA2 => StElem_Ref() "stelem.ref" [Next, 3 -> 0, InlineNone],
     /// Load the element at index onto the top of the stack.
-    0xA3 => LdElem_Any(typeref: Token) "ldelem.any",
+    0xA3 => LdElem_Any(typeref: Token) "ldelem" [Next, 2 -> 1, InlineType],
     /// Replace array element at index with the value on the stack.
-    0xA4 => StElem_Any(typeref: Token) "stelem.any",
+    0xA4 => StElem_Any(typeref: Token) "stelem" [Next, 3 -> 0, InlineType],
     /// Extract a value-type from obj, its boxed representation, and copy to the top of the stack.
-    0xA5 => Unbox_Any(typeref: Token) "unbox.any",
+    0xA5 => Unbox_Any(typeref: Token) "unbox.any" [Next, 1 -> 1, InlineType],
     /// Convert to an int8 (on the stack as int32) and throw an exception on overflow.
-    0xB3 => Conv_Ovf_I1() "conv.ovf.i1",
+    0xB3 => Conv_Ovf_I1() "conv.ovf.i1" [Next, 1 -> 1, InlineNone],
     /// Convert to an unsigned int8 (on the stack as int32) and throw an exception on overflow.
-    0xB4 => Conv_Ovf_U1() "conv.ovf.u1",
+    0xB4 => Conv_Ovf_U1() "conv.ovf.u1" [Next, 1 -> 1, InlineNone],
     /// Convert to an int16 (on the stack as int32) and throw an exception on overflow.
-    0xB5 => Conv_Ovf_I2() "conv.ovf.i2",
+    0xB5 => Conv_Ovf_I2() "conv.ovf.i2" [Next, 1 -> 1, InlineNone],
     /// Convert to an unsigned int16 (on the stack as int32) and throw an exception on overflow.
-    0xB6 => Conv_Ovf_U2() "conv.ovf.u2",
+    0xB6 => Conv_Ovf_U2() "conv.ovf.u2" [Next, 1 -> 1, InlineNone],
     /// Convert to an int32 (on the stack as int32) and throw an exception on overflow.
-    0xB7 => Conv_Ovf_I4() "conv.ovf.i4",
+    0xB7 => Conv_Ovf_I4() "conv.ovf.i4" [Next, 1 -> 1, InlineNone],
     /// Convert to an unsigned int32 (on the stack as int32) and throw an exception on overflow.
-    0xB8 => Conv_Ovf_U4() "conv.ovf.u4",
+    0xB8 => Conv_Ovf_U4() "conv.ovf.u4" [Next, 1 -> 1, InlineNone],
     /// Convert to an int64 (on the stack as int64) and throw an exception on overflow.
-    0xB9 => Conv_Ovf_I8() "conv.ovf.i8",
+    0xB9 => Conv_Ovf_I8() "conv.ovf.i8" [Next, 1 -> 1, InlineNone],
     /// Convert to an unsigned int64 (on the stack as int64) and throw an exception on overflow.
-    0xBA => Conv_Ovf_U8() "conv.ovf.u8",
+    0xBA => Conv_Ovf_U8() "conv.ovf.u8" [Next, 1 -> 1, InlineNone],
     /// Push the address stored in a typed reference.
-    0xC2 => RefAnyVal(class: Token) "refanyval",
+    0xC2 => RefAnyVal(class: Token) "refanyval" [Next, 1 -> 1, InlineType],
     /// Throw ArithmeticException if value is not a finite number.
-    0xC3 => CkFinite(typeref: Token) "ckfinite",
+    0xC3 => CkFinite() "ckfinite" [Next, 1 -> 1, InlineNone],
     /// Push a typed reference to ptr of type class onto the stack.
-    0xC6 => MkRefAny(typeref: Token) "mkrefany",
+    0xC6 => MkRefAny(typeref: Token) "mkrefany" [Next, 1 -> 1, InlineType],
     /// Convert metadata token to its runtime representation.
-    0xD0 => LdToken(token: Token) "ldtoken",
+    0xD0 => LdToken(token: Token) "ldtoken" [Next, 0 -> 1, InlineTok],
     /// Convert to unsigned int16, pushing int32 on stack.
-    0xD1 => Conv_U2() "conv.u2",
+    0xD1 => Conv_U2() "conv.u2" [Next, 1 -> 1, InlineNone],
     /// Convert to unsigned int8, pushing int32 on stack.
-    0xD2 => Conv_U1() "conv.u1",
+    0xD2 => Conv_U1() "conv.u1" [Next, 1 -> 1, InlineNone],
     /// Convert to native int, pushing native int on stack.
-    0xD3 => Conv_I() "conv.i",
+    0xD3 => Conv_I() "conv.i" [Next, 1 -> 1, InlineNone],
     /// Convert to a native int (on the stack as native int) and throw an exception on overflow.
-    0xD4 => Conv_Ovf_I() "conv.ovf.i",
+    0xD4 => Conv_Ovf_I() "conv.ovf.i" [Next, 1 -> 1, InlineNone],
     /// Convert to a native unsigned int (on the stack as native int) and throw an exception on overflow.
-    0xD5 => Conv_Ovf_U() "conv.ovf.u",
+    0xD5 => Conv_Ovf_U() "conv.ovf.u" [Next, 1 -> 1, InlineNone],
     /// Add signed integer values with overflow check.
-    0xD6 => Add_Ovf() "add.ovf",
+    0xD6 => Add_Ovf() "add.ovf" [Next, 2 -> 1, InlineNone],
     /// Add unsigned integer values with overflow check.
-    0xD7 => Add_Ovf_Unsigned() "add.ovf.un",
+    0xD7 => Add_Ovf_Unsigned() "add.ovf.un" [Next, 2 -> 1, InlineNone],
     /// Multiply signed integer values. Signed result shall fit in same size.
-    0xD8 => Mul_Ovf() "mul.ovf",
+    0xD8 => Mul_Ovf() "mul.ovf" [Next, 2 -> 1, InlineNone],
     /// Multiply unsigned integer values. Unsigned result shall fit in same size.
-    0xD9 => Mul_Ovf_Unsigned() "mul.ovf.un",
+    0xD9 => Mul_Ovf_Unsigned() "mul.ovf.un" [Next, 2 -> 1, InlineNone],
     /// Subtract native int from a native int. Signed result shall fit in same size.
-    0xDA => Sub_Ovf() "sub.ovf",
+    0xDA => Sub_Ovf() "sub.ovf" [Next, 2 -> 1, InlineNone],
     /// Subtract native unsigned int from a native unsigned int. Unsigned result shall fit in same size.
-    0xDB => Sub_Ovf_Unsigned() "sub.ovf.un",
-    /// End fault clause of an exception block.
-    0xDC => EndFaultOrFinally() "endfault/endfinally",
+    0xDB => Sub_Ovf_Unsigned() "sub.ovf.un" [Next, 2 -> 1, InlineNone],
+    /// End fault or finally clause of an exception block, also known as `endfault`.
+    0xDC => EndFaultOrFinally() "endfinally" [Return, 0 -> 0, InlineNone],
     /// Exit a protected region of code.
-    0xDD => Leave(offset: i32) "leave",
+    0xDD => Leave(offset: i32) "leave" [Branch, 0 -> 0, InlineBrTarget],
     /// Exit a protected region of code, short form.
-    0xDE => Leave_S(offset: i8) "leave.s",
+    0xDE => Leave_S(offset: i8) "leave.s" [Branch, 0 -> 0, ShortInlineBrTarget],
     /// Store value of type native int into memory at address.
-    0xDF => StInd_I() "stind.i",
+    0xDF => StInd_I() "stind.i" [Next, 2 -> 0, InlineNone],
     /// Convert to native unsigned int, pushing native int on stack.
-    0xE0 => Conv_U() "conv.u",
+    0xE0 => Conv_U() "conv.u" [Next, 1 -> 1, InlineNone],
 
     /// Return argument list handle for the current method.
-    0xFE_00 => ArgList() "arglist",
+    0xFE_00 => ArgList() "arglist" [Next, 0 -> 1, InlineNone],
     /// Push 1 (of type int32) if value1 equals value2, else push 0.
-    0xFE_01 => Ceq() "ceq",
+    0xFE_01 => Ceq() "ceq" [Next, 2 -> 1, InlineNone],
     /// Push 1 (of type int32) if value1 greater than value2, else push 0.
-    0xFE_02 => Cgt() "cgt",
+    0xFE_02 => Cgt() "cgt" [Next, 2 -> 1, InlineNone],
     /// Push 1 (of type int32) if value1 greater than value2, unsigned or unordered, else push 0.
-    0xFE_03 => Cgt_Un() "cgt.un",
+    0xFE_03 => Cgt_Un() "cgt.un" [Next, 2 -> 1, InlineNone],
     /// Push 1 (of type int32) if value1 lower than value2, else push 0.
-    0xFE_04 => Clt() "clt",
+    0xFE_04 => Clt() "clt" [Next, 2 -> 1, InlineNone],
     /// Push 1 (of type int32) if value1 lower than value2, unsigned or unordered, else push 0.
-    0xFE_05 => Clt_Un() "clt.un",
+    0xFE_05 => Clt_Un() "clt.un" [Next, 2 -> 1, InlineNone],
     /// Push a pointer to a method referenced by method, on the stack.
-    0xFE_06 => LdFtn(method: Token) "ldftn",
+    0xFE_06 => LdFtn(method: Token) "ldftn" [Next, 0 -> 1, InlineMethod],
     /// Push address of virtual method on the stack.
-    0xFE_07 => LdVirtFtn(method: Token) "ldvirtftn",
+    0xFE_07 => LdVirtFtn(method: Token) "ldvirtftn" [Next, 1 -> 1, InlineMethod],
     /// Load argument numbered num onto the stack.
-    0xFE_09 => LdArg(index: u16) "ldarg",
+    0xFE_09 => LdArg(index: u16) "ldarg" [Next, 0 -> 1, InlineVar],
     /// Fetch the address of argument argNum.
-    0xFE_0A => LdArgA(index: u16) "ldarga",
+    0xFE_0A => LdArgA(index: u16) "ldarga" [Next, 0 -> 1, InlineVar],
     /// Store value to the argument numbered num.
-    0xFE_0B => StArg(index: u16) "starg",
+    0xFE_0B => StArg(index: u16) "starg" [Next, 1 -> 0, InlineVar],
     /// Load local variable of index indx onto stack.
-    0xFE_0C => LdLoc(index: u16) "ldloc",
+    0xFE_0C => LdLoc(index: u16) "ldloc" [Next, 0 -> 1, InlineVar],
     /// Load address of local variable with index indx.
-    0xFE_0D => LdLocA(index: u16) "ldloca",
+    0xFE_0D => LdLocA(index: u16) "ldloca" [Next, 0 -> 1, InlineVar],
     /// Pop a value from stack into local variable indx.
-    0xFE_0E => StLoc(index: u16) "stloc",
+    0xFE_0E => StLoc(index: u16) "stloc" [Next, 1 -> 0, InlineVar],
     /// Allocate space from the local memory pool.
-    0xFE_0F => LocAlloc(size: u32) "localloc",
+    0xFE_0F => LocAlloc() "localloc" [Next, 1 -> 1, InlineNone],
     /// End an exception handling filter clause.
-    0xFE_11 => EndFilter() "endfilter",
+    0xFE_11 => EndFilter() "endfilter" [Return, 1 -> 0, InlineNone],
     /// Subsequent pointer instruction might be unaligned.
-    0xFE_12 => Unaligned() "unaligned.",
+    0xFE_12 => Unaligned(alignment: u8) "unaligned." [Meta, 0 -> 0, ShortInlineI],
     /// Subsequent pointer reference is volatile.
-    0xFE_13 => Volatile() "volatile.",
+    0xFE_13 => Volatile() "volatile." [Meta, 0 -> 0, InlineNone],
     /// Subsequent call terminates current method.
-    0xFE_14 => Tail() "tail.",
+    0xFE_14 => Tail() "tail." [Meta, 0 -> 0, InlineNone],
     /// Initialize the value at address dest.
-    0xFE_15 => InitObj(type_token: Token) "initobj",
+    0xFE_15 => InitObj(type_token: Token) "initobj" [Next, 1 -> 0, InlineType],
     /// Call a virtual method on a type constrained to be type T.
-    0xFE_16 => Constrained(this_type: Token) "constrained.",
+    0xFE_16 => Constrained(this_type: Token) "constrained." [Meta, 0 -> 0, InlineType],
     /// Copy data from memory to memory.
-    0xFE_17 => CpBlk() "cpblk",
+    0xFE_17 => CpBlk() "cpblk" [Next, 3 -> 0, InlineNone],
     /// Set all bytes in a block of memory to a given byte value.
-    0xFE_18 => InitBlk() "initblk",
+    0xFE_18 => InitBlk() "initblk" [Next, 3 -> 0, InlineNone],
+    /// The specified fault check(s) normally performed as part of the execution of the subsequent instruction can/shall be skipped.
+    0xFE_19 => No(checks: DisabledFaultChecks) "no." [Meta, 0 -> 0, ShortInlineI],
+    /// Rethrow the current exception.
+    0xFE_1A => Rethrow() "rethrow" [Throw, 0 -> 0, InlineNone],
     /// Push the size, in bytes, of a type as an unsigned int32.
-    0xFE_1C => SizeOf(type_token: Token) "sizeof"
+    0xFE_1C => SizeOf(type_token: Token) "sizeof" [Next, 0 -> 1, InlineType],
+    /// Push the type token stored in a typed reference.
+    0xFE_1D => RefAnyType() "refanytype" [Next, 1 -> 1, InlineNone],
+    /// Specify that the subsequent array address operation performs no type check at runtime, and that it returns a controlled-mutability managed pointer.
+    0xFE_1E => Readonly() "readonly." [Meta, 0 -> 0, InlineNone]
 }
 
 impl RawOpcode {
+    /// Whether this opcode transfers control to a branch target, including `leave` and `switch`
     pub fn is_branch(&self) -> bool {
         matches!(
-            self,
-            Self::Br { .. }
-                | Self::Br_False { .. }
-                | Self::Br_True { .. }
-                | Self::Beq { .. }
-                | Self::Bge { .. }
-                | Self::Bgt { .. }
-                | Self::Ble { .. }
-                | Self::Blt { .. }
-                | Self::Bne_Un { .. }
-                | Self::Bge_Un { .. }
-                | Self::Bgt_Un { .. }
-                | Self::Ble_Un { .. }
-                | Self::Blt_Un { .. }
-                | Self::Br_S { .. }
-                | Self::Br_False_S { .. }
-                | Self::Br_True_S { .. }
-                | Self::Beq_S { .. }
-                | Self::Bge_S { .. }
-                | Self::Bgt_S { .. }
-                | Self::Ble_S { .. }
-                | Self::Blt_S { .. }
-                | Self::Bne_Un_S { .. }
-                | Self::Bge_Un_S { .. }
-                | Self::Bgt_Un_S { .. }
-                | Self::Ble_Un_S { .. }
-                | Self::Blt_Un_S { .. }
-                | Self::Switch { .. }
+            self.flow_control(),
+            FlowControl::Branch | FlowControl::CondBranch
         )
     }
 
+    /// Whether this opcode ends a basic block: branches, returns, throws and `jmp`, as well as the
+    /// ends of exception handlers
+    pub fn ends_block(&self) -> bool {
+        matches!(
+            self.flow_control(),
+            FlowControl::Branch
+                | FlowControl::CondBranch
+                | FlowControl::Return
+                | FlowControl::Throw
+        ) || matches!(self, Self::Jmp { .. })
+    }
+
+    /// Whether this opcode modifies the instruction that follows it
+    pub fn is_prefix(&self) -> bool {
+        self.flow_control() == FlowControl::Meta
+    }
+
     /// If this opcode is a branch, returns the offset relative to the start of THIS instruction.
     pub fn branch_offset(&self) -> Option<i32> {
         Some(
@@ -610,17 +820,539 @@ impl RawOpcode {
                 Self::Bgt_Un_S { offset } => i32::from(*offset),
                 Self::Ble_Un_S { offset } => i32::from(*offset),
                 Self::Blt_Un_S { offset } => i32::from(*offset),
+                Self::Leave { offset } => *offset,
+                Self::Leave_S { offset } => i32::from(*offset),
                 _ => return None, // Not a branch
             } + self.size() as i32,
         )
     }
 }
 
+/// How an opcode affects control flow, as listed in the opcode table of the runtime
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlowControl {
+    /// Execution continues with the next instruction
+    Next,
+    /// `break`, which continues with the next instruction after the debugger is done
+    Break,
+    /// Method call. Execution continues with the next instruction, except for `jmp`.
+    Call,
+    /// Exits the method or an exception handler
+    Return,
+    /// Unconditional branch, including `leave`
+    Branch,
+    /// Conditional branch or `switch`, which may also continue with the next instruction
+    CondBranch,
+    Throw,
+    /// Prefix that modifies the next instruction
+    Meta,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StackCount {
+    Fixed(u8),
+    /// Depends on the signature of the called or current method
+    Variable,
+}
+
+/// Encoding of an opcode's operand (ECMA-335 III.1.2)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperandType {
+    InlineNone,
+    /// 8-bit branch offset
+    ShortInlineBrTarget,
+    /// 32-bit branch offset
+    InlineBrTarget,
+    /// 8-bit integer
+    ShortInlineI,
+    /// 32-bit integer
+    InlineI,
+    InlineI8,
+    /// 32-bit float
+    ShortInlineR,
+    /// 64-bit float
+    InlineR,
+    /// MethodDef, MemberRef or MethodSpec token
+    InlineMethod,
+    /// Field or MemberRef token
+    InlineField,
+    /// TypeDef, TypeRef or TypeSpec token
+    InlineType,
+    /// Type, method or field token, as used by `ldtoken`
+    InlineTok,
+    /// User string token
+    InlineString,
+    /// StandAloneSig token
+    InlineSig,
+    /// Target count followed by the 32-bit offsets
+    InlineSwitch,
+    /// 8-bit local or argument index
+    ShortInlineVar,
+    /// 16-bit local or argument index
+    InlineVar,
+}
+
+/// Values popped and pushed by an instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StackEffect {
+    pub pops: u16,
+    pub pushes: u16,
+}
+
+/// Prefixes that modify an instruction (ECMA-335 III.2)
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Prefixes {
+    /// `constrained.`, the type the receiver of the following `callvirt` is constrained to
+    pub constrained: Option<Token>,
+    /// `readonly.`, the following `ldelema` returns a controlled-mutability managed pointer
+    pub readonly: bool,
+    /// `unaligned.`, the alignment of the address in bytes
+    pub unaligned: Option<u8>,
+    /// `volatile.`
+    pub volatile: bool,
+    /// `tail.`, the following call is a tail call
+    pub tail: bool,
+    /// `no.`, the checks the runtime may skip
+    pub disabled_checks: DisabledFaultChecks,
+}
+
+impl Prefixes {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Applies a prefix opcode, returns false if `opcode` is not a prefix
+    pub fn add(&mut self, opcode: &RawOpcode) -> bool {
+        match *opcode {
+            RawOpcode::Constrained { this_type } => self.constrained = Some(this_type),
+            RawOpcode::Readonly {} => self.readonly = true,
+            RawOpcode::Unaligned { alignment } => self.unaligned = Some(alignment),
+            RawOpcode::Volatile {} => self.volatile = true,
+            RawOpcode::Tail {} => self.tail = true,
+            RawOpcode::No { checks } => self.disabled_checks |= checks,
+            _ => return false,
+        }
+        true
+    }
+
+    /// The prefix opcodes, in the order they are encoded
+    pub fn opcodes(&self) -> Vec<RawOpcode> {
+        let mut opcodes = Vec::new();
+        if let Some(this_type) = self.constrained {
+            opcodes.push(RawOpcode::Constrained { this_type });
+        }
+        if self.readonly {
+            opcodes.push(RawOpcode::Readonly {});
+        }
+        if let Some(alignment) = self.unaligned {
+            opcodes.push(RawOpcode::Unaligned { alignment });
+        }
+        if self.volatile {
+            opcodes.push(RawOpcode::Volatile {});
+        }
+        if self.tail {
+            opcodes.push(RawOpcode::Tail {});
+        }
+        if !self.disabled_checks.is_empty() {
+            opcodes.push(RawOpcode::No {
+                checks: self.disabled_checks,
+            });
+        }
+        opcodes
+    }
+
+    /// Encoded size of all prefixes in bytes
+    pub fn size(&self) -> usize {
+        self.opcodes().iter().map(RawOpcode::size).sum()
+    }
+}
+
+/// A decoded instruction of a method body
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instruction {
+    /// Offset of the first byte of the instruction, relative to the start of the method body. This
+    /// is the offset of the first prefix if there are any.
+    pub offset: u32,
+    pub prefixes: Prefixes,
+    pub opcode: RawOpcode,
+    pub operand: Operand,
+}
+
+impl Instruction {
+    pub fn new(offset: u32, prefixes: Prefixes, opcode: RawOpcode) -> Self {
+        let operand = Operand::decode(offset + prefixes.size() as u32, &opcode);
+        Self {
+            offset,
+            prefixes,
+            opcode,
+            operand,
+        }
+    }
+
+    /// Offset of the instruction that directly follows this one
+    pub fn next_offset(&self) -> u32 {
+        self.offset + self.prefixes.size() as u32 + self.opcode.size() as u32
+    }
+
+    /// Number of values this instruction pops and pushes. Calls use the signature of the callee,
+    /// `ret` uses `method`, the signature of the method this instruction belongs to.
+    pub fn stack_effect(
+        &self,
+        image: &CilImage,
+        method: &StandaloneMethodSignature,
+    ) -> Result<StackEffect> {
+        let fixed = |count: StackCount| match count {
+            StackCount::Fixed(count) => count as u16,
+            StackCount::Variable => 0,
+        };
+        let mut effect = StackEffect {
+            pops: fixed(self.opcode.pops()),
+            pushes: fixed(self.opcode.pushes()),
+        };
+
+        let callee = match self.opcode {
+            RawOpcode::Ret {} => {
+                effect.pops = (*method.return_type.strip_modifiers() != Element::Void) as u16;
+                return Ok(effect);
+            }
+            RawOpcode::Call { method } | RawOpcode::CallVirt { method } => {
+                self.callee_signature(image, method)?
+            }
+            RawOpcode::NewObj { ctor } => {
+                // The constructor's `this` is created by newobj rather than popped
+                effect.pops = self.callee_signature(image, ctor)?.parameters.len() as u16;
+                return Ok(effect);
+            }
+            RawOpcode::CallInd { callsitedescr } => {
+                let handle =
+                    StandAloneSigHandle::try_from(callsitedescr).map_err(Error::InvalidToken)?;
+                let signature = image.call_site_signature(handle)?;
+                // The function pointer is popped after the arguments
+                effect.pops = 1;
+                signature
+            }
+            _ => return Ok(effect),
+        };
+
+        let has_this = callee.header.has_this() && !callee.header.explicit_this();
+        effect.pops += callee.parameters.len() as u16 + has_this as u16;
+        effect.pushes = (*callee.return_type.strip_modifiers() != Element::Void) as u16;
+        Ok(effect)
+    }
+
+    fn callee_signature(
+        &self,
+        image: &CilImage,
+        token: Token,
+    ) -> Result<StandaloneMethodSignature> {
+        let method = match image.resolve_token(token)? {
+            TokenReference::Method(method) => method,
+            TokenReference::MethodSpec(handle) => {
+                let method_spec = &image.method_specs[handle.index()];
+                MethodDefOrRef::try_from(method_spec.method as u32)
+                    .map_err(|_| Error::InvalidToken(token))?
+            }
+            _ => return Err(Error::InvalidToken(token)),
+        };
+
+        match method {
+            MethodDefOrRef::MethodDef(index) => image.method_signature(MethodDefHandle(index)),
+            MethodDefOrRef::MemberRef(index) => {
+                match image.member_ref_signature(MemberRefHandle(index))? {
+                    MemberRefSignature::Method(signature) => Ok(signature),
+                    MemberRefSignature::Field(_) => Err(Error::InvalidToken(token)),
+                }
+            }
+        }
+    }
+
+    /// Absolute offsets this instruction can branch to, in operand order. Empty if it is not a
+    /// branch.
+    pub fn branch_targets(&self) -> &[u32] {
+        match &self.operand {
+            Operand::Target(target) => std::slice::from_ref(target),
+            Operand::Switch(targets) => targets,
+            _ => &[],
+        }
+    }
+}
+
+impl Display for Instruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "IL_{:04x}: ", self.offset)?;
+        for prefix in self.prefixes.opcodes() {
+            match prefix {
+                RawOpcode::Constrained { this_type } => {
+                    write!(f, "{} {:#010x} ", prefix.asm_name(), this_type.0)?
+                }
+                RawOpcode::Unaligned { alignment } => {
+                    write!(f, "{} {alignment} ", prefix.asm_name())?
+                }
+                RawOpcode::No { checks } => {
+                    write!(f, "{} {:#x} ", prefix.asm_name(), checks.bits())?
+                }
+                _ => write!(f, "{} ", prefix.asm_name())?,
+            }
+        }
+        write!(f, "{}", self.opcode.asm_name())?;
+        match &self.operand {
+            Operand::None => Ok(()),
+            Operand::Target(target) => write!(f, " IL_{target:04x}"),
+            Operand::Switch(targets) => {
+                write!(f, " (")?;
+                for (i, target) in targets.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "IL_{target:04x}")?;
+                }
+                write!(f, ")")
+            }
+            // Implicit in the opcode for the short forms
+            Operand::Local(_) | Operand::Argument(_) | Operand::I4(_)
+                if self.opcode.size() == 1 =>
+            {
+                Ok(())
+            }
+            Operand::Local(index) | Operand::Argument(index) => write!(f, " {index}"),
+            Operand::I4(value) => write!(f, " {value}"),
+            Operand::I8(value) => write!(f, " {value}"),
+            Operand::R4(value) => write!(f, " {value}"),
+            Operand::R8(value) => write!(f, " {value}"),
+            Operand::Token(token) => write!(f, " {:#010x}", token.0),
+        }
+    }
+}
+
+/// Decodes the instructions of a method body, attaching prefixes to the instruction that follows them
+pub fn decode_instructions(code: &[u8]) -> Result<Vec<Instruction>> {
+    let mut reader = Cursor::new(code);
+    let mut instructions = Vec::new();
+    let mut prefixes = Prefixes::default();
+    let mut offset = 0;
+    while reader.position() < code.len() as u64 {
+        let opcode: RawOpcode = reader.read_le()?;
+        if prefixes.add(&opcode) {
+            continue;
+        }
+
+        instructions.push(Instruction::new(
+            offset,
+            std::mem::take(&mut prefixes),
+            opcode,
+        ));
+        offset = reader.position() as u32;
+    }
+
+    if !prefixes.is_empty() {
+        return Err(binrw::Error::AssertFail {
+            pos: reader.position(),
+            message: "Prefix at the end of the method body".to_string(),
+        }
+        .into());
+    }
+
+    Ok(instructions)
+}
+
+/// Operand of an instruction, with branch targets made absolute.
+///
+/// Short forms that encode their operand in the opcode, such as `ldarg.0` or `ldc.i4.m1`, get the
+/// same operand as their long form.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operand {
+    None,
+    /// Offset of the branch target, relative to the start of the method body
+    Target(u32),
+    /// Offsets of the `switch` targets, relative to the start of the method body
+    Switch(Vec<u32>),
+    /// Index into the local variable signature
+    Local(u16),
+    /// Argument number, where 0 is `this` for instance methods
+    Argument(u16),
+    I4(i32),
+    I8(i64),
+    R4(f32),
+    R8(f64),
+    /// Metadata or user string token, see [`CilImage::resolve_token`]
+    Token(Token),
+}
+
+impl Operand {
+    fn decode(offset: u32, opcode: &RawOpcode) -> Self {
+        let next_offset = offset as i64 + opcode.size() as i64;
+        let target = |relative: i32| (next_offset + relative as i64) as u32;
+
+        match *opcode {
+            RawOpcode::LdArg_0 {} => Self::Argument(0),
+            RawOpcode::LdArg_1 {} => Self::Argument(1),
+            RawOpcode::LdArg_2 {} => Self::Argument(2),
+            RawOpcode::LdArg_3 {} => Self::Argument(3),
+            RawOpcode::LdArg_S { index }
+            | RawOpcode::LdArgA_S { index }
+            | RawOpcode::StArg_S { index } => Self::Argument(index as u16),
+            RawOpcode::LdArg { index }
+            | RawOpcode::LdArgA { index }
+            | RawOpcode::StArg { index } => Self::Argument(index),
+
+            RawOpcode::LdLoc_0 {} | RawOpcode::StLoc_0 {} => Self::Local(0),
+            RawOpcode::LdLoc_1 {} | RawOpcode::StLoc_1 {} => Self::Local(1),
+            RawOpcode::LdLoc_2 {} | RawOpcode::StLoc_2 {} => Self::Local(2),
+            RawOpcode::LdLoc_3 {} | RawOpcode::StLoc_3 {} => Self::Local(3),
+            RawOpcode::LdLoc_S { index }
+            | RawOpcode::LdLocA_S { index }
+            | RawOpcode::StLoc_S { index } => Self::Local(index as u16),
+            RawOpcode::LdLoc { index }
+            | RawOpcode::LdLocA { index }
+            | RawOpcode::StLoc { index } => Self::Local(index),
+
+            RawOpcode::Ldc_I4_M1 {} => Self::I4(-1),
+            RawOpcode::Ldc_I4_0 {} => Self::I4(0),
+            RawOpcode::Ldc_I4_1 {} => Self::I4(1),
+            RawOpcode::Ldc_I4_2 {} => Self::I4(2),
+            RawOpcode::Ldc_I4_3 {} => Self::I4(3),
+            RawOpcode::Ldc_I4_4 {} => Self::I4(4),
+            RawOpcode::Ldc_I4_5 {} => Self::I4(5),
+            RawOpcode::Ldc_I4_6 {} => Self::I4(6),
+            RawOpcode::Ldc_I4_7 {} => Self::I4(7),
+            RawOpcode::Ldc_I4_8 {} => Self::I4(8),
+            RawOpcode::Ldc_I4_S { value } => Self::I4(value as i32),
+            RawOpcode::Ldc_I4 { value } => Self::I4(value),
+            RawOpcode::Ldc_I8 { value } => Self::I8(value),
+            RawOpcode::Ldc_R4 { value } => Self::R4(value),
+            RawOpcode::Ldc_R8 { value } => Self::R8(value),
+
+            RawOpcode::Jmp { method }
+            | RawOpcode::Call { method }
+            | RawOpcode::CallVirt { method }
+            | RawOpcode::LdFtn { method }
+            | RawOpcode::LdVirtFtn { method } => Self::Token(method),
+            RawOpcode::CallInd { callsitedescr } => Self::Token(callsitedescr),
+            RawOpcode::NewObj { ctor } => Self::Token(ctor),
+            RawOpcode::LdStr { string } => Self::Token(string),
+            RawOpcode::LdFld { field }
+            | RawOpcode::LdFlda { field }
+            | RawOpcode::SetFld { field }
+            | RawOpcode::LdsFld { field }
+            | RawOpcode::LdsFlda { field }
+            | RawOpcode::StsFld { field } => Self::Token(field),
+            RawOpcode::CpObj { typeref }
+            | RawOpcode::LdObj { typeref }
+            | RawOpcode::CastClass { typeref }
+            | RawOpcode::IsInst { typeref }
+            | RawOpcode::Unbox { typeref }
+            | RawOpcode::StObj { typeref }
+            | RawOpcode::Box { typeref }
+            | RawOpcode::NewArr { typeref }
+            | RawOpcode::LdElem_Any { typeref }
+            | RawOpcode::StElem_Any { typeref }
+            | RawOpcode::Unbox_Any { typeref }
+            | RawOpcode::MkRefAny { typeref } => Self::Token(typeref),
+            RawOpcode::LdElema { class } | RawOpcode::RefAnyVal { class } => Self::Token(class),
+            RawOpcode::LdToken { token } => Self::Token(token),
+            RawOpcode::InitObj { type_token } | RawOpcode::SizeOf { type_token } => {
+                Self::Token(type_token)
+            }
+
+            RawOpcode::Switch { ref targets } => {
+                Self::Switch(targets.iter().map(|&t| target(t)).collect())
+            }
+            _ => match opcode.branch_offset() {
+                // Relative to the start of this instruction
+                Some(relative) => Self::Target((offset as i64 + relative as i64) as u32),
+                None => Self::None,
+            },
+        }
+    }
+
+    pub fn token(&self) -> Option<Token> {
+        match self {
+            Self::Token(token) => Some(*token),
+            _ => None,
+        }
+    }
+}
+
+/// What the token operand of an instruction refers to
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenReference {
+    Type(TypeDefOrRef),
+    Method(MethodDefOrRef),
+    /// Instantiation of a generic method
+    MethodSpec(MethodSpecHandle),
+    Field(FieldHandle),
+    /// MemberRef with a field signature
+    FieldRef(MemberRefHandle),
+    /// `calli` call site signature
+    Signature(StandAloneSigHandle),
+    String(UserString),
+}
+
+impl CilImage {
+    /// Resolves the token operand of an instruction to the row or string it refers to.
+    ///
+    /// MemberRefs are told apart by their signature, since `ldtoken` accepts both methods and fields.
+    pub fn resolve_token(&self, token: Token) -> Result<TokenReference> {
+        let invalid = || Error::InvalidToken(token);
+        let row_exists = |len: usize| (1..=len).contains(&(token.index() as usize));
+
+        Ok(match token.kind() {
+            TokenKind::TypeDef if row_exists(self.type_defs.len()) => {
+                TokenReference::Type(TypeDefOrRef::TypeDef(token.index()))
+            }
+            TokenKind::TypeRef if row_exists(self.type_refs.len()) => {
+                TokenReference::Type(TypeDefOrRef::TypeRef(token.index()))
+            }
+            TokenKind::TypeSpec if row_exists(self.type_specs.len()) => {
+                TokenReference::Type(TypeDefOrRef::TypeSpec(token.index()))
+            }
+            TokenKind::MethodDef if row_exists(self.method_defs.len()) => {
+                TokenReference::Method(MethodDefOrRef::MethodDef(token.index()))
+            }
+            TokenKind::MethodSpec if row_exists(self.method_specs.len()) => {
+                TokenReference::MethodSpec(MethodSpecHandle(token.index()))
+            }
+            TokenKind::Field if row_exists(self.fields.len()) => {
+                TokenReference::Field(FieldHandle(token.index()))
+            }
+            TokenKind::MemberRef if row_exists(self.member_refs.len()) => {
+                let handle = MemberRefHandle(token.index());
+                match self.member_ref_signature(handle)? {
+                    MemberRefSignature::Field(_) => TokenReference::FieldRef(handle),
+                    MemberRefSignature::Method(_) => {
+                        TokenReference::Method(MethodDefOrRef::MemberRef(token.index()))
+                    }
+                }
+            }
+            TokenKind::StandAloneSig if row_exists(self.stand_alone_sigs.len()) => {
+                TokenReference::Signature(StandAloneSigHandle(token.index()))
+            }
+            TokenKind::UserString => {
+                TokenReference::String(self.user_strings.entry(token).ok_or_else(invalid)?)
+            }
+            _ => return Err(invalid()),
+        })
+    }
+}
+
 bitflags! {
-    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    /// Operand of the `no.` prefix
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
     pub struct DisabledFaultChecks: u8 {
         const TYPE_CHECK = 0x01;
         const RANGE_CHECK = 0x02;
         const NULL_CHECK = 0x04;
     }
 }
+
+impl binrw::BinRead for DisabledFaultChecks {
+    type Args<'a> = ();
+
+    fn read_options<R: std::io::Read + std::io::Seek>(
+        reader: &mut R,
+        endian: binrw::Endian,
+        _args: Self::Args<'_>,
+    ) -> binrw::BinResult<Self> {
+        Ok(Self::from_bits_retain(reader.read_type(endian)?))
+    }
+}
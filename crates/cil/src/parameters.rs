@@ -0,0 +1,120 @@
+//! Method parameters, combining the Param table with the method signature (ECMA-335 II.22.33)
+
+use crate::{
+    Result,
+    constant::ConstantValue,
+    error::Error,
+    image::CilImage,
+    marshal::MarshalDescriptor,
+    meta::{MethodDefHandle, ParamHandle},
+    signature::Element,
+    tables::{HasConstant, HasFieldMarshal, ParamAttributes},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MethodParameter {
+    /// 0 for the return value, 1 for the first parameter. Does not count `this`.
+    pub sequence: u16,
+    /// Param row describing this parameter. Compilers usually leave these out for return values,
+    /// and some leave them out altogether.
+    pub handle: Option<ParamHandle>,
+    /// `None` if there's no Param row or the name is empty
+    pub name: Option<String>,
+    pub param_type: Element,
+    pub flags: ParamAttributes,
+    pub default_value: Option<ConstantValue>,
+    pub marshal: Option<MarshalDescriptor>,
+}
+
+impl MethodParameter {
+    pub fn is_in(&self) -> bool {
+        self.flags.is_in()
+    }
+
+    pub fn is_out(&self) -> bool {
+        self.flags.is_out()
+    }
+
+    pub fn is_optional(&self) -> bool {
+        self.flags.is_optional()
+    }
+
+    pub fn has_default(&self) -> bool {
+        self.flags.has_default()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MethodParameters {
+    pub return_value: MethodParameter,
+    /// Declared parameters in signature order, excluding `this`
+    pub parameters: Vec<MethodParameter>,
+}
+
+impl CilImage {
+    /// Parameters of a method definition, with their names, types, attributes, default values and
+    /// marshalling descriptors.
+    ///
+    /// Param rows are matched to the signature by their sequence number, parameters without a row get
+    /// no name and default attributes.
+    pub fn method_parameters(&self, handle: MethodDefHandle) -> Result<MethodParameters> {
+        let signature = self.method_signature(handle)?;
+
+        let mut rows: Vec<Option<ParamHandle>> = vec![None; signature.parameters.len() + 1];
+        for param in self.params_of(handle) {
+            let row = self
+                .params
+                .get(param.index())
+                .ok_or(Error::InvalidToken(param.token()))?;
+            // Rows past the end of the signature are invalid, keep the first row for a duplicated sequence
+            if let Some(slot @ None) = rows.get_mut(row.sequence as usize) {
+                *slot = Some(param);
+            }
+        }
+
+        let mut parameters = Vec::with_capacity(signature.parameters.len());
+        let return_value = self.method_parameter(0, rows[0], signature.return_type)?;
+        for (i, param_type) in signature.parameters.into_iter().enumerate() {
+            let sequence = i as u16 + 1;
+            parameters.push(self.method_parameter(sequence, rows[i + 1], param_type)?);
+        }
+
+        Ok(MethodParameters {
+            return_value,
+            parameters,
+        })
+    }
+
+    fn method_parameter(
+        &self,
+        sequence: u16,
+        handle: Option<ParamHandle>,
+        param_type: Element,
+    ) -> Result<MethodParameter> {
+        let Some(handle) = handle else {
+            return Ok(MethodParameter {
+                sequence,
+                handle: None,
+                name: None,
+                param_type,
+                flags: ParamAttributes(0),
+                default_value: None,
+                marshal: None,
+            });
+        };
+
+        let param = &self.params[handle.index()];
+        let default_value = self.constant_value(HasConstant::Param(handle.0))?;
+        let marshal = self.marshal_descriptor(HasFieldMarshal::Param(handle.0))?;
+
+        Ok(MethodParameter {
+            sequence,
+            handle: Some(handle),
+            name: (!param.name.is_empty()).then(|| param.name.clone()),
+            param_type,
+            flags: param.flags,
+            default_value,
+            marshal,
+        })
+    }
+}
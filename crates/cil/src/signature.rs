@@ -1,20 +1,27 @@
-use std::{fmt::Display, io::Cursor};
+use std::io::Cursor;
 
 use binrw::{BinRead, BinReaderExt, binread};
 use int_enum::IntEnum;
 
 use crate::{
-    Result, bitfield, image::CilImage, meta::Token, signature, tables::TypeDefOrRef,
-    util::PackedU32,
+    Result, bitfield,
+    error::Error,
+    image::CilImage,
+    meta::{
+        MemberDef, MemberRefHandle, MethodDefHandle, TypeDefHandle, TypeRefHandle, TypeSpecHandle,
+    },
+    tables::{MemberRefParent, ResolutionScope, TypeDefOrRef},
+    util::{PackedI32, PackedU32},
 };
 
 #[repr(u8)]
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SignatureKind {
     StandaloneMethod,
     Field,
     LocalVar,
     Property,
+    MethodSpec,
 }
 
 impl TryFrom<u8> for SignatureKind {
@@ -22,31 +29,58 @@ impl TryFrom<u8> for SignatureKind {
 
     fn try_from(value: u8) -> core::result::Result<Self, Self::Error> {
         match value & 0xF {
-            0x0..=0x5 => Ok(SignatureKind::StandaloneMethod),
+            0x0..=0x5 | 0x9 => Ok(SignatureKind::StandaloneMethod),
             0x6 => Ok(SignatureKind::Field),
             0x7 => Ok(SignatureKind::LocalVar),
             0x8 => Ok(SignatureKind::Property),
+            0xA => Ok(SignatureKind::MethodSpec),
             _ => Err("Invalid signature kind"),
         }
     }
 }
 
+/// Parses a signature blob, checking that its leading calling convention byte matches `expected`.
+/// `pointer_size` is the pointer width of the image in bytes, see [`CilImage::pointer_size`].
+fn parse_signature<T>(blob: &[u8], expected: SignatureKind, pointer_size: u32) -> Result<T>
+where
+    T: for<'a> BinRead<Args<'a> = (u32,)>,
+{
+    let mut reader = Cursor::new(blob);
+    let found: u8 = reader.read_le()?;
+    if SignatureKind::try_from(found) != Ok(expected) {
+        return Err(Error::UnexpectedSignatureKind { expected, found });
+    }
+
+    reader.set_position(0);
+    Ok(reader.read_le_args((pointer_size,))?)
+}
+
+/// Reads a pointer-sized value of `pointer_size` bytes
+#[binrw::parser(reader, endian)]
+fn read_pointer(pointer_size: u32) -> binrw::BinResult<u64> {
+    match pointer_size {
+        8 => u64::read_options(reader, endian, ()),
+        _ => u32::read_options(reader, endian, ()).map(u64::from),
+    }
+}
+
 impl BinRead for SignatureKind {
     type Args<'a> = ();
 
     fn read_options<R: std::io::Read + std::io::Seek>(
         reader: &mut R,
-        endian: binrw::Endian,
-        args: Self::Args<'_>,
+        _endian: binrw::Endian,
+        _args: Self::Args<'_>,
     ) -> binrw::BinResult<Self> {
         let value = reader.read_le::<u8>()?;
-        Self::try_from(value).map_err(|err| binrw::Error::NoVariantMatch {
+        Self::try_from(value).map_err(|_| binrw::Error::NoVariantMatch {
             pos: reader.stream_position().unwrap(),
         })
     }
 }
 
 #[binread]
+#[br(import(pointer_size: u32))]
 #[repr(u8)]
 #[derive(Debug, Clone, PartialEq)]
 #[rustfmt::skip]
@@ -66,30 +100,142 @@ pub enum Element {
     #[br(magic(0x0Cu8))] R4,
     #[br(magic(0x0Du8))] R8,
     #[br(magic(0x0Eu8))] String,
-    #[br(magic(0x0Fu8))] Ptr(Box<Self>),
-    #[br(magic(0x10u8))] ByRef(Box<Self>),
+    #[br(magic(0x0Fu8))] Ptr(#[br(args(pointer_size))] Box<Self>),
+    #[br(magic(0x10u8))] ByRef(#[br(args(pointer_size))] Box<Self>),
     #[br(magic(0x11u8))] ValueType(TypeDefOrRef),
     #[br(magic(0x12u8))] Class(TypeDefOrRef),
     #[br(magic(0x13u8))] Var(PackedU32),
+    /// General (multi-dimensional or non-zero based) array
+    #[br(magic(0x14u8))] Array(#[br(args(pointer_size))] Box<Self>, ArrayShape),
     #[br(magic(0x15u8))] GenericInst {
+        #[br(args(pointer_size))]
         generic_type: Box<Self>,
         #[br(temp)]
-        generic_arg_count: u8,
-        #[br(count = generic_arg_count)]
+        generic_arg_count: PackedU32,
+        #[br(count = generic_arg_count.0 as usize, args { inner: (pointer_size,) })]
         generic_args: Vec<Self>,
     },
+    #[br(magic(0x16u8))] TypedByRef,
     #[br(magic(0x18u8))] IntPtr,
     #[br(magic(0x19u8))] UIntPtr,
-    #[br(magic(0x1Bu8))] FnPtr(Box<StandaloneMethodSignature>),
+    #[br(magic(0x1Bu8))] FnPtr(#[br(args(pointer_size))] Box<StandaloneMethodSignature>),
     #[br(magic(0x1Cu8))] Object,
-    #[br(magic(0x1Du8))] SzArray(Box<Self>),
+    #[br(magic(0x1Du8))] SzArray(#[br(args(pointer_size))] Box<Self>),
     #[br(magic(0x1Eu8))] MVar(PackedU32),
-    #[br(magic(0x1Fu8))] CModRequired(TypeDefOrRef),
-    #[br(magic(0x20u8))] CModOptional(TypeDefOrRef),
-    #[br(magic(0x45u8))] Pinned(Box<Self>),
+    /// Required custom modifier (modreq) applied to the type that follows it
+    #[br(magic(0x1Fu8))] CModRequired(TypeDefOrRef, #[br(args(pointer_size))] Box<Self>),
+    /// Optional custom modifier (modopt) applied to the type that follows it
+    #[br(magic(0x20u8))] CModOptional(TypeDefOrRef, #[br(args(pointer_size))] Box<Self>),
+    /// Type handle used internally by the runtime, never emitted by compilers.
+    /// Pointer-sized, so 4 bytes in PE32 images and 8 bytes in PE32+ images.
+    #[br(magic(0x21u8))] Internal(#[br(parse_with = read_pointer, args(pointer_size))] u64),
+    #[br(magic(0x45u8))] Pinned(#[br(args(pointer_size))] Box<Self>),
+}
+
+/// Shape of a general array (`ELEMENT_TYPE_ARRAY`), ECMA-335 II.23.2.13
+#[binread]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArrayShape {
+    #[br(map = |r: PackedU32| r.0)]
+    pub rank: u32,
+    #[br(temp)]
+    size_count: PackedU32,
+    #[br(count = size_count.0 as usize, map = |v: Vec<PackedU32>| v.into_iter().map(|s| s.0).collect())]
+    pub sizes: Vec<u32>,
+    #[br(temp)]
+    lower_bound_count: PackedU32,
+    #[br(count = lower_bound_count.0 as usize, map = |v: Vec<PackedI32>| v.into_iter().map(|b| b.0).collect())]
+    pub lower_bounds: Vec<i32>,
+}
+
+impl ArrayShape {
+    /// Formats the dimensions in ILAsm notation, eg. `0...,0...` or `5,2...7`
+    pub fn dimensions(&self) -> String {
+        (0..self.rank as usize)
+            .map(|i| {
+                let lower = self.lower_bounds.get(i).copied();
+                let size = self.sizes.get(i).copied();
+                match (lower, size) {
+                    (Some(0), Some(size)) | (None, Some(size)) => size.to_string(),
+                    (Some(lower), Some(size)) => {
+                        format!("{lower}...{}", lower as i64 + size as i64 - 1)
+                    }
+                    (Some(lower), None) => format!("{lower}..."),
+                    (None, None) => String::new(),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+    }
 }
 
 impl Element {
+    /// Replaces generic parameters with the arguments from `context`.
+    /// Parameters without a corresponding argument are left as they are.
+    pub fn substitute(&self, context: &GenericContext) -> Element {
+        let sub = |inner: &Element| Box::new(inner.substitute(context));
+        match self {
+            Element::Var(n) => context
+                .type_args
+                .get(n.0 as usize)
+                .cloned()
+                .unwrap_or_else(|| self.clone()),
+            Element::MVar(n) => context
+                .method_args
+                .get(n.0 as usize)
+                .cloned()
+                .unwrap_or_else(|| self.clone()),
+            Element::Ptr(inner) => Element::Ptr(sub(inner)),
+            Element::ByRef(inner) => Element::ByRef(sub(inner)),
+            Element::SzArray(inner) => Element::SzArray(sub(inner)),
+            Element::Pinned(inner) => Element::Pinned(sub(inner)),
+            Element::Array(inner, shape) => Element::Array(sub(inner), shape.clone()),
+            Element::CModRequired(modifier, inner) => Element::CModRequired(*modifier, sub(inner)),
+            Element::CModOptional(modifier, inner) => Element::CModOptional(*modifier, sub(inner)),
+            Element::GenericInst {
+                generic_type,
+                generic_args,
+            } => Element::GenericInst {
+                generic_type: sub(generic_type),
+                generic_args: generic_args.iter().map(|a| a.substitute(context)).collect(),
+            },
+            Element::FnPtr(signature) => Element::FnPtr(Box::new(signature.substitute(context))),
+            _ => self.clone(),
+        }
+    }
+
+    /// Whether the type refers to a generic parameter anywhere, ie. it is not fully instantiated
+    pub fn is_open(&self) -> bool {
+        match self {
+            Element::Var(_) | Element::MVar(_) => true,
+            Element::Ptr(inner)
+            | Element::ByRef(inner)
+            | Element::SzArray(inner)
+            | Element::Pinned(inner)
+            | Element::Array(inner, _)
+            | Element::CModRequired(_, inner)
+            | Element::CModOptional(_, inner) => inner.is_open(),
+            Element::GenericInst {
+                generic_type,
+                generic_args,
+            } => generic_type.is_open() || generic_args.iter().any(Element::is_open),
+            Element::FnPtr(signature) => {
+                signature.return_type.is_open() || signature.parameters.iter().any(Element::is_open)
+            }
+            _ => false,
+        }
+    }
+
+    /// The type without any leading modreqs or modopts, such as the `modreq(IsExternalInit)` on
+    /// the return type of init-only setters
+    pub fn strip_modifiers(&self) -> &Element {
+        let mut element = self;
+        while let Element::CModRequired(_, inner) | Element::CModOptional(_, inner) = element {
+            element = inner;
+        }
+        element
+    }
+
     pub fn debug_print(&self, image: &CilImage) -> String {
         let s = match self {
             Element::End => "<end>",
@@ -110,19 +256,22 @@ impl Element {
             Element::ByRef(inner) => &format!("ref {}", inner.debug_print(image)),
             Element::ValueType(token) => {
                 if let Some(typename) = token.name_with_namespace(image) {
-                    &format!("{typename}")
+                    &typename.to_string()
                 } else {
                     &format!("<unk:{:?}>", token)
                 }
             }
             Element::Class(token) => {
                 if let Some(typename) = token.name_with_namespace(image) {
-                    &format!("{typename}")
+                    &typename.to_string()
                 } else {
                     &format!("<unk:{:?}>", token)
                 }
             }
             Element::Var(index) => &format!("var{}", index.0),
+            Element::Array(inner, shape) => {
+                &format!("{}[{}]", inner.debug_print(image), shape.dimensions())
+            }
             Element::GenericInst {
                 generic_type,
                 generic_args,
@@ -136,18 +285,24 @@ impl Element {
                 &format!("{}<{}>", generic_type.debug_print(image), args)
             }
             Element::String => "string",
+            Element::TypedByRef => "typedref",
             Element::IntPtr => "nint",
             Element::UIntPtr => "nuint",
-            Element::FnPtr(signature) => &format!("{}", signature.debug_print(image)),
+            Element::FnPtr(signature) => &signature.debug_print(image),
             Element::Object => "object",
             Element::SzArray(inner) => &format!("{}[]", inner.debug_print(image)),
             Element::MVar(index) => &format!("mvar{}", index.0),
-            Element::CModRequired(type_def_or_ref) => {
-                &format!("cmodreq({:?})", type_def_or_ref.name_with_namespace(image))
-            }
-            Element::CModOptional(type_def_or_ref) => {
-                &format!("cmodopt({:?})", type_def_or_ref.name_with_namespace(image))
-            }
+            Element::CModRequired(type_def_or_ref, inner) => &format!(
+                "{} cmodreq({:?})",
+                inner.debug_print(image),
+                type_def_or_ref.name_with_namespace(image)
+            ),
+            Element::CModOptional(type_def_or_ref, inner) => &format!(
+                "{} cmodopt({:?})",
+                inner.debug_print(image),
+                type_def_or_ref.name_with_namespace(image)
+            ),
+            Element::Internal(handle) => &format!("<internal:{handle:#x}>"),
             Element::Pinned(boxed_element) => {
                 &format!("pinned {}", boxed_element.debug_print(image))
             }
@@ -159,7 +314,8 @@ impl Element {
 
 bitfield! {
     pub struct StandaloneMethodSigHeader : u8 {
-        enum call_type: MethodCallType @ 0x03 >> 0,
+        enum call_type: MethodCallType @ 0x0F >> 0,
+        flag is_generic: bool @ 0x10,
         flag has_this: bool @ 0x20,
         flag explicit_this: bool @ 0x40
     }
@@ -167,7 +323,7 @@ bitfield! {
 
 impl Default for StandaloneMethodSigHeader {
     fn default() -> Self {
-        Self(0)
+        Self(MethodCallType::Default as u8)
     }
 }
 
@@ -180,33 +336,134 @@ pub enum MethodCallType {
     ThisCall = 3,
     FastCall = 4,
     Vararg = 5,
+    /// Unmanaged function pointer, the actual convention is given by `CallConv*` modopts on the return type
+    Unmanaged = 9,
 }
 
-#[binread]
+/// Generic arguments substituted for `Var` (type) and `MVar` (method) parameters
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GenericContext {
+    pub type_args: Vec<Element>,
+    pub method_args: Vec<Element>,
+}
+
+impl GenericContext {
+    pub fn new(type_args: Vec<Element>, method_args: Vec<Element>) -> Self {
+        Self {
+            type_args,
+            method_args,
+        }
+    }
+
+    /// Context for members of an instantiated generic type, eg. `List<string>` binds `!0` to `string`.
+    /// Any other type yields an empty context.
+    pub fn from_type(instance: &Element) -> Self {
+        match instance {
+            Element::GenericInst { generic_args, .. } => Self::new(generic_args.clone(), vec![]),
+            _ => Self::default(),
+        }
+    }
+
+    pub fn with_method_args(mut self, method_args: Vec<Element>) -> Self {
+        self.method_args = method_args;
+        self
+    }
+}
+
+/// ECMA-335 `ELEMENT_TYPE_SENTINEL`, marks the start of the variable arguments in a vararg call site
+const SENTINEL: u8 = 0x41;
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct StandaloneMethodSignature {
     pub header: StandaloneMethodSigHeader,
-    #[br(temp)]
-    count: PackedU32,
+    /// Number of generic parameters, only non-zero when `header.is_generic()` is set
+    pub generic_param_count: u32,
     pub return_type: Element,
-    #[br(count = count.0 as usize)]
     pub parameters: Vec<Element>,
+    /// Index into `parameters` of the first variable argument, if the signature contains a sentinel
+    pub sentinel: Option<usize>,
 }
 
 impl Default for StandaloneMethodSignature {
     fn default() -> Self {
         Self {
             header: StandaloneMethodSigHeader::default(),
+            generic_param_count: 0,
             return_type: Element::Void,
             parameters: Vec::new(),
+            sentinel: None,
         }
     }
 }
 
+impl BinRead for StandaloneMethodSignature {
+    /// Pointer width of the image in bytes
+    type Args<'a> = (u32,);
+
+    fn read_options<R: std::io::Read + std::io::Seek>(
+        reader: &mut R,
+        endian: binrw::Endian,
+        (pointer_size,): Self::Args<'_>,
+    ) -> binrw::BinResult<Self> {
+        let pos = reader.stream_position()?;
+        let header: StandaloneMethodSigHeader = reader.read_type(endian)?;
+        // Nested function pointer signatures don't go through `parse_signature`, so the calling
+        // convention has to be checked here for `call_type` to be infallible
+        if MethodCallType::try_from(header.0 & 0x0F).is_err() {
+            return Err(binrw::Error::AssertFail {
+                pos,
+                message: format!("Invalid method calling convention {:#04x}", header.0),
+            });
+        }
+
+        let generic_param_count = if header.is_generic() {
+            reader.read_type::<PackedU32>(endian)?.0
+        } else {
+            0
+        };
+
+        let count: PackedU32 = reader.read_type(endian)?;
+        let return_type: Element = reader.read_type_args(endian, (pointer_size,))?;
+
+        let mut parameters = Vec::with_capacity(count.0 as usize);
+        let mut sentinel = None;
+        while parameters.len() < count.0 as usize {
+            let marker: u8 = reader.read_type(endian)?;
+            if marker == SENTINEL && sentinel.is_none() {
+                sentinel = Some(parameters.len());
+                continue;
+            }
+
+            reader.seek(std::io::SeekFrom::Current(-1))?;
+            parameters.push(reader.read_type_args(endian, (pointer_size,))?);
+        }
+
+        Ok(Self {
+            header,
+            generic_param_count,
+            return_type,
+            parameters,
+            sentinel,
+        })
+    }
+}
+
 impl StandaloneMethodSignature {
-    pub fn parse(blob: &[u8]) -> Result<Self> {
-        let mut reader = Cursor::new(blob);
-        Ok(reader.read_le()?)
+    pub fn parse(blob: &[u8], pointer_size: u32) -> Result<Self> {
+        parse_signature(blob, SignatureKind::StandaloneMethod, pointer_size)
+    }
+
+    /// Instantiates the return and parameter types with the arguments from `context`
+    pub fn substitute(&self, context: &GenericContext) -> Self {
+        Self {
+            return_type: self.return_type.substitute(context),
+            parameters: self
+                .parameters
+                .iter()
+                .map(|p| p.substitute(context))
+                .collect(),
+            ..self.clone()
+        }
     }
 
     pub fn debug_print(&self, image: &CilImage) -> String {
@@ -216,18 +473,27 @@ impl StandaloneMethodSignature {
             s.push_str("this ");
         }
 
-        s.push_str("fn(");
-
-        if !self.parameters.is_empty() {
-            s.push_str(
-                &self
-                    .parameters
-                    .iter()
-                    .map(|p| p.debug_print(image))
+        s.push_str("fn");
+        if self.generic_param_count > 0 {
+            s.push_str(&format!(
+                "<{}>",
+                (0..self.generic_param_count)
+                    .map(|i| format!("mvar{i}"))
                     .collect::<Vec<_>>()
-                    .join(", "),
-            );
+                    .join(", ")
+            ));
         }
+        s.push('(');
+
+        let mut parameters = self
+            .parameters
+            .iter()
+            .map(|p| p.debug_print(image))
+            .collect::<Vec<_>>();
+        if let Some(sentinel) = self.sentinel {
+            parameters.insert(sentinel, "...".to_string());
+        }
+        s.push_str(&parameters.join(", "));
 
         s.push_str(") -> ");
         s.push_str(&self.return_type.debug_print(image));
@@ -235,3 +501,449 @@ impl StandaloneMethodSignature {
         s.to_string()
     }
 }
+
+/// Signature of a field definition or field reference, ECMA-335 II.23.2.4
+#[binread]
+#[br(magic(0x06u8), import(pointer_size: u32))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldSignature {
+    #[br(args(pointer_size))]
+    pub field_type: Element,
+}
+
+impl FieldSignature {
+    pub fn parse(blob: &[u8], pointer_size: u32) -> Result<Self> {
+        parse_signature(blob, SignatureKind::Field, pointer_size)
+    }
+
+    pub fn substitute(&self, context: &GenericContext) -> Self {
+        Self {
+            field_type: self.field_type.substitute(context),
+        }
+    }
+}
+
+/// Signature of the local variables of a method body, ECMA-335 II.23.2.6
+#[binread]
+#[br(magic(0x07u8), import(pointer_size: u32))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocalVarSignature {
+    #[br(temp)]
+    count: PackedU32,
+    #[br(count = count.0 as usize, args { inner: (pointer_size,) })]
+    pub locals: Vec<Element>,
+}
+
+impl LocalVarSignature {
+    pub fn parse(blob: &[u8], pointer_size: u32) -> Result<Self> {
+        parse_signature(blob, SignatureKind::LocalVar, pointer_size)
+    }
+
+    pub fn substitute(&self, context: &GenericContext) -> Self {
+        Self {
+            locals: self.locals.iter().map(|l| l.substitute(context)).collect(),
+        }
+    }
+}
+
+/// Signature of a property, ECMA-335 II.23.2.5
+#[binread]
+#[br(import(pointer_size: u32))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct PropertySignature {
+    #[br(temp, assert(header & 0x0F == 0x08, "Invalid property signature header {header:#04x}"))]
+    header: u8,
+    #[br(calc(header & 0x20 != 0))]
+    pub has_this: bool,
+    #[br(temp)]
+    count: PackedU32,
+    #[br(args(pointer_size))]
+    pub property_type: Element,
+    /// Parameters of an indexed property
+    #[br(count = count.0 as usize, args { inner: (pointer_size,) })]
+    pub parameters: Vec<Element>,
+}
+
+impl PropertySignature {
+    pub fn parse(blob: &[u8], pointer_size: u32) -> Result<Self> {
+        parse_signature(blob, SignatureKind::Property, pointer_size)
+    }
+
+    pub fn substitute(&self, context: &GenericContext) -> Self {
+        Self {
+            has_this: self.has_this,
+            property_type: self.property_type.substitute(context),
+            parameters: self
+                .parameters
+                .iter()
+                .map(|p| p.substitute(context))
+                .collect(),
+        }
+    }
+}
+
+/// Generic arguments of a generic method instantiation, ECMA-335 II.23.2.15
+#[binread]
+#[br(magic(0x0Au8), import(pointer_size: u32))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct MethodSpecSignature {
+    #[br(temp)]
+    count: PackedU32,
+    #[br(count = count.0 as usize, args { inner: (pointer_size,) })]
+    pub generic_args: Vec<Element>,
+}
+
+impl MethodSpecSignature {
+    pub fn parse(blob: &[u8], pointer_size: u32) -> Result<Self> {
+        parse_signature(blob, SignatureKind::MethodSpec, pointer_size)
+    }
+}
+
+/// Type described by a TypeSpec row, ECMA-335 II.23.2.14
+///
+/// Unlike the other signatures this one has no leading calling convention byte.
+#[binread]
+#[br(import(pointer_size: u32))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeSpecSignature {
+    #[br(args(pointer_size))]
+    pub element: Element,
+}
+
+impl TypeSpecSignature {
+    pub fn parse(blob: &[u8], pointer_size: u32) -> Result<Self> {
+        let mut reader = Cursor::new(blob);
+        Ok(reader.read_le_args((pointer_size,))?)
+    }
+}
+
+/// A MemberRef signature can describe either a method or a field
+#[derive(Debug, Clone, PartialEq)]
+pub enum MemberRefSignature {
+    Method(StandaloneMethodSignature),
+    Field(FieldSignature),
+}
+
+impl MemberRefSignature {
+    pub fn parse(blob: &[u8], pointer_size: u32) -> Result<Self> {
+        match blob.first().map(|&b| SignatureKind::try_from(b)) {
+            Some(Ok(SignatureKind::Field)) => {
+                Ok(Self::Field(FieldSignature::parse(blob, pointer_size)?))
+            }
+            _ => Ok(Self::Method(StandaloneMethodSignature::parse(
+                blob,
+                pointer_size,
+            )?)),
+        }
+    }
+
+    pub fn substitute(&self, context: &GenericContext) -> Self {
+        match self {
+            Self::Method(signature) => Self::Method(signature.substitute(context)),
+            Self::Field(signature) => Self::Field(signature.substitute(context)),
+        }
+    }
+}
+
+/// Compares types and signatures that may come from different images.
+///
+/// Named types are compared by identity rather than by token: namespace, name, enclosing types and
+/// defining assembly, so a TypeRef in `left` equals the TypeDef it refers to in `right`.
+/// Assemblies are matched by name and version, public key tokens are not checked.
+#[derive(Clone, Copy)]
+pub struct SignatureComparer<'a> {
+    left: &'a CilImage,
+    right: &'a CilImage,
+    ignore_modopts: bool,
+    ignore_assembly_versions: bool,
+}
+
+/// Identity of a named type, independent of the image it is referenced from
+#[derive(Debug, PartialEq)]
+struct TypeIdentity<'a> {
+    namespace: &'a str,
+    /// Innermost type first
+    names: Vec<&'a str>,
+    assembly: Option<(&'a str, [u16; 4])>,
+}
+
+impl<'a> SignatureComparer<'a> {
+    /// Creates a strict comparer, types from `left` are compared against types from `right`
+    pub fn new(left: &'a CilImage, right: &'a CilImage) -> Self {
+        Self {
+            left,
+            right,
+            ignore_modopts: false,
+            ignore_assembly_versions: false,
+        }
+    }
+
+    /// Skip optional custom modifiers (modopt) on both sides. Required modifiers are always compared.
+    pub fn ignore_modopts(mut self, ignore: bool) -> Self {
+        self.ignore_modopts = ignore;
+        self
+    }
+
+    /// Match assemblies by simple name only
+    pub fn ignore_assembly_versions(mut self, ignore: bool) -> Self {
+        self.ignore_assembly_versions = ignore;
+        self
+    }
+
+    /// Compares a type from `left` with a type from `right`
+    pub fn types_equal(&self, a: &Element, b: &Element) -> bool {
+        match (self.strip_modopts(a), self.strip_modopts(b)) {
+            (Element::Var(x), Element::Var(y)) | (Element::MVar(x), Element::MVar(y)) => x == y,
+            (Element::Ptr(x), Element::Ptr(y))
+            | (Element::ByRef(x), Element::ByRef(y))
+            | (Element::SzArray(x), Element::SzArray(y))
+            | (Element::Pinned(x), Element::Pinned(y)) => self.types_equal(x, y),
+            (Element::Array(x, x_shape), Element::Array(y, y_shape)) => {
+                x_shape == y_shape && self.types_equal(x, y)
+            }
+            (Element::ValueType(x), Element::ValueType(y))
+            | (Element::Class(x), Element::Class(y)) => self.type_refs_equal(*x, *y),
+            (
+                Element::GenericInst {
+                    generic_type: x,
+                    generic_args: x_args,
+                },
+                Element::GenericInst {
+                    generic_type: y,
+                    generic_args: y_args,
+                },
+            ) => {
+                self.types_equal(x, y)
+                    && x_args.len() == y_args.len()
+                    && x_args
+                        .iter()
+                        .zip(y_args)
+                        .all(|(x, y)| self.types_equal(x, y))
+            }
+            (Element::FnPtr(x), Element::FnPtr(y)) => self.method_signatures_equal(x, y),
+            (Element::CModRequired(x_mod, x), Element::CModRequired(y_mod, y))
+            | (Element::CModOptional(x_mod, x), Element::CModOptional(y_mod, y)) => {
+                self.type_refs_equal(*x_mod, *y_mod) && self.types_equal(x, y)
+            }
+            // Remaining variants carry no image-specific data
+            (a, b) => a == b,
+        }
+    }
+
+    /// Compares a TypeDef, TypeRef or TypeSpec from `left` with one from `right`
+    pub fn type_refs_equal(&self, a: TypeDefOrRef, b: TypeDefOrRef) -> bool {
+        match (a, b) {
+            (TypeDefOrRef::TypeSpec(x), TypeDefOrRef::TypeSpec(y)) => {
+                match (
+                    self.left.type_spec(TypeSpecHandle(x)),
+                    self.right.type_spec(TypeSpecHandle(y)),
+                ) {
+                    (Ok(x), Ok(y)) => self.types_equal(&x, &y),
+                    _ => false,
+                }
+            }
+            (TypeDefOrRef::TypeSpec(_), _) | (_, TypeDefOrRef::TypeSpec(_)) => false,
+            (a, b) => {
+                let (Some(x), Some(y)) =
+                    (type_identity(self.left, a), type_identity(self.right, b))
+                else {
+                    return false;
+                };
+
+                x.namespace == y.namespace
+                    && x.names == y.names
+                    && match (x.assembly, y.assembly) {
+                        (Some((x_name, x_version)), Some((y_name, y_version))) => {
+                            x_name.eq_ignore_ascii_case(y_name)
+                                && (self.ignore_assembly_versions || x_version == y_version)
+                        }
+                        // Images without an assembly manifest (netmodules) can't be told apart
+                        _ => true,
+                    }
+            }
+        }
+    }
+
+    pub fn method_signatures_equal(
+        &self,
+        a: &StandaloneMethodSignature,
+        b: &StandaloneMethodSignature,
+    ) -> bool {
+        a.header == b.header
+            && a.generic_param_count == b.generic_param_count
+            && a.sentinel == b.sentinel
+            && a.parameters.len() == b.parameters.len()
+            && self.types_equal(&a.return_type, &b.return_type)
+            && a.parameters
+                .iter()
+                .zip(&b.parameters)
+                .all(|(x, y)| self.types_equal(x, y))
+    }
+
+    pub fn field_signatures_equal(&self, a: &FieldSignature, b: &FieldSignature) -> bool {
+        self.types_equal(&a.field_type, &b.field_type)
+    }
+
+    /// Binds a MemberRef of `left` to the method or field definition in `right` it refers to.
+    ///
+    /// `right` is the image of the assembly the reference points to, or `left` itself for references
+    /// to its own module. Returns `None` if the parent type or a member with a matching name and
+    /// signature can't be found. Members of array types are provided by the runtime and never bind.
+    pub fn bind_member_ref(&self, handle: MemberRefHandle) -> Result<Option<MemberDef>> {
+        let member_ref = self
+            .left
+            .member_refs
+            .get(handle.index())
+            .ok_or(Error::InvalidToken(handle.token()))?;
+        let signature = self.left.member_ref_signature(handle)?;
+
+        let parent = MemberRefParent::try_from(member_ref.class_index as u32)
+            .map_err(|_| Error::InvalidToken(handle.token()))?;
+        let parent_type = match parent {
+            MemberRefParent::TypeDef(index) => TypeDefOrRef::TypeDef(index),
+            MemberRefParent::TypeRef(index) => TypeDefOrRef::TypeRef(index),
+            MemberRefParent::TypeSpec(index) => {
+                match self.left.type_spec(TypeSpecHandle(index))? {
+                    Element::GenericInst { generic_type, .. } => match *generic_type {
+                        Element::Class(t) | Element::ValueType(t) => t,
+                        _ => return Ok(None),
+                    },
+                    _ => return Ok(None),
+                }
+            }
+            // Global members live on the <Module> type, which is always the first TypeDef
+            MemberRefParent::ModuleRef(_) => {
+                return Ok(self.find_member(TypeDefHandle(1), &member_ref.name, &signature));
+            }
+            // Vararg call site of a method defined in the same module
+            MemberRefParent::MethodDef(index) => {
+                return Ok(std::ptr::eq(self.left, self.right)
+                    .then_some(MemberDef::Method(MethodDefHandle(index))));
+            }
+        };
+
+        let Some(owner) = (1..=self.right.type_defs.len() as u32)
+            .find(|&i| self.type_refs_equal(parent_type, TypeDefOrRef::TypeDef(i)))
+        else {
+            return Ok(None);
+        };
+
+        Ok(self.find_member(TypeDefHandle(owner), &member_ref.name, &signature))
+    }
+
+    fn find_member(
+        &self,
+        owner: TypeDefHandle,
+        name: &str,
+        signature: &MemberRefSignature,
+    ) -> Option<MemberDef> {
+        match signature {
+            MemberRefSignature::Method(signature) => {
+                // Vararg references append the variable arguments after a sentinel, the
+                // definition only declares the fixed ones
+                let mut signature = signature.clone();
+                if let Some(sentinel) = signature.sentinel.take() {
+                    signature.parameters.truncate(sentinel);
+                }
+
+                self.right
+                    .methods_of(owner)
+                    .find(|&m| {
+                        self.right.method_defs[m.index()].0.name == name
+                            && self
+                                .right
+                                .method_signature(m)
+                                .is_ok_and(|def| self.method_signatures_equal(&signature, &def))
+                    })
+                    .map(MemberDef::Method)
+            }
+            MemberRefSignature::Field(signature) => self
+                .right
+                .fields_of(owner)
+                .find(|&f| {
+                    self.right.fields[f.index()].name == name
+                        && self
+                            .right
+                            .field_signature(f)
+                            .is_ok_and(|def| self.field_signatures_equal(signature, &def))
+                })
+                .map(MemberDef::Field),
+        }
+    }
+
+    fn strip_modopts<'e>(&self, mut element: &'e Element) -> &'e Element {
+        if self.ignore_modopts {
+            while let Element::CModOptional(_, inner) = element {
+                element = inner;
+            }
+        }
+        element
+    }
+}
+
+fn type_identity(image: &CilImage, t: TypeDefOrRef) -> Option<TypeIdentity<'_>> {
+    let own_assembly = || {
+        image.assemblies.first().map(|a| {
+            (
+                a.name.as_str(),
+                [
+                    a.major_version,
+                    a.minor_version,
+                    a.build_number,
+                    a.revision_number,
+                ],
+            )
+        })
+    };
+
+    match t {
+        TypeDefOrRef::TypeDef(index) => {
+            let mut type_def = image.type_defs.get((index as usize).checked_sub(1)?)?;
+            let mut names = vec![type_def.type_name.as_str()];
+            let mut current = TypeDefHandle(index);
+            while let Some(enclosing) = image.enclosing_type_of(current) {
+                // Guard against cycles in malformed NestedClass tables, like `type_def_name`
+                if names.len() > image.nested_classes.len() {
+                    break;
+                }
+                type_def = image.type_defs.get(enclosing.index())?;
+                names.push(type_def.type_name.as_str());
+                current = enclosing;
+            }
+
+            Some(TypeIdentity {
+                namespace: &type_def.type_namespace,
+                names,
+                assembly: own_assembly(),
+            })
+        }
+        TypeDefOrRef::TypeRef(index) => {
+            let chain = image.type_ref_chain(TypeRefHandle(index))?;
+            let outermost = chain.last()?;
+            let assembly = match ResolutionScope::try_from(outermost.resolution_scope as u32) {
+                Ok(ResolutionScope::AssemblyRef(assembly)) => {
+                    let a = image
+                        .assembly_refs
+                        .get((assembly as usize).checked_sub(1)?)?;
+                    Some((
+                        a.name.as_str(),
+                        [
+                            a.major_version,
+                            a.minor_version,
+                            a.build_number,
+                            a.revision_number,
+                        ],
+                    ))
+                }
+                _ => own_assembly(),
+            };
+
+            Some(TypeIdentity {
+                namespace: &outermost.namespace,
+                names: chain.iter().map(|r| r.name.as_str()).collect(),
+                assembly,
+            })
+        }
+        TypeDefOrRef::TypeSpec(_) => None,
+    }
+}
@@ -1,3 +1,5 @@
+use std::borrow::Cow;
+
 use crate::meta::{StringIndex, Token, TokenKind};
 
 pub struct StringHeap {
@@ -19,7 +21,7 @@ impl StringHeap {
             bytes.push(byte);
         }
 
-        Some(String::from_utf8(bytes).ok()?)
+        String::from_utf8(bytes).ok()
     }
 
     pub fn try_get(&self, index: StringIndex) -> Result<String, std::string::FromUtf8Error> {
@@ -34,6 +36,25 @@ impl StringHeap {
 
         String::from_utf8(bytes)
     }
+
+    /// Every string in the heap with its index, starting with the empty string at index 0. Strings that
+    /// aren't valid UTF-8 are converted lossily, the padding at the end of the heap is skipped.
+    ///
+    /// Stops at the first string past the 64K that a 16-bit [`StringIndex`] can address.
+    pub fn iter(&self) -> impl Iterator<Item = (StringIndex, Cow<'_, str>)> {
+        let mut offset = 0;
+        std::iter::from_fn(move || {
+            let rest = self.data.get(offset..).filter(|r| !r.is_empty())?;
+            let length = rest.iter().position(|&b| b == 0).unwrap_or(rest.len());
+            let entry = (
+                StringIndex(u16::try_from(offset).ok()?),
+                String::from_utf8_lossy(&rest[..length]),
+            );
+            offset += length + 1;
+            Some(entry)
+        })
+        .filter(|(index, s)| index.0 == 0 || !s.is_empty())
+    }
 }
 
 pub struct BlobHeap {
@@ -46,37 +67,51 @@ impl BlobHeap {
     }
 
     pub fn get(&self, index: u32) -> Option<&[u8]> {
-        let mut len = 0;
-        let mut offset = index as usize;
+        let (offset, length) = self.entry_bounds(index as usize)?;
+        self.data.get(offset..offset + length)
+    }
+
+    /// Every blob in the heap with its index, starting with the empty blob at index 0. The padding at the
+    /// end of the heap is skipped.
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &[u8])> {
+        let mut index = 0;
+        std::iter::from_fn(move || {
+            let (offset, length) = self.entry_bounds(index)?;
+            let data = self.data.get(offset..offset + length)?;
+            let entry = (index as u32, data);
+            index = offset + length;
+            Some(entry)
+        })
+        .filter(|(index, data)| *index == 0 || !data.is_empty())
+    }
+
+    /// Offset of the data and length of the blob at `index`
+    fn entry_bounds(&self, index: usize) -> Option<(usize, usize)> {
         // Varint encoding
         // For unsigned integers:
         //   - If the value lies between 0 (0x00) and 127 (0x7F), inclusive, encode as a one-byte integer (bit 7 is clear, value held in bits 6 through 0)
         //   - If the value lies between 28 (0x80) and 214-1 (0x3FFF), inclusive, encode as a 2-byte integer with bit 15 set, bit 14 clear (value held in bits 13 through 0)
         //   - Otherwise, encode as a 4-byte integer, with bit 31 set, bit 30 set, bit 29 clear (value held in bits 28 through 0)
-        let first_byte = self.data[offset];
+        let first_byte = *self.data.get(index)?;
         if first_byte & 0x80 == 0 {
             // One-byte integer (0-127)
-            len = first_byte as usize;
-            offset += 1;
+            Some((index + 1, first_byte as usize))
         } else if first_byte & 0xC0 == 0x80 {
             // Two-byte integer (128-16383)
-            let second_byte = self.data[offset + 1];
-            len = (((first_byte & 0x3F) as usize) << 8) | (second_byte as usize);
-            offset += 2;
+            let second_byte = *self.data.get(index + 1)?;
+            let length = (((first_byte & 0x3F) as usize) << 8) | (second_byte as usize);
+            Some((index + 2, length))
         } else if first_byte & 0xE0 == 0xC0 {
             // Four-byte integer
-            let second_byte = self.data[offset + 1];
-            let third_byte = self.data[offset + 2];
-            let fourth_byte = self.data[offset + 3];
-            len = (((first_byte & 0x1F) as usize) << 24)
-                | ((second_byte as usize) << 16)
-                | ((third_byte as usize) << 8)
-                | (fourth_byte as usize);
-            offset += 4;
+            let bytes = self.data.get(index + 1..index + 4)?;
+            let length = (((first_byte & 0x1F) as usize) << 24)
+                | ((bytes[0] as usize) << 16)
+                | ((bytes[1] as usize) << 8)
+                | (bytes[2] as usize);
+            Some((index + 4, length))
+        } else {
+            None
         }
-
-        let end = offset + len;
-        self.data.get(offset..end)
     }
 }
 
@@ -92,23 +127,73 @@ impl UserStringHeap {
     }
 
     pub fn get(&self, token: Token) -> Option<String> {
+        self.entry(token).map(|s| s.value)
+    }
+
+    /// The string a `ldstr` token refers to, together with its flag byte
+    pub fn entry(&self, token: Token) -> Option<UserString> {
         assert_eq!(
             token.kind(),
             TokenKind::UserString,
             "Token is not a UserString"
         );
 
-        let data = self.blob.get(token.index())?;
+        UserString::parse(self.blob.get(token.index())?)
+    }
 
-        let mut values: Vec<u16> = Vec::new();
-        for i in (0..data.len()).step_by(2) {
-            if i + 1 >= data.len() {
-                break;
-            }
-            let value = u16::from_le_bytes([data[i], data[i + 1]]);
-            values.push(value);
+    /// Every string in the heap with its `0x70` token. The empty entry at index 0 and the padding at
+    /// the end of the heap are skipped, an empty string still has its flag byte. Strings that aren't
+    /// valid UTF-16 are converted lossily.
+    pub fn iter(&self) -> impl Iterator<Item = (Token, UserString)> {
+        self.blob
+            .iter()
+            .filter(|(_, data)| !data.is_empty())
+            .map(|(index, data)| {
+                let token = Token(((TokenKind::UserString as u32) << 24) | index);
+                (token, UserString::parse_lossy(data))
+            })
+    }
+}
+
+/// An entry of the #US heap (ECMA-335 II.24.2.4)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserString {
+    pub value: String,
+    /// Set by the final byte of the entry if any character has a non-zero high byte or is one of the
+    /// special characters 0x01-0x08, 0x0E-0x1F, 0x27, 0x2D or 0x7F
+    pub has_special_characters: bool,
+}
+
+impl UserString {
+    fn parse(data: &[u8]) -> Option<Self> {
+        let (characters, has_special_characters) = Self::split(data);
+        Some(Self {
+            value: String::from_utf16(&characters).ok()?,
+            has_special_characters,
+        })
+    }
+
+    /// Like [`Self::parse`], but unpaired surrogates are replaced with U+FFFD
+    fn parse_lossy(data: &[u8]) -> Self {
+        let (characters, has_special_characters) = Self::split(data);
+        Self {
+            value: String::from_utf16_lossy(&characters),
+            has_special_characters,
         }
+    }
 
-        Some(String::from_utf16(&values).ok()?)
+    /// The UTF-16 characters of an entry and its flag
+    fn split(data: &[u8]) -> (Vec<u16>, bool) {
+        // UTF-16 characters followed by the flag byte
+        let (characters, flag) = match data.len() % 2 {
+            1 => data.split_at(data.len() - 1),
+            _ => (data, &[][..]),
+        };
+
+        let characters = characters
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .collect();
+        (characters, flag.first().is_some_and(|&f| f != 0))
     }
 }
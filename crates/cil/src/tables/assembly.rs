@@ -2,7 +2,7 @@ use std::fmt::Debug;
 
 use binrw::binread;
 
-use crate::{meta::StringIndex, strings::StringHeap};
+use crate::{bitfield, meta::StringIndex, strings::StringHeap};
 
 #[binread]
 #[derive(Debug)]
@@ -15,7 +15,7 @@ pub struct Assembly {
     pub build_number: u16,
     pub revision_number: u16,
 
-    pub flags: u32,
+    pub flags: AssemblyFlags,
     pub public_key_blob_index: u16,
     #[br(try_map = |s: StringIndex| strings.try_get(s))]
     pub name: String,
@@ -30,6 +30,47 @@ pub enum AssemblyHashAlgorithm {
     None = 0x0000,
     MD5 = 0x8003,
     SHA1 = 0x8004,
+    SHA256 = 0x800C,
+    SHA384 = 0x800D,
+    SHA512 = 0x800E,
+}
+
+bitfield! {
+    pub struct AssemblyFlags : u32 {
+        flag has_public_key: bool @ 0x0001,
+        flag is_retargetable: bool @ 0x0100,
+        enum content_type: AssemblyContentType @ 0x0E00 >> 9,
+        flag disable_jit_optimizer: bool @ 0x4000,
+        flag enable_jit_tracking: bool @ 0x8000
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssemblyContentType {
+    Default,
+    WindowsRuntime,
+    /// Reserved value, kept so that reading the flags of a malformed image can't fail
+    Unknown(u32),
+}
+
+impl From<u32> for AssemblyContentType {
+    fn from(value: u32) -> Self {
+        match value {
+            0 => Self::Default,
+            1 => Self::WindowsRuntime,
+            _ => Self::Unknown(value),
+        }
+    }
+}
+
+impl From<AssemblyContentType> for u32 {
+    fn from(value: AssemblyContentType) -> Self {
+        match value {
+            AssemblyContentType::Default => 0,
+            AssemblyContentType::WindowsRuntime => 1,
+            AssemblyContentType::Unknown(value) => value,
+        }
+    }
 }
 
 #[binread]
@@ -41,7 +82,8 @@ pub struct AssemblyRef {
     pub build_number: u16,
     pub revision_number: u16,
 
-    pub flags: u32,
+    pub flags: AssemblyFlags,
+    /// Full public key if `flags.has_public_key()` is set, otherwise the 8 byte token (or empty)
     pub public_key_or_token_blob_index: u16,
     #[br(try_map = |s: StringIndex| strings.try_get(s))]
     pub name: String,
@@ -49,3 +91,38 @@ pub struct AssemblyRef {
     pub culture: String,
     pub hash_value_blob_index: u16,
 }
+
+#[binread]
+#[derive(Debug)]
+#[br(import(strings: &StringHeap))]
+pub struct ExportedType {
+    pub flags: u32,
+    /// Hint for the TypeDef row in the target module, may be 0
+    pub type_def_id: u32,
+    #[br(try_map = |s: StringIndex| strings.try_get(s))]
+    pub type_name: String,
+    #[br(try_map = |s: StringIndex| strings.try_get(s))]
+    pub type_namespace: String,
+    pub implementation: u16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Implementation {
+    File(u32),
+    AssemblyRef(u32),
+    /// The type is nested inside another exported type
+    ExportedType(u32),
+}
+
+impl TryFrom<u32> for Implementation {
+    type Error = ();
+    fn try_from(v: u32) -> Result<Self, Self::Error> {
+        let index = v >> 2;
+        match v & 0b11 {
+            0 => Ok(Self::File(index)),
+            1 => Ok(Self::AssemblyRef(index)),
+            2 => Ok(Self::ExportedType(index)),
+            _ => Err(()),
+        }
+    }
+}
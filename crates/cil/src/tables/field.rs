@@ -51,3 +51,11 @@ pub struct FieldRva {
     pub rva: u32,
     pub field_index: u16,
 }
+
+#[binread]
+#[derive(Debug)]
+pub struct FieldMarshal {
+    /// HasFieldMarshal coded index of the field or parameter
+    pub parent: u16,
+    pub native_type_blob_index: u16,
+}
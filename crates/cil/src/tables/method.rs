@@ -28,6 +28,7 @@ bitfield! {
         flag is_synchronized: bool @ 0x0020,
         flag no_inlining: bool @ 0x0008,
         flag no_optimization: bool @ 0x0040,
+        flag aggressive_inlining: bool @ 0x0100,
     }
 }
 
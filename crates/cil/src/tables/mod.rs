@@ -20,7 +20,7 @@ use int_enum::IntEnum;
 use crate::{
     bitfield,
     image::{CilImage, TypeName},
-    meta::{GuidIndex, StringIndex},
+    meta::{GuidIndex, StringIndex, TypeDefHandle, TypeRefHandle},
     strings::StringHeap,
     util::PackedU32,
 };
@@ -75,6 +75,7 @@ bitfield! {
         enum native_string_format: NativeStringFormat @ 0x00030000 >> 16,
         flag is_rt_special_name: bool @ 0x00000800,
         flag has_security: bool @ 0x00040000,
+        flag is_before_field_init: bool @ 0x00100000,
         flag is_type_forwarder: bool @ 0x00200000,
     }
 }
@@ -93,7 +94,7 @@ pub enum Visibility {
 }
 
 #[repr(u32)]
-#[derive(Debug, IntEnum)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, IntEnum)]
 pub enum Layout {
     Auto = 0,
     Sequential = 1,
@@ -150,6 +151,7 @@ pub struct DeclSecurity {
 #[binread]
 #[derive(Debug)]
 pub struct ClassLayout {
+    /// Field alignment in bytes, 0 means the platform default
     pub packing_size: u16,
     pub class_size: u32,
     pub parent: u16,
@@ -162,6 +164,24 @@ pub struct FieldLayout {
     pub field: u16,
 }
 
+#[binread]
+#[derive(Debug)]
+pub struct EventMap {
+    pub parent: u16,
+    pub event_list: u16,
+}
+
+#[binread]
+#[derive(Debug)]
+#[br(import(strings: &StringHeap))]
+pub struct Event {
+    pub flags: u16,
+    #[br(try_map = |s: StringIndex| strings.try_get(s))]
+    pub name: String,
+    /// TypeDefOrRef coded index of the delegate type
+    pub event_type: u16,
+}
+
 #[binread]
 #[derive(Debug)]
 pub struct PropertyMap {
@@ -195,6 +215,14 @@ pub struct MethodImpl {
     pub method_declaration: u16,
 }
 
+#[binread]
+#[derive(Debug)]
+#[br(import(strings: &StringHeap))]
+pub struct ModuleRef {
+    #[br(try_map = |s: StringIndex| strings.try_get(s))]
+    pub name: String,
+}
+
 #[binread]
 #[derive(Debug)]
 pub struct TypeSpec {
@@ -233,6 +261,7 @@ pub struct GenericParam {
 #[binread]
 #[derive(Debug)]
 pub struct MethodSpec {
+    /// MethodDefOrRef coded index of the generic method
     pub method: u16,
     pub instantiation_blob_index: u16,
 }
@@ -244,67 +273,20 @@ pub struct GenericParamConstraint {
     pub constraint: u16,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub enum TypeDefOrRef {
-    TypeDef(u32),
-    TypeRef(u32),
-    TypeSpec(u32),
-}
-
 impl TypeDefOrRef {
     pub fn name_with_namespace(&self, image: &CilImage) -> Option<String> {
-        let s = match self {
-            TypeDefOrRef::TypeDef(index) => image
-                .type_defs
-                .get(*index as usize - 1)
-                .map(|td| format!("{}.{}", td.type_namespace, td.type_name)),
-            TypeDefOrRef::TypeRef(index) => image
-                .type_refs
-                .get(*index as usize - 1)
-                .map(|tr| format!("{}.{}", tr.namespace, tr.name)),
-            TypeDefOrRef::TypeSpec(_) => None,
-        };
-
-        if let Some(s) = s.clone()
-            && s.starts_with('.')
-        {
-            Some(s[1..].to_string())
-        } else {
-            s
-        }
+        self.typename(image).map(|t| t.to_string())
     }
 
     pub fn typename(&self, image: &CilImage) -> Option<TypeName> {
         match self {
-            TypeDefOrRef::TypeDef(index) => {
-                image.type_defs.get(*index as usize - 1).map(|td| TypeName {
-                    namespace: td.type_namespace.clone(),
-                    name: td.type_name.clone(),
-                })
-            }
-            TypeDefOrRef::TypeRef(index) => {
-                image.type_refs.get(*index as usize - 1).map(|tr| TypeName {
-                    namespace: tr.namespace.clone(),
-                    name: tr.name.clone(),
-                })
-            }
+            TypeDefOrRef::TypeDef(index) => image.type_def_name(TypeDefHandle(*index)),
+            TypeDefOrRef::TypeRef(index) => image.type_ref_name(TypeRefHandle(*index)),
             TypeDefOrRef::TypeSpec(_) => None,
         }
     }
 }
 
-impl TryFrom<u32> for TypeDefOrRef {
-    type Error = ();
-    fn try_from(v: u32) -> Result<Self, Self::Error> {
-        let index = v >> 2;
-        match v & 0b11 {
-            0 => Ok(TypeDefOrRef::TypeDef(index)),
-            1 => Ok(TypeDefOrRef::TypeRef(index)),
-            2 => Ok(TypeDefOrRef::TypeSpec(index)),
-            _ => Err(()),
-        }
-    }
-}
 impl BinRead for TypeDefOrRef {
     type Args<'a> = ();
 
@@ -320,36 +302,28 @@ impl BinRead for TypeDefOrRef {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub enum MemberRefParent {
-    TypeDef(u32),
-    TypeRef(u32),
-    ModuleRef(u32),
-    MethodDef(u32),
-    TypeSpec(u32),
-}
-
 impl MemberRefParent {
     pub fn typename(&self, image: &CilImage) -> Option<TypeName> {
         match self {
-            Self::TypeDef(index) => image.type_defs.get(*index as usize - 1).map(|td| TypeName {
-                namespace: td.type_namespace.clone(),
-                name: td.type_name.clone(),
-            }),
-            Self::TypeRef(index) => image.type_refs.get(*index as usize - 1).map(|tr| TypeName {
-                namespace: tr.namespace.clone(),
-                name: tr.name.clone(),
-            }),
-            Self::ModuleRef(index) => image.modules.get(*index as usize - 1).map(|mr| TypeName {
-                namespace: "".to_string(),
-                name: mr.name.clone(),
-            }),
+            Self::TypeDef(index) => image.type_def_name(TypeDefHandle(*index)),
+            Self::TypeRef(index) => image.type_ref_name(TypeRefHandle(*index)),
+            Self::ModuleRef(index) => {
+                image
+                    .module_refs
+                    .get(*index as usize - 1)
+                    .map(|mr| TypeName {
+                        namespace: "".to_string(),
+                        enclosing: vec![],
+                        name: mr.name.clone(),
+                    })
+            }
             Self::MethodDef(index) => {
                 image
                     .method_defs
                     .get(*index as usize - 1)
                     .map(|md| TypeName {
                         namespace: "".to_string(),
+                        enclosing: vec![],
                         name: md.0.name.clone(),
                     })
             }
@@ -358,17 +332,142 @@ impl MemberRefParent {
     }
 }
 
-impl TryFrom<u32> for MemberRefParent {
-    type Error = ();
-    fn try_from(v: u32) -> Result<Self, Self::Error> {
-        let index = v >> 3;
-        match v & 0b111 {
-            0 => Ok(Self::TypeDef(index)),
-            1 => Ok(Self::TypeRef(index)),
-            2 => Ok(Self::ModuleRef(index)),
-            3 => Ok(Self::MethodDef(index)),
-            4 => Ok(Self::TypeSpec(index)),
-            _ => Err(()),
+/// Defines a coded index (ECMA-335 II.24.2.6) with `tag_bits` low bits selecting the table
+macro_rules! coded_index {
+    ($(
+        $(#[doc = $description:expr])?
+        $name:ident : $tag_bits:literal {
+            $($(#[doc = $variant_description:expr])? $variant:ident = $tag:literal),* $(,)?
         }
+    )*) => {
+        $(
+            $(#[doc = $description])?
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+            pub enum $name {
+                $($(#[doc = $variant_description])? $variant(u32)),*
+            }
+
+            impl $name {
+                pub fn encode(&self) -> u32 {
+                    match self {
+                        $(Self::$variant(index) => (index << $tag_bits) | $tag),*
+                    }
+                }
+            }
+
+            impl TryFrom<u32> for $name {
+                type Error = ();
+                fn try_from(v: u32) -> Result<Self, Self::Error> {
+                    let index = v >> $tag_bits;
+                    match v & ((1 << $tag_bits) - 1) {
+                        $($tag => Ok(Self::$variant(index)),)*
+                        _ => Err(()),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+coded_index! {
+    /// Type in a signature, an extends clause or an InterfaceImpl row
+    TypeDefOrRef: 2 {
+        TypeDef = 0,
+        TypeRef = 1,
+        TypeSpec = 2,
+    }
+
+    /// Type or member a MemberRef row belongs to
+    MemberRefParent: 3 {
+        TypeDef = 0,
+        TypeRef = 1,
+        ModuleRef = 2,
+        MethodDef = 3,
+        TypeSpec = 4,
+    }
+
+    /// Method of a MethodSpec or MethodImpl row
+    MethodDefOrRef: 1 {
+        MethodDef = 0,
+        MemberRef = 1,
+    }
+
+    /// Scope a TypeRef row is resolved in
+    ResolutionScope: 2 {
+        Module = 0,
+        ModuleRef = 1,
+        AssemblyRef = 2,
+        /// The type is nested inside another TypeRef
+        TypeRef = 3,
+    }
+
+    /// Owner of a GenericParam row
+    TypeOrMethodDef: 1 {
+        TypeDef = 0,
+        MethodDef = 1,
+    }
+
+    /// Owner of a Constant row
+    HasConstant: 2 {
+        Field = 0,
+        Param = 1,
+        Property = 2,
+    }
+
+    /// Owner of a FieldMarshal row
+    HasFieldMarshal: 1 {
+        Field = 0,
+        Param = 1,
+    }
+
+    /// Owner of a DeclSecurity row
+    HasDeclSecurity: 2 {
+        TypeDef = 0,
+        MethodDef = 1,
+        Assembly = 2,
+    }
+
+    /// Member an ImplMap row forwards to unmanaged code
+    MemberForwarded: 1 {
+        Field = 0,
+        MethodDef = 1,
+    }
+
+    /// Constructor of a CustomAttribute row
+    CustomAttributeType: 3 {
+        MethodDef = 2,
+        MemberRef = 3,
+    }
+
+    /// Event or property a MethodSemantics row associates a method with
+    HasSemantics: 1 {
+        Event = 0,
+        Property = 1,
+    }
+
+    /// Owner of a CustomAttribute row
+    HasCustomAttribute: 5 {
+        MethodDef = 0,
+        Field = 1,
+        TypeRef = 2,
+        TypeDef = 3,
+        Param = 4,
+        InterfaceImpl = 5,
+        MemberRef = 6,
+        Module = 7,
+        DeclSecurity = 8,
+        Property = 9,
+        Event = 10,
+        StandAloneSig = 11,
+        ModuleRef = 12,
+        TypeSpec = 13,
+        Assembly = 14,
+        AssemblyRef = 15,
+        File = 16,
+        ExportedType = 17,
+        ManifestResource = 18,
+        GenericParam = 19,
+        GenericParamConstraint = 20,
+        MethodSpec = 21,
     }
 }
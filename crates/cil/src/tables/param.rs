@@ -2,14 +2,25 @@ use std::fmt::Debug;
 
 use binrw::binread;
 
-use crate::{meta::StringIndex, strings::StringHeap};
+use crate::{bitfield, meta::StringIndex, strings::StringHeap};
 
 #[binread]
 #[derive(Debug)]
 #[br(import(strings: &StringHeap))]
 pub struct Param {
-    pub flags: u16,
+    pub flags: ParamAttributes,
+    /// 0 for the return value, 1 for the first parameter
     pub sequence: u16,
     #[br(try_map = |s: StringIndex| strings.try_get(s))]
     pub name: String,
 }
+
+bitfield! {
+    pub struct ParamAttributes : u16 {
+        flag is_in: bool @ 0x0001,
+        flag is_out: bool @ 0x0002,
+        flag is_optional: bool @ 0x0010,
+        flag has_default: bool @ 0x1000,
+        flag has_field_marshal: bool @ 0x2000,
+    }
+}
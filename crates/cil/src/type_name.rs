@@ -0,0 +1,555 @@
+//! Parser for reflection type names, as used by `Type.GetType`, custom attribute arguments of type
+//! `System.Type` and serialized data, eg. ``Ns.Outer+Inner`1[[System.Int32, mscorlib]][], Asm, Version=1.0.0.0``
+
+use std::{fmt::Display, str::FromStr};
+
+use crate::{
+    Result,
+    error::Error,
+    hierarchy::{ScopedType, TypeHierarchy},
+    image::{CilImage, TypeName},
+    meta::{ExportedTypeHandle, TypeDefHandle},
+    signature::{ArrayShape, Element},
+    tables::{Implementation, ResolutionScope, TypeDefOrRef},
+    util::PackedU32,
+};
+
+/// Characters that have to be escaped with a backslash inside a type name
+const SPECIAL_CHARACTERS: &[char] = &['+', ',', '[', ']', '*', '&', '\\'];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TypeNameSuffix {
+    /// `*`
+    Pointer,
+    /// `&`
+    ByRef,
+    /// `[]`
+    SzArray,
+    /// `[*]` for rank 1, `[,]` for rank 2, etc.
+    Array(u32),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReflectionTypeName {
+    pub namespace: String,
+    /// Name of the type followed by the names of the types nested in it, outermost first.
+    /// Generic arity suffixes such as `` `1`` are kept.
+    pub names: Vec<String>,
+    pub generic_args: Vec<ReflectionTypeName>,
+    /// Pointer, by-ref and array suffixes, in the order they are applied
+    pub suffixes: Vec<TypeNameSuffix>,
+    /// Assembly display name, eg. `mscorlib, Version=4.0.0.0, Culture=neutral`
+    pub assembly: Option<String>,
+}
+
+impl ReflectionTypeName {
+    pub fn parse(name: &str) -> Result<Self> {
+        let mut parser = Parser {
+            chars: name.chars().collect(),
+            position: 0,
+        };
+
+        let type_name = parser.assembly_qualified_name(true)?;
+        parser.skip_whitespace();
+        if parser.position != parser.chars.len() {
+            return Err(parser.error("unexpected trailing characters"));
+        }
+
+        Ok(type_name)
+    }
+
+    /// Simple name of the assembly, without version, culture or public key token
+    pub fn assembly_name(&self) -> Option<&str> {
+        self.assembly
+            .as_deref()
+            .map(|a| a.split(',').next().unwrap_or(a).trim())
+    }
+
+    /// Namespace-qualified name of the type itself, eg. ``Ns.Outer+Inner`1``
+    pub fn full_name(&self) -> String {
+        let names = self
+            .names
+            .iter()
+            .map(|n| escape(n))
+            .collect::<Vec<_>>()
+            .join("+");
+        if self.namespace.is_empty() {
+            names
+        } else {
+            format!("{}.{names}", escape(&self.namespace))
+        }
+    }
+
+    /// Resolves the name against the TypeDef, TypeRef and ExportedType tables of `image`, producing the
+    /// same representation the signature decoder uses.
+    ///
+    /// TypeRefs to other assemblies can't be resolved without a resolver, so whether they are value
+    /// types is unknown and they are assumed to be classes. Types forwarded to other assemblies fail
+    /// with [`Error::ForwardedType`], use [`Self::resolve_in`] to follow them.
+    pub fn resolve(&self, image: &CilImage) -> Result<Element> {
+        Ok(self.resolve_in(&TypeHierarchy::new(image))?.element)
+    }
+
+    /// Resolves the name against the image of `hierarchy`, following type references and type
+    /// forwarders into the assemblies its resolver provides.
+    ///
+    /// Forwarded types are scoped to the image that defines them, generic instances with arguments
+    /// from several images refer to them through [`ScopedType::type_args`]. TypeRefs that still can't
+    /// be resolved are assumed to be classes, as in [`Self::resolve`].
+    pub fn resolve_in<'a>(&self, hierarchy: &TypeHierarchy<'a>) -> Result<ScopedType<'a>> {
+        let mut ty = if let Some(primitive) = self.primitive() {
+            ScopedType::new(hierarchy.image, primitive)
+        } else {
+            let (image, base) = self.find_type(hierarchy)?;
+            if self.generic_args.is_empty() {
+                ScopedType::new(image, base)
+            } else {
+                let args = self
+                    .generic_args
+                    .iter()
+                    .map(|a| a.resolve_in(hierarchy))
+                    .collect::<Result<Vec<_>>>()?;
+                let element = Element::GenericInst {
+                    generic_type: Box::new(base),
+                    generic_args: (0..args.len() as u32)
+                        .map(|i| Element::Var(PackedU32(i)))
+                        .collect(),
+                };
+                hierarchy.instantiate(image, element, &args)
+            }
+        };
+
+        for suffix in &self.suffixes {
+            let element = Box::new(ty.element);
+            ty.element = match suffix {
+                TypeNameSuffix::Pointer => Element::Ptr(element),
+                TypeNameSuffix::ByRef => Element::ByRef(element),
+                TypeNameSuffix::SzArray => Element::SzArray(element),
+                TypeNameSuffix::Array(rank) => Element::Array(
+                    element,
+                    ArrayShape {
+                        rank: *rank,
+                        sizes: vec![],
+                        lower_bounds: vec![],
+                    },
+                ),
+            };
+        }
+
+        Ok(ty)
+    }
+
+    fn primitive(&self) -> Option<Element> {
+        if self.namespace != "System" || self.names.len() != 1 || !self.generic_args.is_empty() {
+            return None;
+        }
+
+        Some(match self.names[0].as_str() {
+            "Void" => Element::Void,
+            "Boolean" => Element::Boolean,
+            "Char" => Element::Char,
+            "SByte" => Element::I1,
+            "Byte" => Element::U1,
+            "Int16" => Element::I2,
+            "UInt16" => Element::U2,
+            "Int32" => Element::I4,
+            "UInt32" => Element::U4,
+            "Int64" => Element::I8,
+            "UInt64" => Element::U8,
+            "Single" => Element::R4,
+            "Double" => Element::R8,
+            "String" => Element::String,
+            "Object" => Element::Object,
+            "IntPtr" => Element::IntPtr,
+            "UIntPtr" => Element::UIntPtr,
+            "TypedReference" => Element::TypedByRef,
+            _ => return None,
+        })
+    }
+
+    /// The image defining or referencing the named type, and the type itself without generic arguments
+    fn find_type<'a>(&self, hierarchy: &TypeHierarchy<'a>) -> Result<(&'a CilImage, Element)> {
+        let image = hierarchy.image;
+        let local = match self.assembly_name() {
+            None => true,
+            Some(name) => image.assemblies.first().is_some_and(|a| a.name == name),
+        };
+
+        if local
+            && let Some(index) = (1..=image.type_defs.len() as u32)
+                .find(|&i| self.matches_type_def(image, i, self.names.len()))
+        {
+            return Ok((
+                image,
+                hierarchy.type_def(image, TypeDefHandle(index)).element,
+            ));
+        }
+
+        if let Some(index) = (1..=image.type_refs.len() as u32)
+            .find(|&i| self.matches_type_ref(image, i, self.names.len()))
+        {
+            let token = TypeDefOrRef::TypeRef(index);
+            let element = match hierarchy.resolve(image, token) {
+                Some((definition, handle)) if definition.is_value_type(handle) => {
+                    Element::ValueType(token)
+                }
+                _ => Element::Class(token),
+            };
+            return Ok((image, element));
+        }
+
+        if let Some(index) = (1..=image.exported_types.len() as u32)
+            .find(|&i| self.matches_exported_type(image, i, self.names.len()))
+        {
+            let (name, enclosing) = self.names.split_last().expect("type names are never empty");
+            let type_name = TypeName {
+                namespace: self.namespace.clone(),
+                enclosing: enclosing.to_vec(),
+                name: name.clone(),
+            };
+            return hierarchy
+                .find_type_def(image, &type_name, 0)
+                .map(|(image, handle)| (image, hierarchy.type_def(image, handle).element))
+                .ok_or_else(|| Error::ForwardedType {
+                    name: self.full_name(),
+                    assembly: self
+                        .exported_type_assembly(image, ExportedTypeHandle(index))
+                        .unwrap_or_default(),
+                });
+        }
+
+        Err(Error::UnresolvedType(self.to_string()))
+    }
+
+    /// Whether TypeDef `index` is the type named by the first `depth` components of `names`
+    fn matches_type_def(&self, image: &CilImage, index: u32, depth: usize) -> bool {
+        let Some(type_def) = image.type_defs.get((index as usize).wrapping_sub(1)) else {
+            return false;
+        };
+        if type_def.type_name != self.names[depth - 1] {
+            return false;
+        }
+
+        match image.enclosing_type_of(TypeDefHandle(index)) {
+            Some(enclosing) => depth > 1 && self.matches_type_def(image, enclosing.0, depth - 1),
+            None => depth == 1 && type_def.type_namespace == self.namespace,
+        }
+    }
+
+    fn matches_type_ref(&self, image: &CilImage, index: u32, depth: usize) -> bool {
+        let Some(type_ref) = image.type_refs.get((index as usize).wrapping_sub(1)) else {
+            return false;
+        };
+        if type_ref.name != self.names[depth - 1] {
+            return false;
+        }
+
+        match ResolutionScope::try_from(type_ref.resolution_scope as u32) {
+            Ok(ResolutionScope::TypeRef(enclosing)) => {
+                depth > 1 && self.matches_type_ref(image, enclosing, depth - 1)
+            }
+            Ok(ResolutionScope::AssemblyRef(assembly)) => {
+                depth == 1
+                    && type_ref.namespace == self.namespace
+                    && self.assembly_name().is_none_or(|name| {
+                        image
+                            .assembly_refs
+                            .get((assembly as usize).wrapping_sub(1))
+                            .is_some_and(|a| a.name == name)
+                    })
+            }
+            _ => depth == 1 && type_ref.namespace == self.namespace,
+        }
+    }
+
+    fn matches_exported_type(&self, image: &CilImage, index: u32, depth: usize) -> bool {
+        let Some(exported) = image.exported_types.get((index as usize).wrapping_sub(1)) else {
+            return false;
+        };
+        if exported.type_name != self.names[depth - 1] {
+            return false;
+        }
+
+        match Implementation::try_from(exported.implementation as u32) {
+            Ok(Implementation::ExportedType(enclosing)) => {
+                depth > 1 && self.matches_exported_type(image, enclosing, depth - 1)
+            }
+            _ => depth == 1 && exported.type_namespace == self.namespace,
+        }
+    }
+
+    fn exported_type_assembly(
+        &self,
+        image: &CilImage,
+        handle: ExportedTypeHandle,
+    ) -> Option<String> {
+        let exported = image.exported_types.get(handle.index())?;
+        match Implementation::try_from(exported.implementation as u32).ok()? {
+            Implementation::AssemblyRef(index) => image
+                .assembly_refs
+                .get((index as usize).checked_sub(1)?)
+                .map(|a| a.name.clone()),
+            Implementation::ExportedType(index) => {
+                self.exported_type_assembly(image, ExportedTypeHandle(index))
+            }
+            Implementation::File(_) => image.assemblies.first().map(|a| a.name.clone()),
+        }
+    }
+}
+
+impl FromStr for ReflectionTypeName {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s)
+    }
+}
+
+impl Display for ReflectionTypeName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.full_name())?;
+
+        if !self.generic_args.is_empty() {
+            write!(f, "[")?;
+            for (i, arg) in self.generic_args.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ",")?;
+                }
+                write!(f, "[{arg}]")?;
+            }
+            write!(f, "]")?;
+        }
+
+        for suffix in &self.suffixes {
+            match suffix {
+                TypeNameSuffix::Pointer => write!(f, "*")?,
+                TypeNameSuffix::ByRef => write!(f, "&")?,
+                TypeNameSuffix::SzArray => write!(f, "[]")?,
+                TypeNameSuffix::Array(1) => write!(f, "[*]")?,
+                TypeNameSuffix::Array(rank) => write!(f, "[{}]", ",".repeat(*rank as usize - 1))?,
+            }
+        }
+
+        if let Some(assembly) = &self.assembly {
+            write!(f, ", {assembly}")?;
+        }
+
+        Ok(())
+    }
+}
+
+fn escape(name: &str) -> String {
+    let mut s = String::with_capacity(name.len());
+    for c in name.chars() {
+        if SPECIAL_CHARACTERS.contains(&c) {
+            s.push('\\');
+        }
+        s.push(c);
+    }
+    s
+}
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    /// Peeks at the next character that is not whitespace
+    fn peek_significant(&self) -> Option<char> {
+        self.chars[self.position..]
+            .iter()
+            .copied()
+            .find(|c| !c.is_whitespace())
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.position += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<()> {
+        self.skip_whitespace();
+        if self.peek() == Some(expected) {
+            self.position += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{expected}'")))
+        }
+    }
+
+    fn error(&self, message: &str) -> Error {
+        Error::InvalidTypeName {
+            position: self.position,
+            message: message.to_string(),
+        }
+    }
+
+    /// `TypeName [, AssemblyName]`. Inside generic argument brackets the assembly name ends at `]`.
+    fn assembly_qualified_name(&mut self, top_level: bool) -> Result<ReflectionTypeName> {
+        let mut type_name = self.type_name()?;
+
+        self.skip_whitespace();
+        if self.peek() == Some(',') {
+            self.position += 1;
+            let start = self.position;
+            while let Some(c) = self.peek() {
+                if !top_level && c == ']' {
+                    break;
+                }
+                self.position += 1;
+                if c == '\\' {
+                    self.position += 1;
+                }
+            }
+
+            let assembly = self.chars[start..self.position.min(self.chars.len())]
+                .iter()
+                .collect::<String>();
+            let assembly = assembly.trim();
+            if assembly.is_empty() {
+                return Err(self.error("expected an assembly name"));
+            }
+            type_name.assembly = Some(assembly.to_string());
+        }
+
+        Ok(type_name)
+    }
+
+    /// `FullName ['[' GenericArgs ']'] Suffix*`
+    fn type_name(&mut self) -> Result<ReflectionTypeName> {
+        self.skip_whitespace();
+        let (first, namespace_split) = self.identifier()?;
+        let (namespace, first) = match namespace_split {
+            Some(split) => (first[..split].to_string(), first[split + 1..].to_string()),
+            None => (String::new(), first),
+        };
+
+        let mut names = vec![first];
+        while self.peek() == Some('+') {
+            self.position += 1;
+            names.push(self.identifier()?.0);
+        }
+
+        let mut generic_args = Vec::new();
+        if self.peek() == Some('[')
+            && !matches!(
+                self.chars[self.position + 1..]
+                    .iter()
+                    .find(|c| !c.is_whitespace()),
+                Some(']' | ',' | '*') | None
+            )
+        {
+            self.position += 1;
+            loop {
+                let arg = if self.peek_significant() == Some('[') {
+                    self.expect('[')?;
+                    let arg = self.assembly_qualified_name(false)?;
+                    self.expect(']')?;
+                    arg
+                } else {
+                    self.type_name()?
+                };
+                generic_args.push(arg);
+
+                self.skip_whitespace();
+                match self.peek() {
+                    Some(',') => self.position += 1,
+                    Some(']') => {
+                        self.position += 1;
+                        break;
+                    }
+                    _ => return Err(self.error("expected ',' or ']' in generic arguments")),
+                }
+            }
+        }
+
+        let mut suffixes = Vec::new();
+        loop {
+            match self.peek() {
+                Some('*') => {
+                    self.position += 1;
+                    suffixes.push(TypeNameSuffix::Pointer);
+                }
+                Some('&') => {
+                    self.position += 1;
+                    suffixes.push(TypeNameSuffix::ByRef);
+                }
+                Some('[') => {
+                    self.position += 1;
+                    self.skip_whitespace();
+                    if self.peek() == Some('*') {
+                        self.position += 1;
+                        self.expect(']')?;
+                        suffixes.push(TypeNameSuffix::Array(1));
+                        continue;
+                    }
+
+                    let mut rank = 1;
+                    loop {
+                        self.skip_whitespace();
+                        match self.peek() {
+                            Some(',') => rank += 1,
+                            Some(']') => break,
+                            _ => return Err(self.error("expected ',' or ']' in array rank")),
+                        }
+                        self.position += 1;
+                    }
+                    self.position += 1;
+
+                    suffixes.push(if rank == 1 {
+                        TypeNameSuffix::SzArray
+                    } else {
+                        TypeNameSuffix::Array(rank)
+                    });
+                }
+                _ => break,
+            }
+        }
+
+        Ok(ReflectionTypeName {
+            namespace,
+            names,
+            generic_args,
+            suffixes,
+            assembly: None,
+        })
+    }
+
+    /// Reads an unescaped name component, returning it together with the byte offset of its last
+    /// unescaped `.`, which separates the namespace from the type name
+    fn identifier(&mut self) -> Result<(String, Option<usize>)> {
+        let mut name = String::new();
+        let mut last_dot = None;
+        while let Some(c) = self.peek() {
+            match c {
+                '\\' => {
+                    self.position += 1;
+                    let escaped = self
+                        .peek()
+                        .ok_or_else(|| self.error("unterminated escape sequence"))?;
+                    name.push(escaped);
+                }
+                '.' => {
+                    last_dot = Some(name.len());
+                    name.push(c);
+                }
+                c if SPECIAL_CHARACTERS.contains(&c) => break,
+                c => name.push(c),
+            }
+            self.position += 1;
+        }
+
+        let trimmed = name.trim_end();
+        if trimmed.is_empty() {
+            return Err(self.error("expected a type name"));
+        }
+        let last_dot = last_dot.filter(|&d| d < trimmed.len());
+        Ok((trimmed.to_string(), last_dot))
+    }
+}
@@ -71,6 +71,7 @@ macro_rules! bitfield {
 
 pub trait ReadExt {
     fn read_compressed_u32(&mut self) -> binrw::BinResult<u32>;
+    fn read_compressed_i32(&mut self) -> binrw::BinResult<i32>;
 }
 
 impl<T> ReadExt for T
@@ -111,6 +112,43 @@ where
 
         Ok(result)
     }
+
+    fn read_compressed_i32(&mut self) -> binrw::BinResult<i32> {
+        // Signed integers use the same length prefix as unsigned ones, but the value is rotated
+        // left by one so that the sign bit ends up in bit 0:
+        //   - 1 byte:  6 bits of magnitude, negative values are sign-extended from 0xFFFFFFC0
+        //   - 2 bytes: 13 bits of magnitude, negative values are sign-extended from 0xFFFFE000
+        //   - 4 bytes: 28 bits of magnitude, negative values are sign-extended from 0xF0000000
+        let mut buf = [0u8; 1];
+        self.read_exact(&mut buf)?;
+        let first_byte = buf[0];
+        let (raw, sign_extension) = if first_byte & 0x80 == 0 {
+            (first_byte as u32, 0xFFFFFFC0u32)
+        } else if first_byte & 0xC0 == 0x80 {
+            let mut data = [0u8; 1];
+            self.read_exact(&mut data)?;
+            (
+                (((first_byte & 0x3F) as u32) << 8) | (data[0] as u32),
+                0xFFFFE000,
+            )
+        } else {
+            let mut data = [0u8; 3];
+            self.read_exact(&mut data)?;
+            (
+                (((first_byte & 0x1F) as u32) << 24)
+                    | ((data[0] as u32) << 16)
+                    | ((data[1] as u32) << 8)
+                    | (data[2] as u32),
+                0xF0000000,
+            )
+        };
+
+        if raw & 1 == 0 {
+            Ok((raw >> 1) as i32)
+        } else {
+            Ok(((raw >> 1) | sign_extension) as i32)
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -128,3 +166,19 @@ impl BinRead for PackedU32 {
         Ok(PackedU32(value))
     }
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PackedI32(pub i32);
+
+impl BinRead for PackedI32 {
+    type Args<'a> = ();
+
+    fn read_options<R: Read + std::io::Seek>(
+        reader: &mut R,
+        _endian: binrw::Endian,
+        _args: Self::Args<'_>,
+    ) -> binrw::BinResult<Self> {
+        let value = reader.read_compressed_i32()?;
+        Ok(PackedI32(value))
+    }
+}
@@ -0,0 +1,1304 @@
+//! Evaluation stack verification of method bodies, in the spirit of PEVerify and ILVerify
+//!
+//! The verifier interprets a method body abstractly, tracking the depth of the evaluation stack and
+//! the verification type of every value on it (ECMA-335 III.1.8.1.2). It reports underflows,
+//! `.maxstack` violations, paths that join with different stacks and operands of the wrong type.
+//!
+//! Types from assemblies that can't be resolved are [`StackType::Unknown`], since they could be
+//! enums, and never cause an error. Unverifiable but legal code, such as pointer arithmetic on
+//! managed pointers, isn't reported either.
+
+use std::{collections::BTreeMap, fmt::Display};
+
+use thiserror::Error;
+
+use crate::{
+    Result,
+    body::{ExceptionClause, ExceptionClauseKind},
+    error::Error,
+    hierarchy::{AssemblyResolver, TypeHierarchy},
+    image::CilImage,
+    meta::{
+        FieldHandle, MemberRefHandle, MethodDefHandle, StandAloneSigHandle, Token, TypeDefHandle,
+        TypeSpecHandle,
+    },
+    opcodes::{FlowControl, Instruction, Operand, RawOpcode, TokenReference},
+    signature::{Element, MemberRefSignature, StandaloneMethodSignature},
+    tables::{MemberRefParent, MethodDefOrRef, TypeDefOrRef},
+};
+
+/// Type of a value on the evaluation stack
+#[derive(Debug, Clone, PartialEq)]
+pub enum StackType {
+    /// `int32`, also used for booleans, characters and the small integer types
+    Int32,
+    Int64,
+    /// `native int`, also used for unmanaged pointers
+    NativeInt,
+    /// `F`, floating point values of either precision
+    Float,
+    /// `O`, object references including `null`
+    Object,
+    /// `&`, managed pointers
+    ByRef,
+    /// Instance of a value type, or of a generic parameter that could be one
+    ValueType(Element),
+    /// A value of a type that couldn't be resolved, compatible with every other type
+    Unknown,
+}
+
+impl StackType {
+    fn is_integer(&self) -> bool {
+        matches!(self, Self::Int32 | Self::Int64 | Self::NativeInt)
+    }
+
+    fn is_numeric(&self) -> bool {
+        self.is_integer() || *self == Self::Float
+    }
+
+    /// Type of a slot where two paths join, `None` if the types are incompatible
+    fn merge(&self, other: &Self) -> Option<Self> {
+        match (self, other) {
+            (a, b) if a == b => Some(a.clone()),
+            (Self::Unknown, _) | (_, Self::Unknown) => Some(Self::Unknown),
+            _ => None,
+        }
+    }
+}
+
+impl Display for StackType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Int32 => "int32",
+            Self::Int64 => "int64",
+            Self::NativeInt => "native int",
+            Self::Float => "F",
+            Self::Object => "O",
+            Self::ByRef => "&",
+            Self::ValueType(_) => "value type",
+            Self::Unknown => "unknown",
+        })
+    }
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum VerificationErrorKind {
+    #[error("stack underflow, {needed} operands needed but the stack holds {available}")]
+    StackUnderflow { needed: u16, available: usize },
+
+    #[error("stack depth {depth} exceeds .maxstack {max_stack}")]
+    MaxStackExceeded { depth: usize, max_stack: u16 },
+
+    #[error("stack depth {found} differs from depth {expected} on another path")]
+    DepthMismatch { expected: usize, found: usize },
+
+    #[error("stack slot {slot} is {found} here but {expected} on another path")]
+    InconsistentMerge {
+        slot: usize,
+        expected: StackType,
+        found: StackType,
+    },
+
+    #[error("expected {expected}, found {found}")]
+    UnexpectedType {
+        expected: &'static str,
+        found: StackType,
+    },
+
+    #[error("{0} with a non-empty stack")]
+    NonEmptyStack(&'static str),
+
+    #[error("protected region entered with a non-empty stack")]
+    TryEntryWithNonEmptyStack,
+
+    #[error("execution falls through the end of the method body")]
+    FallsOffEnd,
+
+    #[error("local variable {0} does not exist")]
+    InvalidLocal(u16),
+
+    #[error("argument {0} does not exist")]
+    InvalidArgument(u16),
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("IL_{offset:04x}: {kind}")]
+pub struct VerificationError {
+    /// Offset of the offending instruction
+    pub offset: u32,
+    pub kind: VerificationErrorKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StackAnalysis {
+    /// Evaluation stack before each instruction, bottom first. `None` for unreachable instructions.
+    pub states: Vec<Option<Vec<StackType>>>,
+    /// Values each instruction pushes, bottom first. Empty for unreachable instructions.
+    pub pushed: Vec<Vec<StackType>>,
+    /// Types of the arguments, starting with `this` for instance methods
+    pub arguments: Vec<Element>,
+    pub locals: Vec<Element>,
+    /// Deepest the stack gets on any path
+    pub max_depth: usize,
+    /// Errors in offset order
+    pub errors: Vec<VerificationError>,
+}
+
+impl StackAnalysis {
+    /// Stack before the instruction at `index`, `None` if it is unreachable
+    pub fn stack_before(&self, index: usize) -> Option<&[StackType]> {
+        self.states.get(index)?.as_deref()
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+/// What the verifier knows about the method being verified
+struct Body<'b> {
+    signature: &'b StandaloneMethodSignature,
+    /// Types of the arguments, including `this`
+    arguments: Vec<Element>,
+    locals: Vec<Element>,
+}
+
+/// Collects the errors of one instruction
+struct Checker<'e> {
+    offset: u32,
+    errors: &'e mut Vec<VerificationError>,
+}
+
+impl Checker<'_> {
+    fn error(&mut self, kind: VerificationErrorKind) {
+        self.errors.push(VerificationError {
+            offset: self.offset,
+            kind,
+        });
+    }
+
+    /// Reports `found` unless `ok` holds or its type is unknown
+    fn expect(&mut self, found: &StackType, ok: bool, expected: &'static str) {
+        if !ok && *found != StackType::Unknown {
+            self.error(VerificationErrorKind::UnexpectedType {
+                expected,
+                found: found.clone(),
+            });
+        }
+    }
+
+    fn expect_integer(&mut self, found: &StackType) {
+        self.expect(
+            found,
+            matches!(found, StackType::Int32 | StackType::NativeInt),
+            "int32 or native int",
+        );
+    }
+
+    fn expect_object(&mut self, found: &StackType) {
+        self.expect(found, *found == StackType::Object, "O");
+    }
+
+    fn expect_address(&mut self, found: &StackType) {
+        self.expect(
+            found,
+            matches!(found, StackType::ByRef | StackType::NativeInt),
+            "& or native int",
+        );
+    }
+}
+
+/// Verifies the evaluation stack of method bodies in an image
+pub struct Verifier<'a> {
+    hierarchy: TypeHierarchy<'a>,
+}
+
+impl<'a> Verifier<'a> {
+    pub fn new(image: &'a CilImage) -> Self {
+        Self {
+            hierarchy: TypeHierarchy::new(image),
+        }
+    }
+
+    /// Resolve types from referenced assemblies with `resolver`, so that their enums and value
+    /// types are checked instead of being unknown
+    pub fn with_resolver(mut self, resolver: &'a dyn AssemblyResolver) -> Self {
+        self.hierarchy = self.hierarchy.with_resolver(resolver);
+        self
+    }
+
+    fn image(&self) -> &'a CilImage {
+        self.hierarchy.image
+    }
+
+    /// Verification type of a value of type `element` once it is loaded on the stack
+    pub fn stack_type(&self, element: &Element) -> StackType {
+        match element {
+            Element::Boolean
+            | Element::Char
+            | Element::I1
+            | Element::U1
+            | Element::I2
+            | Element::U2
+            | Element::I4
+            | Element::U4 => StackType::Int32,
+            Element::I8 | Element::U8 => StackType::Int64,
+            Element::R4 | Element::R8 => StackType::Float,
+            Element::IntPtr | Element::UIntPtr | Element::Ptr(_) | Element::FnPtr(_) => {
+                StackType::NativeInt
+            }
+            Element::String
+            | Element::Object
+            | Element::Class(_)
+            | Element::SzArray(_)
+            | Element::Array(..) => StackType::Object,
+            Element::ByRef(_) => StackType::ByRef,
+            Element::CModRequired(_, inner)
+            | Element::CModOptional(_, inner)
+            | Element::Pinned(inner) => self.stack_type(inner),
+            Element::ValueType(token) => match self.hierarchy.resolve(self.image(), *token) {
+                Some((image, handle)) => match enum_underlying_type(image, handle) {
+                    Some(underlying) => self.stack_type(&underlying),
+                    None => StackType::ValueType(element.clone()),
+                },
+                None => StackType::Unknown,
+            },
+            Element::GenericInst { generic_type, .. } => match generic_type.as_ref() {
+                Element::ValueType(_) => StackType::ValueType(element.clone()),
+                _ => StackType::Object,
+            },
+            Element::TypedByRef | Element::Var(_) | Element::MVar(_) => {
+                StackType::ValueType(element.clone())
+            }
+            _ => StackType::Unknown,
+        }
+    }
+
+    /// Whether a value of type `value` can be stored in a location of type `target`
+    fn is_assignable(&self, value: &StackType, target: &Element) -> bool {
+        let target = self.stack_type(target);
+        match (value, &target) {
+            (StackType::Unknown, _) | (_, StackType::Unknown) => true,
+            // Stores truncate or extend native integers implicitly (ECMA-335 III.1.6)
+            (StackType::Int32 | StackType::NativeInt, StackType::Int32 | StackType::NativeInt) => {
+                true
+            }
+            (StackType::ByRef | StackType::NativeInt, StackType::ByRef | StackType::NativeInt) => {
+                true
+            }
+            // Generic parameters may be instantiated with reference types
+            (StackType::Object, StackType::ValueType(Element::Var(_) | Element::MVar(_))) => true,
+            _ => *value == target,
+        }
+    }
+
+    /// Type named by a type token, `None` for references to types that can't be resolved
+    fn type_token(&self, token: Token) -> Result<Option<Element>> {
+        let TokenReference::Type(token) = self.image().resolve_token(token)? else {
+            return Err(Error::InvalidToken(token));
+        };
+        self.type_element(token)
+    }
+
+    fn type_element(&self, token: TypeDefOrRef) -> Result<Option<Element>> {
+        if let TypeDefOrRef::TypeSpec(index) = token {
+            return self.image().type_spec(TypeSpecHandle(index)).map(Some);
+        }
+        Ok(self
+            .hierarchy
+            .resolve(self.image(), token)
+            .map(|(image, handle)| {
+                if image.is_value_type(handle) {
+                    Element::ValueType(token)
+                } else {
+                    Element::Class(token)
+                }
+            }))
+    }
+
+    /// Stack type of the type named by a type token
+    fn token_stack_type(&self, token: Token) -> Result<StackType> {
+        Ok(self
+            .type_token(token)?
+            .map_or(StackType::Unknown, |e| self.stack_type(&e)))
+    }
+
+    fn field_type(&self, token: Token) -> Result<Element> {
+        let image = self.image();
+        match image.resolve_token(token)? {
+            TokenReference::Field(handle) => image.field_type(handle),
+            TokenReference::FieldRef(handle) => {
+                match image.instantiated_member_ref_signature(handle)? {
+                    MemberRefSignature::Field(signature) => Ok(signature.field_type),
+                    MemberRefSignature::Method(_) => Err(Error::InvalidToken(token)),
+                }
+            }
+            _ => Err(Error::InvalidToken(token)),
+        }
+    }
+
+    /// Signature of a called method with generic arguments applied, and the type declaring it if
+    /// it is known
+    fn callee(&self, token: Token) -> Result<(StandaloneMethodSignature, Option<Element>)> {
+        let image = self.image();
+        let method = match image.resolve_token(token)? {
+            TokenReference::Method(method) => method,
+            TokenReference::MethodSpec(handle) => {
+                let signature = image.instantiated_method_spec_signature(handle)?;
+                let method =
+                    MethodDefOrRef::try_from(image.method_specs[handle.index()].method as u32)
+                        .map_err(|_| Error::InvalidToken(token))?;
+                return Ok((signature, self.declaring_type(method)?));
+            }
+            _ => return Err(Error::InvalidToken(token)),
+        };
+
+        let signature = match method {
+            MethodDefOrRef::MethodDef(index) => image.method_signature(MethodDefHandle(index))?,
+            MethodDefOrRef::MemberRef(index) => {
+                match image.instantiated_member_ref_signature(MemberRefHandle(index))? {
+                    MemberRefSignature::Method(signature) => signature,
+                    MemberRefSignature::Field(_) => return Err(Error::InvalidToken(token)),
+                }
+            }
+        };
+        Ok((signature, self.declaring_type(method)?))
+    }
+
+    fn declaring_type(&self, method: MethodDefOrRef) -> Result<Option<Element>> {
+        let image = self.image();
+        match method {
+            MethodDefOrRef::MethodDef(index) => {
+                match image.declaring_type_of(MethodDefHandle(index)) {
+                    Some(handle) => self.type_element(TypeDefOrRef::TypeDef(handle.0)),
+                    None => Ok(None),
+                }
+            }
+            MethodDefOrRef::MemberRef(index) => {
+                let handle = MemberRefHandle(index);
+                let member_ref = image
+                    .member_refs
+                    .get(handle.index())
+                    .ok_or(Error::InvalidToken(handle.token()))?;
+                match MemberRefParent::try_from(member_ref.class_index as u32) {
+                    Ok(MemberRefParent::TypeDef(index)) => {
+                        self.type_element(TypeDefOrRef::TypeDef(index))
+                    }
+                    Ok(MemberRefParent::TypeRef(index)) => {
+                        self.type_element(TypeDefOrRef::TypeRef(index))
+                    }
+                    Ok(MemberRefParent::TypeSpec(index)) => {
+                        self.type_element(TypeDefOrRef::TypeSpec(index))
+                    }
+                    // Vararg call sites of a method definition
+                    Ok(MemberRefParent::MethodDef(index)) => {
+                        self.declaring_type(MethodDefOrRef::MethodDef(index))
+                    }
+                    _ => Ok(None),
+                }
+            }
+        }
+    }
+
+    /// Verifies the body of a method definition. Methods without IL verify trivially.
+    pub fn verify(&self, handle: MethodDefHandle) -> Result<StackAnalysis> {
+        let image = self.image();
+        let (_, header, instructions) = image
+            .method_defs
+            .get(handle.index())
+            .ok_or(Error::InvalidToken(handle.token()))?;
+        let signature = image.method_signature(handle)?;
+
+        let mut arguments = Vec::new();
+        if signature.header.has_this() && !signature.header.explicit_this() {
+            let this = match image.declaring_type_of(handle) {
+                Some(owner) if image.is_value_type(owner) => {
+                    Element::ByRef(Box::new(Element::ValueType(TypeDefOrRef::TypeDef(owner.0))))
+                }
+                Some(owner) => Element::Class(TypeDefOrRef::TypeDef(owner.0)),
+                None => Element::Object,
+            };
+            arguments.push(this);
+        }
+        arguments.extend(signature.parameters.iter().cloned());
+
+        let locals = match header.local_var_sig_token {
+            Some(token) => {
+                let handle = StandAloneSigHandle::try_from(token).map_err(Error::InvalidToken)?;
+                image.local_var_signature(handle)?.locals
+            }
+            None => Vec::new(),
+        };
+
+        let body = Body {
+            signature: &signature,
+            arguments,
+            locals,
+        };
+        self.verify_body(
+            &body,
+            header.max_stack,
+            instructions,
+            &header.exception_clauses,
+        )
+    }
+
+    fn verify_body(
+        &self,
+        body: &Body,
+        max_stack: u16,
+        instructions: &[Instruction],
+        exception_clauses: &[ExceptionClause],
+    ) -> Result<StackAnalysis> {
+        let index_of = |offset: u32| {
+            instructions
+                .binary_search_by_key(&offset, |i| i.offset)
+                .map_err(|_| Error::InvalidBranchTarget(offset))
+        };
+
+        let mut states: Vec<Option<Vec<StackType>>> = vec![None; instructions.len()];
+        let mut worklist = Vec::new();
+        let mut seed = |index: usize, stack: Vec<StackType>| {
+            if states[index].is_none() {
+                states[index] = Some(stack);
+                worklist.push(index);
+            }
+        };
+        if !instructions.is_empty() {
+            seed(0, Vec::new());
+        }
+        for clause in exception_clauses {
+            // Catch and filter blocks start with the exception object on the stack
+            match clause.kind {
+                ExceptionClauseKind::Catch(_) => {
+                    seed(index_of(clause.handler_offset)?, vec![StackType::Object])
+                }
+                ExceptionClauseKind::Filter(offset) => {
+                    seed(index_of(offset)?, vec![StackType::Object]);
+                    seed(index_of(clause.handler_offset)?, vec![StackType::Object]);
+                }
+                ExceptionClauseKind::Finally | ExceptionClauseKind::Fault => {
+                    seed(index_of(clause.handler_offset)?, Vec::new())
+                }
+            }
+        }
+
+        // Merge errors are kept per instruction, so that each join is reported once
+        let mut merge_errors = BTreeMap::new();
+        let mut ignored = Vec::new();
+        while let Some(index) = worklist.pop() {
+            let instruction = &instructions[index];
+            let mut stack = states[index].clone().unwrap();
+            self.execute(body, instruction, &mut stack, &mut ignored)?;
+            ignored.clear();
+
+            for target in successors(instruction, instructions.len(), index, &index_of)? {
+                let Some(target) = target else {
+                    continue;
+                };
+                let Some(existing) = &mut states[target] else {
+                    states[target] = Some(stack.clone());
+                    worklist.push(target);
+                    continue;
+                };
+
+                let mut error = |kind| {
+                    merge_errors.entry(target).or_insert(VerificationError {
+                        offset: instructions[target].offset,
+                        kind,
+                    });
+                };
+                if existing.len() != stack.len() {
+                    error(VerificationErrorKind::DepthMismatch {
+                        expected: existing.len(),
+                        found: stack.len(),
+                    });
+                    continue;
+                }
+                let mut changed = false;
+                for (slot, (existing, incoming)) in existing.iter_mut().zip(&stack).enumerate() {
+                    match existing.merge(incoming) {
+                        Some(merged) => {
+                            if merged != *existing {
+                                *existing = merged;
+                                changed = true;
+                            }
+                        }
+                        None => error(VerificationErrorKind::InconsistentMerge {
+                            slot,
+                            expected: existing.clone(),
+                            found: incoming.clone(),
+                        }),
+                    }
+                }
+                if changed {
+                    worklist.push(target);
+                }
+            }
+        }
+
+        // Run every reachable instruction once more on its final stack to report its errors
+        let mut errors = merge_errors.into_values().collect::<Vec<_>>();
+        let mut max_depth = 0;
+        let mut pushed = vec![Vec::new(); instructions.len()];
+        for (index, instruction) in instructions.iter().enumerate() {
+            let Some(mut stack) = states[index].clone() else {
+                continue;
+            };
+            max_depth = max_depth.max(stack.len());
+            self.execute(body, instruction, &mut stack, &mut errors)?;
+            let pushes = instruction.stack_effect(self.image(), body.signature)?.pushes as usize;
+            pushed[index] = stack[stack.len() - pushes.min(stack.len())..].to_vec();
+            if stack.len() > max_depth {
+                max_depth = stack.len();
+                if max_depth > max_stack as usize
+                    && !errors
+                        .iter()
+                        .any(|e| matches!(e.kind, VerificationErrorKind::MaxStackExceeded { .. }))
+                {
+                    errors.push(VerificationError {
+                        offset: instruction.offset,
+                        kind: VerificationErrorKind::MaxStackExceeded {
+                            depth: stack.len(),
+                            max_stack,
+                        },
+                    });
+                }
+            }
+            if successors(instruction, instructions.len(), index, &index_of)?.contains(&None) {
+                errors.push(VerificationError {
+                    offset: instruction.offset,
+                    kind: VerificationErrorKind::FallsOffEnd,
+                });
+            }
+        }
+
+        let mut try_offsets = exception_clauses
+            .iter()
+            .map(|c| c.try_offset)
+            .collect::<Vec<_>>();
+        try_offsets.sort();
+        try_offsets.dedup();
+        for offset in try_offsets {
+            if states[index_of(offset)?]
+                .as_ref()
+                .is_some_and(|s| !s.is_empty())
+            {
+                errors.push(VerificationError {
+                    offset,
+                    kind: VerificationErrorKind::TryEntryWithNonEmptyStack,
+                });
+            }
+        }
+
+        errors.sort_by_key(|e| e.offset);
+        Ok(StackAnalysis {
+            states,
+            pushed,
+            arguments: body.arguments.clone(),
+            locals: body.locals.clone(),
+            max_depth,
+            errors,
+        })
+    }
+
+    /// Applies an instruction to `stack`, reporting the problems it finds to `errors`
+    fn execute(
+        &self,
+        body: &Body,
+        instruction: &Instruction,
+        stack: &mut Vec<StackType>,
+        errors: &mut Vec<VerificationError>,
+    ) -> Result<()> {
+        use StackType::*;
+
+        let mut check = Checker {
+            offset: instruction.offset,
+            errors,
+        };
+        let effect = instruction.stack_effect(self.image(), body.signature)?;
+        if stack.len() < effect.pops as usize {
+            check.error(VerificationErrorKind::StackUnderflow {
+                needed: effect.pops,
+                available: stack.len(),
+            });
+            let missing = effect.pops as usize - stack.len();
+            stack.splice(0..0, std::iter::repeat_n(Unknown, missing));
+        }
+        let popped = stack.split_off(stack.len() - effect.pops as usize);
+
+        let pushed = match &instruction.opcode {
+            RawOpcode::Nop {}
+            | RawOpcode::Break {}
+            | RawOpcode::Pop {}
+            | RawOpcode::Jmp { .. }
+            | RawOpcode::Rethrow {}
+            | RawOpcode::Br { .. }
+            | RawOpcode::Br_S { .. } => vec![],
+
+            RawOpcode::LdArg_0 {}
+            | RawOpcode::LdArg_1 {}
+            | RawOpcode::LdArg_2 {}
+            | RawOpcode::LdArg_3 {}
+            | RawOpcode::LdArg_S { .. }
+            | RawOpcode::LdArg { .. }
+            | RawOpcode::LdArgA_S { .. }
+            | RawOpcode::LdArgA { .. }
+            | RawOpcode::StArg_S { .. }
+            | RawOpcode::StArg { .. } => {
+                let Operand::Argument(index) = instruction.operand else {
+                    unreachable!()
+                };
+                let Some(argument) = body.arguments.get(index as usize) else {
+                    check.error(VerificationErrorKind::InvalidArgument(index));
+                    stack.extend((effect.pushes > 0).then_some(Unknown));
+                    return Ok(());
+                };
+                match instruction.opcode {
+                    RawOpcode::LdArgA_S { .. } | RawOpcode::LdArgA { .. } => vec![ByRef],
+                    RawOpcode::StArg_S { .. } | RawOpcode::StArg { .. } => {
+                        if !self.is_assignable(&popped[0], argument) {
+                            check.expect(&popped[0], false, "a value of the argument's type");
+                        }
+                        vec![]
+                    }
+                    _ => vec![self.stack_type(argument)],
+                }
+            }
+
+            RawOpcode::LdLoc_0 {}
+            | RawOpcode::LdLoc_1 {}
+            | RawOpcode::LdLoc_2 {}
+            | RawOpcode::LdLoc_3 {}
+            | RawOpcode::LdLoc_S { .. }
+            | RawOpcode::LdLoc { .. }
+            | RawOpcode::LdLocA_S { .. }
+            | RawOpcode::LdLocA { .. }
+            | RawOpcode::StLoc_0 {}
+            | RawOpcode::StLoc_1 {}
+            | RawOpcode::StLoc_2 {}
+            | RawOpcode::StLoc_3 {}
+            | RawOpcode::StLoc_S { .. }
+            | RawOpcode::StLoc { .. } => {
+                let Operand::Local(index) = instruction.operand else {
+                    unreachable!()
+                };
+                let Some(local) = body.locals.get(index as usize) else {
+                    check.error(VerificationErrorKind::InvalidLocal(index));
+                    stack.extend((effect.pushes > 0).then_some(Unknown));
+                    return Ok(());
+                };
+                match instruction.opcode {
+                    RawOpcode::LdLocA_S { .. } | RawOpcode::LdLocA { .. } => vec![ByRef],
+                    RawOpcode::LdLoc_0 {}
+                    | RawOpcode::LdLoc_1 {}
+                    | RawOpcode::LdLoc_2 {}
+                    | RawOpcode::LdLoc_3 {}
+                    | RawOpcode::LdLoc_S { .. }
+                    | RawOpcode::LdLoc { .. } => vec![self.stack_type(local)],
+                    _ => {
+                        if !self.is_assignable(&popped[0], local) {
+                            check.expect(&popped[0], false, "a value of the local's type");
+                        }
+                        vec![]
+                    }
+                }
+            }
+
+            RawOpcode::LdNull {} | RawOpcode::LdStr { .. } => vec![Object],
+            RawOpcode::Ldc_I4_M1 {}
+            | RawOpcode::Ldc_I4_0 {}
+            | RawOpcode::Ldc_I4_1 {}
+            | RawOpcode::Ldc_I4_2 {}
+            | RawOpcode::Ldc_I4_3 {}
+            | RawOpcode::Ldc_I4_4 {}
+            | RawOpcode::Ldc_I4_5 {}
+            | RawOpcode::Ldc_I4_6 {}
+            | RawOpcode::Ldc_I4_7 {}
+            | RawOpcode::Ldc_I4_8 {}
+            | RawOpcode::Ldc_I4_S { .. }
+            | RawOpcode::Ldc_I4 { .. }
+            | RawOpcode::SizeOf { .. } => vec![Int32],
+            RawOpcode::Ldc_I8 { .. } => vec![Int64],
+            RawOpcode::Ldc_R4 { .. } | RawOpcode::Ldc_R8 { .. } => vec![Float],
+            RawOpcode::Dup {} => vec![popped[0].clone(), popped[0].clone()],
+
+            RawOpcode::Call { method } | RawOpcode::CallVirt { method } => {
+                let (signature, _) = self.callee(*method)?;
+                self.check_arguments(&mut check, &signature, &popped);
+                self.return_value(&signature)
+            }
+            RawOpcode::CallInd { callsitedescr } => {
+                let handle =
+                    StandAloneSigHandle::try_from(*callsitedescr).map_err(Error::InvalidToken)?;
+                let signature = self.image().call_site_signature(handle)?;
+                let (arguments, pointer) = popped.split_at(popped.len() - 1);
+                check.expect(&pointer[0], pointer[0] == NativeInt, "native int");
+                self.check_arguments(&mut check, &signature, arguments);
+                self.return_value(&signature)
+            }
+            RawOpcode::NewObj { ctor } => {
+                let (signature, owner) = self.callee(*ctor)?;
+                for (value, parameter) in popped.iter().zip(&signature.parameters) {
+                    if !self.is_assignable(value, parameter) {
+                        check.expect(value, false, "a value of the parameter's type");
+                    }
+                }
+                vec![owner.map_or(Unknown, |owner| self.stack_type(&owner))]
+            }
+            RawOpcode::Ret {} => {
+                if let Some(value) = popped.first()
+                    && !self.is_assignable(value, &body.signature.return_type)
+                {
+                    check.expect(value, false, "a value of the return type");
+                }
+                if !stack.is_empty() {
+                    check.error(VerificationErrorKind::NonEmptyStack("ret"));
+                }
+                vec![]
+            }
+
+            RawOpcode::Br_False_S { .. }
+            | RawOpcode::Br_True_S { .. }
+            | RawOpcode::Br_False { .. }
+            | RawOpcode::Br_True { .. } => {
+                let value = &popped[0];
+                check.expect(
+                    value,
+                    value.is_integer() || matches!(value, Object | ByRef),
+                    "an integer, O or &",
+                );
+                vec![]
+            }
+            RawOpcode::Beq_S { .. }
+            | RawOpcode::Beq { .. }
+            | RawOpcode::Bne_Un_S { .. }
+            | RawOpcode::Bne_Un { .. }
+            | RawOpcode::Bgt_Un_S { .. }
+            | RawOpcode::Bgt_Un { .. }
+            | RawOpcode::Ceq {}
+            | RawOpcode::Cgt_Un {} => {
+                check_comparison(&mut check, &popped[0], &popped[1], true);
+                self.comparison_result(instruction)
+            }
+            RawOpcode::Bge_S { .. }
+            | RawOpcode::Bgt_S { .. }
+            | RawOpcode::Ble_S { .. }
+            | RawOpcode::Blt_S { .. }
+            | RawOpcode::Bge_Un_S { .. }
+            | RawOpcode::Ble_Un_S { .. }
+            | RawOpcode::Blt_Un_S { .. }
+            | RawOpcode::Bge { .. }
+            | RawOpcode::Bgt { .. }
+            | RawOpcode::Ble { .. }
+            | RawOpcode::Blt { .. }
+            | RawOpcode::Bge_Un { .. }
+            | RawOpcode::Ble_Un { .. }
+            | RawOpcode::Blt_Un { .. }
+            | RawOpcode::Cgt {}
+            | RawOpcode::Clt {}
+            | RawOpcode::Clt_Un {} => {
+                check_comparison(&mut check, &popped[0], &popped[1], false);
+                self.comparison_result(instruction)
+            }
+            RawOpcode::Switch { .. } => {
+                check.expect_integer(&popped[0]);
+                vec![]
+            }
+
+            RawOpcode::Add {}
+            | RawOpcode::Sub {}
+            | RawOpcode::Mul {}
+            | RawOpcode::Div {}
+            | RawOpcode::Rem {}
+            | RawOpcode::DivUnsigned {}
+            | RawOpcode::RemUnsigned {}
+            | RawOpcode::And {}
+            | RawOpcode::Or {}
+            | RawOpcode::Xor {}
+            | RawOpcode::Add_Ovf {}
+            | RawOpcode::Add_Ovf_Unsigned {}
+            | RawOpcode::Sub_Ovf {}
+            | RawOpcode::Sub_Ovf_Unsigned {}
+            | RawOpcode::Mul_Ovf {}
+            | RawOpcode::Mul_Ovf_Unsigned {} => {
+                let (a, b) = (&popped[0], &popped[1]);
+                match arithmetic(&instruction.opcode, a, b) {
+                    Some(result) => vec![result],
+                    None => {
+                        let found = if matches!(a, Int32 | Int64 | NativeInt | Float) {
+                            b
+                        } else {
+                            a
+                        };
+                        check.expect(found, false, "operands of matching numeric types");
+                        vec![Unknown]
+                    }
+                }
+            }
+            RawOpcode::Shl {} | RawOpcode::Shr {} | RawOpcode::ShrUnsigned {} => {
+                let (value, amount) = (&popped[0], &popped[1]);
+                check.expect(value, value.is_integer(), "int32, int64 or native int");
+                check.expect_integer(amount);
+                vec![value.clone()]
+            }
+            RawOpcode::Neg {} => {
+                check.expect(&popped[0], popped[0].is_numeric(), "a number");
+                vec![popped[0].clone()]
+            }
+            RawOpcode::Not {} => {
+                check.expect(
+                    &popped[0],
+                    popped[0].is_integer(),
+                    "int32, int64 or native int",
+                );
+                vec![popped[0].clone()]
+            }
+            RawOpcode::CkFinite {} => {
+                check.expect(&popped[0], popped[0] == Float, "F");
+                vec![Float]
+            }
+
+            opcode @ (RawOpcode::Conv_I1 {}
+            | RawOpcode::Conv_I2 {}
+            | RawOpcode::Conv_I4 {}
+            | RawOpcode::Conv_I8 {}
+            | RawOpcode::Conv_R4 {}
+            | RawOpcode::Conv_R8 {}
+            | RawOpcode::Conv_U4 {}
+            | RawOpcode::Conv_U8 {}
+            | RawOpcode::Conv_R_Un {}
+            | RawOpcode::Conv_U2 {}
+            | RawOpcode::Conv_U1 {}
+            | RawOpcode::Conv_I {}
+            | RawOpcode::Conv_U {}
+            | RawOpcode::Conv_Ovf_I1 {}
+            | RawOpcode::Conv_Ovf_U1 {}
+            | RawOpcode::Conv_Ovf_I2 {}
+            | RawOpcode::Conv_Ovf_U2 {}
+            | RawOpcode::Conv_Ovf_I4 {}
+            | RawOpcode::Conv_Ovf_U4 {}
+            | RawOpcode::Conv_Ovf_I8 {}
+            | RawOpcode::Conv_Ovf_U8 {}
+            | RawOpcode::Conv_Ovf_I {}
+            | RawOpcode::Conv_Ovf_U {}
+            | RawOpcode::Conv_Ovf_I1_Unsigned {}
+            | RawOpcode::Conv_Ovf_I2_Unsigned {}
+            | RawOpcode::Conv_Ovf_I4_Unsigned {}
+            | RawOpcode::Conv_Ovf_I8_Unsigned {}
+            | RawOpcode::Conv_Ovf_U1_Unsigned {}
+            | RawOpcode::Conv_Ovf_U2_Unsigned {}
+            | RawOpcode::Conv_Ovf_U4_Unsigned {}
+            | RawOpcode::Conv_Ovf_U8_Unsigned {}
+            | RawOpcode::Conv_Ovf_I_Unsigned {}
+            | RawOpcode::Conv_Ovf_U_Unsigned {}) => {
+                let value = &popped[0];
+                let result = conversion_result(opcode);
+                // Pinned references are turned into pointers with conv.i and conv.u
+                let from_reference = result == NativeInt && matches!(value, ByRef | Object);
+                check.expect(value, value.is_numeric() || from_reference, "a number");
+                vec![result]
+            }
+
+            RawOpcode::LdInd_I1 {}
+            | RawOpcode::LdInd_U1 {}
+            | RawOpcode::LdInd_I2 {}
+            | RawOpcode::LdInd_U2 {}
+            | RawOpcode::LdInd_I4 {}
+            | RawOpcode::LdInd_U4 {}
+            | RawOpcode::LdInd_I8 {}
+            | RawOpcode::LdInd_I {}
+            | RawOpcode::LdInd_R4 {}
+            | RawOpcode::LdInd_R8 {}
+            | RawOpcode::LdInd_Ref {} => {
+                check.expect_address(&popped[0]);
+                vec![element_access_type(&instruction.opcode)]
+            }
+            RawOpcode::StInd_Ref {}
+            | RawOpcode::StInd_I1 {}
+            | RawOpcode::StInd_I2 {}
+            | RawOpcode::StInd_I4 {}
+            | RawOpcode::StInd_I8 {}
+            | RawOpcode::StInd_R4 {}
+            | RawOpcode::StInd_R8 {}
+            | RawOpcode::StInd_I {} => {
+                check.expect_address(&popped[0]);
+                check_element_value(&mut check, &instruction.opcode, &popped[1]);
+                vec![]
+            }
+            RawOpcode::LdObj { typeref } => {
+                check.expect_address(&popped[0]);
+                vec![self.token_stack_type(*typeref)?]
+            }
+            RawOpcode::StObj { typeref } => {
+                check.expect_address(&popped[0]);
+                if let Some(element) = self.type_token(*typeref)?
+                    && !self.is_assignable(&popped[1], &element)
+                {
+                    check.expect(&popped[1], false, "a value of the operand type");
+                }
+                vec![]
+            }
+            RawOpcode::CpObj { .. } => {
+                check.expect_address(&popped[0]);
+                check.expect_address(&popped[1]);
+                vec![]
+            }
+            RawOpcode::InitObj { .. } => {
+                check.expect_address(&popped[0]);
+                vec![]
+            }
+            RawOpcode::CpBlk {} | RawOpcode::InitBlk {} => {
+                check.expect_address(&popped[0]);
+                check.expect_integer(&popped[2]);
+                vec![]
+            }
+            RawOpcode::LocAlloc {} => {
+                check.expect_integer(&popped[0]);
+                vec![NativeInt]
+            }
+
+            RawOpcode::LdFld { field } => {
+                let object = &popped[0];
+                check.expect(
+                    object,
+                    matches!(object, Object | ByRef | NativeInt | ValueType(_)),
+                    "O, &, native int or a value type",
+                );
+                vec![self.stack_type(&self.field_type(*field)?)]
+            }
+            RawOpcode::LdFlda { .. } => {
+                check.expect(
+                    &popped[0],
+                    matches!(popped[0], Object | ByRef | NativeInt),
+                    "O, & or native int",
+                );
+                vec![ByRef]
+            }
+            RawOpcode::SetFld { field } => {
+                check.expect(
+                    &popped[0],
+                    matches!(popped[0], Object | ByRef | NativeInt),
+                    "O, & or native int",
+                );
+                if !self.is_assignable(&popped[1], &self.field_type(*field)?) {
+                    check.expect(&popped[1], false, "a value of the field's type");
+                }
+                vec![]
+            }
+            RawOpcode::LdsFld { field } => vec![self.stack_type(&self.field_type(*field)?)],
+            RawOpcode::LdsFlda { .. } => vec![ByRef],
+            RawOpcode::StsFld { field } => {
+                if !self.is_assignable(&popped[0], &self.field_type(*field)?) {
+                    check.expect(&popped[0], false, "a value of the field's type");
+                }
+                vec![]
+            }
+
+            RawOpcode::NewArr { .. } => {
+                check.expect_integer(&popped[0]);
+                vec![Object]
+            }
+            RawOpcode::LdLen {} => {
+                check.expect_object(&popped[0]);
+                vec![NativeInt]
+            }
+            RawOpcode::LdElema { .. } => {
+                check.expect_object(&popped[0]);
+                check.expect_integer(&popped[1]);
+                vec![ByRef]
+            }
+            RawOpcode::LdElem_Any { typeref } => {
+                check.expect_object(&popped[0]);
+                check.expect_integer(&popped[1]);
+                vec![self.token_stack_type(*typeref)?]
+            }
+            RawOpcode::LdElem_I1 {}
+            | RawOpcode::LdElem_U1 {}
+            | RawOpcode::LdElem_I2 {}
+            | RawOpcode::LdElem_U2 {}
+            | RawOpcode::LdElem_I4 {}
+            | RawOpcode::LdElem_U4 {}
+            | RawOpcode::LdElem_I8 {}
+            | RawOpcode::LdElem_I {}
+            | RawOpcode::LdElem_R4 {}
+            | RawOpcode::LdElem_R8 {}
+            | RawOpcode::LdElem_Ref {} => {
+                check.expect_object(&popped[0]);
+                check.expect_integer(&popped[1]);
+                vec![element_access_type(&instruction.opcode)]
+            }
+            RawOpcode::StElem_Any { typeref } => {
+                check.expect_object(&popped[0]);
+                check.expect_integer(&popped[1]);
+                if let Some(element) = self.type_token(*typeref)?
+                    && !self.is_assignable(&popped[2], &element)
+                {
+                    check.expect(&popped[2], false, "a value of the element type");
+                }
+                vec![]
+            }
+            RawOpcode::StElem_I {}
+            | RawOpcode::StElem_I1 {}
+            | RawOpcode::StElem_I2 {}
+            | RawOpcode::StElem_I4 {}
+            | RawOpcode::StElem_I8 {}
+            | RawOpcode::StElem_R4 {}
+            | RawOpcode::StElem_R8 {}
+            | RawOpcode::StElem_Ref {} => {
+                check.expect_object(&popped[0]);
+                check.expect_integer(&popped[1]);
+                check_element_value(&mut check, &instruction.opcode, &popped[2]);
+                vec![]
+            }
+
+            RawOpcode::Box { .. } => vec![Object],
+            RawOpcode::Unbox { .. } => {
+                check.expect_object(&popped[0]);
+                vec![ByRef]
+            }
+            RawOpcode::Unbox_Any { typeref } => {
+                check.expect_object(&popped[0]);
+                vec![self.token_stack_type(*typeref)?]
+            }
+            RawOpcode::CastClass { .. } | RawOpcode::IsInst { .. } => {
+                check.expect_object(&popped[0]);
+                vec![Object]
+            }
+            RawOpcode::Throw {} => {
+                check.expect_object(&popped[0]);
+                vec![]
+            }
+
+            RawOpcode::LdFtn { .. } => vec![NativeInt],
+            RawOpcode::LdVirtFtn { .. } => {
+                check.expect_object(&popped[0]);
+                vec![NativeInt]
+            }
+            RawOpcode::MkRefAny { .. } => {
+                check.expect_address(&popped[0]);
+                vec![ValueType(Element::TypedByRef)]
+            }
+            RawOpcode::RefAnyVal { .. } => vec![ByRef],
+            // Runtime handles are value types from the core library
+            RawOpcode::LdToken { .. } | RawOpcode::RefAnyType {} | RawOpcode::ArgList {} => {
+                vec![Unknown]
+            }
+
+            RawOpcode::Leave { .. } | RawOpcode::Leave_S { .. } => {
+                if !stack.is_empty() {
+                    check.error(VerificationErrorKind::NonEmptyStack("leave"));
+                }
+                stack.clear();
+                vec![]
+            }
+            RawOpcode::EndFaultOrFinally {} => {
+                stack.clear();
+                vec![]
+            }
+            RawOpcode::EndFilter {} => {
+                check.expect(&popped[0], popped[0] == Int32, "int32");
+                if !stack.is_empty() {
+                    check.error(VerificationErrorKind::NonEmptyStack("endfilter"));
+                }
+                vec![]
+            }
+
+            _ => (0..effect.pushes).map(|_| Unknown).collect(),
+        };
+        stack.extend(pushed);
+        Ok(())
+    }
+
+    /// Checks the arguments of a call, starting with `this` for instance methods
+    fn check_arguments(
+        &self,
+        check: &mut Checker,
+        signature: &StandaloneMethodSignature,
+        arguments: &[StackType],
+    ) {
+        let has_this = signature.header.has_this() && !signature.header.explicit_this();
+        let mut arguments = arguments.iter();
+        if has_this && let Some(this) = arguments.next() {
+            check.expect(
+                this,
+                matches!(
+                    this,
+                    StackType::Object | StackType::ByRef | StackType::NativeInt
+                ),
+                "O, & or native int",
+            );
+        }
+        for (value, parameter) in arguments.zip(&signature.parameters) {
+            if !self.is_assignable(value, parameter) {
+                check.expect(value, false, "a value of the parameter's type");
+            }
+        }
+    }
+
+    fn return_value(&self, signature: &StandaloneMethodSignature) -> Vec<StackType> {
+        match signature.return_type.strip_modifiers() {
+            Element::Void => vec![],
+            return_type => vec![self.stack_type(return_type)],
+        }
+    }
+
+    fn comparison_result(&self, instruction: &Instruction) -> Vec<StackType> {
+        match instruction.opcode.flow_control() {
+            FlowControl::CondBranch => vec![],
+            _ => vec![StackType::Int32],
+        }
+    }
+}
+
+/// Underlying type of an enum, `None` if the type definition isn't an enum
+fn enum_underlying_type(image: &CilImage, handle: TypeDefHandle) -> Option<Element> {
+    let type_def = image.type_defs.get(handle.index())?;
+    if type_def.extends == 0 {
+        return None;
+    }
+    let base = TypeDefOrRef::try_from(type_def.extends as u32)
+        .ok()?
+        .typename(image)?;
+    if base.namespace != "System" || base.name != "Enum" {
+        return None;
+    }
+    // The only instance field of an enum holds its value
+    let field = image
+        .fields_of(handle)
+        .find(|f: &FieldHandle| !image.fields[f.index()].flags.is_static())?;
+    image.field_type(field).ok()
+}
+
+/// Indices of the instructions control can continue with after `instruction`. `None` stands for
+/// falling through the end of the body.
+fn successors(
+    instruction: &Instruction,
+    count: usize,
+    index: usize,
+    index_of: &impl Fn(u32) -> Result<usize>,
+) -> Result<Vec<Option<usize>>> {
+    let mut successors = Vec::new();
+    if matches!(instruction.opcode, RawOpcode::Jmp { .. }) {
+        return Ok(successors);
+    }
+    for &target in instruction.branch_targets() {
+        successors.push(Some(index_of(target)?));
+    }
+    if !instruction.opcode.ends_block()
+        || instruction.opcode.flow_control() == FlowControl::CondBranch
+    {
+        successors.push((index + 1 < count).then_some(index + 1));
+    }
+    Ok(successors)
+}
+
+/// Result of a binary arithmetic or bitwise operation (ECMA-335 III.1.5), `None` if the operands
+/// can't be combined
+fn arithmetic(opcode: &RawOpcode, a: &StackType, b: &StackType) -> Option<StackType> {
+    use StackType::*;
+
+    // Whether the opcode takes floats, adds to a pointer or subtracts from one
+    let (floats, add, sub) = match opcode {
+        RawOpcode::Add {} => (true, true, false),
+        RawOpcode::Sub {} => (true, false, true),
+        RawOpcode::Mul {} | RawOpcode::Div {} | RawOpcode::Rem {} => (true, false, false),
+        RawOpcode::Add_Ovf {} | RawOpcode::Add_Ovf_Unsigned {} => (false, true, false),
+        RawOpcode::Sub_Ovf {} | RawOpcode::Sub_Ovf_Unsigned {} => (false, false, true),
+        _ => (false, false, false),
+    };
+    Some(match (a, b) {
+        (Unknown, other) | (other, Unknown) => match other {
+            Int32 | Int64 | NativeInt | Float | ByRef => other.clone(),
+            _ => Unknown,
+        },
+        (Int32, Int32) => Int32,
+        (Int32 | NativeInt, Int32 | NativeInt) => NativeInt,
+        (Int64, Int64) => Int64,
+        (Float, Float) if floats => Float,
+        (ByRef, Int32 | NativeInt) if add || sub => ByRef,
+        (Int32 | NativeInt, ByRef) if add => ByRef,
+        (ByRef, ByRef) if sub => NativeInt,
+        _ => return None,
+    })
+}
+
+/// Checks the operands of a comparison (ECMA-335 III.1.5). Object references and mixed pointers
+/// can only be compared for equality, which includes `cgt.un` as used for `!= null`.
+fn check_comparison(check: &mut Checker, a: &StackType, b: &StackType, equality: bool) {
+    use StackType::*;
+
+    let ok = match (a, b) {
+        (Unknown, _) | (_, Unknown) => true,
+        (Int32 | NativeInt, Int32 | NativeInt) => true,
+        (Int64, Int64) | (Float, Float) | (ByRef, ByRef) => true,
+        (NativeInt, ByRef) | (ByRef, NativeInt) | (Object, Object) => equality,
+        _ => false,
+    };
+    if !ok {
+        let found = if matches!(a, Int32 | Int64 | NativeInt | Float) {
+            b
+        } else {
+            a
+        };
+        check.expect(found, false, "comparable operands");
+    }
+}
+
+fn conversion_result(opcode: &RawOpcode) -> StackType {
+    match opcode {
+        RawOpcode::Conv_I8 {}
+        | RawOpcode::Conv_U8 {}
+        | RawOpcode::Conv_Ovf_I8 {}
+        | RawOpcode::Conv_Ovf_U8 {}
+        | RawOpcode::Conv_Ovf_I8_Unsigned {}
+        | RawOpcode::Conv_Ovf_U8_Unsigned {} => StackType::Int64,
+        RawOpcode::Conv_R4 {} | RawOpcode::Conv_R8 {} | RawOpcode::Conv_R_Un {} => StackType::Float,
+        RawOpcode::Conv_I {}
+        | RawOpcode::Conv_U {}
+        | RawOpcode::Conv_Ovf_I {}
+        | RawOpcode::Conv_Ovf_U {}
+        | RawOpcode::Conv_Ovf_I_Unsigned {}
+        | RawOpcode::Conv_Ovf_U_Unsigned {} => StackType::NativeInt,
+        _ => StackType::Int32,
+    }
+}
+
+/// Type loaded or stored by the typed `ldind`, `stind`, `ldelem` and `stelem` variants
+fn element_access_type(opcode: &RawOpcode) -> StackType {
+    match opcode {
+        RawOpcode::LdInd_I8 {}
+        | RawOpcode::StInd_I8 {}
+        | RawOpcode::LdElem_I8 {}
+        | RawOpcode::StElem_I8 {} => StackType::Int64,
+        RawOpcode::LdInd_I {}
+        | RawOpcode::StInd_I {}
+        | RawOpcode::LdElem_I {}
+        | RawOpcode::StElem_I {} => StackType::NativeInt,
+        RawOpcode::LdInd_R4 {}
+        | RawOpcode::LdInd_R8 {}
+        | RawOpcode::StInd_R4 {}
+        | RawOpcode::StInd_R8 {}
+        | RawOpcode::LdElem_R4 {}
+        | RawOpcode::LdElem_R8 {}
+        | RawOpcode::StElem_R4 {}
+        | RawOpcode::StElem_R8 {} => StackType::Float,
+        RawOpcode::LdInd_Ref {}
+        | RawOpcode::StInd_Ref {}
+        | RawOpcode::LdElem_Ref {}
+        | RawOpcode::StElem_Ref {} => StackType::Object,
+        _ => StackType::Int32,
+    }
+}
+
+fn check_element_value(check: &mut Checker, opcode: &RawOpcode, value: &StackType) {
+    use StackType::*;
+
+    let ok = match (element_access_type(opcode), value) {
+        (Int32 | NativeInt, Int32 | NativeInt) => true,
+        (expected, value) => expected == *value,
+    };
+    if !ok {
+        check.expect(value, false, "a value of the element type");
+    }
+}
@@ -0,0 +1,522 @@
+//! Virtual method and interface slot resolution (ECMA-335 II.10.3 and II.12.2)
+//!
+//! Slots are laid out the way the runtime does it: a virtual method marked `ReuseSlot` takes over the
+//! slot of an inherited virtual method with the same name and signature, a `NewSlot` method (or one
+//! that doesn't match anything) starts a new slot, and MethodImpl rows override slots explicitly.
+
+use crate::{
+    Result,
+    hierarchy::{ScopedType, TypeHierarchy},
+    image::CilImage,
+    meta::{MemberRefHandle, MethodDefHandle, TypeDefHandle, TypeSpecHandle},
+    signature::{
+        Element, MemberRefSignature, StandaloneMethodSigHeader, StandaloneMethodSignature,
+    },
+    tables::{
+        ClassSemantics, MemberAccess, MemberRefParent, MethodAttributes, MethodDefOrRef,
+        TypeDefOrRef, VtableLayout,
+    },
+};
+
+/// A method definition and the image it belongs to
+pub type ResolvedMethodDef<'a> = (&'a CilImage, MethodDefHandle);
+
+/// How deep base type chains are followed, in case an image has circular inheritance
+const MAX_HIERARCHY_DEPTH: usize = 256;
+
+#[derive(Clone)]
+pub struct VtableSlot<'a> {
+    /// Virtual method that introduced the slot
+    pub declaration: ResolvedMethodDef<'a>,
+    /// Method that is called through this slot, may be abstract
+    pub implementation: ResolvedMethodDef<'a>,
+    /// Methods that implemented the slot before `implementation` overrode them, starting with the
+    /// declaration. Empty if nothing overrides the declaration.
+    pub overridden: Vec<ResolvedMethodDef<'a>>,
+}
+
+impl VtableSlot<'_> {
+    /// Whether `method` is or was the implementation of this slot
+    pub fn contains(&self, method: ResolvedMethodDef<'_>) -> bool {
+        same_method(self.declaration, method)
+            || same_method(self.implementation, method)
+            || self.overridden.iter().any(|&m| same_method(m, method))
+    }
+}
+
+#[derive(Clone)]
+pub struct InterfaceSlot<'a> {
+    pub interface: ScopedType<'a>,
+    pub declaration: ResolvedMethodDef<'a>,
+    /// `None` if the type doesn't implement the method, which is only valid for abstract types
+    pub implementation: Option<ResolvedMethodDef<'a>>,
+}
+
+/// Virtual and interface method slots of a type
+#[derive(Clone)]
+pub struct MethodTable<'a> {
+    /// Slots in the order they were introduced, starting with the ones of the root base type
+    pub slots: Vec<VtableSlot<'a>>,
+    pub interface_slots: Vec<InterfaceSlot<'a>>,
+}
+
+impl<'a> MethodTable<'a> {
+    /// Slot that `method`, a virtual method of the type or one of its base types, occupies
+    pub fn slot_of(&self, method: ResolvedMethodDef<'_>) -> Option<&VtableSlot<'a>> {
+        self.slots.iter().find(|s| s.contains(method))
+    }
+}
+
+/// A method signature with the type parameters of its owner replaced by arguments
+struct InstantiatedSignature<'a> {
+    header: StandaloneMethodSigHeader,
+    generic_param_count: u32,
+    return_type: ScopedType<'a>,
+    parameters: Vec<ScopedType<'a>>,
+}
+
+/// A virtual method, with its signature instantiated for the type whose method table it's in
+struct SlotMethod<'a> {
+    method: ResolvedMethodDef<'a>,
+    name: &'a str,
+    flags: MethodAttributes,
+    signature: InstantiatedSignature<'a>,
+}
+
+struct Slot<'a> {
+    declaration: SlotMethod<'a>,
+    implementation: SlotMethod<'a>,
+    overridden: Vec<ResolvedMethodDef<'a>>,
+}
+
+impl<'a> TypeHierarchy<'a> {
+    /// Virtual and interface method slots of a type definition
+    pub fn method_table(&self, handle: TypeDefHandle) -> Result<MethodTable<'a>> {
+        let ty = self.type_def(self.image, handle);
+        Ok(self.method_table_of(&ty)?.unwrap_or(MethodTable {
+            slots: vec![],
+            interface_slots: vec![],
+        }))
+    }
+
+    /// Method table of a type from any image, `None` if its definition can't be resolved
+    pub fn method_table_of(&self, ty: &ScopedType<'a>) -> Result<Option<MethodTable<'a>>> {
+        let Some(slots) = self.vtable_slots(ty, 0)? else {
+            return Ok(None);
+        };
+        let interface_slots = self.interface_slots(ty, &slots)?;
+
+        Ok(Some(MethodTable {
+            slots: slots
+                .into_iter()
+                .map(|s| VtableSlot {
+                    declaration: s.declaration.method,
+                    implementation: s.implementation.method,
+                    overridden: s.overridden,
+                })
+                .collect(),
+            interface_slots,
+        }))
+    }
+
+    /// Finds the method definition a MethodDef or MemberRef token from `image` refers to
+    pub fn resolve_method(
+        &self,
+        image: &'a CilImage,
+        token: MethodDefOrRef,
+    ) -> Result<Option<ResolvedMethodDef<'a>>> {
+        Ok(self
+            .resolve_method_with_owner(image, token, &[])?
+            .map(|(method, _)| method))
+    }
+
+    /// Methods of the image that override `method` or implement it for an interface, directly or by
+    /// overriding another override
+    pub fn overrides_of(&self, method: ResolvedMethodDef<'a>) -> Result<Vec<MethodDefHandle>> {
+        let mut overrides = vec![];
+        let mut push = |implementation: ResolvedMethodDef<'a>| {
+            if std::ptr::eq(implementation.0, self.image)
+                && !same_method(implementation, method)
+                && !overrides.contains(&implementation.1)
+            {
+                overrides.push(implementation.1);
+            }
+        };
+
+        for handle in self.type_def_handles() {
+            let table = self.method_table(handle)?;
+            for slot in &table.slots {
+                if slot.contains(method) {
+                    push(slot.implementation);
+                }
+            }
+            for slot in &table.interface_slots {
+                if same_method(slot.declaration, method)
+                    && let Some(implementation) = slot.implementation
+                {
+                    push(implementation);
+                }
+            }
+        }
+
+        overrides.sort_by_key(|m| m.0);
+        Ok(overrides)
+    }
+
+    /// Method that a `callvirt` of `method`, a token from the image, calls on an instance of `receiver`.
+    /// Non-virtual methods are returned as they are.
+    pub fn dispatch(
+        &self,
+        method: MethodDefOrRef,
+        receiver: &Element,
+    ) -> Result<Option<ResolvedMethodDef<'a>>> {
+        let Some((target, owner)) = self.resolve_method_with_owner(self.image, method, &[])? else {
+            return Ok(None);
+        };
+        let flags = target.0.method_defs[target.1.index()].0.flags;
+        if !flags.is_virtual() || flags.is_static() {
+            return Ok(Some(target));
+        }
+
+        let receiver = ScopedType::new(self.image, receiver.clone());
+        let Some(table) = self.method_table_of(&receiver)? else {
+            return Ok(None);
+        };
+
+        if self.is_interface_method(target) {
+            // A type can implement several instantiations of the same generic interface
+            let slot = table
+                .interface_slots
+                .iter()
+                .filter(|s| same_method(s.declaration, target))
+                .find(|s| self.same_type(&s.interface, &owner) || !is_generic_instance(&owner))
+                .or_else(|| {
+                    table
+                        .interface_slots
+                        .iter()
+                        .find(|s| same_method(s.declaration, target))
+                });
+            return Ok(slot.and_then(|s| s.implementation));
+        }
+
+        Ok(table.slot_of(target).map(|s| s.implementation))
+    }
+
+    fn is_interface_method(&self, method: ResolvedMethodDef<'a>) -> bool {
+        let (image, handle) = method;
+        image.declaring_type_of(handle).is_some_and(|t| {
+            matches!(
+                image.type_defs[t.index()].flags.class_semantics(),
+                ClassSemantics::Interface
+            )
+        })
+    }
+
+    fn vtable_slots(&self, ty: &ScopedType<'a>, depth: usize) -> Result<Option<Vec<Slot<'a>>>> {
+        let Some((image, handle, args)) = self.definition(ty)? else {
+            return Ok(None);
+        };
+        if depth > MAX_HIERARCHY_DEPTH {
+            return Ok(Some(vec![]));
+        }
+
+        let mut slots = match self.base_of(ty)? {
+            Some(base) => self.vtable_slots(&base, depth + 1)?.unwrap_or_default(),
+            None => vec![],
+        };
+
+        for method in image.methods_of(handle) {
+            let flags = image.method_defs[method.index()].0.flags;
+            if !flags.is_virtual() || flags.is_static() {
+                continue;
+            }
+
+            let method = self.slot_method((image, method), &args)?;
+            let reused = match flags.vtable_layout() {
+                VtableLayout::ReuseSlot => self.find_overridable_slot(&slots, &method),
+                VtableLayout::NewSlot => None,
+            };
+
+            match reused {
+                Some(index) => {
+                    let slot = &mut slots[index];
+                    let previous = std::mem::replace(&mut slot.implementation, method);
+                    slot.overridden.push(previous.method);
+                }
+                None => slots.push(Slot {
+                    declaration: self.slot_method(method.method, &args)?,
+                    implementation: method,
+                    overridden: vec![],
+                }),
+            }
+        }
+
+        // Explicit overrides replace whatever implementation the slot has so far
+        for method_impl in image.method_impls_of(handle) {
+            let (Ok(declaration), Ok(body)) = (
+                MethodDefOrRef::try_from(method_impl.method_declaration as u32),
+                MethodDefOrRef::try_from(method_impl.method_body as u32),
+            ) else {
+                continue;
+            };
+            let (Some((declaration, _)), Some((body, _))) = (
+                self.resolve_method_with_owner(image, declaration, &args)?,
+                self.resolve_method_with_owner(image, body, &args)?,
+            ) else {
+                continue;
+            };
+
+            if let Some(slot) = slots.iter_mut().find(|s| {
+                same_method(s.declaration.method, declaration)
+                    || same_method(s.implementation.method, declaration)
+                    || s.overridden.iter().any(|&m| same_method(m, declaration))
+            }) && !same_method(slot.implementation.method, body)
+            {
+                let body = self.slot_method(body, &args)?;
+                let previous = std::mem::replace(&mut slot.implementation, body);
+                slot.overridden.push(previous.method);
+            }
+        }
+
+        Ok(Some(slots))
+    }
+
+    /// Most derived slot a `ReuseSlot` method overrides, matching by name and signature
+    fn find_overridable_slot(&self, slots: &[Slot<'a>], method: &SlotMethod<'a>) -> Option<usize> {
+        for (i, slot) in slots.iter().enumerate().rev() {
+            let current = &slot.implementation;
+            if current.name != method.name {
+                continue;
+            }
+
+            if self.signatures_match(&current.signature, &method.signature) {
+                // Final methods can't be overridden and private ones aren't visible, the method gets a
+                // slot of its own instead
+                let accessible = match current.flags.access() {
+                    MemberAccess::Private | MemberAccess::CompilerControlled => false,
+                    // Strict internal methods can only be overridden from the same assembly
+                    MemberAccess::Assembly | MemberAccess::FamilyAndAssembly
+                        if current.flags.is_strict() =>
+                    {
+                        std::ptr::eq(current.method.0, method.method.0)
+                    }
+                    _ => true,
+                };
+                let overridable = accessible && !current.flags.is_final();
+                return overridable.then_some(i);
+            }
+
+            // A method that isn't HideBySig hides every inherited method with the same name
+            let declaration = &slot.declaration;
+            if declaration.name == method.name && !declaration.flags.hide_by_sig() {
+                return None;
+            }
+        }
+        None
+    }
+
+    fn interface_slots(
+        &self,
+        ty: &ScopedType<'a>,
+        slots: &[Slot<'a>],
+    ) -> Result<Vec<InterfaceSlot<'a>>> {
+        // Explicit implementations of the type and its bases, most derived first
+        let mut explicit = vec![];
+        let mut current = Some(ty.clone());
+        let mut depth = 0;
+        while let Some(t) = current
+            && depth <= MAX_HIERARCHY_DEPTH
+        {
+            if let Some((image, handle, args)) = self.definition(&t)? {
+                for method_impl in image.method_impls_of(handle) {
+                    let (Ok(declaration), Ok(body)) = (
+                        MethodDefOrRef::try_from(method_impl.method_declaration as u32),
+                        MethodDefOrRef::try_from(method_impl.method_body as u32),
+                    ) else {
+                        continue;
+                    };
+                    if let (Some(declaration), Some((body, _))) = (
+                        self.resolve_method_with_owner(image, declaration, &args)?,
+                        self.resolve_method_with_owner(image, body, &args)?,
+                    ) {
+                        explicit.push((declaration, body));
+                    }
+                }
+            }
+            current = self.base_of(&t)?;
+            depth += 1;
+        }
+
+        let mut interface_slots = vec![];
+        for interface in self.all_interfaces(ty)? {
+            let Some((image, handle, args)) = self.definition(&interface)? else {
+                continue;
+            };
+
+            for method in image.methods_of(handle) {
+                let flags = image.method_defs[method.index()].0.flags;
+                // Static virtual members are resolved through constraints, not through instances
+                if !flags.is_virtual() || flags.is_static() {
+                    continue;
+                }
+                let declaration = self.slot_method((image, method), &args)?;
+
+                let explicit_implementation = explicit
+                    .iter()
+                    .find(|((d, owner), _)| {
+                        same_method(*d, declaration.method)
+                            && (!is_generic_instance(owner) || self.same_type(owner, &interface))
+                    })
+                    .map(|(_, body)| *body);
+
+                let implementation = explicit_implementation
+                    .or_else(|| {
+                        slots
+                            .iter()
+                            .rev()
+                            .map(|s| &s.implementation)
+                            .find(|m| {
+                                m.name == declaration.name
+                                    && matches!(m.flags.access(), MemberAccess::Public)
+                                    && self.signatures_match(&m.signature, &declaration.signature)
+                            })
+                            .map(|m| m.method)
+                    })
+                    // Default interface methods
+                    .or_else(|| (!flags.is_abstract()).then_some(declaration.method));
+
+                interface_slots.push(InterfaceSlot {
+                    interface: interface.clone(),
+                    declaration: declaration.method,
+                    implementation,
+                });
+            }
+        }
+
+        Ok(interface_slots)
+    }
+
+    fn slot_method(
+        &self,
+        method: ResolvedMethodDef<'a>,
+        type_args: &[ScopedType<'a>],
+    ) -> Result<SlotMethod<'a>> {
+        let (image, handle) = method;
+        let (definition, _, _) = &image.method_defs[handle.index()];
+        let signature = image.method_signature(handle)?;
+        Ok(SlotMethod {
+            method,
+            name: &definition.name,
+            flags: definition.flags,
+            signature: self.instantiated_signature(image, signature, type_args),
+        })
+    }
+
+    fn instantiated_signature(
+        &self,
+        image: &'a CilImage,
+        signature: StandaloneMethodSignature,
+        type_args: &[ScopedType<'a>],
+    ) -> InstantiatedSignature<'a> {
+        InstantiatedSignature {
+            header: signature.header,
+            generic_param_count: signature.generic_param_count,
+            return_type: self.instantiate(image, signature.return_type, type_args),
+            parameters: signature
+                .parameters
+                .into_iter()
+                .map(|p| self.instantiate(image, p, type_args))
+                .collect(),
+        }
+    }
+
+    fn signatures_match(
+        &self,
+        a: &InstantiatedSignature<'a>,
+        b: &InstantiatedSignature<'a>,
+    ) -> bool {
+        a.header == b.header
+            && a.generic_param_count == b.generic_param_count
+            && a.parameters.len() == b.parameters.len()
+            && self.same_type(&a.return_type, &b.return_type)
+            && a.parameters
+                .iter()
+                .zip(&b.parameters)
+                .all(|(x, y)| self.same_type(x, y))
+    }
+
+    /// Resolves a method token from `image` together with the type it was referenced through, eg.
+    /// `IEquatable<int>` for a MemberRef on a TypeSpec. `type_args` are the generic arguments of the
+    /// type the token appears in.
+    fn resolve_method_with_owner(
+        &self,
+        image: &'a CilImage,
+        token: MethodDefOrRef,
+        type_args: &[ScopedType<'a>],
+    ) -> Result<Option<(ResolvedMethodDef<'a>, ScopedType<'a>)>> {
+        let handle = match token {
+            MethodDefOrRef::MethodDef(index) => {
+                let handle = MethodDefHandle(index);
+                return Ok(image
+                    .declaring_type_of(handle)
+                    .map(|owner| ((image, handle), self.type_def(image, owner))));
+            }
+            MethodDefOrRef::MemberRef(index) => MemberRefHandle(index),
+        };
+
+        let Some(member_ref) = image.member_refs.get(handle.index()) else {
+            return Ok(None);
+        };
+        let MemberRefSignature::Method(mut signature) = image.member_ref_signature(handle)? else {
+            return Ok(None);
+        };
+        // Vararg references append the variable arguments after a sentinel
+        if let Some(sentinel) = signature.sentinel.take() {
+            signature.parameters.truncate(sentinel);
+        }
+
+        let owner = match MemberRefParent::try_from(member_ref.class_index as u32) {
+            Ok(MemberRefParent::TypeDef(index)) => {
+                self.type_element(image, TypeDefOrRef::TypeDef(index))?
+            }
+            Ok(MemberRefParent::TypeRef(index)) => {
+                self.type_element(image, TypeDefOrRef::TypeRef(index))?
+            }
+            Ok(MemberRefParent::TypeSpec(index)) => image.type_spec(TypeSpecHandle(index))?,
+            Ok(MemberRefParent::MethodDef(index)) => {
+                return self.resolve_method_with_owner(
+                    image,
+                    MethodDefOrRef::MethodDef(index),
+                    type_args,
+                );
+            }
+            Ok(MemberRefParent::ModuleRef(_)) | Err(_) => return Ok(None),
+        };
+        let owner = self.instantiate(image, owner, type_args);
+        let Some((owner_image, owner_handle, _)) = self.definition(&owner)? else {
+            return Ok(None);
+        };
+
+        // Both signatures are in terms of the owner's own type parameters
+        let reference = self.instantiated_signature(image, signature, &[]);
+        for method in owner_image.methods_of(owner_handle) {
+            if owner_image.method_defs[method.index()].0.name != member_ref.name {
+                continue;
+            }
+            let definition = self.slot_method((owner_image, method), &[])?;
+            if self.signatures_match(&reference, &definition.signature) {
+                return Ok(Some(((owner_image, method), owner)));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+fn same_method(a: ResolvedMethodDef<'_>, b: ResolvedMethodDef<'_>) -> bool {
+    std::ptr::eq(a.0, b.0) && a.1 == b.1
+}
+
+fn is_generic_instance(ty: &ScopedType<'_>) -> bool {
+    matches!(ty.element, Element::GenericInst { .. })
+}
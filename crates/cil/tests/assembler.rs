@@ -0,0 +1,131 @@
+mod common;
+
+use std::collections::HashMap;
+
+use cil::{
+    assembler::{AssembledModule, assemble},
+    error::Error,
+    image::CilImage,
+    meta::TypeDefHandle,
+};
+use common::test_images;
+
+/// Name of a type in the form `Namespace.Outer/Inner`
+fn type_name(module: &AssembledModule, handle: TypeDefHandle) -> String {
+    let type_decl = &module.type_defs[handle.index()];
+    match type_decl.enclosing {
+        Some(enclosing) => format!("{}/{}", type_name(module, enclosing), type_decl.name),
+        None if type_decl.namespace.is_empty() => type_decl.name.clone(),
+        None => format!("{}.{}", type_decl.namespace, type_decl.name),
+    }
+}
+
+/// Line and message of the error `source` fails to assemble with
+fn error(source: &str) -> (usize, String) {
+    match assemble(source) {
+        Err(Error::InvalidILAsm { line, message }) => (line, message),
+        other => panic!("expected an ILAsm error, got {other:?}"),
+    }
+}
+
+#[test]
+fn disassembly_assembles_to_the_same_instructions() {
+    for path in test_images() {
+        let image = CilImage::load(&path).unwrap();
+        let module = assemble(&image.disassemble().unwrap()).unwrap();
+
+        // Types may be written in a different order, methods keep theirs
+        let mut assembled = HashMap::<String, Vec<_>>::new();
+        for (_, owner, method) in module.methods() {
+            let opcodes = method.body.as_ref().map(|(_, instructions)| {
+                instructions
+                    .iter()
+                    .map(|i| i.opcode.asm_name())
+                    .collect::<Vec<_>>()
+            });
+            assembled
+                .entry(type_name(&module, owner))
+                .or_default()
+                .push((method.name.clone(), opcodes));
+        }
+
+        for handle in (1..=image.type_defs.len() as u32).map(TypeDefHandle) {
+            let name = image.type_def_name(handle).unwrap().to_string();
+            let original = image
+                .methods_of(handle)
+                .map(|method| {
+                    let (definition, _, instructions) = &image.method_defs[method.index()];
+                    let opcodes = (definition.rva != 0)
+                        .then(|| instructions.iter().map(|i| i.opcode.asm_name()).collect());
+                    (definition.name.clone(), opcodes)
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(
+                assembled.remove(&name).unwrap_or_default(),
+                original,
+                "methods of {name} in {}",
+                path.display()
+            );
+        }
+        assert!(assembled.is_empty(), "{:?}", assembled.keys());
+    }
+}
+
+#[test]
+fn errors_report_their_line() {
+    assert_eq!(
+        error(
+            ".class public A extends [mscorlib]System.Object {
+                .method public static void M() cil managed {
+                    nop
+                    frobnicate
+                    ret
+                }
+            }"
+        ),
+        (4, "unknown instruction frobnicate".to_string())
+    );
+    assert_eq!(
+        error(
+            ".class public A extends [mscorlib]System.Object {
+                .method public static void M() cil managed {
+                    br.s done
+                    ret
+                }
+            }"
+        ),
+        (3, "undefined label done".to_string())
+    );
+    assert_eq!(
+        error(
+            ".class public A extends [mscorlib]System.Object {
+                .method public static void M() cil managed {
+                    ldloc missing
+                    ret
+                }
+            }"
+        ),
+        (3, "undefined variable missing".to_string())
+    );
+    assert_eq!(
+        error(
+            ".class public A extends [mscorlib]System.Object {
+            }
+
+            .class public A extends [mscorlib]System.Object {
+            }"
+        ),
+        (4, "duplicate class A".to_string())
+    );
+    assert_eq!(
+        error(
+            ".class public A extends [mscorlib]System.Object {
+                .method public static void M() cil managed {
+                    ldstr \"unterminated
+                    ret
+                }
+            }"
+        ),
+        (3, "unterminated string".to_string())
+    );
+}
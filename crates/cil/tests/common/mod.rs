@@ -0,0 +1,64 @@
+//! Helpers shared by the integration tests
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+
+use cil::{assembler::assemble, flow::ControlFlowGraph, image::CilImage, strings::BlobHeap};
+
+pub fn load(name: &str) -> CilImage {
+    CilImage::load(format!("{}/../../tests/{name}", env!("CARGO_MANIFEST_DIR"))).unwrap()
+}
+
+/// Paths of every test DLL, sorted
+pub fn test_images() -> Vec<PathBuf> {
+    let tests = concat!(env!("CARGO_MANIFEST_DIR"), "/../../tests");
+    let mut images = std::fs::read_dir(tests)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|e| e == "dll"))
+        .collect::<Vec<_>>();
+    images.sort();
+    assert!(!images.is_empty());
+    images
+}
+
+/// Copy of `heap` with `blobs` appended, returned with the index of each new blob
+pub fn with_blobs(heap: &BlobHeap, blobs: &[&[u8]]) -> (BlobHeap, Vec<u16>) {
+    fn append(data: &mut Vec<u8>, blob: &[u8]) {
+        if blob.len() < 0x80 {
+            data.push(blob.len() as u8);
+        } else {
+            data.extend_from_slice(&(0x8000 | blob.len() as u16).to_be_bytes());
+        }
+        data.extend_from_slice(blob);
+    }
+
+    let mut data = Vec::new();
+    for (index, blob) in heap.iter() {
+        data.resize(index as usize, 0);
+        append(&mut data, blob);
+    }
+
+    let indices = blobs
+        .iter()
+        .map(|blob| {
+            let index = data.len() as u16;
+            append(&mut data, blob);
+            index
+        })
+        .collect();
+    (BlobHeap::new(data), indices)
+}
+
+/// Graph of `code`, assembled as the body of a static method without arguments
+pub fn control_flow_graph(code: &str) -> ControlFlowGraph {
+    let source = format!(
+        ".class public A extends [mscorlib]System.Object {{
+            .method public static void M() cil managed {{ {code} }}
+        }}"
+    );
+    let module = assemble(&source).unwrap();
+    let (_, _, method) = module.methods().next().unwrap();
+    let (header, instructions) = method.body.as_ref().unwrap();
+    ControlFlowGraph::new(instructions, &header.exception_clauses).unwrap()
+}
@@ -0,0 +1,119 @@
+mod common;
+
+use cil::{
+    assembler::assemble,
+    body::{ExceptionClause, ExceptionClauseKind},
+    image::{CilImage, MethodHeader},
+    meta::Token,
+};
+use common::{load, test_images};
+
+/// Disassembly of the method whose declaration starts with `declaration`, up to its closing brace
+fn method_source<'a>(source: &'a str, declaration: &str) -> &'a str {
+    let start = source
+        .find(declaration)
+        .unwrap_or_else(|| panic!("{declaration} is not in the disassembly"));
+    let end = start + source[start..].find("\n  }\n").unwrap() + "\n  }".len();
+    &source[start..end]
+}
+
+#[test]
+fn nested_exception_blocks() {
+    let source = load("Exceptions.dll").disassemble().unwrap();
+    assert_eq!(
+        method_source(&source, ".method public hidebysig static void Main()"),
+        r#".method public hidebysig static void Main() cil managed
+  {
+    .maxstack 2
+    .locals init (class [mscorlib]System.Exception V_0)
+    IL_0000:  nop
+    .try
+    {
+      .try
+      {
+        IL_0001:  nop
+        IL_0002:  ldstr "Testing exception handling..."
+        IL_0007:  call void [mscorlib]System.Console::WriteLine(string)
+        IL_000c:  nop
+        IL_000d:  call void Test.Exceptions::TestExceptionHandling()
+        IL_0012:  nop
+        IL_0013:  nop
+        IL_0014:  leave.s IL_0031
+      }
+      catch [mscorlib]System.Exception
+      {
+        IL_0016:  stloc.0
+        IL_0017:  nop
+        IL_0018:  ldstr "Caught an exception: "
+        IL_001d:  ldloc.0
+        IL_001e:  callvirt instance string [mscorlib]System.Exception::get_Message()
+        IL_0023:  call string [mscorlib]System.String::Concat(string, string)
+        IL_0028:  call void [mscorlib]System.Console::WriteLine(string)
+        IL_002d:  nop
+        IL_002e:  nop
+        IL_002f:  leave.s IL_0031
+      }
+      IL_0031:  leave.s IL_0041
+    }
+    finally
+    {
+      IL_0033:  nop
+      IL_0034:  ldstr "Finally block executed."
+      IL_0039:  call void [mscorlib]System.Console::WriteLine(string)
+      IL_003e:  nop
+      IL_003f:  nop
+      IL_0040:  endfinally
+    }
+    IL_0041:  ret
+  }"#
+    );
+}
+
+#[test]
+fn disassembly_assembles() {
+    for path in test_images() {
+        let image = CilImage::load(&path).unwrap();
+        let source = image.disassemble().unwrap();
+        let module = assemble(&source)
+            .unwrap_or_else(|e| panic!("failed to assemble {}: {e}", path.display()));
+
+        assert_eq!(
+            module.type_defs.len(),
+            image.type_defs.len(),
+            "{}",
+            path.display()
+        );
+        assert_eq!(
+            module.methods().count(),
+            image.method_defs.len(),
+            "{}",
+            path.display()
+        );
+        // Every exception clause is written out as a block and read back. Catch types are left out,
+        // the assembler numbers TypeRefs in the order it meets them.
+        let clauses = |headers: &mut dyn Iterator<Item = &MethodHeader>| {
+            let mut clauses = headers
+                .flat_map(|h| &h.exception_clauses)
+                .map(|c| ExceptionClause {
+                    kind: match c.kind {
+                        ExceptionClauseKind::Catch(_) => ExceptionClauseKind::Catch(Token(0)),
+                        kind => kind,
+                    },
+                    ..*c
+                })
+                .collect::<Vec<_>>();
+            clauses.sort_by_key(|c| format!("{c:?}"));
+            clauses
+        };
+        assert_eq!(
+            clauses(
+                &mut module
+                    .methods()
+                    .filter_map(|(_, _, m)| m.body.as_ref().map(|(header, _)| header))
+            ),
+            clauses(&mut image.method_defs.iter().map(|(_, header, _)| header)),
+            "{}",
+            path.display()
+        );
+    }
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use cil::{
+    dominance::{DominanceFrontiers, DominatorTree},
+    flow::BlockId,
+    loops::LoopForest,
+};
+use common::control_flow_graph;
+
+#[test]
+fn loop_at_entry_is_in_its_own_frontier() {
+    let cfg = control_flow_graph("top: ldc.i4.0 brtrue.s top ret");
+    assert_eq!(cfg.len(), 2);
+
+    let tree = DominatorTree::dominators(&cfg);
+    let frontiers = DominanceFrontiers::new(&cfg, &tree);
+    assert_eq!(frontiers.of(BlockId(0)), [BlockId(0)]);
+    assert_eq!(frontiers.of(BlockId(1)), []);
+}
+
+#[test]
+fn entry_without_back_edge_has_empty_frontier() {
+    let cfg = control_flow_graph("ldc.i4.0 brtrue.s done nop done: ret");
+    assert_eq!(cfg.len(), 3);
+
+    let tree = DominatorTree::dominators(&cfg);
+    let frontiers = DominanceFrontiers::new(&cfg, &tree);
+    assert_eq!(frontiers.of(BlockId(0)), []);
+    assert_eq!(frontiers.of(BlockId(1)), [BlockId(2)]);
+}
+
+#[test]
+fn unreachable_predecessor_is_not_a_loop_entry() {
+    // 0: br.s head, 1: dead: br.s body, 2: head: nop, 3: body: ldc.i4.0 brtrue.s head, 4: ret
+    let cfg =
+        control_flow_graph("br.s head dead: br.s body head: nop body: ldc.i4.0 brtrue.s head ret");
+    assert_eq!(cfg.len(), 5);
+
+    let tree = DominatorTree::dominators(&cfg);
+    assert!(!tree.is_reachable(BlockId(1)));
+    let forest = LoopForest::new(&cfg, &tree);
+    assert_eq!(forest.loops.len(), 1);
+    assert_eq!(forest.loops[0].header, BlockId(2));
+    assert_eq!(forest.loops[0].blocks, [BlockId(2), BlockId(3)]);
+    assert_eq!(forest.irreducible_regions, []);
+    assert!(forest.is_reducible());
+}
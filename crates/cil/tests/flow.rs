@@ -0,0 +1,138 @@
+mod common;
+
+use cil::flow::{BlockId, ControlFlowGraph, EdgeKind};
+use common::control_flow_graph;
+
+/// Targets and kinds of the edges leaving `block`
+fn successors(cfg: &ControlFlowGraph, block: usize) -> Vec<(usize, EdgeKind)> {
+    cfg.block(BlockId(block))
+        .successors
+        .iter()
+        .map(|e| (e.target.0, e.kind))
+        .collect()
+}
+
+#[test]
+fn switch_cases_and_default() {
+    // 0: ldc.i4.0 switch, 1: nop, 2: a: nop, 3: b: ret
+    let cfg = control_flow_graph("ldc.i4.0 switch (a, b) nop a: nop b: ret");
+    assert_eq!(cfg.len(), 4);
+    assert_eq!(
+        successors(&cfg, 0),
+        [
+            (2, EdgeKind::SwitchCase(0)),
+            (3, EdgeKind::SwitchCase(1)),
+            (1, EdgeKind::Fallthrough),
+        ]
+    );
+    assert_eq!(successors(&cfg, 1), [(2, EdgeKind::Fallthrough)]);
+}
+
+#[test]
+fn jmp_leaves_the_method() {
+    let cfg = control_flow_graph("nop jmp void A::M() ret");
+    assert_eq!(cfg.len(), 2);
+    assert_eq!(successors(&cfg, 0), []);
+    assert!(cfg.block(BlockId(1)).predecessors.is_empty());
+}
+
+#[test]
+fn finally_continues_at_every_leave_target() {
+    // 0: ldc.i4.0 brtrue.s, 1: leave.s first, 2: second: leave.s done, 3: endfinally,
+    // 4: first: nop, 5: done: ret
+    let cfg = control_flow_graph(
+        ".try {
+            ldc.i4.0
+            brtrue.s second
+            leave.s first
+        second:
+            leave.s done
+        } finally {
+            endfinally
+        }
+        first: nop
+        done: ret",
+    );
+    assert_eq!(cfg.len(), 6);
+    assert_eq!(
+        successors(&cfg, 0),
+        [
+            (2, EdgeKind::ConditionalTrue),
+            (1, EdgeKind::ConditionalFalse),
+            (3, EdgeKind::Exceptional),
+        ]
+    );
+    assert_eq!(
+        successors(&cfg, 1),
+        [(4, EdgeKind::Leave), (3, EdgeKind::Exceptional)]
+    );
+    assert_eq!(
+        successors(&cfg, 2),
+        [(5, EdgeKind::Leave), (3, EdgeKind::Exceptional)]
+    );
+    assert_eq!(
+        successors(&cfg, 3),
+        [(4, EdgeKind::EndFinally), (5, EdgeKind::EndFinally)]
+    );
+    assert_eq!(successors(&cfg, 4), [(5, EdgeKind::Fallthrough)]);
+}
+
+#[test]
+fn filter_ends_at_its_handler() {
+    // 0: nop leave.s, 1: filter, 2: handler, 3: done: ret
+    let cfg = control_flow_graph(
+        ".try {
+            nop
+            leave.s done
+        } filter {
+            pop
+            ldc.i4.1
+            endfilter
+        } {
+            pop
+            leave.s done
+        }
+        done: ret",
+    );
+    assert_eq!(cfg.len(), 4);
+    assert_eq!(
+        successors(&cfg, 0),
+        [(3, EdgeKind::Leave), (1, EdgeKind::Exceptional)]
+    );
+    assert_eq!(successors(&cfg, 1), [(2, EdgeKind::EndFilter)]);
+    assert_eq!(successors(&cfg, 2), [(3, EdgeKind::Leave)]);
+}
+
+#[test]
+fn nested_try_blocks_reach_every_enclosing_handler() {
+    // 0: inner try, 1: catch, 2: finally, 3: done: ret
+    let cfg = control_flow_graph(
+        ".try {
+            .try {
+                nop
+                leave.s done
+            } catch [mscorlib]System.Exception {
+                pop
+                leave.s done
+            }
+        } finally {
+            endfinally
+        }
+        done: ret",
+    );
+    assert_eq!(cfg.len(), 4);
+    assert_eq!(
+        successors(&cfg, 0),
+        [
+            (3, EdgeKind::Leave),
+            (1, EdgeKind::Exceptional),
+            (2, EdgeKind::Exceptional),
+        ]
+    );
+    assert_eq!(
+        successors(&cfg, 1),
+        [(3, EdgeKind::Leave), (2, EdgeKind::Exceptional)]
+    );
+    assert_eq!(successors(&cfg, 2), [(3, EdgeKind::EndFinally)]);
+    assert_eq!(successors(&cfg, 3), []);
+}
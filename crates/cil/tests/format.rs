@@ -0,0 +1,10 @@
+use cil::format::ilasm_name;
+
+#[test]
+fn ilasm_names_are_quoted_per_segment() {
+    assert_eq!(ilasm_name("System.Object"), "System.Object");
+    assert_eq!(ilasm_name(".ctor"), ".ctor");
+    assert_eq!(ilasm_name("value"), "'value'");
+    assert_eq!(ilasm_name("My.value.Ns"), "'My.value.Ns'");
+    assert_eq!(ilasm_name("<Module>"), "'<Module>'");
+}
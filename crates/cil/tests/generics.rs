@@ -0,0 +1,62 @@
+use cil::{
+    image::CilImage, meta::MethodSpecHandle, signature::Element, strings::BlobHeap,
+    tables::TypeDefOrRef,
+};
+
+/// Builds a blob heap from `blobs`, returning it with the index of each blob
+fn blob_heap(blobs: &[&[u8]]) -> (BlobHeap, Vec<u16>) {
+    let mut data = vec![0];
+    let mut indices = vec![];
+    for blob in blobs {
+        indices.push(data.len() as u16);
+        data.push(blob.len() as u8);
+        data.extend_from_slice(blob);
+    }
+    (BlobHeap::new(data), indices)
+}
+
+#[test]
+fn method_spec_on_generic_parent_substitutes_once() {
+    let mut image =
+        CilImage::load(concat!(env!("CARGO_MANIFEST_DIR"), "/../../tests/Game.dll")).unwrap();
+
+    // `class G<!0, !!0> Parent<!!0>::Method<T>()`, instantiated as `Method<int32>`. The `!!0` in the
+    // parent refers to the calling method and has to survive, only the one in the signature is bound.
+    let (blobs, indices) = blob_heap(&[
+        // HASTHIS | GENERIC, 1 generic parameter, no parameters, returns GENERICINST CLASS TypeRef 1 <!0, !!0>
+        &[
+            0x30, 0x01, 0x00, 0x15, 0x12, 0x05, 0x02, 0x13, 0x00, 0x1E, 0x00,
+        ],
+        // GENERICINST CLASS TypeRef 1 <!!0>
+        &[0x15, 0x12, 0x05, 0x01, 0x1E, 0x00],
+        // GENERICINST <int32>
+        &[0x0A, 0x01, 0x08],
+    ]);
+    image.blobs = blobs;
+    image.member_refs[0].signature_blob_index = indices[0];
+    // MemberRefParent::TypeSpec(1)
+    image.member_refs[0].class_index = (1 << 3) | 4;
+    image.type_specs[0].signature_blob_index = indices[1];
+    // MethodDefOrRef::MemberRef(1)
+    image.method_specs[0].method = (1 << 1) | 1;
+    image.method_specs[0].instantiation_blob_index = indices[2];
+
+    let signature = image
+        .instantiated_method_spec_signature(MethodSpecHandle(1))
+        .unwrap();
+    let Element::GenericInst {
+        generic_type,
+        generic_args,
+    } = &signature.return_type
+    else {
+        panic!(
+            "expected a generic instance, found {:?}",
+            signature.return_type
+        );
+    };
+    assert_eq!(**generic_type, Element::Class(TypeDefOrRef::TypeRef(1)));
+    assert!(matches!(
+        generic_args[..],
+        [Element::MVar(index), Element::I4] if index.0 == 0
+    ));
+}
@@ -0,0 +1,93 @@
+use cil::{
+    hierarchy::TypeHierarchy, image::CilImage, meta::TypeRefHandle, signature::Element,
+    tables::TypeDefOrRef,
+};
+
+fn load(name: &str) -> CilImage {
+    CilImage::load(format!("{}/../../tests/{name}", env!("CARGO_MANIFEST_DIR"))).unwrap()
+}
+
+/// `class` reference to the type called `name`
+fn class(image: &CilImage, name: &str) -> Element {
+    let index = (1..=image.type_refs.len() as u32)
+        .find(|&i| image.type_ref_name(TypeRefHandle(i)).unwrap().to_string() == name)
+        .unwrap_or_else(|| panic!("no reference to {name}"));
+    Element::Class(TypeDefOrRef::TypeRef(index))
+}
+
+fn generic(image: &CilImage, name: &str, arg: Element) -> Element {
+    Element::GenericInst {
+        generic_type: Box::new(class(image, name)),
+        generic_args: vec![arg],
+    }
+}
+
+#[test]
+fn primitives_are_assignable_to_their_system_types() {
+    let image = load("Dawn.dll");
+    let hierarchy = TypeHierarchy::new(&image);
+    let assignable =
+        |source: &Element, target: &Element| hierarchy.is_assignable_to(source, target).unwrap();
+
+    let string = class(&image, "System.String");
+    let int32 = class(&image, "System.Int32");
+    let value_type = class(&image, "System.ValueType");
+    assert!(assignable(&Element::String, &string));
+    assert!(assignable(&string, &Element::String));
+    assert!(assignable(&Element::I4, &int32));
+    assert!(assignable(&Element::I4, &value_type));
+    assert!(!assignable(&Element::String, &value_type));
+    assert!(!assignable(&Element::Object, &value_type));
+    assert!(!assignable(&Element::I4, &string));
+
+    let enumerable = class(&image, "System.Collections.IEnumerable");
+    let equatable = "System.IEquatable`1";
+    assert!(assignable(&Element::String, &enumerable));
+    assert!(assignable(
+        &Element::String,
+        &generic(&image, equatable, Element::String)
+    ));
+    assert!(assignable(
+        &Element::String,
+        &generic(
+            &image,
+            "System.Collections.Generic.IEnumerable`1",
+            Element::Char
+        )
+    ));
+    assert!(assignable(
+        &Element::I4,
+        &generic(&image, equatable, Element::I4)
+    ));
+    assert!(!assignable(
+        &Element::I4,
+        &generic(&image, equatable, Element::I8)
+    ));
+    assert!(!assignable(&Element::I4, &enumerable));
+}
+
+#[test]
+fn arrays_are_assignable_to_collection_interfaces() {
+    let image = load("Dawn.dll");
+    let hierarchy = TypeHierarchy::new(&image);
+    let assignable =
+        |source: &Element, target: &Element| hierarchy.is_assignable_to(source, target).unwrap();
+
+    let ints = Element::SzArray(Box::new(Element::I4));
+    let strings = Element::SzArray(Box::new(Element::String));
+    let enumerable = "System.Collections.Generic.IEnumerable`1";
+    assert!(assignable(&ints, &generic(&image, enumerable, Element::I4)));
+    assert!(!assignable(
+        &ints,
+        &generic(&image, enumerable, Element::Object)
+    ));
+    assert!(assignable(
+        &strings,
+        &generic(&image, enumerable, Element::Object)
+    ));
+    assert!(assignable(
+        &ints,
+        &class(&image, "System.Collections.IEnumerable")
+    ));
+    assert!(assignable(&ints, &class(&image, "System.Array")));
+}
@@ -0,0 +1,79 @@
+use cil::{
+    identity::{AssemblyIdentity, AssemblyVersion, PublicKeyOrToken, public_key_token},
+    image::CilImage,
+    meta::AssemblyRefHandle,
+    tables::{AssemblyContentType, AssemblyFlags},
+};
+
+const MSCORLIB_TOKEN: [u8; 8] = [0xb7, 0x7a, 0x5c, 0x56, 0x19, 0x34, 0xe0, 0x89];
+
+fn load(name: &str) -> CilImage {
+    CilImage::load(format!("{}/../../tests/{name}", env!("CARGO_MANIFEST_DIR"))).unwrap()
+}
+
+#[test]
+fn reserved_content_types_are_kept() {
+    let mut identity =
+        AssemblyIdentity::parse("Foo, Version=1.2.3.4, ContentType=WindowsRuntime").unwrap();
+    assert_eq!(identity.content_type(), AssemblyContentType::WindowsRuntime);
+
+    identity.flags = AssemblyFlags(0x0E00);
+    assert_eq!(identity.content_type(), AssemblyContentType::Unknown(7));
+    assert!(!identity.to_string().contains("ContentType"));
+    assert!(format!("{:?}", identity.flags).contains("Unknown(7)"));
+}
+
+#[test]
+fn ecma_key_token() {
+    // The "ECMA key" the core library is signed with
+    let key = [0, 0, 0, 0, 0, 0, 0, 0, 4, 0, 0, 0, 0, 0, 0, 0];
+    assert_eq!(public_key_token(&key), MSCORLIB_TOKEN);
+}
+
+#[test]
+fn assembly_ref_identities() {
+    let image = load("HelloWorld.dll");
+    let mscorlib = image.assembly_ref_identity(AssemblyRefHandle(1)).unwrap();
+    assert_eq!(mscorlib.name, "mscorlib");
+    assert_eq!(
+        mscorlib.version,
+        AssemblyVersion {
+            major: 4,
+            minor: 0,
+            build: 0,
+            revision: 0
+        }
+    );
+    assert_eq!(mscorlib.culture, None);
+    assert_eq!(mscorlib.public_key, PublicKeyOrToken::Token(MSCORLIB_TOKEN));
+    assert!(image.assembly_ref_identity(AssemblyRefHandle(2)).is_err());
+
+    // Dawn isn't strong-named
+    let image = load("Game.dll");
+    let dawn = image.assembly_ref_identity(AssemblyRefHandle(2)).unwrap();
+    assert_eq!(dawn.name, "Dawn");
+    assert_eq!(dawn.public_key, PublicKeyOrToken::None);
+    assert_eq!(dawn.public_key_token(), None);
+}
+
+#[test]
+fn display_name_round_trips() {
+    let image = load("HelloWorld.dll");
+    let mscorlib = image.assembly_ref_identity(AssemblyRefHandle(1)).unwrap();
+    let display_name = mscorlib.to_string();
+    assert_eq!(
+        display_name,
+        "mscorlib, Version=4.0.0.0, Culture=neutral, PublicKeyToken=b77a5c561934e089"
+    );
+
+    let parsed: AssemblyIdentity = display_name.parse().unwrap();
+    assert_eq!(parsed, mscorlib);
+    assert_eq!(parsed.to_string(), display_name);
+
+    let own = image.assembly_identity().unwrap().unwrap();
+    assert_eq!(
+        own.to_string(),
+        "HelloWorld, Version=0.0.0.0, Culture=neutral, PublicKeyToken=null"
+    );
+    assert_eq!(own.to_string().parse::<AssemblyIdentity>().unwrap(), own);
+}
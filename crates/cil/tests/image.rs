@@ -0,0 +1,87 @@
+use cil::{
+    image::CilImage,
+    tables::{HasCustomAttribute, TypeOrMethodDef},
+};
+
+#[test]
+fn section_tail_reads_as_zeroes() {
+    let mut data = std::fs::read(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../../tests/HelloWorld.dll"
+    ))
+    .unwrap();
+    let u16_at = |data: &[u8], offset: usize| u16::from_le_bytes([data[offset], data[offset + 1]]);
+    let u32_at = |data: &[u8], offset: usize| {
+        u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+    };
+
+    // Grow the virtual size of the last section past its raw data
+    let pe_header = u32_at(&data, 0x3C) as usize;
+    let section_count = u16_at(&data, pe_header + 6) as usize;
+    let optional_header_size = u16_at(&data, pe_header + 20) as usize;
+    let section = pe_header + 24 + optional_header_size + (section_count - 1) * 40;
+    let rva = u32_at(&data, section + 12);
+    let raw_size = u32_at(&data, section + 16);
+    data[section + 8..section + 12].copy_from_slice(&(raw_size + 0x100).to_le_bytes());
+
+    let image = CilImage::read(&data).unwrap();
+    let raw = image.data_at_rva(rva, raw_size as usize).unwrap();
+    let tail = image.data_at_rva(rva + raw_size - 4, 8).unwrap();
+    assert_eq!(tail[..4], raw[raw.len() - 4..]);
+    assert_eq!(tail[4..], [0; 4]);
+    assert_eq!(
+        image.data_at_rva(rva + raw_size, 0x100).unwrap().as_ref(),
+        [0; 0x100]
+    );
+    assert!(image.data_at_rva(rva + raw_size, 0x101).is_none());
+}
+
+#[test]
+fn unsorted_tables_find_the_same_rows() {
+    const CUSTOM_ATTRIBUTE: u64 = 1 << 0x0C;
+    const GENERIC_PARAM: u64 = 1 << 0x2A;
+
+    let mut generic = 0;
+    for name in ["Game.dll", "Dawn.dll"] {
+        let path = format!("{}/../../tests/{name}", env!("CARGO_MANIFEST_DIR"));
+        let sorted = CilImage::load(&path).unwrap();
+        assert_eq!(
+            sorted.sorted_tables & (CUSTOM_ATTRIBUTE | GENERIC_PARAM),
+            CUSTOM_ATTRIBUTE | GENERIC_PARAM
+        );
+        let mut unsorted = CilImage::load(&path).unwrap();
+        unsorted.sorted_tables = 0;
+
+        let parents = sorted
+            .custom_attributes
+            .iter()
+            .map(|ca| HasCustomAttribute::try_from(ca.parent_index as u32).unwrap())
+            // A row without attributes
+            .chain([HasCustomAttribute::TypeDef(1)]);
+        for parent in parents {
+            let attributes = |image: &CilImage| {
+                image
+                    .custom_attributes_of(parent)
+                    .map(|ca| (ca.type_index, ca.value_blob_index))
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(attributes(&sorted), attributes(&unsorted), "{parent:?}");
+        }
+
+        let owners = (1..=sorted.type_defs.len() as u32)
+            .map(TypeOrMethodDef::TypeDef)
+            .chain((1..=sorted.method_defs.len() as u32).map(TypeOrMethodDef::MethodDef));
+        for owner in owners {
+            let params = |image: &CilImage| {
+                image
+                    .generic_params_of(owner)
+                    .map(|gp| (gp.number, gp.name.clone()))
+                    .collect::<Vec<_>>()
+            };
+            let found = params(&sorted);
+            generic += usize::from(!found.is_empty());
+            assert_eq!(found, params(&unsorted), "{owner:?}");
+        }
+    }
+    assert!(generic > 0);
+}
@@ -0,0 +1,124 @@
+use cil::{
+    error::Error,
+    image::CilImage,
+    layout::{LayoutEngine, ValueTypeLayout},
+    meta::TypeDefHandle,
+    tables::{ClassLayout, FieldLayout, TypeAttributes},
+};
+
+// `Dawn.SystemPointers`, three IntPtr fields
+const SYSTEM_POINTERS: TypeDefHandle = TypeDefHandle(54);
+// `Dawn.CRawConvarValue`, an IntPtr followed by two single byte value types
+const RAW_CONVAR_VALUE: TypeDefHandle = TypeDefHandle(120);
+// `Dawn.CMapListInfo`, a fixed buffer followed by a uint32
+const MAP_LIST_INFO: TypeDefHandle = TypeDefHandle(115);
+// The fixed buffer of `CMapListInfo`
+const NAME_BUFFER: TypeDefHandle = TypeDefHandle(145);
+
+fn dawn() -> CilImage {
+    CilImage::load(concat!(env!("CARGO_MANIFEST_DIR"), "/../../tests/Dawn.dll")).unwrap()
+}
+
+fn layout(engine: &LayoutEngine, handle: TypeDefHandle) -> ValueTypeLayout {
+    engine.value_type_layout(handle).unwrap().known().unwrap()
+}
+
+fn offsets(layout: &ValueTypeLayout) -> Vec<u32> {
+    layout.fields.iter().map(|f| f.offset).collect()
+}
+
+/// Adds a ClassLayout row for `parent`, keeping the table sorted
+fn set_class_layout(
+    image: &mut CilImage,
+    parent: TypeDefHandle,
+    packing_size: u16,
+    class_size: u32,
+) {
+    image
+        .class_layouts
+        .retain(|row| row.parent as u32 != parent.0);
+    image.class_layouts.push(ClassLayout {
+        packing_size,
+        class_size,
+        parent: parent.0 as u16,
+    });
+    image.class_layouts.sort_by_key(|row| row.parent);
+}
+
+#[test]
+fn pointer_size_defaults_to_the_image() {
+    let image = dawn();
+    assert_eq!(image.pointer_size, 4);
+
+    let system_pointers = layout(&LayoutEngine::new(&image), SYSTEM_POINTERS);
+    assert_eq!((system_pointers.size, system_pointers.alignment), (12, 4));
+    assert_eq!(offsets(&system_pointers), [0, 4, 8]);
+
+    let system_pointers = layout(
+        &LayoutEngine::new(&image).with_pointer_size(8),
+        SYSTEM_POINTERS,
+    );
+    assert_eq!((system_pointers.size, system_pointers.alignment), (24, 8));
+    assert_eq!(offsets(&system_pointers), [0, 8, 16]);
+}
+
+#[test]
+fn explicit_layout_uses_field_offsets() {
+    let mut image = dawn();
+    // Explicit layout, with the first two fields overlapping
+    let flags = &mut image.type_defs[SYSTEM_POINTERS.index()].flags;
+    *flags = TypeAttributes((flags.0 & !0x18) | 0x10);
+    let fields = image.fields_of(SYSTEM_POINTERS).collect::<Vec<_>>();
+    for (field, offset) in fields.into_iter().zip([0, 0, 12]) {
+        image.field_layouts.push(FieldLayout {
+            offset,
+            field: field.0 as u16,
+        });
+    }
+    image.field_layouts.sort_by_key(|row| row.field);
+
+    let system_pointers = layout(&LayoutEngine::new(&image), SYSTEM_POINTERS);
+    assert_eq!(offsets(&system_pointers), [0, 0, 12]);
+    assert_eq!((system_pointers.size, system_pointers.alignment), (16, 4));
+}
+
+#[test]
+fn packing_and_class_size() {
+    let mut image = dawn();
+    let engine = LayoutEngine::new(&image).with_pointer_size(8);
+    let value = layout(&engine, RAW_CONVAR_VALUE);
+    assert_eq!(offsets(&value), [0, 8, 9]);
+    assert_eq!((value.size, value.alignment), (16, 8));
+
+    // .pack 2
+    set_class_layout(&mut image, RAW_CONVAR_VALUE, 2, 0);
+    let engine = LayoutEngine::new(&image).with_pointer_size(8);
+    let value = layout(&engine, RAW_CONVAR_VALUE);
+    assert_eq!(offsets(&value), [0, 8, 9]);
+    assert_eq!((value.size, value.alignment), (10, 2));
+
+    // .pack 2 .size 32, which can only grow the type
+    set_class_layout(&mut image, RAW_CONVAR_VALUE, 2, 32);
+    let engine = LayoutEngine::new(&image).with_pointer_size(8);
+    assert_eq!(layout(&engine, RAW_CONVAR_VALUE).size, 32);
+    set_class_layout(&mut image, RAW_CONVAR_VALUE, 2, 4);
+    let engine = LayoutEngine::new(&image).with_pointer_size(8);
+    assert_eq!(layout(&engine, RAW_CONVAR_VALUE).size, 10);
+}
+
+#[test]
+fn struct_containing_itself_is_rejected() {
+    let mut image = dawn();
+    // Give the fixed buffer's only field the type of the buffer itself, which is the signature of
+    // the field of `CMapListInfo` that holds the buffer
+    let holder = image.fields_of(MAP_LIST_INFO).next().unwrap();
+    let buffer_field = image.fields_of(NAME_BUFFER).next().unwrap();
+    image.fields[buffer_field.index()].signature_blob_index =
+        image.fields[holder.index()].signature_blob_index;
+
+    let engine = LayoutEngine::new(&image);
+    assert!(matches!(
+        engine.value_type_layout(MAP_LIST_INFO),
+        Err(Error::RecursiveLayout(token)) if token == NAME_BUFFER.token()
+    ));
+}
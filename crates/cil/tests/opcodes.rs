@@ -0,0 +1,154 @@
+mod common;
+
+use cil::{
+    meta::{MethodDefHandle, Token},
+    opcodes::{
+        DisabledFaultChecks, Instruction, Operand, OperandType, Prefixes, RawOpcode, StackEffect,
+        decode_instructions,
+    },
+    tables::StandAloneSig,
+};
+use common::{load, with_blobs};
+
+/// `HelloWorld::.ctor(int32)` as a MemberRef and as a MethodDef
+const CTOR_REF: Token = Token(0x0A000001);
+const CTOR_DEF: Token = Token(0x06000002);
+/// `Console::WriteLine(string)`
+const WRITE_LINE: Token = Token(0x0A000007);
+
+#[test]
+fn branch_targets_are_absolute() {
+    let code = [
+        0x00, // IL_0000: nop
+        0x45, 0x02, 0x00, 0x00, 0x00, // IL_0001: switch (IL_000e, IL_0000)
+        0x00, 0x00, 0x00, 0x00, //
+        0xF2, 0xFF, 0xFF, 0xFF, //
+        0x2B, 0xF0, // IL_000e: br.s IL_0000
+        0x2A, // IL_0010: ret
+    ];
+    let instructions = decode_instructions(&code).unwrap();
+    let offsets = instructions.iter().map(|i| i.offset).collect::<Vec<_>>();
+    assert_eq!(offsets, [0x00, 0x01, 0x0e, 0x10]);
+
+    let switch = &instructions[1];
+    assert!(matches!(switch.opcode, RawOpcode::Switch { .. }));
+    assert_eq!(switch.operand, Operand::Switch(vec![0x0e, 0x00]));
+    assert_eq!(switch.branch_targets(), [0x0e, 0x00]);
+    assert_eq!(switch.next_offset(), 0x0e);
+
+    let branch = &instructions[2];
+    assert_eq!(branch.operand, Operand::Target(0x00));
+    assert_eq!(branch.to_string(), "IL_000e: br.s IL_0000");
+    assert!(instructions[3].branch_targets().is_empty());
+}
+
+#[test]
+fn stack_effects_of_calls() {
+    let mut image = load("HelloWorld.dll");
+    let (blobs, indices) = with_blobs(
+        &image.blobs,
+        &[
+            // int32 (int32, string)
+            &[0x00, 0x02, 0x08, 0x08, 0x0E],
+            // instance explicit void (object)
+            &[0x60, 0x01, 0x01, 0x1C],
+        ],
+    );
+    image.blobs = blobs;
+    image.stand_alone_sigs.push(StandAloneSig {
+        signature_blob_index: indices[0],
+    });
+    let call_site = Token(0x11000000 | image.stand_alone_sigs.len() as u32);
+
+    let main = image.method_signature(MethodDefHandle(3)).unwrap();
+    let effect = |opcode: RawOpcode| {
+        let effect = Instruction::new(0, Prefixes::default(), opcode)
+            .stack_effect(&image, &main)
+            .unwrap();
+        (effect.pops, effect.pushes)
+    };
+
+    // The object is created rather than popped, and pushed as the result
+    assert_eq!(effect(RawOpcode::NewObj { ctor: CTOR_REF }), (1, 1));
+    assert_eq!(effect(RawOpcode::NewObj { ctor: CTOR_DEF }), (1, 1));
+    assert_eq!(effect(RawOpcode::Call { method: CTOR_REF }), (2, 0));
+    assert_eq!(effect(RawOpcode::CallVirt { method: CTOR_DEF }), (2, 0));
+    assert_eq!(effect(RawOpcode::Call { method: WRITE_LINE }), (1, 0));
+    // Arguments and the function pointer
+    assert_eq!(
+        effect(RawOpcode::CallInd {
+            callsitedescr: call_site
+        }),
+        (3, 1)
+    );
+    assert_eq!(effect(RawOpcode::Ret {}), (0, 0));
+
+    // With an explicit `this`, the instance is the first parameter of the signature
+    image.member_refs[WRITE_LINE.index() as usize - 1].signature_blob_index = indices[1];
+    let explicit_this = Instruction::new(
+        0,
+        Prefixes::default(),
+        RawOpcode::Call { method: WRITE_LINE },
+    )
+    .stack_effect(&image, &main)
+    .unwrap();
+    assert_eq!(explicit_this, StackEffect { pops: 1, pushes: 0 });
+}
+
+#[test]
+fn static_field_opcodes() {
+    let code = [0x7E, 0x01, 0x00, 0x00, 0x04, 0x7F, 0x01, 0x00, 0x00, 0x04];
+    let instructions = decode_instructions(&code).unwrap();
+    let field = Token(0x04000001);
+    assert_eq!(instructions[0].opcode, RawOpcode::LdsFld { field });
+    assert_eq!(instructions[0].to_string(), "IL_0000: ldsfld 0x04000001");
+    assert_eq!(instructions[1].opcode, RawOpcode::LdsFlda { field });
+    assert_eq!(instructions[1].to_string(), "IL_0005: ldsflda 0x04000001");
+}
+
+#[test]
+fn ckfinite_has_no_operand() {
+    let instructions = decode_instructions(&[0xC3, 0x2A]).unwrap();
+    assert_eq!(instructions[0].opcode, RawOpcode::CkFinite {});
+    assert_eq!(
+        instructions[0].opcode.operand_type(),
+        OperandType::InlineNone
+    );
+    assert_eq!(instructions[0].operand, Operand::None);
+    assert_eq!(instructions[1].offset, 1);
+}
+
+#[test]
+fn prefixes_attach_to_the_next_instruction() {
+    let code = [
+        0x00, // IL_0000: nop
+        0xFE, 0x12, 0x02, // IL_0001: unaligned. 2
+        0xFE, 0x13, //        volatile.
+        0x4A, //              ldind.i4
+        0xFE, 0x19, 0x05, // IL_0007: no. 0x5
+        0x9A, //              ldelem.ref
+        0x2A, // IL_000b: ret
+    ];
+    let instructions = decode_instructions(&code).unwrap();
+    let offsets = instructions.iter().map(|i| i.offset).collect::<Vec<_>>();
+    assert_eq!(offsets, [0x00, 0x01, 0x07, 0x0b]);
+
+    let load = &instructions[1];
+    assert_eq!(load.opcode, RawOpcode::LdInd_I4 {});
+    assert_eq!(load.prefixes.unaligned, Some(2));
+    assert!(load.prefixes.volatile);
+    assert_eq!(load.next_offset(), 0x07);
+    assert_eq!(load.to_string(), "IL_0001: unaligned. 2 volatile. ldind.i4");
+
+    let element = &instructions[2];
+    assert_eq!(element.opcode, RawOpcode::LdElem_Ref {});
+    assert_eq!(
+        element.prefixes.disabled_checks,
+        DisabledFaultChecks::TYPE_CHECK | DisabledFaultChecks::NULL_CHECK
+    );
+    assert_eq!(element.next_offset(), 0x0b);
+
+    // A prefix has to be followed by the instruction it modifies
+    assert!(decode_instructions(&[0x00, 0xFE, 0x14]).is_err());
+    assert!(decode_instructions(&[0xFE, 0x12]).is_err());
+}
@@ -0,0 +1,153 @@
+mod common;
+
+use cil::{
+    constant::ConstantValue,
+    marshal::{MarshalDescriptor, NativeType},
+    meta::{MethodDefHandle, ParamHandle},
+    signature::Element,
+    tables::{FieldMarshal, HasFieldMarshal},
+};
+use common::{load, with_blobs};
+
+#[test]
+fn parameters_without_param_rows() {
+    // `HelloWorld::.ctor(int32)` has no Param rows at all
+    let image = load("HelloWorld.dll");
+    let parameters = image.method_parameters(MethodDefHandle(2)).unwrap();
+    assert_eq!(parameters.return_value.handle, None);
+    let [parameter] = &parameters.parameters[..] else {
+        panic!("expected one parameter, found {parameters:?}");
+    };
+    assert_eq!(parameter.sequence, 1);
+    assert_eq!(parameter.handle, None);
+    assert_eq!(parameter.name, None);
+    assert_eq!(parameter.param_type, Element::I4);
+    assert_eq!(parameter.flags.0, 0);
+
+    // `DamageInfo::.ctor`, with the row of its first parameter moved past the end of the signature
+    let mut image = load("Game.dll");
+    let constructor = MethodDefHandle(81);
+    let attacker = image.params_of(constructor).next().unwrap();
+    assert_eq!(image.params[attacker.index()].name, "attacker");
+    image.params[attacker.index()].sequence = 20;
+
+    let parameters = image.method_parameters(constructor).unwrap();
+    assert_eq!(parameters.parameters.len(), 9);
+    assert_eq!(parameters.parameters[0].handle, None);
+    assert_eq!(parameters.parameters[0].name, None);
+    assert_eq!(parameters.parameters[1].name.as_deref(), Some("damage"));
+    assert_eq!(parameters.parameters[1].sequence, 2);
+}
+
+#[test]
+fn return_value_param_row() {
+    // `Dawn.HandleExtensions::WithIndex<T>`, whose Param rows start with one for the return value
+    let image = load("Dawn.dll");
+    let parameters = image.method_parameters(MethodDefHandle(1)).unwrap();
+    assert_eq!(parameters.return_value.sequence, 0);
+    assert_eq!(parameters.return_value.handle, Some(ParamHandle(1)));
+    assert_eq!(parameters.return_value.name, None);
+    assert_eq!(parameters.parameters[0].handle, Some(ParamHandle(2)));
+    assert_eq!(parameters.parameters[0].name.as_deref(), Some("self"));
+}
+
+#[test]
+fn default_values() {
+    let image = load("Game.dll");
+    let parameters = image.method_parameters(MethodDefHandle(81)).unwrap();
+    let defaults = parameters
+        .parameters
+        .iter()
+        .map(|p| (p.name.as_deref().unwrap(), p.default_value.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        defaults,
+        [
+            ("attacker", None),
+            ("damage", None),
+            ("inflictor", Some(ConstantValue::Null)),
+            ("position", Some(ConstantValue::Null)),
+            ("force", Some(ConstantValue::Null)),
+            ("damageType", Some(ConstantValue::I4(0))),
+            ("isWeakpoint", Some(ConstantValue::Boolean(false))),
+            ("weakpointMultiplier", Some(ConstantValue::R4(1.0))),
+            ("flags", Some(ConstantValue::I4(0))),
+        ]
+    );
+    let optional = &parameters.parameters[7];
+    assert!(optional.is_optional() && optional.has_default());
+    assert!(!parameters.parameters[1].has_default());
+
+    assert_eq!(
+        ConstantValue::parse(0x0E, &[b'h', 0, b'i', 0]).unwrap(),
+        ConstantValue::String("hi".to_string())
+    );
+    assert_eq!(
+        ConstantValue::parse(0x0A, &(-2i64).to_le_bytes()).unwrap(),
+        ConstantValue::I8(-2)
+    );
+    assert!(ConstantValue::parse(0x08, &[1, 0]).is_err());
+}
+
+#[test]
+fn marshal_descriptors() {
+    // Interop.dll has no FieldMarshal rows of its own, add `[MarshalAs(UnmanagedType.LPArray,
+    // ArraySubType = UnmanagedType.I4, SizeParamIndex = 1)]` to the parameter of
+    // `RuntimeDllImportCall(int)`
+    let mut image = load("Interop.dll");
+    let method = (1..=image.method_defs.len() as u32)
+        .map(MethodDefHandle)
+        .find(|m| image.method_defs[m.index()].0.name == "RuntimeDllImportCall")
+        .unwrap();
+    let param = image.params_of(method).next().unwrap();
+    let (blobs, indices) = with_blobs(&image.blobs, &[&[0x2A, 0x07, 0x01, 0x00, 0x01]]);
+    image.blobs = blobs;
+    image.field_marshals.push(FieldMarshal {
+        parent: HasFieldMarshal::Param(param.0).encode() as u16,
+        native_type_blob_index: indices[0],
+    });
+
+    let parameters = image.method_parameters(method).unwrap();
+    assert_eq!(
+        parameters.parameters[0].marshal,
+        Some(MarshalDescriptor::Array {
+            element_type: Some(NativeType::I4),
+            size_parameter_index: Some(1),
+            element_count: Some(0),
+        })
+    );
+    assert_eq!(parameters.return_value.marshal, None);
+
+    let parse = |blob: &[u8]| MarshalDescriptor::parse(blob).unwrap();
+    assert_eq!(
+        parse(&[0x15]),
+        MarshalDescriptor::Simple(NativeType::LPWStr)
+    );
+    assert_eq!(
+        parse(&[0x1E, 0x10, 0x04]),
+        MarshalDescriptor::FixedArray {
+            size: 16,
+            element_type: Some(NativeType::U1)
+        }
+    );
+    // The parameter index is only meaningful if the trailing flags say so
+    assert_eq!(
+        parse(&[0x2A, 0x50, 0x00, 0x08, 0x00]),
+        MarshalDescriptor::Array {
+            element_type: None,
+            size_parameter_index: None,
+            element_count: Some(8),
+        }
+    );
+    assert_eq!(
+        parse(&[0x1C, 0x02]),
+        MarshalDescriptor::Interface {
+            kind: NativeType::Interface,
+            iid_parameter_index: Some(2)
+        }
+    );
+    assert_eq!(
+        parse(&[0x7F, 0x01]),
+        MarshalDescriptor::Unknown(vec![0x7F, 0x01])
+    );
+}
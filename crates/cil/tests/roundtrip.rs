@@ -0,0 +1,75 @@
+mod common;
+
+use cil::{
+    assembler::assemble, body::decode_method_body, encoder::encode_method_body, image::CilImage,
+    signature::StandaloneMethodSignature,
+};
+use common::test_images;
+
+#[test]
+fn method_bodies_survive_encoding() {
+    for path in test_images() {
+        let image = CilImage::load(&path).unwrap();
+        for (method, header, instructions) in &image.method_defs {
+            if method.rva == 0 {
+                continue;
+            }
+
+            let context = format!("{} in {}", method.name, path.display());
+            let body = encode_method_body(header, instructions)
+                .unwrap_or_else(|e| panic!("failed to encode {context}: {e}"));
+            let (decoded_header, decoded) = decode_method_body(&body)
+                .unwrap_or_else(|e| panic!("failed to decode {context}: {e}"));
+
+            assert_eq!(&decoded, instructions, "instructions of {context}");
+            // The header includes the exception clauses
+            assert_eq!(&decoded_header, header, "header of {context}");
+        }
+    }
+}
+
+#[test]
+fn switch_without_targets() {
+    let module = assemble(
+        ".class public A extends [mscorlib]System.Object {
+            .method public static void M() cil managed {
+                ldc.i4.0
+                switch ()
+                ret
+            }
+        }",
+    )
+    .unwrap();
+    let (_, _, method) = module.methods().next().unwrap();
+    let (header, instructions) = method.body.as_ref().unwrap();
+
+    let image = CilImage::load(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../../tests/HelloWorld.dll"
+    ))
+    .unwrap();
+    let signature = StandaloneMethodSignature::default();
+    assert_eq!(image.max_stack(&signature, instructions, &[]).unwrap(), 1);
+
+    let body = image
+        .encode_method_body(&signature, header, instructions)
+        .unwrap();
+    let (_, decoded) = decode_method_body(&body).unwrap();
+    assert_eq!(&decoded, instructions);
+}
+
+#[test]
+fn code_size_past_the_end_is_rejected() {
+    // Fat header claiming 4 GiB of code, followed by a single `ret`
+    let mut body = vec![0x03, 0x30, 0x08, 0x00, 0xFF, 0xFF, 0xFF, 0xFF];
+    body.extend([0x00, 0x00, 0x00, 0x00, 0x2A]);
+    assert!(decode_method_body(&body).is_err());
+
+    body[4..8].copy_from_slice(&1u32.to_le_bytes());
+    let (header, instructions) = decode_method_body(&body).unwrap();
+    assert_eq!(header.code_size, 1);
+    assert_eq!(instructions.len(), 1);
+
+    // Tiny header claiming two bytes of code
+    assert!(decode_method_body(&[0x0A, 0x2A]).is_err());
+}
@@ -0,0 +1,223 @@
+mod common;
+
+use cil::{
+    error::Error,
+    format::{TypeNameFormatter, TypeNameStyle},
+    image::CilImage,
+    meta::{FieldHandle, MemberDef, MemberRefHandle, MethodDefHandle, TypeRefHandle},
+    signature::{
+        Element, FieldSignature, LocalVarSignature, MemberRefSignature, MethodCallType,
+        MethodSpecSignature, PropertySignature, SignatureComparer, SignatureKind,
+        StandaloneMethodSignature, TypeSpecSignature,
+    },
+    tables::TypeDefOrRef,
+};
+use common::{load, with_blobs};
+
+#[test]
+fn internal_handle_is_pointer_sized() {
+    // FIELD, ELEMENT_TYPE_INTERNAL, followed by a pointer
+    let blob = [0x06, 0x21, 0x78, 0x56, 0x34, 0x12, 0xF0, 0xDE, 0xBC, 0x9A];
+
+    let signature = FieldSignature::parse(&blob[..6], 4).unwrap();
+    assert_eq!(signature.field_type, Element::Internal(0x12345678));
+
+    let signature = FieldSignature::parse(&blob, 8).unwrap();
+    assert_eq!(signature.field_type, Element::Internal(0x9ABCDEF012345678));
+}
+
+#[test]
+fn nested_calling_convention_is_validated() {
+    // DEFAULT, 1 parameter, returns void, parameter is FNPTR with calling convention `cc`
+    let blob = |cc: u8| [0x00, 0x01, 0x01, 0x1B, cc, 0x00, 0x01];
+
+    let signature = StandaloneMethodSignature::parse(&blob(0x09), 4).unwrap();
+    let Element::FnPtr(inner) = &signature.parameters[0] else {
+        panic!(
+            "expected a function pointer, found {:?}",
+            signature.parameters[0]
+        );
+    };
+    assert_eq!(inner.header.call_type(), MethodCallType::Unmanaged);
+
+    for cc in (0x06..=0x08).chain(0x0A..=0x0F) {
+        assert!(StandaloneMethodSignature::parse(&blob(cc), 4).is_err());
+    }
+}
+
+#[test]
+fn cyclic_enclosing_type_refs_terminate() {
+    let mut image =
+        CilImage::load(concat!(env!("CARGO_MANIFEST_DIR"), "/../../tests/Game.dll")).unwrap();
+    // ResolutionScope::TypeRef, TypeRef 1 and 2 are nested in each other
+    image.type_refs[0].resolution_scope = (2 << 2) | 3;
+    image.type_refs[1].resolution_scope = (1 << 2) | 3;
+
+    let comparer = SignatureComparer::new(&image, &image);
+    assert!(comparer.type_refs_equal(TypeDefOrRef::TypeRef(1), TypeDefOrRef::TypeRef(1)));
+    assert!(!comparer.type_refs_equal(TypeDefOrRef::TypeRef(1), TypeDefOrRef::TypeRef(2)));
+    assert!(image.type_ref_name(TypeRefHandle(1)).is_some());
+    TypeNameFormatter::new(&image, TypeNameStyle::ILAsm).format_type(TypeDefOrRef::TypeRef(1));
+}
+
+#[test]
+fn each_signature_kind_parses() {
+    // FIELD int32
+    let field = FieldSignature::parse(&[0x06, 0x08], 4).unwrap();
+    assert_eq!(field.field_type, Element::I4);
+
+    // LOCAL_SIG, 2 locals: int32, pinned string
+    let locals = LocalVarSignature::parse(&[0x07, 0x02, 0x08, 0x45, 0x0E], 4).unwrap();
+    assert_eq!(
+        locals.locals,
+        [Element::I4, Element::Pinned(Box::new(Element::String))]
+    );
+
+    // PROPERTY | HASTHIS, 1 parameter, returns bool, indexed by int32
+    let property = PropertySignature::parse(&[0x28, 0x01, 0x02, 0x08], 4).unwrap();
+    assert!(property.has_this);
+    assert_eq!(property.property_type, Element::Boolean);
+    assert_eq!(property.parameters, [Element::I4]);
+
+    // GENERICINST, 2 arguments: string, object
+    let spec = MethodSpecSignature::parse(&[0x0A, 0x02, 0x0E, 0x1C], 4).unwrap();
+    assert_eq!(spec.generic_args, [Element::String, Element::Object]);
+
+    // int32[], without a leading calling convention
+    let spec = TypeSpecSignature::parse(&[0x1D, 0x08], 4).unwrap();
+    assert_eq!(spec.element, Element::SzArray(Box::new(Element::I4)));
+}
+
+#[test]
+fn wrong_signature_kind_is_rejected() {
+    let unexpected = |result: Result<(), Error>, expected| match result {
+        Err(Error::UnexpectedSignatureKind { expected: e, found }) => {
+            assert_eq!(e, expected);
+            found
+        }
+        other => panic!("expected {expected:?} to be rejected, got {other:?}"),
+    };
+
+    // A field signature parsed as every other kind
+    let field = [0x06, 0x08];
+    assert_eq!(
+        unexpected(
+            LocalVarSignature::parse(&field, 4).map(drop),
+            SignatureKind::LocalVar
+        ),
+        0x06
+    );
+    unexpected(
+        PropertySignature::parse(&field, 4).map(drop),
+        SignatureKind::Property,
+    );
+    unexpected(
+        MethodSpecSignature::parse(&field, 4).map(drop),
+        SignatureKind::MethodSpec,
+    );
+    unexpected(
+        StandaloneMethodSignature::parse(&field, 4).map(drop),
+        SignatureKind::StandaloneMethod,
+    );
+
+    // A local variable signature parsed as a field
+    assert_eq!(
+        unexpected(
+            FieldSignature::parse(&[0x07, 0x01, 0x08], 4).map(drop),
+            SignatureKind::Field
+        ),
+        0x07
+    );
+
+    // Kind 0x0B and element type 0x17 don't exist
+    assert!(FieldSignature::parse(&[0x0B, 0x08], 4).is_err());
+    assert!(TypeSpecSignature::parse(&[0x17], 4).is_err());
+}
+
+#[test]
+fn member_refs_bind_to_definitions() {
+    let game = load("Game.dll");
+    let dawn = load("Dawn.dll");
+    let comparer = SignatureComparer::new(&game, &dawn);
+
+    // `Dawn.GameObject::Transform` and `Dawn.GameObject::.ctor(string)`
+    assert_eq!(
+        comparer.bind_member_ref(MemberRefHandle(62)).unwrap(),
+        Some(MemberDef::Field(FieldHandle(5)))
+    );
+    assert_eq!(
+        comparer.bind_member_ref(MemberRefHandle(59)).unwrap(),
+        Some(MemberDef::Method(MethodDefHandle(44)))
+    );
+
+    let mut bound = 0;
+    for (i, member_ref) in game.member_refs.iter().enumerate() {
+        let name = match comparer
+            .bind_member_ref(MemberRefHandle(i as u32 + 1))
+            .unwrap()
+        {
+            Some(MemberDef::Method(m)) => &dawn.method_defs[m.index()].0.name,
+            Some(MemberDef::Field(f)) => &dawn.fields[f.index()].name,
+            None => continue,
+        };
+        assert_eq!(name, &member_ref.name);
+        bound += 1;
+    }
+    assert!(bound > 30);
+}
+
+#[test]
+fn member_ref_binding_options() {
+    let mut game = load("Game.dll");
+    let dawn = load("Dawn.dll");
+    // `Dawn.Components.CustomModel::set_ModelPath(string)`
+    let set_model_path = MemberRefHandle(61);
+    let expected = Some(MemberDef::Method(MethodDefHandle(502)));
+
+    // Reference a newer Dawn
+    game.assembly_refs[1].major_version = 2;
+    let comparer = SignatureComparer::new(&game, &dawn);
+    assert_eq!(comparer.bind_member_ref(set_model_path).unwrap(), None);
+    let comparer = comparer.ignore_assembly_versions(true);
+    assert_eq!(comparer.bind_member_ref(set_model_path).unwrap(), expected);
+
+    // HASTHIS, 1 parameter, returns modopt(TypeRef 1) void, takes a string
+    game.assembly_refs[1].major_version = 1;
+    let (blobs, indices) = with_blobs(&game.blobs, &[&[0x20, 0x01, 0x20, 0x05, 0x01, 0x0E]]);
+    game.blobs = blobs;
+    game.member_refs[set_model_path.index()].signature_blob_index = indices[0];
+    let comparer = SignatureComparer::new(&game, &dawn);
+    assert_eq!(comparer.bind_member_ref(set_model_path).unwrap(), None);
+    let comparer = comparer.ignore_modopts(true);
+    assert_eq!(comparer.bind_member_ref(set_model_path).unwrap(), expected);
+}
+
+#[test]
+fn vararg_member_ref_binds_to_fixed_parameters() {
+    let mut game = load("Game.dll");
+    let mut dawn = load("Dawn.dll");
+    let register = MemberRefHandle(82);
+    let definition = MethodDefHandle(333);
+
+    // VARARG, returns void, takes a string and an object, then an int32 after the sentinel
+    let (blobs, indices) = with_blobs(&game.blobs, &[&[0x05, 0x03, 0x01, 0x0E, 0x1C, 0x41, 0x08]]);
+    game.blobs = blobs;
+    game.member_refs[register.index()].signature_blob_index = indices[0];
+    // The definition only declares the fixed parameters
+    let (blobs, indices) = with_blobs(&dawn.blobs, &[&[0x05, 0x02, 0x01, 0x0E, 0x1C]]);
+    dawn.blobs = blobs;
+    dawn.method_defs[definition.index()].0.signature_blob_index = indices[0];
+
+    let signature = game.member_ref_signature(register).unwrap();
+    let MemberRefSignature::Method(signature) = signature else {
+        panic!("expected a method signature");
+    };
+    assert_eq!(signature.sentinel, Some(2));
+    assert_eq!(signature.parameters.len(), 3);
+
+    let comparer = SignatureComparer::new(&game, &dawn);
+    assert_eq!(
+        comparer.bind_member_ref(register).unwrap(),
+        Some(MemberDef::Method(definition))
+    );
+}
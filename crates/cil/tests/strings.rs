@@ -0,0 +1,28 @@
+use cil::strings::{StringHeap, UserStringHeap};
+
+#[test]
+fn user_strings_with_unpaired_surrogates_are_kept() {
+    // Empty entry, "A" and a lone high surrogate, each followed by its flag byte
+    let heap = UserStringHeap::new(vec![0x00, 0x03, 0x41, 0x00, 0x00, 0x03, 0x00, 0xD8, 0x01]);
+
+    let entries = heap.iter().collect::<Vec<_>>();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].1.value, "A");
+    assert_eq!(entries[1].0.index(), 5);
+    assert_eq!(entries[1].1.value, "\u{FFFD}");
+    assert!(entries[1].1.has_special_characters);
+    assert_eq!(heap.get(entries[1].0), None);
+}
+
+#[test]
+fn string_indices_stop_at_16_bits() {
+    let mut data = vec![0];
+    for _ in 0..0x2100 {
+        data.extend_from_slice(b"abcdefg\0");
+    }
+
+    let heap = StringHeap::new(data);
+    let entries = heap.iter().collect::<Vec<_>>();
+    assert_eq!(entries.last().unwrap().0.0, 0xFFF9);
+    assert!(entries.iter().all(|(_, s)| s.is_empty() || s == "abcdefg"));
+}
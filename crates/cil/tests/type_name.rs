@@ -0,0 +1,42 @@
+use cil::{
+    hierarchy::TypeHierarchy, image::CilImage, signature::Element, type_name::ReflectionTypeName,
+};
+
+fn load(name: &str) -> CilImage {
+    CilImage::load(format!("{}/../../tests/{name}", env!("CARGO_MANIFEST_DIR"))).unwrap()
+}
+
+#[test]
+fn type_refs_resolve_to_value_types() {
+    let game = load("Game.dll");
+    let resolver = vec![load("Dawn.dll")];
+    let hierarchy = TypeHierarchy::new(&game).with_resolver(&resolver);
+
+    for name in ["Dawn.Vector3, Dawn", "Dawn.Native.Input.Key, Dawn"] {
+        let name = ReflectionTypeName::parse(name).unwrap();
+        assert!(matches!(name.resolve(&game).unwrap(), Element::Class(_)));
+
+        let resolved = name.resolve_in(&hierarchy).unwrap();
+        assert!(std::ptr::eq(resolved.image, &game));
+        assert!(matches!(resolved.element, Element::ValueType(_)));
+    }
+}
+
+#[test]
+fn generic_arguments_are_resolved() {
+    let game = load("Game.dll");
+    let resolver = vec![load("Dawn.dll")];
+    let hierarchy = TypeHierarchy::new(&game).with_resolver(&resolver);
+
+    let name =
+        ReflectionTypeName::parse("System.Collections.Generic.List`1[[Dawn.Vector3, Dawn]][]")
+            .unwrap();
+    let resolved = name.resolve_in(&hierarchy).unwrap();
+    let Element::SzArray(element) = &resolved.element else {
+        panic!("expected an array, found {:?}", resolved.element);
+    };
+    let Element::GenericInst { generic_args, .. } = element.as_ref() else {
+        panic!("expected a generic instance, found {element:?}");
+    };
+    assert!(matches!(generic_args[..], [Element::ValueType(_)]));
+}
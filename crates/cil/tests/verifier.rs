@@ -0,0 +1,168 @@
+use cil::{
+    assembler::assemble,
+    image::{CilImage, MethodHeader},
+    meta::MethodDefHandle,
+    signature::Element,
+    verifier::{StackType, VerificationErrorKind, Verifier},
+};
+
+/// Verifies `code` as the body of an instance method returning `void` without locals
+fn errors(code: &str) -> Vec<VerificationErrorKind> {
+    let source = format!(
+        ".class public A extends [mscorlib]System.Object {{
+            .method public instance void M() cil managed {{ {code} }}
+        }}"
+    );
+    let module = assemble(&source).unwrap();
+    let (_, _, method) = module.methods().next().unwrap();
+    let (header, instructions) = method.body.clone().unwrap();
+
+    // Borrow a method with the same shape from a real image to hold the body
+    let mut image = CilImage::load(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../../tests/HelloWorld.dll"
+    ))
+    .unwrap();
+    let target = (0..image.method_defs.len())
+        .find(|&i| {
+            let (_, header, _) = &image.method_defs[i];
+            let signature = image
+                .method_signature(MethodDefHandle(i as u32 + 1))
+                .unwrap();
+            header.local_var_sig_token.is_none()
+                && signature.header.has_this()
+                && signature.parameters.is_empty()
+                && signature.return_type == Element::Void
+        })
+        .unwrap();
+    image.method_defs[target].1 = MethodHeader {
+        local_var_sig_token: None,
+        ..header
+    };
+    image.method_defs[target].2 = instructions;
+
+    Verifier::new(&image)
+        .verify(MethodDefHandle(target as u32 + 1))
+        .unwrap()
+        .errors
+        .into_iter()
+        .map(|error| error.kind)
+        .collect()
+}
+
+#[test]
+fn valid_body_has_no_errors() {
+    assert_eq!(
+        errors("ldc.i4.1 ldc.i4.2 add brtrue done ldc.i4.0 pop done: ret"),
+        []
+    );
+}
+
+#[test]
+fn stack_underflow() {
+    assert_eq!(
+        errors("ldc.i4.0 add pop ret"),
+        [VerificationErrorKind::StackUnderflow {
+            needed: 2,
+            available: 1
+        }]
+    );
+}
+
+#[test]
+fn depth_mismatch() {
+    assert_eq!(
+        errors("ldc.i4.0 brtrue join ldc.i4.1 join: ret"),
+        [VerificationErrorKind::DepthMismatch {
+            expected: 0,
+            found: 1
+        }]
+    );
+}
+
+#[test]
+fn inconsistent_merge() {
+    assert_eq!(
+        errors("ldc.i4.0 brtrue other ldc.i4.1 br join other: ldc.r8 1.0 join: pop ret"),
+        [VerificationErrorKind::InconsistentMerge {
+            slot: 0,
+            expected: StackType::Int32,
+            found: StackType::Float
+        }]
+    );
+}
+
+#[test]
+fn unexpected_type() {
+    assert_eq!(
+        errors("ldc.i4.1 ldc.i8 2 add pop ret"),
+        [VerificationErrorKind::UnexpectedType {
+            expected: "operands of matching numeric types",
+            found: StackType::Int64
+        }]
+    );
+}
+
+#[test]
+fn max_stack_exceeded() {
+    assert_eq!(
+        errors(".maxstack 1 ldc.i4.1 ldc.i4.2 pop pop ret"),
+        [VerificationErrorKind::MaxStackExceeded {
+            depth: 2,
+            max_stack: 1
+        }]
+    );
+}
+
+#[test]
+fn ret_with_non_empty_stack() {
+    assert_eq!(
+        errors("ldc.i4.1 ret"),
+        [VerificationErrorKind::NonEmptyStack("ret")]
+    );
+}
+
+#[test]
+fn leave_with_non_empty_stack() {
+    assert_eq!(
+        errors(".try { ldc.i4.1 leave done } finally { endfinally } done: ret"),
+        [VerificationErrorKind::NonEmptyStack("leave")]
+    );
+}
+
+#[test]
+fn endfilter_with_non_empty_stack() {
+    assert_eq!(
+        errors(
+            ".try { leave done }
+             filter { pop ldc.i4.1 ldc.i4.1 endfilter }
+             { pop leave done }
+             done: ret"
+        ),
+        [VerificationErrorKind::NonEmptyStack("endfilter")]
+    );
+}
+
+#[test]
+fn try_entry_with_non_empty_stack() {
+    assert_eq!(
+        errors("ldc.i4.1 .try { pop leave done } finally { endfinally } done: ret"),
+        [VerificationErrorKind::TryEntryWithNonEmptyStack]
+    );
+}
+
+#[test]
+fn falls_off_end() {
+    assert_eq!(errors("nop"), [VerificationErrorKind::FallsOffEnd]);
+}
+
+#[test]
+fn invalid_local_and_argument() {
+    assert_eq!(
+        errors("ldloc.0 pop ldarg.1 pop ret"),
+        [
+            VerificationErrorKind::InvalidLocal(0),
+            VerificationErrorKind::InvalidArgument(1)
+        ]
+    );
+}
@@ -0,0 +1,296 @@
+mod common;
+
+use cil::{
+    hierarchy::TypeHierarchy,
+    image::CilImage,
+    meta::{MethodDefHandle, TypeDefHandle},
+    signature::Element,
+    tables::{MethodDefOrRef, MethodImpl, TypeDefOrRef},
+    vtable::{MethodTable, ResolvedMethodDef},
+};
+use common::load;
+
+/// `Dawn.Component`, whose `OnUpdate` is overridden in Dawn and in Game
+const COMPONENT_ON_UPDATE: MethodDefHandle = MethodDefHandle(10);
+/// `Dawn.Console.DebugMenu::get_Title`, abstract
+const DEBUG_MENU_GET_TITLE: MethodDefHandle = MethodDefHandle(470);
+/// `Dawn.Console.DebugMenu::get_EntryCount`, abstract
+const DEBUG_MENU_GET_ENTRY_COUNT: MethodDefHandle = MethodDefHandle(473);
+/// `Dawn.Console.CutscenePicker`, derives from `DebugMenu`
+const CUTSCENE_PICKER: TypeDefHandle = TypeDefHandle(74);
+const CUTSCENE_PICKER_GET_TITLE: MethodDefHandle = MethodDefHandle(459);
+/// `Dawn.Components.InlineComponent`, derives from `Component`
+const INLINE_COMPONENT: TypeDefHandle = TypeDefHandle(80);
+/// `Dawn.Interop.INativeObject::get_IsValid`
+const NATIVE_OBJECT_IS_VALID: MethodDefHandle = MethodDefHandle(424);
+/// `Dawn.Interop.NativeObject<T>`, implements `INativeObject` explicitly
+const NATIVE_OBJECT: TypeDefHandle = TypeDefHandle(62);
+
+/// `Game.Components.BaseHealth`, derives from `Dawn.Component`
+const BASE_HEALTH: TypeDefHandle = TypeDefHandle(26);
+/// `Game.Components.CombatantHealth`, derives from `BaseHealth`
+const COMBATANT_HEALTH: TypeDefHandle = TypeDefHandle(27);
+/// `Game.MapLoadTrigger`, implements `ITriggerListener.OnTriggerEnter` explicitly
+const MAP_LOAD_TRIGGER: TypeDefHandle = TypeDefHandle(10);
+
+fn name((image, handle): ResolvedMethodDef<'_>) -> String {
+    let owner = image.declaring_type_of(handle).unwrap();
+    format!(
+        "{}::{}",
+        image.type_def_name(owner).unwrap(),
+        image.method_defs[handle.index()].0.name
+    )
+}
+
+fn names(methods: &[ResolvedMethodDef<'_>]) -> Vec<String> {
+    methods.iter().map(|&m| name(m)).collect()
+}
+
+/// Implementations of the slots of a method table, in slot order
+fn implementations(table: &MethodTable<'_>) -> Vec<String> {
+    table.slots.iter().map(|s| name(s.implementation)).collect()
+}
+
+fn set_flags(image: &mut CilImage, method: MethodDefHandle, set: u16, clear: u16) {
+    let flags = &mut image.method_defs[method.index()].0.flags;
+    flags.0 = (flags.0 & !clear) | set;
+}
+
+#[test]
+fn reuse_slot_methods_override_inherited_slots() {
+    let dawn = load("Dawn.dll");
+    let hierarchy = TypeHierarchy::new(&dawn);
+    let table = hierarchy.method_table(CUTSCENE_PICKER).unwrap();
+    assert_eq!(
+        implementations(&table),
+        [
+            "Dawn.Console.CutscenePicker::get_Title",
+            "Dawn.Console.CutscenePicker::get_EntryCount",
+            "Dawn.Console.CutscenePicker::GetEntryText",
+            "Dawn.Console.CutscenePicker::SelectEntry",
+        ]
+    );
+    let slot = table.slot_of((&dawn, CUTSCENE_PICKER_GET_TITLE)).unwrap();
+    assert_eq!(name(slot.declaration), "Dawn.Console.DebugMenu::get_Title");
+    assert_eq!(
+        names(&slot.overridden),
+        ["Dawn.Console.DebugMenu::get_Title"]
+    );
+
+    let overrides = hierarchy
+        .overrides_of((&dawn, DEBUG_MENU_GET_TITLE))
+        .unwrap()
+        .into_iter()
+        .map(|m| name((&dawn, m)))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        overrides,
+        [
+            "Dawn.Console.CutscenePicker::get_Title",
+            "Dawn.Console.DebugMenuSelector::get_Title",
+            "Dawn.Console.MapDestinationPicker::get_Title",
+            "Dawn.Console.MapPicker::get_Title",
+        ]
+    );
+
+    // Overrides across assemblies stack up in the slot of the declaration
+    let game = load("Game.dll");
+    let images = vec![dawn];
+    let hierarchy = TypeHierarchy::new(&game).with_resolver(&images);
+    let table = hierarchy.method_table(COMBATANT_HEALTH).unwrap();
+    let slot = table.slot_of((&images[0], COMPONENT_ON_UPDATE)).unwrap();
+    assert_eq!(
+        name(slot.implementation),
+        "Game.Components.CombatantHealth::OnUpdate"
+    );
+    assert_eq!(
+        names(&slot.overridden),
+        [
+            "Dawn.Component::OnUpdate",
+            "Game.Components.BaseHealth::OnUpdate"
+        ]
+    );
+}
+
+#[test]
+fn new_slot_and_final_methods_hide_inherited_slots() {
+    let mut dawn = load("Dawn.dll");
+    set_flags(&mut dawn, CUTSCENE_PICKER_GET_TITLE, 0x0100, 0);
+    let table = TypeHierarchy::new(&dawn)
+        .method_table(CUTSCENE_PICKER)
+        .unwrap();
+    assert_eq!(table.slots.len(), 5);
+    let inherited = table.slot_of((&dawn, DEBUG_MENU_GET_TITLE)).unwrap();
+    assert_eq!(
+        name(inherited.implementation),
+        "Dawn.Console.DebugMenu::get_Title"
+    );
+    assert!(inherited.overridden.is_empty());
+    let hiding = table.slot_of((&dawn, CUTSCENE_PICKER_GET_TITLE)).unwrap();
+    assert_eq!(
+        name(hiding.declaration),
+        "Dawn.Console.CutscenePicker::get_Title"
+    );
+
+    // A final method can't be overridden even by a ReuseSlot method
+    let mut dawn = load("Dawn.dll");
+    set_flags(&mut dawn, DEBUG_MENU_GET_TITLE, 0x0020, 0x0400);
+    let table = TypeHierarchy::new(&dawn)
+        .method_table(CUTSCENE_PICKER)
+        .unwrap();
+    assert_eq!(table.slots.len(), 5);
+    assert_eq!(
+        name(table.slots[0].implementation),
+        "Dawn.Console.DebugMenu::get_Title"
+    );
+}
+
+#[test]
+fn strict_internal_methods_are_only_overridden_in_their_assembly() {
+    // Make `Component::OnUpdate` `strict assembly`
+    let mut dawn = load("Dawn.dll");
+    set_flags(&mut dawn, COMPONENT_ON_UPDATE, 0x0203, 0x0007);
+
+    let table = TypeHierarchy::new(&dawn)
+        .method_table(INLINE_COMPONENT)
+        .unwrap();
+    let slot = table.slot_of((&dawn, COMPONENT_ON_UPDATE)).unwrap();
+    assert_eq!(
+        name(slot.implementation),
+        "Dawn.Components.InlineComponent::OnUpdate"
+    );
+
+    let game = load("Game.dll");
+    let images = vec![dawn];
+    let table = TypeHierarchy::new(&game)
+        .with_resolver(&images)
+        .method_table(BASE_HEALTH)
+        .unwrap();
+    let slot = table.slot_of((&images[0], COMPONENT_ON_UPDATE)).unwrap();
+    assert_eq!(name(slot.implementation), "Dawn.Component::OnUpdate");
+    let own = table
+        .slots
+        .iter()
+        .find(|s| name(s.declaration) == "Game.Components.BaseHealth::OnUpdate")
+        .unwrap();
+    assert!(own.overridden.is_empty());
+}
+
+#[test]
+fn methods_without_hide_by_sig_hide_by_name() {
+    // Give `DebugMenu` a second `get_Title`, returning the entry count, after the original one
+    let mut dawn = load("Dawn.dll");
+    dawn.method_defs[DEBUG_MENU_GET_ENTRY_COUNT.index()].0.name = "get_Title".to_string();
+    let table = TypeHierarchy::new(&dawn)
+        .method_table(CUTSCENE_PICKER)
+        .unwrap();
+    assert_eq!(
+        name(table.slots[0].implementation),
+        "Dawn.Console.CutscenePicker::get_Title"
+    );
+
+    // Without HideBySig the overload hides the `get_Title` the picker means to override
+    set_flags(&mut dawn, DEBUG_MENU_GET_ENTRY_COUNT, 0, 0x0080);
+    let table = TypeHierarchy::new(&dawn)
+        .method_table(CUTSCENE_PICKER)
+        .unwrap();
+    assert_eq!(
+        name(table.slots[0].implementation),
+        "Dawn.Console.DebugMenu::get_Title"
+    );
+    assert!(
+        table
+            .slot_of((&dawn, CUTSCENE_PICKER_GET_TITLE))
+            .unwrap()
+            .overridden
+            .is_empty()
+    );
+}
+
+#[test]
+fn method_impls_override_slots() {
+    // `.override` of an interface method, the other method of the interface has a default body
+    let game = load("Game.dll");
+    let images = vec![load("Dawn.dll")];
+    let table = TypeHierarchy::new(&game)
+        .with_resolver(&images)
+        .method_table(MAP_LOAD_TRIGGER)
+        .unwrap();
+    let slots = table
+        .interface_slots
+        .iter()
+        .map(|s| (name(s.declaration), s.implementation.map(name)))
+        .collect::<Vec<_>>();
+    assert!(
+        slots.contains(&(
+            "Dawn.Components.Physics.ITriggerListener::OnTriggerEnter".to_string(),
+            Some(
+                "Game.MapLoadTrigger::Dawn.Components.Physics.ITriggerListener.OnTriggerEnter"
+                    .to_string()
+            )
+        ))
+    );
+    assert!(slots.contains(&(
+        "Dawn.Components.Physics.ITriggerListener::OnTriggerExit".to_string(),
+        Some("Dawn.Components.Physics.ITriggerListener::OnTriggerExit".to_string())
+    )));
+
+    // `.override` of a class method by a `newslot` method, which still gets a slot of its own
+    let mut dawn = load("Dawn.dll");
+    set_flags(&mut dawn, CUTSCENE_PICKER_GET_TITLE, 0x0100, 0);
+    dawn.method_impls.push(MethodImpl {
+        class: CUTSCENE_PICKER.0 as u16,
+        method_body: MethodDefOrRef::MethodDef(CUTSCENE_PICKER_GET_TITLE.0).encode() as u16,
+        method_declaration: MethodDefOrRef::MethodDef(DEBUG_MENU_GET_TITLE.0).encode() as u16,
+    });
+    dawn.sorted_tables = 0;
+    let table = TypeHierarchy::new(&dawn)
+        .method_table(CUTSCENE_PICKER)
+        .unwrap();
+    assert_eq!(table.slots.len(), 5);
+    let slot = table.slot_of((&dawn, DEBUG_MENU_GET_TITLE)).unwrap();
+    assert_eq!(
+        name(slot.implementation),
+        "Dawn.Console.CutscenePicker::get_Title"
+    );
+    assert_eq!(
+        names(&slot.overridden),
+        ["Dawn.Console.DebugMenu::get_Title"]
+    );
+}
+
+#[test]
+fn dispatch_through_classes_and_interfaces() {
+    let dawn = load("Dawn.dll");
+    let hierarchy = TypeHierarchy::new(&dawn);
+    let dispatch = |method: MethodDefHandle, receiver: Element| {
+        hierarchy
+            .dispatch(MethodDefOrRef::MethodDef(method.0), &receiver)
+            .unwrap()
+            .map(name)
+    };
+
+    assert_eq!(
+        dispatch(
+            DEBUG_MENU_GET_TITLE,
+            Element::Class(TypeDefOrRef::TypeDef(CUTSCENE_PICKER.0))
+        )
+        .as_deref(),
+        Some("Dawn.Console.CutscenePicker::get_Title")
+    );
+
+    let native_object = Element::GenericInst {
+        generic_type: Box::new(Element::Class(TypeDefOrRef::TypeDef(NATIVE_OBJECT.0))),
+        generic_args: vec![Element::I4],
+    };
+    assert_eq!(
+        dispatch(NATIVE_OBJECT_IS_VALID, native_object.clone()).as_deref(),
+        Some("Dawn.Interop.NativeObject`1::Dawn.Interop.INativeObject.get_IsValid")
+    );
+
+    // Non-virtual methods are called as they are, whatever the receiver
+    assert_eq!(
+        dispatch(MethodDefHandle(1), native_object).as_deref(),
+        Some("IEnumerableExtensions::WithIndex")
+    );
+}
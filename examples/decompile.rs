@@ -1,12 +1,12 @@
-use binrw::BinReaderExt;
 use cascade::{Error, Result, opcodes::Opcode};
-use std::{fmt::Write as _, io::Cursor};
+use std::fmt::Write as _;
 
 use cil::{
-    ReadExt,
     image::{CilImage, TypeName},
-    opcodes::RawOpcode,
-    signature::{self, Element, SignatureKind, StandaloneMethodSignature},
+    meta::{MethodDefHandle, StandAloneSigHandle},
+    opcodes::Instruction,
+    parameters::MethodParameters,
+    signature::{self, Element, StandaloneMethodSignature},
 };
 
 fn main() {
@@ -25,70 +25,38 @@ fn main() {
             .get(i + 1)
             .map_or(image.method_defs.len(), |t| t.method_list as usize - 1);
 
-        for (method, header, bytecode) in &image.method_defs[method_start..method_end] {
+        for (j, (method, header, bytecode)) in image.method_defs[method_start..method_end]
+            .iter()
+            .enumerate()
+        {
+            let handle = MethodDefHandle((method_start + j) as u32 + 1);
             let signature_blob = image
                 .blobs
                 .get(method.signature_blob_index as u32)
                 .expect("Invalid method signature token");
 
-            let signature = if signature_blob.is_empty() {
-                None
-            } else {
-                Some(
-                    StandaloneMethodSignature::parse(signature_blob)
-                        .expect("Invalid method signature"),
-                )
-            };
+            let signature = StandaloneMethodSignature::parse(signature_blob, image.pointer_size)
+                .expect("Invalid method signature");
 
-            let mut locals = vec![];
-            if let Some(standalone_token) = header.local_var_sig_token
-                && standalone_token.index() > 0
+            let locals = if let Some(local_var_sig_token) = header.local_var_sig_token
+                && let Ok(handle) = StandAloneSigHandle::try_from(local_var_sig_token)
             {
-                let sig_token = &image.stand_alone_sigs[standalone_token.index() as usize - 1];
-                let blob = image
-                    .blobs
-                    .get(sig_token.signature_blob_index as u32)
-                    .expect("Invalid local var signature token");
-
                 println!(
-                    "// method {} sig={:?}",
+                    "// method {} sig={}",
                     method.name,
-                    signature.as_ref().map(|s| s.debug_print(&image)),
+                    signature.debug_print(&image),
                 );
 
-                let kind = SignatureKind::try_from(blob[0] & 0xF).expect("Invalid signature kind");
-                assert_eq!(kind, SignatureKind::LocalVar);
-
-                let mut c = Cursor::new(&blob[1..]);
-                let count = c.read_compressed_u32().unwrap();
-                for _ in 0..count {
-                    match c.read_le::<signature::Element>() {
-                        Ok(element) => {
-                            locals.push(element);
-                        }
-                        Err(e) => {
-                            eprintln!("Error reading local var element: {}", e);
-                            break;
-                        }
-                    }
-                }
+                image
+                    .local_var_signature(handle)
+                    .expect("Invalid local var signature")
+                    .locals
             } else {
                 println!("// method {}", method.name,);
-            }
-            // println!("  method {}", method.name);
-
-            // for (j, instruction) in bytecode {
-            //     print!("    IL_{j:04x}: ");
-            //     match instruction {
-            //         b if instruction.is_branch() => {
-            //             let target = *j as i32 + b.branch_offset().unwrap();
-            //             println!("{} IL_{target:04x}", b.asm_name());
-            //         }
-            //         m => println!("{m}"),
-            //     }
-            // }
+                vec![]
+            };
 
-            let decompiler = MethodDecompiler::new(&image, method, bytecode, &locals);
+            let decompiler = MethodDecompiler::new(&image, handle, method, bytecode, &locals);
 
             match decompiler.decompile() {
                 Ok(output) => {
@@ -110,7 +78,8 @@ struct MethodDecompiler<'img> {
     image: &'img CilImage,
     method: &'img cil::tables::Method,
     signature: StandaloneMethodSignature,
-    bytecode: &'img [(u32, RawOpcode)],
+    parameters: MethodParameters,
+    bytecode: &'img [Instruction],
     label_offsets: Vec<u32>,
     locals: &'img [signature::Element],
 
@@ -120,8 +89,9 @@ struct MethodDecompiler<'img> {
 impl<'img> MethodDecompiler<'img> {
     fn new(
         image: &'img CilImage,
+        handle: MethodDefHandle,
         method: &'img cil::tables::Method,
-        bytecode: &'img [(u32, RawOpcode)],
+        bytecode: &'img [Instruction],
         locals: &'img [signature::Element],
     ) -> Self {
         let signature_blob = image
@@ -129,31 +99,23 @@ impl<'img> MethodDecompiler<'img> {
             .get(method.signature_blob_index as u32)
             .expect("Invalid method signature token");
 
-        let signature = if signature_blob.is_empty() {
-            None
-        } else {
-            Some(
-                StandaloneMethodSignature::parse(signature_blob).expect("Invalid method signature"),
-            )
-        };
+        let signature = StandaloneMethodSignature::parse(signature_blob, image.pointer_size)
+            .expect("Invalid method signature");
+        let parameters = image
+            .method_parameters(handle)
+            .expect("Invalid method parameters");
 
-        let mut label_offsets = vec![];
-        for (offset, opcode) in bytecode.iter().filter(|(_, op)| op.is_branch()) {
-            if let RawOpcode::Switch { targets } = opcode {
-                label_offsets.extend(
-                    targets
-                        .iter()
-                        .map(|&target| (*offset as i32 + target + opcode.size() as i32) as u32),
-                );
-            } else {
-                label_offsets.push((*offset as i32 + opcode.branch_offset().unwrap()) as u32);
-            }
-        }
+        let label_offsets = bytecode
+            .iter()
+            .flat_map(|instruction| instruction.branch_targets())
+            .copied()
+            .collect();
 
         Self {
             image,
             method,
-            signature: signature.unwrap_or_default(),
+            signature,
+            parameters,
             bytecode,
             label_offsets,
             locals,
@@ -181,7 +143,10 @@ impl<'img> MethodDecompiler<'img> {
                 .parameters
                 .iter()
                 .enumerate()
-                .map(|(i, p)| format!("{} {}", p.debug_print(self.image), self.arg_var(i as u16)))
+                .map(|(i, p)| {
+                    let index = i as u16 + self.signature.header.has_this() as u16;
+                    format!("{} {}", p.debug_print(self.image), self.arg_var(index))
+                })
                 .collect::<Vec<_>>()
                 .join(", ")
         )?;
@@ -198,15 +163,15 @@ impl<'img> MethodDecompiler<'img> {
             writeln!(&mut output)?;
         }
 
-        for (op_offset, raw_opcode) in self.bytecode {
-            if self.label_offsets.contains(op_offset) {
-                writeln!(&mut output, "IL_{:04x}:", op_offset)?;
+        for instruction in self.bytecode {
+            if self.label_offsets.contains(&instruction.offset) {
+                writeln!(&mut output, "IL_{:04x}:", instruction.offset)?;
             }
 
-            let opcode = Opcode::from(raw_opcode.clone());
-            // writeln!(&mut output, "// IL_{:04x}: {opcode:?}", op_offset)?;
+            let opcode = Opcode::from(instruction);
+            // writeln!(&mut output, "// {instruction}")?;
             match opcode {
-                Opcode::Nop {} => {}
+                Opcode::Nop => {}
                 Opcode::LoadConstantI4(value) => {
                     self.stack.push(value.to_string());
                 }
@@ -245,34 +210,34 @@ impl<'img> MethodDecompiler<'img> {
                             .expect("Invalid user string index")
                     ));
                 }
-                Opcode::Add(ovf) => {
+                Opcode::Add(_ovf) => {
                     let right = self.stack.pop()?;
                     let left = self.stack.pop()?;
                     self.stack.push(format!("({} + {})", left, right));
                 }
-                Opcode::Subtract(ovf) => {
+                Opcode::Subtract(_ovf) => {
                     let right = self.stack.pop()?;
                     let left = self.stack.pop()?;
                     self.stack.push(format!("({} - {})", left, right));
                 }
-                Opcode::Multiply(ovf) => {
+                Opcode::Multiply(_ovf) => {
                     let right = self.stack.pop()?;
                     let left = self.stack.pop()?;
                     self.stack.push(format!("({} * {})", left, right));
                 }
-                Opcode::Divide { unsigned } => {
+                Opcode::Divide { unsigned: _ } => {
                     let right = self.stack.pop()?;
                     let left = self.stack.pop()?;
                     self.stack.push(format!("({} / {})", left, right));
                 }
-                Opcode::Remainder { unsigned } => {
+                Opcode::Remainder { unsigned: _ } => {
                     let right = self.stack.pop()?;
                     let left = self.stack.pop()?;
                     self.stack.push(format!("({} % {})", left, right));
                 }
                 Opcode::Compare {
                     comparison,
-                    unsigned,
+                    unsigned: _,
                 } => {
                     let right = self.stack.pop()?;
                     let left = self.stack.pop()?;
@@ -326,39 +291,26 @@ impl<'img> MethodDecompiler<'img> {
                     }
                     writeln!(&mut output, " // {}", signature.debug_print(self.image))?;
                 }
-                Opcode::Branch(offset) => {
-                    writeln!(
-                        &mut output,
-                        "    goto IL_{:04x};",
-                        *op_offset as i32 + raw_opcode.size() as i32 + offset
-                    )?;
+                Opcode::Branch(target) => {
+                    writeln!(&mut output, "    goto IL_{target:04x};")?;
                 }
                 Opcode::BranchConditional {
-                    offset,
+                    target,
                     comparison,
-                    unsigned,
+                    unsigned: _,
                 } => {
                     let expression = if comparison.is_true_false() {
                         comparison.operator(self.stack.pop()?, None)
                     } else {
                         comparison.operator(self.stack.pop()?, Some(self.stack.pop()?))
                     };
-                    writeln!(
-                        &mut output,
-                        "    if ({expression}) goto IL_{:04x};",
-                        *op_offset as i32 + raw_opcode.size() as i32 + offset
-                    )?;
+                    writeln!(&mut output, "    if ({expression}) goto IL_{target:04x};")?;
                 }
                 Opcode::Switch { targets } => {
-                    let mut cases = Vec::new();
-                    for target in targets {
-                        let target_offset = *op_offset as i32 + raw_opcode.size() as i32 + target;
-                        cases.push(format!("IL_{:04x}", target_offset));
-                    }
                     let switch_expression = self.stack.pop()?;
                     writeln!(&mut output, "    switch ({}) {{", switch_expression)?;
-                    for (i, case) in cases.iter().enumerate() {
-                        writeln!(&mut output, "        case {}: goto {}; break;", i, case)?;
+                    for (i, target) in targets.iter().enumerate() {
+                        writeln!(&mut output, "        case {i}: goto IL_{target:04x};")?;
                     }
                     writeln!(&mut output, "        default: break;")?;
                     writeln!(&mut output, "    }}")?;
@@ -417,8 +369,20 @@ impl<'img> MethodDecompiler<'img> {
         format!("var{index}")
     }
 
+    /// Name of the argument `ldarg`/`starg` refer to by `index`, where 0 is `this` for instance methods
     fn arg_var(&self, index: u16) -> String {
-        format!("arg{index}")
+        let parameter = if self.signature.header.has_this() {
+            match index.checked_sub(1) {
+                Some(index) => self.parameters.parameters.get(index as usize),
+                None => return "this".to_string(),
+            }
+        } else {
+            self.parameters.parameters.get(index as usize)
+        };
+
+        parameter
+            .and_then(|p| p.name.clone())
+            .unwrap_or_else(|| format!("arg{index}"))
     }
 
     fn translate_method_path(&self, typename: &TypeName, path_cs: &str) -> String {
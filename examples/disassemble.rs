@@ -0,0 +1,7 @@
+use cil::image::CilImage;
+
+fn main() {
+    let file = std::env::args().nth(1).expect("No file provided");
+    let image = CilImage::load(file).expect("Failed to load CIL image");
+    print!("{}", image.disassemble().expect("Failed to disassemble image"));
+}
@@ -10,6 +10,12 @@ pub enum Error {
 
     #[error("Unimplemented opcode: {0:?}")]
     UnimplementedOpcode(crate::opcodes::Opcode),
+
+    #[error("CIL error: {0}")]
+    CilError(#[from] cil::error::Error),
+
+    #[error("Method body failed verification: {0}")]
+    VerificationError(Box<cil::verifier::VerificationError>),
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
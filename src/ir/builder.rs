@@ -0,0 +1,1111 @@
+//! Construction of the IR from a control flow graph and a stack analysis
+//!
+//! Each block is translated on its own, starting from a stack of loads of the temporaries that
+//! hold its entry stack. Slot `n` of the entry stacks of blocks that share a predecessor is held
+//! in the same temporary, which the predecessors assign before their terminator.
+
+use cil::{
+    body::ExceptionClauseKind,
+    flow::{BlockId, ControlFlowGraph, EdgeKind},
+    image::CilImage,
+    meta::{MethodDefHandle, Token},
+    opcodes::{Instruction, Operand, RawOpcode},
+    signature::{Element, StandaloneMethodSignature},
+    verifier::{StackAnalysis, StackType, Verifier},
+};
+
+use super::{
+    AccessType, BinaryOperator, Block, Call, CallTarget, Constant, Expression, ExpressionKind,
+    MethodBody, Statement, Terminator, UnaryOperator, Variable, VariableId, VariableKind,
+};
+use crate::{
+    Error, Result,
+    opcodes::{Comparison, OverflowCheck},
+};
+
+pub(super) fn build(
+    image: &CilImage,
+    handle: MethodDefHandle,
+    cfg: ControlFlowGraph,
+    analysis: &StackAnalysis,
+) -> Result<MethodBody> {
+    if let Some(error) = analysis.errors.first() {
+        return Err(Error::VerificationError(Box::new(error.clone())));
+    }
+    let (_, _, instructions) = image
+        .method_defs
+        .get(handle.index())
+        .ok_or(cil::error::Error::InvalidToken(handle.token()))?;
+    let signature = image.method_signature(handle)?;
+
+    let verifier = Verifier::new(image);
+    let mut variables = Vec::new();
+    for (index, element) in analysis.arguments.iter().enumerate() {
+        variables.push(Variable {
+            kind: VariableKind::Argument(index as u16),
+            element: Some(element.clone()),
+            ty: verifier.stack_type(element),
+        });
+    }
+    for (index, element) in analysis.locals.iter().enumerate() {
+        variables.push(Variable {
+            kind: VariableKind::Local(index as u16),
+            element: Some(element.clone()),
+            ty: verifier.stack_type(element),
+        });
+    }
+
+    let mut builder = Builder {
+        image,
+        signature: &signature,
+        instructions,
+        cfg: &cfg,
+        analysis,
+        exception_entries: exception_entries(&cfg),
+        entry_slots: Vec::new(),
+        variables,
+        statements: Vec::new(),
+        stack: Vec::new(),
+        terminator: None,
+    };
+    builder.allocate_entry_slots();
+
+    let blocks = (0..cfg.len())
+        .map(|b| builder.block(BlockId(b)))
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(MethodBody {
+        variables: builder.variables,
+        blocks,
+        cfg,
+    })
+}
+
+/// Blocks that start with the exception on the stack, the entries of catch handlers and filters
+fn exception_entries(cfg: &ControlFlowGraph) -> Vec<bool> {
+    let mut entries = vec![false; cfg.len()];
+    for clause in &cfg.exception_clauses {
+        let offsets = match clause.kind {
+            ExceptionClauseKind::Catch(_) => vec![clause.handler_offset],
+            ExceptionClauseKind::Filter(offset) => vec![offset, clause.handler_offset],
+            ExceptionClauseKind::Finally | ExceptionClauseKind::Fault => vec![],
+        };
+        for offset in offsets {
+            if let Some(block) = cfg.block_at(offset) {
+                entries[block.0] = true;
+            }
+        }
+    }
+    entries
+}
+
+struct Builder<'a> {
+    image: &'a CilImage,
+    signature: &'a StandaloneMethodSignature,
+    instructions: &'a [Instruction],
+    cfg: &'a ControlFlowGraph,
+    analysis: &'a StackAnalysis,
+    exception_entries: Vec<bool>,
+    /// Temporaries holding the entry stack of each block, bottom first
+    entry_slots: Vec<Vec<VariableId>>,
+    variables: Vec<Variable>,
+
+    // State of the block being translated
+    statements: Vec<Statement>,
+    stack: Vec<Expression>,
+    terminator: Option<Terminator>,
+}
+
+impl Builder<'_> {
+    /// Stack before the first instruction of `block`, `None` if it is unreachable
+    fn entry_stack(&self, block: BlockId) -> Option<&[StackType]> {
+        let instructions = &self.cfg.block(block).instructions;
+        if instructions.is_empty() {
+            return None;
+        }
+        self.analysis.stack_before(instructions.start)
+    }
+
+    /// Gives every entry stack slot a temporary, sharing them between blocks that are entered
+    /// from the same block
+    fn allocate_entry_slots(&mut self) {
+        let mut first_slot = Vec::with_capacity(self.cfg.len());
+        let mut slot_count = 0;
+        for b in 0..self.cfg.len() {
+            first_slot.push(slot_count);
+            if !self.exception_entries[b] {
+                slot_count += self.entry_stack(BlockId(b)).map_or(0, <[_]>::len);
+            }
+        }
+        let depth = |b: usize| first_slot.get(b + 1).copied().unwrap_or(slot_count) - first_slot[b];
+
+        // Union-find over the slots of all blocks
+        let mut parent = (0..slot_count).collect::<Vec<_>>();
+        fn find(parent: &mut [usize], mut slot: usize) -> usize {
+            while parent[slot] != slot {
+                parent[slot] = parent[parent[slot]];
+                slot = parent[slot];
+            }
+            slot
+        }
+        for block in &self.cfg.blocks {
+            let mut targets = block
+                .successors
+                .iter()
+                .filter(|e| {
+                    matches!(
+                        e.kind,
+                        EdgeKind::Fallthrough
+                            | EdgeKind::Branch
+                            | EdgeKind::ConditionalTrue
+                            | EdgeKind::ConditionalFalse
+                            | EdgeKind::SwitchCase(_)
+                    )
+                })
+                .map(|e| e.target.0);
+            let Some(first) = targets.next() else {
+                continue;
+            };
+            for other in targets {
+                for slot in 0..depth(first).min(depth(other)) {
+                    let a = find(&mut parent, first_slot[first] + slot);
+                    let b = find(&mut parent, first_slot[other] + slot);
+                    parent[b] = a;
+                }
+            }
+        }
+
+        let mut temporaries = vec![None; slot_count];
+        for (b, &first) in first_slot.iter().enumerate() {
+            let mut slots = Vec::with_capacity(depth(b));
+            for slot in 0..depth(b) {
+                let root = find(&mut parent, first + slot);
+                let variable = match temporaries[root] {
+                    Some(variable) => variable,
+                    None => {
+                        let ty = self.entry_stack(BlockId(b)).unwrap()[slot].clone();
+                        let variable = self.temporary(ty);
+                        temporaries[root] = Some(variable);
+                        variable
+                    }
+                };
+                slots.push(variable);
+            }
+            self.entry_slots.push(slots);
+        }
+    }
+
+    fn temporary(&mut self, ty: StackType) -> VariableId {
+        self.variables.push(Variable {
+            kind: VariableKind::Temporary,
+            element: None,
+            ty,
+        });
+        VariableId(self.variables.len() - 1)
+    }
+
+    fn block(&mut self, id: BlockId) -> Result<Block> {
+        if self.entry_stack(id).is_none() {
+            return Ok(Block {
+                statements: Vec::new(),
+                terminator: Terminator::Unreachable,
+            });
+        }
+
+        self.stack = if self.exception_entries[id.0] {
+            vec![Expression::new(
+                ExpressionKind::CaughtException,
+                StackType::Object,
+            )]
+        } else {
+            self.entry_slots[id.0]
+                .iter()
+                .map(|&variable| self.load(variable))
+                .collect()
+        };
+        for index in self.cfg.block(id).instructions.clone() {
+            self.instruction(index)?;
+        }
+
+        let terminator = match self.terminator.take() {
+            Some(terminator) => terminator,
+            None => {
+                let next = self.block_at(self.cfg.block(id).end)?;
+                Terminator::Branch(next)
+            }
+        };
+        match &terminator {
+            Terminator::Branch(target)
+            | Terminator::Conditional {
+                fallthrough: target,
+                ..
+            }
+            | Terminator::Switch {
+                default: target, ..
+            } => self.hand_over(*target),
+            _ => {
+                for value in std::mem::take(&mut self.stack) {
+                    if !value.is_stable(&self.variables) {
+                        self.statements.push(Statement::Discard(value));
+                    }
+                }
+            }
+        }
+
+        Ok(Block {
+            statements: std::mem::take(&mut self.statements),
+            terminator,
+        })
+    }
+
+    /// Assigns the values left on the stack to the entry temporaries of `target`, which it shares
+    /// with the other successors of the block
+    fn hand_over(&mut self, target: BlockId) {
+        let slots = &self.entry_slots[target.0];
+        for (slot, value) in std::mem::take(&mut self.stack).into_iter().enumerate() {
+            match slots.get(slot) {
+                Some(&variable) if value.kind == ExpressionKind::Load(variable) => {}
+                Some(&variable) => self.statements.push(Statement::Assign { variable, value }),
+                None if value.is_stable(&self.variables) => {}
+                None => self.statements.push(Statement::Discard(value)),
+            }
+        }
+    }
+
+    fn block_at(&self, offset: u32) -> Result<BlockId> {
+        self.cfg
+            .block_at(offset)
+            .ok_or(cil::error::Error::InvalidBranchTarget(offset).into())
+    }
+
+    fn load(&self, variable: VariableId) -> Expression {
+        Expression::new(
+            ExpressionKind::Load(variable),
+            self.variables[variable.0].ty.clone(),
+        )
+    }
+
+    fn pop(&mut self) -> Result<Expression> {
+        self.stack.pop().ok_or(Error::StackUnderflow)
+    }
+
+    /// Pops `count` values, returned in the order they were pushed
+    fn pop_many(&mut self, count: usize) -> Result<Vec<Expression>> {
+        let start = self
+            .stack
+            .len()
+            .checked_sub(count)
+            .ok_or(Error::StackUnderflow)?;
+        Ok(self.stack.split_off(start))
+    }
+
+    /// Pushes the result of the instruction at `index`
+    fn push(&mut self, index: usize, kind: ExpressionKind) {
+        let ty = self.analysis.pushed[index]
+            .last()
+            .cloned()
+            .unwrap_or(StackType::Unknown);
+        self.stack.push(Expression::new(kind, ty));
+    }
+
+    /// Moves the values on the stack that could change or have side effects into temporaries, so
+    /// that they are evaluated before the statement that follows
+    fn spill(&mut self) {
+        for slot in 0..self.stack.len() {
+            if self.stack[slot].is_stable(&self.variables) {
+                continue;
+            }
+            let variable = self.temporary(self.stack[slot].ty.clone());
+            let load = self.load(variable);
+            let value = std::mem::replace(&mut self.stack[slot], load);
+            self.statements.push(Statement::Assign { variable, value });
+        }
+    }
+
+    /// Adds a statement whose operands have already been popped
+    fn emit(&mut self, statement: Statement) {
+        self.spill();
+        self.statements.push(statement);
+    }
+
+    fn instruction(&mut self, index: usize) -> Result<()> {
+        use ExpressionKind as E;
+
+        let instruction = &self.instructions[index];
+        let token = match instruction.operand {
+            Operand::Token(token) => token,
+            _ => Token::default(),
+        };
+        let target = instruction.branch_targets().first().copied();
+
+        match &instruction.opcode {
+            RawOpcode::Nop {} => {}
+            RawOpcode::Break {} => self.emit(Statement::Breakpoint),
+
+            RawOpcode::LdArg_0 {}
+            | RawOpcode::LdArg_1 {}
+            | RawOpcode::LdArg_2 {}
+            | RawOpcode::LdArg_3 {}
+            | RawOpcode::LdArg_S { .. }
+            | RawOpcode::LdArg { .. }
+            | RawOpcode::LdLoc_0 {}
+            | RawOpcode::LdLoc_1 {}
+            | RawOpcode::LdLoc_2 {}
+            | RawOpcode::LdLoc_3 {}
+            | RawOpcode::LdLoc_S { .. }
+            | RawOpcode::LdLoc { .. } => {
+                let variable = self.operand_variable(instruction);
+                self.push(index, E::Load(variable));
+            }
+            RawOpcode::LdArgA_S { .. }
+            | RawOpcode::LdArgA { .. }
+            | RawOpcode::LdLocA_S { .. }
+            | RawOpcode::LdLocA { .. } => {
+                let variable = self.operand_variable(instruction);
+                self.push(index, E::Address(variable));
+            }
+            RawOpcode::StArg_S { .. }
+            | RawOpcode::StArg { .. }
+            | RawOpcode::StLoc_0 {}
+            | RawOpcode::StLoc_1 {}
+            | RawOpcode::StLoc_2 {}
+            | RawOpcode::StLoc_3 {}
+            | RawOpcode::StLoc_S { .. }
+            | RawOpcode::StLoc { .. } => {
+                let variable = self.operand_variable(instruction);
+                let value = self.pop()?;
+                self.emit(Statement::Assign { variable, value });
+            }
+
+            RawOpcode::LdNull {} => self.push(index, E::Constant(Constant::Null)),
+            RawOpcode::LdStr { .. } => self.push(index, E::Constant(Constant::String(token))),
+            RawOpcode::Ldc_I4_M1 {}
+            | RawOpcode::Ldc_I4_0 {}
+            | RawOpcode::Ldc_I4_1 {}
+            | RawOpcode::Ldc_I4_2 {}
+            | RawOpcode::Ldc_I4_3 {}
+            | RawOpcode::Ldc_I4_4 {}
+            | RawOpcode::Ldc_I4_5 {}
+            | RawOpcode::Ldc_I4_6 {}
+            | RawOpcode::Ldc_I4_7 {}
+            | RawOpcode::Ldc_I4_8 {}
+            | RawOpcode::Ldc_I4_S { .. }
+            | RawOpcode::Ldc_I4 { .. }
+            | RawOpcode::Ldc_I8 { .. }
+            | RawOpcode::Ldc_R4 { .. }
+            | RawOpcode::Ldc_R8 { .. } => {
+                let constant = match instruction.operand {
+                    Operand::I4(value) => Constant::I4(value),
+                    Operand::I8(value) => Constant::I8(value),
+                    Operand::R4(value) => Constant::R4(value),
+                    Operand::R8(value) => Constant::R8(value),
+                    _ => unreachable!(),
+                };
+                self.push(index, E::Constant(constant));
+            }
+
+            RawOpcode::Dup {} => {
+                if !self
+                    .stack
+                    .last()
+                    .ok_or(Error::StackUnderflow)?
+                    .is_stable(&self.variables)
+                {
+                    self.spill();
+                }
+                let value = self.stack.last().unwrap().clone();
+                self.stack.push(value);
+            }
+            RawOpcode::Pop {} => {
+                let value = self.pop()?;
+                if !value.is_stable(&self.variables) {
+                    self.emit(Statement::Discard(value));
+                }
+            }
+
+            RawOpcode::Call { method } | RawOpcode::CallVirt { method } => {
+                let target = match instruction.opcode {
+                    RawOpcode::Call { .. } => CallTarget::Method(*method),
+                    _ => CallTarget::Virtual(*method),
+                };
+                self.call(index, target)?;
+            }
+            RawOpcode::CallInd { callsitedescr } => {
+                let pointer = self.pop()?;
+                self.call(
+                    index,
+                    CallTarget::Indirect {
+                        signature: *callsitedescr,
+                        pointer: Box::new(pointer),
+                    },
+                )?;
+            }
+            RawOpcode::NewObj { ctor } => {
+                let count = self.effect(instruction)?.pops as usize;
+                let arguments = self.pop_many(count)?;
+                self.push(
+                    index,
+                    E::NewObject {
+                        constructor: *ctor,
+                        arguments,
+                    },
+                );
+            }
+            RawOpcode::Jmp { method } => self.terminator = Some(Terminator::Jump(*method)),
+            RawOpcode::Ret {} => {
+                let value = match self.effect(instruction)?.pops {
+                    0 => None,
+                    _ => Some(self.pop()?),
+                };
+                self.terminator = Some(Terminator::Return(value));
+            }
+
+            RawOpcode::Br_S { .. } | RawOpcode::Br { .. } => {
+                let target = self.block_at(target.unwrap())?;
+                self.terminator = Some(Terminator::Branch(target));
+            }
+            RawOpcode::Leave_S { .. } | RawOpcode::Leave { .. } => {
+                let target = self.block_at(target.unwrap())?;
+                self.terminator = Some(Terminator::Leave(target));
+            }
+            RawOpcode::Br_False_S { .. }
+            | RawOpcode::Br_True_S { .. }
+            | RawOpcode::Br_False { .. }
+            | RawOpcode::Br_True { .. } => {
+                let value = self.pop()?;
+                let condition = match instruction.opcode {
+                    RawOpcode::Br_True_S { .. } | RawOpcode::Br_True { .. } => value,
+                    _ => Expression::new(
+                        E::Unary {
+                            operator: UnaryOperator::LogicalNot,
+                            operand: Box::new(value),
+                        },
+                        StackType::Int32,
+                    ),
+                };
+                self.conditional(instruction, condition)?;
+            }
+            RawOpcode::Beq_S { .. }
+            | RawOpcode::Bge_S { .. }
+            | RawOpcode::Bgt_S { .. }
+            | RawOpcode::Ble_S { .. }
+            | RawOpcode::Blt_S { .. }
+            | RawOpcode::Bne_Un_S { .. }
+            | RawOpcode::Bge_Un_S { .. }
+            | RawOpcode::Bgt_Un_S { .. }
+            | RawOpcode::Ble_Un_S { .. }
+            | RawOpcode::Blt_Un_S { .. }
+            | RawOpcode::Beq { .. }
+            | RawOpcode::Bge { .. }
+            | RawOpcode::Bgt { .. }
+            | RawOpcode::Ble { .. }
+            | RawOpcode::Blt { .. }
+            | RawOpcode::Bne_Un { .. }
+            | RawOpcode::Bge_Un { .. }
+            | RawOpcode::Bgt_Un { .. }
+            | RawOpcode::Ble_Un { .. }
+            | RawOpcode::Blt_Un { .. } => {
+                let (comparison, unsigned) = comparison(&instruction.opcode);
+                let right = self.pop()?;
+                let left = self.pop()?;
+                let condition = Expression::new(
+                    E::Compare {
+                        comparison,
+                        unsigned,
+                        left: Box::new(left),
+                        right: Box::new(right),
+                    },
+                    StackType::Int32,
+                );
+                self.conditional(instruction, condition)?;
+            }
+            RawOpcode::Switch { .. } => {
+                let value = self.pop()?;
+                let targets = instruction
+                    .branch_targets()
+                    .iter()
+                    .map(|&offset| self.block_at(offset))
+                    .collect::<Result<Vec<_>>>()?;
+                let default = self.block_at(instruction.next_offset())?;
+                self.terminator = Some(Terminator::Switch {
+                    value,
+                    targets,
+                    default,
+                });
+            }
+            RawOpcode::Ceq {}
+            | RawOpcode::Cgt {}
+            | RawOpcode::Cgt_Un {}
+            | RawOpcode::Clt {}
+            | RawOpcode::Clt_Un {} => {
+                let (comparison, unsigned) = comparison(&instruction.opcode);
+                let right = self.pop()?;
+                let left = self.pop()?;
+                self.push(
+                    index,
+                    E::Compare {
+                        comparison,
+                        unsigned,
+                        left: Box::new(left),
+                        right: Box::new(right),
+                    },
+                );
+            }
+
+            RawOpcode::Add {}
+            | RawOpcode::Add_Ovf {}
+            | RawOpcode::Add_Ovf_Unsigned {}
+            | RawOpcode::Sub {}
+            | RawOpcode::Sub_Ovf {}
+            | RawOpcode::Sub_Ovf_Unsigned {}
+            | RawOpcode::Mul {}
+            | RawOpcode::Mul_Ovf {}
+            | RawOpcode::Mul_Ovf_Unsigned {}
+            | RawOpcode::Div {}
+            | RawOpcode::DivUnsigned {}
+            | RawOpcode::Rem {}
+            | RawOpcode::RemUnsigned {}
+            | RawOpcode::And {}
+            | RawOpcode::Or {}
+            | RawOpcode::Xor {}
+            | RawOpcode::Shl {}
+            | RawOpcode::Shr {}
+            | RawOpcode::ShrUnsigned {} => {
+                let operator = binary_operator(&instruction.opcode);
+                let right = self.pop()?;
+                let left = self.pop()?;
+                self.push(
+                    index,
+                    E::Binary {
+                        operator,
+                        left: Box::new(left),
+                        right: Box::new(right),
+                    },
+                );
+            }
+            RawOpcode::Neg {} | RawOpcode::Not {} => {
+                let operator = match instruction.opcode {
+                    RawOpcode::Neg {} => UnaryOperator::Negate,
+                    _ => UnaryOperator::Not,
+                };
+                let operand = self.pop()?;
+                self.push(
+                    index,
+                    E::Unary {
+                        operator,
+                        operand: Box::new(operand),
+                    },
+                );
+            }
+            RawOpcode::CkFinite {} => {
+                let value = self.pop()?;
+                self.push(index, E::CheckFinite(Box::new(value)));
+            }
+
+            RawOpcode::Conv_I1 {}
+            | RawOpcode::Conv_I2 {}
+            | RawOpcode::Conv_I4 {}
+            | RawOpcode::Conv_I8 {}
+            | RawOpcode::Conv_R4 {}
+            | RawOpcode::Conv_R8 {}
+            | RawOpcode::Conv_U4 {}
+            | RawOpcode::Conv_U8 {}
+            | RawOpcode::Conv_R_Un {}
+            | RawOpcode::Conv_U2 {}
+            | RawOpcode::Conv_U1 {}
+            | RawOpcode::Conv_I {}
+            | RawOpcode::Conv_U {}
+            | RawOpcode::Conv_Ovf_I1 {}
+            | RawOpcode::Conv_Ovf_U1 {}
+            | RawOpcode::Conv_Ovf_I2 {}
+            | RawOpcode::Conv_Ovf_U2 {}
+            | RawOpcode::Conv_Ovf_I4 {}
+            | RawOpcode::Conv_Ovf_U4 {}
+            | RawOpcode::Conv_Ovf_I8 {}
+            | RawOpcode::Conv_Ovf_U8 {}
+            | RawOpcode::Conv_Ovf_I {}
+            | RawOpcode::Conv_Ovf_U {}
+            | RawOpcode::Conv_Ovf_I1_Unsigned {}
+            | RawOpcode::Conv_Ovf_I2_Unsigned {}
+            | RawOpcode::Conv_Ovf_I4_Unsigned {}
+            | RawOpcode::Conv_Ovf_I8_Unsigned {}
+            | RawOpcode::Conv_Ovf_U1_Unsigned {}
+            | RawOpcode::Conv_Ovf_U2_Unsigned {}
+            | RawOpcode::Conv_Ovf_U4_Unsigned {}
+            | RawOpcode::Conv_Ovf_U8_Unsigned {}
+            | RawOpcode::Conv_Ovf_I_Unsigned {}
+            | RawOpcode::Conv_Ovf_U_Unsigned {} => {
+                let (target, checked, unsigned) = conversion(&instruction.opcode);
+                let operand = self.pop()?;
+                self.push(
+                    index,
+                    E::Convert {
+                        target,
+                        checked,
+                        unsigned,
+                        operand: Box::new(operand),
+                    },
+                );
+            }
+
+            RawOpcode::LdInd_I1 {}
+            | RawOpcode::LdInd_U1 {}
+            | RawOpcode::LdInd_I2 {}
+            | RawOpcode::LdInd_U2 {}
+            | RawOpcode::LdInd_I4 {}
+            | RawOpcode::LdInd_U4 {}
+            | RawOpcode::LdInd_I8 {}
+            | RawOpcode::LdInd_I {}
+            | RawOpcode::LdInd_R4 {}
+            | RawOpcode::LdInd_R8 {}
+            | RawOpcode::LdInd_Ref {}
+            | RawOpcode::LdObj { .. } => {
+                let address = self.pop()?;
+                self.push(
+                    index,
+                    E::LoadIndirect {
+                        address: Box::new(address),
+                        ty: access_type(&instruction.opcode, token),
+                        volatile: instruction.prefixes.volatile,
+                    },
+                );
+            }
+            RawOpcode::StInd_Ref {}
+            | RawOpcode::StInd_I1 {}
+            | RawOpcode::StInd_I2 {}
+            | RawOpcode::StInd_I4 {}
+            | RawOpcode::StInd_I8 {}
+            | RawOpcode::StInd_R4 {}
+            | RawOpcode::StInd_R8 {}
+            | RawOpcode::StInd_I {}
+            | RawOpcode::StObj { .. } => {
+                let value = self.pop()?;
+                let address = self.pop()?;
+                self.emit(Statement::StoreIndirect {
+                    address,
+                    value,
+                    ty: access_type(&instruction.opcode, token),
+                    volatile: instruction.prefixes.volatile,
+                });
+            }
+            RawOpcode::CpObj { .. } => {
+                let source = self.pop()?;
+                let destination = self.pop()?;
+                self.emit(Statement::CopyObject {
+                    destination,
+                    source,
+                    ty: token,
+                });
+            }
+            RawOpcode::InitObj { .. } => {
+                let address = self.pop()?;
+                self.emit(Statement::InitObject { address, ty: token });
+            }
+            RawOpcode::CpBlk {} => {
+                let size = self.pop()?;
+                let source = self.pop()?;
+                let destination = self.pop()?;
+                self.emit(Statement::CopyBlock {
+                    destination,
+                    source,
+                    size,
+                });
+            }
+            RawOpcode::InitBlk {} => {
+                let size = self.pop()?;
+                let value = self.pop()?;
+                let address = self.pop()?;
+                self.emit(Statement::InitBlock {
+                    address,
+                    value,
+                    size,
+                });
+            }
+            RawOpcode::LocAlloc {} => {
+                let size = self.pop()?;
+                self.push(index, E::LocalAlloc(Box::new(size)));
+            }
+
+            RawOpcode::LdFld { field } => {
+                let object = self.pop()?;
+                self.push(
+                    index,
+                    E::Field {
+                        object: Some(Box::new(object)),
+                        field: *field,
+                        volatile: instruction.prefixes.volatile,
+                    },
+                );
+            }
+            RawOpcode::LdsFld { field } => self.push(
+                index,
+                E::Field {
+                    object: None,
+                    field: *field,
+                    volatile: instruction.prefixes.volatile,
+                },
+            ),
+            RawOpcode::LdFlda { field } => {
+                let object = self.pop()?;
+                self.push(
+                    index,
+                    E::FieldAddress {
+                        object: Some(Box::new(object)),
+                        field: *field,
+                    },
+                );
+            }
+            RawOpcode::LdsFlda { field } => self.push(
+                index,
+                E::FieldAddress {
+                    object: None,
+                    field: *field,
+                },
+            ),
+            RawOpcode::SetFld { field } => {
+                let value = self.pop()?;
+                let object = self.pop()?;
+                self.emit(Statement::StoreField {
+                    object: Some(object),
+                    field: *field,
+                    value,
+                    volatile: instruction.prefixes.volatile,
+                });
+            }
+            RawOpcode::StsFld { field } => {
+                let value = self.pop()?;
+                self.emit(Statement::StoreField {
+                    object: None,
+                    field: *field,
+                    value,
+                    volatile: instruction.prefixes.volatile,
+                });
+            }
+
+            RawOpcode::NewArr { .. } => {
+                let length = self.pop()?;
+                self.push(
+                    index,
+                    E::NewArray {
+                        element: token,
+                        length: Box::new(length),
+                    },
+                );
+            }
+            RawOpcode::LdLen {} => {
+                let array = self.pop()?;
+                self.push(index, E::ArrayLength(Box::new(array)));
+            }
+            RawOpcode::LdElema { .. } => {
+                let array_index = self.pop()?;
+                let array = self.pop()?;
+                self.push(
+                    index,
+                    E::ArrayElementAddress {
+                        array: Box::new(array),
+                        index: Box::new(array_index),
+                        ty: token,
+                    },
+                );
+            }
+            RawOpcode::LdElem_I1 {}
+            | RawOpcode::LdElem_U1 {}
+            | RawOpcode::LdElem_I2 {}
+            | RawOpcode::LdElem_U2 {}
+            | RawOpcode::LdElem_I4 {}
+            | RawOpcode::LdElem_U4 {}
+            | RawOpcode::LdElem_I8 {}
+            | RawOpcode::LdElem_I {}
+            | RawOpcode::LdElem_R4 {}
+            | RawOpcode::LdElem_R8 {}
+            | RawOpcode::LdElem_Ref {}
+            | RawOpcode::LdElem_Any { .. } => {
+                let array_index = self.pop()?;
+                let array = self.pop()?;
+                self.push(
+                    index,
+                    E::ArrayElement {
+                        array: Box::new(array),
+                        index: Box::new(array_index),
+                        ty: access_type(&instruction.opcode, token),
+                    },
+                );
+            }
+            RawOpcode::StElem_I {}
+            | RawOpcode::StElem_I1 {}
+            | RawOpcode::StElem_I2 {}
+            | RawOpcode::StElem_I4 {}
+            | RawOpcode::StElem_I8 {}
+            | RawOpcode::StElem_R4 {}
+            | RawOpcode::StElem_R8 {}
+            | RawOpcode::StElem_Ref {}
+            | RawOpcode::StElem_Any { .. } => {
+                let value = self.pop()?;
+                let array_index = self.pop()?;
+                let array = self.pop()?;
+                self.emit(Statement::StoreElement {
+                    array,
+                    index: array_index,
+                    value,
+                    ty: access_type(&instruction.opcode, token),
+                });
+            }
+
+            RawOpcode::Box { .. }
+            | RawOpcode::Unbox { .. }
+            | RawOpcode::Unbox_Any { .. }
+            | RawOpcode::CastClass { .. }
+            | RawOpcode::IsInst { .. } => {
+                let value = Box::new(self.pop()?);
+                let ty = token;
+                let kind = match instruction.opcode {
+                    RawOpcode::Box { .. } => E::Box { ty, value },
+                    RawOpcode::Unbox { .. } => E::Unbox { ty, object: value },
+                    RawOpcode::Unbox_Any { .. } => E::UnboxAny { ty, object: value },
+                    RawOpcode::CastClass { .. } => E::CastClass { ty, object: value },
+                    _ => E::IsInstance { ty, object: value },
+                };
+                self.push(index, kind);
+            }
+            RawOpcode::SizeOf { .. } => self.push(index, E::SizeOf(token)),
+            RawOpcode::LdToken { .. } => self.push(index, E::LoadToken(token)),
+            RawOpcode::LdFtn { method } => self.push(
+                index,
+                E::FunctionPointer {
+                    method: *method,
+                    object: None,
+                },
+            ),
+            RawOpcode::LdVirtFtn { method } => {
+                let object = self.pop()?;
+                self.push(
+                    index,
+                    E::FunctionPointer {
+                        method: *method,
+                        object: Some(Box::new(object)),
+                    },
+                );
+            }
+            RawOpcode::MkRefAny { .. } => {
+                let address = self.pop()?;
+                self.push(
+                    index,
+                    E::MakeTypedReference {
+                        ty: token,
+                        address: Box::new(address),
+                    },
+                );
+            }
+            RawOpcode::RefAnyVal { .. } => {
+                let reference = self.pop()?;
+                self.push(
+                    index,
+                    E::TypedReferenceValue {
+                        ty: token,
+                        reference: Box::new(reference),
+                    },
+                );
+            }
+            RawOpcode::RefAnyType {} => {
+                let reference = self.pop()?;
+                self.push(index, E::TypedReferenceType(Box::new(reference)));
+            }
+            RawOpcode::ArgList {} => self.push(index, E::ArgumentList),
+
+            RawOpcode::Throw {} => {
+                let value = self.pop()?;
+                self.terminator = Some(Terminator::Throw(value));
+            }
+            RawOpcode::Rethrow {} => self.terminator = Some(Terminator::Rethrow),
+            RawOpcode::EndFaultOrFinally {} => self.terminator = Some(Terminator::EndFinally),
+            RawOpcode::EndFilter {} => {
+                let value = self.pop()?;
+                self.terminator = Some(Terminator::EndFilter(value));
+            }
+
+            // Prefixes are decoded into the instruction they apply to
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Variable of a `ldarg`, `ldloc`, `starg` or `stloc` family instruction
+    fn operand_variable(&self, instruction: &Instruction) -> VariableId {
+        match instruction.operand {
+            Operand::Argument(index) => VariableId(index as usize),
+            Operand::Local(index) => VariableId(self.analysis.arguments.len() + index as usize),
+            _ => unreachable!(),
+        }
+    }
+
+    fn effect(&self, instruction: &Instruction) -> Result<cil::opcodes::StackEffect> {
+        Ok(instruction.stack_effect(self.image, self.signature)?)
+    }
+
+    fn call(&mut self, index: usize, target: CallTarget) -> Result<()> {
+        let instruction = &self.instructions[index];
+        let effect = self.effect(instruction)?;
+        // The function pointer of `calli` has been popped already
+        let count = match target {
+            CallTarget::Indirect { .. } => effect.pops as usize - 1,
+            _ => effect.pops as usize,
+        };
+        let call = Call {
+            target,
+            arguments: self.pop_many(count)?,
+            constrained: instruction.prefixes.constrained,
+            tail: instruction.prefixes.tail,
+        };
+        if effect.pushes > 0 {
+            self.push(index, ExpressionKind::Call(call));
+        } else {
+            self.emit(Statement::Call(call));
+        }
+        Ok(())
+    }
+
+    fn conditional(&mut self, instruction: &Instruction, condition: Expression) -> Result<()> {
+        let taken = self.block_at(instruction.branch_targets()[0])?;
+        let fallthrough = self.block_at(instruction.next_offset())?;
+        self.terminator = Some(Terminator::Conditional {
+            condition,
+            taken,
+            fallthrough,
+        });
+        Ok(())
+    }
+}
+
+fn comparison(opcode: &RawOpcode) -> (Comparison, bool) {
+    match opcode {
+        RawOpcode::Beq_S { .. } | RawOpcode::Beq { .. } | RawOpcode::Ceq {} => {
+            (Comparison::Equal, false)
+        }
+        RawOpcode::Bne_Un_S { .. } | RawOpcode::Bne_Un { .. } => (Comparison::NotEqual, true),
+        RawOpcode::Bge_S { .. } | RawOpcode::Bge { .. } => (Comparison::GreaterOrEqual, false),
+        RawOpcode::Bge_Un_S { .. } | RawOpcode::Bge_Un { .. } => (Comparison::GreaterOrEqual, true),
+        RawOpcode::Bgt_S { .. } | RawOpcode::Bgt { .. } | RawOpcode::Cgt {} => {
+            (Comparison::Greater, false)
+        }
+        RawOpcode::Bgt_Un_S { .. } | RawOpcode::Bgt_Un { .. } | RawOpcode::Cgt_Un {} => {
+            (Comparison::Greater, true)
+        }
+        RawOpcode::Ble_S { .. } | RawOpcode::Ble { .. } => (Comparison::LessOrEqual, false),
+        RawOpcode::Ble_Un_S { .. } | RawOpcode::Ble_Un { .. } => (Comparison::LessOrEqual, true),
+        RawOpcode::Blt_S { .. } | RawOpcode::Blt { .. } | RawOpcode::Clt {} => {
+            (Comparison::Less, false)
+        }
+        RawOpcode::Blt_Un_S { .. } | RawOpcode::Blt_Un { .. } | RawOpcode::Clt_Un {} => {
+            (Comparison::Less, true)
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn binary_operator(opcode: &RawOpcode) -> BinaryOperator {
+    match opcode {
+        RawOpcode::Add {} => BinaryOperator::Add(OverflowCheck::Off),
+        RawOpcode::Add_Ovf {} => BinaryOperator::Add(OverflowCheck::Signed),
+        RawOpcode::Add_Ovf_Unsigned {} => BinaryOperator::Add(OverflowCheck::Unsigned),
+        RawOpcode::Sub {} => BinaryOperator::Subtract(OverflowCheck::Off),
+        RawOpcode::Sub_Ovf {} => BinaryOperator::Subtract(OverflowCheck::Signed),
+        RawOpcode::Sub_Ovf_Unsigned {} => BinaryOperator::Subtract(OverflowCheck::Unsigned),
+        RawOpcode::Mul {} => BinaryOperator::Multiply(OverflowCheck::Off),
+        RawOpcode::Mul_Ovf {} => BinaryOperator::Multiply(OverflowCheck::Signed),
+        RawOpcode::Mul_Ovf_Unsigned {} => BinaryOperator::Multiply(OverflowCheck::Unsigned),
+        RawOpcode::Div {} => BinaryOperator::Divide { unsigned: false },
+        RawOpcode::DivUnsigned {} => BinaryOperator::Divide { unsigned: true },
+        RawOpcode::Rem {} => BinaryOperator::Remainder { unsigned: false },
+        RawOpcode::RemUnsigned {} => BinaryOperator::Remainder { unsigned: true },
+        RawOpcode::And {} => BinaryOperator::And,
+        RawOpcode::Or {} => BinaryOperator::Or,
+        RawOpcode::Xor {} => BinaryOperator::Xor,
+        RawOpcode::Shl {} => BinaryOperator::ShiftLeft,
+        RawOpcode::Shr {} => BinaryOperator::ShiftRight { unsigned: false },
+        RawOpcode::ShrUnsigned {} => BinaryOperator::ShiftRight { unsigned: true },
+        _ => unreachable!(),
+    }
+}
+
+/// Target, whether the conversion is checked, and whether the operand is unsigned
+fn conversion(opcode: &RawOpcode) -> (Element, bool, bool) {
+    match opcode {
+        RawOpcode::Conv_I1 {} => (Element::I1, false, false),
+        RawOpcode::Conv_I2 {} => (Element::I2, false, false),
+        RawOpcode::Conv_I4 {} => (Element::I4, false, false),
+        RawOpcode::Conv_I8 {} => (Element::I8, false, false),
+        RawOpcode::Conv_U1 {} => (Element::U1, false, false),
+        RawOpcode::Conv_U2 {} => (Element::U2, false, false),
+        RawOpcode::Conv_U4 {} => (Element::U4, false, false),
+        RawOpcode::Conv_U8 {} => (Element::U8, false, false),
+        RawOpcode::Conv_I {} => (Element::IntPtr, false, false),
+        RawOpcode::Conv_U {} => (Element::UIntPtr, false, false),
+        RawOpcode::Conv_R4 {} => (Element::R4, false, false),
+        RawOpcode::Conv_R8 {} => (Element::R8, false, false),
+        RawOpcode::Conv_R_Un {} => (Element::R8, false, true),
+        RawOpcode::Conv_Ovf_I1 {} => (Element::I1, true, false),
+        RawOpcode::Conv_Ovf_I2 {} => (Element::I2, true, false),
+        RawOpcode::Conv_Ovf_I4 {} => (Element::I4, true, false),
+        RawOpcode::Conv_Ovf_I8 {} => (Element::I8, true, false),
+        RawOpcode::Conv_Ovf_U1 {} => (Element::U1, true, false),
+        RawOpcode::Conv_Ovf_U2 {} => (Element::U2, true, false),
+        RawOpcode::Conv_Ovf_U4 {} => (Element::U4, true, false),
+        RawOpcode::Conv_Ovf_U8 {} => (Element::U8, true, false),
+        RawOpcode::Conv_Ovf_I {} => (Element::IntPtr, true, false),
+        RawOpcode::Conv_Ovf_U {} => (Element::UIntPtr, true, false),
+        RawOpcode::Conv_Ovf_I1_Unsigned {} => (Element::I1, true, true),
+        RawOpcode::Conv_Ovf_I2_Unsigned {} => (Element::I2, true, true),
+        RawOpcode::Conv_Ovf_I4_Unsigned {} => (Element::I4, true, true),
+        RawOpcode::Conv_Ovf_I8_Unsigned {} => (Element::I8, true, true),
+        RawOpcode::Conv_Ovf_U1_Unsigned {} => (Element::U1, true, true),
+        RawOpcode::Conv_Ovf_U2_Unsigned {} => (Element::U2, true, true),
+        RawOpcode::Conv_Ovf_U4_Unsigned {} => (Element::U4, true, true),
+        RawOpcode::Conv_Ovf_U8_Unsigned {} => (Element::U8, true, true),
+        RawOpcode::Conv_Ovf_I_Unsigned {} => (Element::IntPtr, true, true),
+        RawOpcode::Conv_Ovf_U_Unsigned {} => (Element::UIntPtr, true, true),
+        _ => unreachable!(),
+    }
+}
+
+/// Type accessed by the `ldind`, `stind`, `ldelem` and `stelem` families, `ldobj` and `stobj`
+fn access_type(opcode: &RawOpcode, token: Token) -> AccessType {
+    let element = match opcode {
+        RawOpcode::LdInd_I1 {}
+        | RawOpcode::StInd_I1 {}
+        | RawOpcode::LdElem_I1 {}
+        | RawOpcode::StElem_I1 {} => Element::I1,
+        RawOpcode::LdInd_U1 {} | RawOpcode::LdElem_U1 {} => Element::U1,
+        RawOpcode::LdInd_I2 {}
+        | RawOpcode::StInd_I2 {}
+        | RawOpcode::LdElem_I2 {}
+        | RawOpcode::StElem_I2 {} => Element::I2,
+        RawOpcode::LdInd_U2 {} | RawOpcode::LdElem_U2 {} => Element::U2,
+        RawOpcode::LdInd_I4 {}
+        | RawOpcode::StInd_I4 {}
+        | RawOpcode::LdElem_I4 {}
+        | RawOpcode::StElem_I4 {} => Element::I4,
+        RawOpcode::LdInd_U4 {} | RawOpcode::LdElem_U4 {} => Element::U4,
+        RawOpcode::LdInd_I8 {}
+        | RawOpcode::StInd_I8 {}
+        | RawOpcode::LdElem_I8 {}
+        | RawOpcode::StElem_I8 {} => Element::I8,
+        RawOpcode::LdInd_I {}
+        | RawOpcode::StInd_I {}
+        | RawOpcode::LdElem_I {}
+        | RawOpcode::StElem_I {} => Element::IntPtr,
+        RawOpcode::LdInd_R4 {}
+        | RawOpcode::StInd_R4 {}
+        | RawOpcode::LdElem_R4 {}
+        | RawOpcode::StElem_R4 {} => Element::R4,
+        RawOpcode::LdInd_R8 {}
+        | RawOpcode::StInd_R8 {}
+        | RawOpcode::LdElem_R8 {}
+        | RawOpcode::StElem_R8 {} => Element::R8,
+        RawOpcode::LdInd_Ref {}
+        | RawOpcode::StInd_Ref {}
+        | RawOpcode::LdElem_Ref {}
+        | RawOpcode::StElem_Ref {} => Element::Object,
+        _ => return AccessType::Token(token),
+    };
+    AccessType::Element(element)
+}
@@ -0,0 +1,786 @@
+//! Stack-free intermediate representation of method bodies
+//!
+//! Every instruction of a body is turned into statements over typed expression trees. Values
+//! don't live on an implicit evaluation stack: those that have to outlive a statement, or flow from
+//! one block into the next, are held in temporary variables. Blocks keep the ids of the
+//! [`ControlFlowGraph`] the body was built from, so the dominance and loop analyses of `cil` apply
+//! to the IR unchanged.
+
+mod builder;
+
+use std::fmt::{Display, Formatter, Write as _};
+
+use cil::{
+    flow::{BlockId, ControlFlowGraph},
+    image::CilImage,
+    meta::{MethodDefHandle, Token},
+    signature::Element,
+    verifier::{StackAnalysis, StackType, Verifier},
+};
+
+use crate::{
+    Result,
+    opcodes::{Comparison, OverflowCheck},
+};
+
+/// Index of a variable in [`MethodBody::variables`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct VariableId(pub usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VariableKind {
+    /// Argument number, where 0 is `this` for instance methods
+    Argument(u16),
+    Local(u16),
+    /// Stack slot that is held across a statement, or from a block into its successors
+    Temporary,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variable {
+    pub kind: VariableKind,
+    /// Declared type of arguments and locals, `None` for temporaries
+    pub element: Option<Element>,
+    pub ty: StackType,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constant {
+    I4(i32),
+    I8(i64),
+    R4(f32),
+    R8(f64),
+    Null,
+    /// User string token
+    String(Token),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOperator {
+    Add(OverflowCheck),
+    Subtract(OverflowCheck),
+    Multiply(OverflowCheck),
+    Divide { unsigned: bool },
+    Remainder { unsigned: bool },
+    And,
+    Or,
+    Xor,
+    ShiftLeft,
+    ShiftRight { unsigned: bool },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOperator {
+    Negate,
+    /// Bitwise complement
+    Not,
+    /// 1 for zero or null and 0 otherwise, the condition of `brfalse`
+    LogicalNot,
+}
+
+/// Type of a value read from or written to memory
+#[derive(Debug, Clone, PartialEq)]
+pub enum AccessType {
+    /// Implied by the opcode, eg. `ldind.i4` or `stelem.ref`
+    Element(Element),
+    /// TypeDef, TypeRef or TypeSpec token of the opcode, eg. `ldobj` or `stelem`
+    Token(Token),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CallTarget {
+    /// `call`
+    Method(Token),
+    /// `callvirt`
+    Virtual(Token),
+    /// `calli` through a function pointer, with the token of the call site signature
+    Indirect {
+        signature: Token,
+        pointer: Box<Expression>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Call {
+    pub target: CallTarget,
+    /// Arguments in signature order, starting with `this` for instance methods
+    pub arguments: Vec<Expression>,
+    /// Type the receiver is constrained to by a `constrained.` prefix
+    pub constrained: Option<Token>,
+    /// Whether the call has a `tail.` prefix
+    pub tail: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expression {
+    pub kind: ExpressionKind,
+    /// Type of the value on the evaluation stack
+    pub ty: StackType,
+}
+
+/// Operands are evaluated from left to right, in the order the original instructions pushed them
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExpressionKind {
+    Constant(Constant),
+    Load(VariableId),
+    Address(VariableId),
+    Binary {
+        operator: BinaryOperator,
+        left: Box<Expression>,
+        right: Box<Expression>,
+    },
+    Unary {
+        operator: UnaryOperator,
+        operand: Box<Expression>,
+    },
+    /// Evaluates to 1 if the comparison holds and 0 otherwise
+    Compare {
+        comparison: Comparison,
+        /// Unsigned for integers, unordered for floats
+        unsigned: bool,
+        left: Box<Expression>,
+        right: Box<Expression>,
+    },
+    Convert {
+        /// One of the primitive integer and float elements, or `IntPtr` and `UIntPtr`
+        target: Element,
+        /// Whether an overflow throws
+        checked: bool,
+        /// Whether the operand is read as unsigned
+        unsigned: bool,
+        operand: Box<Expression>,
+    },
+    /// `ckfinite`
+    CheckFinite(Box<Expression>),
+    Call(Call),
+    NewObject {
+        constructor: Token,
+        arguments: Vec<Expression>,
+    },
+    /// Value of a field, `object` is `None` for static fields
+    Field {
+        object: Option<Box<Expression>>,
+        field: Token,
+        volatile: bool,
+    },
+    FieldAddress {
+        object: Option<Box<Expression>>,
+        field: Token,
+    },
+    LoadIndirect {
+        address: Box<Expression>,
+        ty: AccessType,
+        volatile: bool,
+    },
+    ArrayElement {
+        array: Box<Expression>,
+        index: Box<Expression>,
+        ty: AccessType,
+    },
+    ArrayElementAddress {
+        array: Box<Expression>,
+        index: Box<Expression>,
+        ty: Token,
+    },
+    ArrayLength(Box<Expression>),
+    NewArray {
+        element: Token,
+        length: Box<Expression>,
+    },
+    Box {
+        ty: Token,
+        value: Box<Expression>,
+    },
+    /// Address of the value inside a boxed object
+    Unbox {
+        ty: Token,
+        object: Box<Expression>,
+    },
+    UnboxAny {
+        ty: Token,
+        object: Box<Expression>,
+    },
+    CastClass {
+        ty: Token,
+        object: Box<Expression>,
+    },
+    /// The object if it is an instance of `ty`, null otherwise
+    IsInstance {
+        ty: Token,
+        object: Box<Expression>,
+    },
+    SizeOf(Token),
+    /// Runtime handle of a type, method or field token
+    LoadToken(Token),
+    /// `ldftn`, or `ldvirtftn` when `object` is set
+    FunctionPointer {
+        method: Token,
+        object: Option<Box<Expression>>,
+    },
+    LocalAlloc(Box<Expression>),
+    MakeTypedReference {
+        ty: Token,
+        address: Box<Expression>,
+    },
+    TypedReferenceValue {
+        ty: Token,
+        reference: Box<Expression>,
+    },
+    TypedReferenceType(Box<Expression>),
+    ArgumentList,
+    /// The exception being handled, at the start of a catch handler or filter
+    CaughtException,
+}
+
+impl Expression {
+    pub fn new(kind: ExpressionKind, ty: StackType) -> Self {
+        Self { kind, ty }
+    }
+
+    /// Direct subexpressions in evaluation order
+    pub fn operands(&self) -> Vec<&Expression> {
+        use ExpressionKind::*;
+
+        match &self.kind {
+            Constant(_) | Load(_) | Address(_) | SizeOf(_) | LoadToken(_) | ArgumentList
+            | CaughtException => vec![],
+            Binary { left, right, .. } | Compare { left, right, .. } => vec![left, right],
+            Unary { operand: value, .. }
+            | Convert { operand: value, .. }
+            | CheckFinite(value)
+            | LoadIndirect { address: value, .. }
+            | ArrayLength(value)
+            | NewArray { length: value, .. }
+            | Box { value, .. }
+            | Unbox { object: value, .. }
+            | UnboxAny { object: value, .. }
+            | CastClass { object: value, .. }
+            | IsInstance { object: value, .. }
+            | LocalAlloc(value)
+            | MakeTypedReference { address: value, .. }
+            | TypedReferenceValue {
+                reference: value, ..
+            }
+            | TypedReferenceType(value) => vec![value],
+            Call(call) => call.operands(),
+            NewObject { arguments, .. } => arguments.iter().collect(),
+            Field { object, .. } | FieldAddress { object, .. } | FunctionPointer { object, .. } => {
+                object.as_deref().into_iter().collect()
+            }
+            ArrayElement { array, index, .. } | ArrayElementAddress { array, index, .. } => {
+                vec![array, index]
+            }
+        }
+    }
+
+    /// Whether the value can't change between being pushed and being used, so that it doesn't
+    /// have to be evaluated before the statements in between
+    pub fn is_stable(&self, variables: &[Variable]) -> bool {
+        match &self.kind {
+            ExpressionKind::Constant(_)
+            | ExpressionKind::Address(_)
+            | ExpressionKind::SizeOf(_)
+            | ExpressionKind::LoadToken(_)
+            | ExpressionKind::ArgumentList
+            | ExpressionKind::CaughtException
+            | ExpressionKind::FunctionPointer { object: None, .. } => true,
+            ExpressionKind::Load(variable) => variables[variable.0].kind == VariableKind::Temporary,
+            _ => false,
+        }
+    }
+}
+
+impl Call {
+    /// Subexpressions in evaluation order, the arguments followed by the function pointer of
+    /// `calli`
+    pub fn operands(&self) -> Vec<&Expression> {
+        let mut operands = self.arguments.iter().collect::<Vec<_>>();
+        if let CallTarget::Indirect { pointer, .. } = &self.target {
+            operands.push(pointer);
+        }
+        operands
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    /// `starg`, `stloc`, or a stack slot being held in a temporary
+    Assign {
+        variable: VariableId,
+        value: Expression,
+    },
+    /// `stfld`, or `stsfld` when `object` is `None`
+    StoreField {
+        object: Option<Expression>,
+        field: Token,
+        value: Expression,
+        volatile: bool,
+    },
+    StoreIndirect {
+        address: Expression,
+        value: Expression,
+        ty: AccessType,
+        volatile: bool,
+    },
+    StoreElement {
+        array: Expression,
+        index: Expression,
+        value: Expression,
+        ty: AccessType,
+    },
+    /// Call without a return value
+    Call(Call),
+    /// Expression evaluated for its side effects, its value is discarded with `pop`
+    Discard(Expression),
+    InitObject {
+        address: Expression,
+        ty: Token,
+    },
+    CopyObject {
+        destination: Expression,
+        source: Expression,
+        ty: Token,
+    },
+    CopyBlock {
+        destination: Expression,
+        source: Expression,
+        size: Expression,
+    },
+    InitBlock {
+        address: Expression,
+        value: Expression,
+        size: Expression,
+    },
+    /// `break`
+    Breakpoint,
+}
+
+/// How control leaves a block. Its operands are evaluated after the statements of the block.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Terminator {
+    /// `br`, or falling through into the next block
+    Branch(BlockId),
+    Conditional {
+        /// Integer, object reference or pointer that is taken as true when it isn't zero
+        condition: Expression,
+        taken: BlockId,
+        fallthrough: BlockId,
+    },
+    Switch {
+        value: Expression,
+        targets: Vec<BlockId>,
+        default: BlockId,
+    },
+    Return(Option<Expression>),
+    Throw(Expression),
+    Rethrow,
+    /// `leave` out of a protected region or handler
+    Leave(BlockId),
+    EndFinally,
+    /// End of a filter, with the value that decides whether its handler runs
+    EndFilter(Expression),
+    /// `jmp` to a method with the same arguments
+    Jump(Token),
+    /// The block can't be reached
+    Unreachable,
+}
+
+impl Terminator {
+    /// Blocks control continues with, not counting exception handlers and `finally` blocks
+    pub fn targets(&self) -> Vec<BlockId> {
+        match self {
+            Self::Branch(target) | Self::Leave(target) => vec![*target],
+            Self::Conditional {
+                taken, fallthrough, ..
+            } => vec![*taken, *fallthrough],
+            Self::Switch {
+                targets, default, ..
+            } => targets.iter().chain([default]).copied().collect(),
+            _ => vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub statements: Vec<Statement>,
+    pub terminator: Terminator,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MethodBody {
+    /// The arguments, starting with `this` for instance methods, followed by the locals and the
+    /// temporaries
+    pub variables: Vec<Variable>,
+    /// One block for each block of `cfg`, in the same order
+    pub blocks: Vec<Block>,
+    pub cfg: ControlFlowGraph,
+}
+
+impl MethodBody {
+    /// Builds the IR of a method definition. The body has to verify, see
+    /// [`MethodBody::from_analysis`] to use a verifier that resolves external types.
+    pub fn new(image: &CilImage, handle: MethodDefHandle) -> Result<Self> {
+        let cfg = image.control_flow_graph(handle)?;
+        let analysis = Verifier::new(image).verify(handle)?;
+        Self::from_analysis(image, handle, cfg, &analysis)
+    }
+
+    /// Builds the IR of a method definition from its control flow graph and stack analysis. Fails
+    /// with the first verification error of `analysis`, if there is one.
+    pub fn from_analysis(
+        image: &CilImage,
+        handle: MethodDefHandle,
+        cfg: ControlFlowGraph,
+        analysis: &StackAnalysis,
+    ) -> Result<Self> {
+        builder::build(image, handle, cfg, analysis)
+    }
+
+    pub fn variable(&self, id: VariableId) -> &Variable {
+        &self.variables[id.0]
+    }
+
+    pub fn block(&self, id: BlockId) -> &Block {
+        &self.blocks[id.0]
+    }
+
+    /// Variable of argument `index`, where 0 is `this` for instance methods
+    pub fn argument(&self, index: u16) -> Option<VariableId> {
+        self.variables
+            .iter()
+            .position(|v| v.kind == VariableKind::Argument(index))
+            .map(VariableId)
+    }
+
+    pub fn local(&self, index: u16) -> Option<VariableId> {
+        self.variables
+            .iter()
+            .position(|v| v.kind == VariableKind::Local(index))
+            .map(VariableId)
+    }
+}
+
+impl Display for MethodBody {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (i, variable) in self.variables.iter().enumerate() {
+            let kind = match variable.kind {
+                VariableKind::Argument(index) => format!("arg {index}"),
+                VariableKind::Local(index) => format!("local {index}"),
+                VariableKind::Temporary => "temporary".to_string(),
+            };
+            writeln!(f, "v{i}: {} ({kind})", variable.ty)?;
+        }
+
+        for (i, block) in self.blocks.iter().enumerate() {
+            writeln!(f)?;
+            writeln!(f, "block{i}: // IL_{:04x}", self.cfg.blocks[i].start)?;
+            for statement in &block.statements {
+                writeln!(f, "    {statement}")?;
+            }
+            writeln!(f, "    {}", block.terminator)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Statement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Assign { variable, value } => write!(f, "v{} = {value}", variable.0),
+            Self::StoreField {
+                object,
+                field,
+                value,
+                volatile,
+            } => {
+                let volatile = if *volatile { "volatile. " } else { "" };
+                match object {
+                    Some(object) => write!(f, "{volatile}stfld {field:08x}({object}, {value})"),
+                    None => write!(f, "{volatile}stsfld {field:08x}({value})"),
+                }
+            }
+            Self::StoreIndirect {
+                address,
+                value,
+                ty,
+                volatile,
+            } => {
+                let volatile = if *volatile { "volatile. " } else { "" };
+                write!(f, "{volatile}stind {ty}({address}, {value})")
+            }
+            Self::StoreElement {
+                array,
+                index,
+                value,
+                ty,
+            } => write!(f, "stelem {ty}({array}, {index}, {value})"),
+            Self::Call(call) => write!(f, "{call}"),
+            Self::Discard(value) => write!(f, "pop({value})"),
+            Self::InitObject { address, ty } => write!(f, "initobj {ty:08x}({address})"),
+            Self::CopyObject {
+                destination,
+                source,
+                ty,
+            } => write!(f, "cpobj {ty:08x}({destination}, {source})"),
+            Self::CopyBlock {
+                destination,
+                source,
+                size,
+            } => write!(f, "cpblk({destination}, {source}, {size})"),
+            Self::InitBlock {
+                address,
+                value,
+                size,
+            } => write!(f, "initblk({address}, {value}, {size})"),
+            Self::Breakpoint => f.write_str("break"),
+        }
+    }
+}
+
+impl Display for Terminator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Branch(target) => write!(f, "goto block{}", target.0),
+            Self::Conditional {
+                condition,
+                taken,
+                fallthrough,
+            } => write!(
+                f,
+                "if {condition} goto block{} else block{}",
+                taken.0, fallthrough.0
+            ),
+            Self::Switch {
+                value,
+                targets,
+                default,
+            } => {
+                write!(f, "switch {value} [")?;
+                for (i, target) in targets.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "block{}", target.0)?;
+                }
+                write!(f, "] else block{}", default.0)
+            }
+            Self::Return(Some(value)) => write!(f, "return {value}"),
+            Self::Return(None) => f.write_str("return"),
+            Self::Throw(value) => write!(f, "throw {value}"),
+            Self::Rethrow => f.write_str("rethrow"),
+            Self::Leave(target) => write!(f, "leave block{}", target.0),
+            Self::EndFinally => f.write_str("endfinally"),
+            Self::EndFilter(value) => write!(f, "endfilter {value}"),
+            Self::Jump(method) => write!(f, "jmp {method:08x}"),
+            Self::Unreachable => f.write_str("unreachable"),
+        }
+    }
+}
+
+impl Display for Call {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some(ty) = self.constrained {
+            write!(f, "constrained. {ty:08x} ")?;
+        }
+        if self.tail {
+            f.write_str("tail. ")?;
+        }
+        match &self.target {
+            CallTarget::Method(method) => write!(f, "call {method:08x}(")?,
+            CallTarget::Virtual(method) => write!(f, "callvirt {method:08x}(")?,
+            CallTarget::Indirect { signature, pointer } => {
+                write!(f, "calli {signature:08x} [{pointer}](")?
+            }
+        }
+        write_list(f, &self.arguments)?;
+        f.write_char(')')
+    }
+}
+
+impl Display for Expression {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        use ExpressionKind::*;
+
+        match &self.kind {
+            Constant(constant) => match constant {
+                self::Constant::I4(value) => write!(f, "{value}"),
+                self::Constant::I8(value) => write!(f, "{value}L"),
+                self::Constant::R4(value) => write!(f, "{value:?}f"),
+                self::Constant::R8(value) => write!(f, "{value:?}"),
+                self::Constant::Null => f.write_str("null"),
+                self::Constant::String(token) => write!(f, "ldstr {token:08x}"),
+            },
+            Load(variable) => write!(f, "v{}", variable.0),
+            Address(variable) => write!(f, "&v{}", variable.0),
+            Binary {
+                operator,
+                left,
+                right,
+            } => {
+                let (symbol, suffix) = match operator {
+                    BinaryOperator::Add(overflow) => ("+", overflow_suffix(*overflow)),
+                    BinaryOperator::Subtract(overflow) => ("-", overflow_suffix(*overflow)),
+                    BinaryOperator::Multiply(overflow) => ("*", overflow_suffix(*overflow)),
+                    BinaryOperator::Divide { unsigned } => ("/", unsigned_suffix(*unsigned)),
+                    BinaryOperator::Remainder { unsigned } => ("%", unsigned_suffix(*unsigned)),
+                    BinaryOperator::And => ("&", ""),
+                    BinaryOperator::Or => ("|", ""),
+                    BinaryOperator::Xor => ("^", ""),
+                    BinaryOperator::ShiftLeft => ("<<", ""),
+                    BinaryOperator::ShiftRight { unsigned } => (">>", unsigned_suffix(*unsigned)),
+                };
+                write!(f, "({left} {symbol}{suffix} {right})")
+            }
+            Unary { operator, operand } => {
+                let symbol = match operator {
+                    UnaryOperator::Negate => "-",
+                    UnaryOperator::Not => "~",
+                    UnaryOperator::LogicalNot => "!",
+                };
+                write!(f, "{symbol}{operand}")
+            }
+            Compare {
+                comparison,
+                unsigned,
+                left,
+                right,
+            } => {
+                let symbol = match comparison {
+                    Comparison::Equal => "==",
+                    Comparison::Greater => ">",
+                    Comparison::GreaterOrEqual => ">=",
+                    Comparison::Less => "<",
+                    Comparison::LessOrEqual => "<=",
+                    Comparison::NotEqual => "!=",
+                    Comparison::One => "== 1",
+                    Comparison::Zero => "== 0",
+                };
+                write!(f, "({left} {symbol}{} {right})", unsigned_suffix(*unsigned))
+            }
+            Convert {
+                target,
+                checked,
+                unsigned,
+                operand,
+            } => {
+                let checked = if *checked { ".ovf" } else { "" };
+                write!(
+                    f,
+                    "conv{checked}.{}{}({operand})",
+                    element_name(target),
+                    unsigned_suffix(*unsigned)
+                )
+            }
+            CheckFinite(value) => write!(f, "ckfinite({value})"),
+            Call(call) => write!(f, "{call}"),
+            NewObject {
+                constructor,
+                arguments,
+            } => {
+                write!(f, "newobj {constructor:08x}(")?;
+                write_list(f, arguments)?;
+                f.write_char(')')
+            }
+            Field {
+                object,
+                field,
+                volatile,
+            } => {
+                let volatile = if *volatile { "volatile. " } else { "" };
+                match object {
+                    Some(object) => write!(f, "{volatile}ldfld {field:08x}({object})"),
+                    None => write!(f, "{volatile}ldsfld {field:08x}"),
+                }
+            }
+            FieldAddress { object, field } => match object {
+                Some(object) => write!(f, "ldflda {field:08x}({object})"),
+                None => write!(f, "ldsflda {field:08x}"),
+            },
+            LoadIndirect {
+                address,
+                ty,
+                volatile,
+            } => {
+                let volatile = if *volatile { "volatile. " } else { "" };
+                write!(f, "{volatile}ldind {ty}({address})")
+            }
+            ArrayElement { array, index, ty } => write!(f, "ldelem {ty}({array}, {index})"),
+            ArrayElementAddress { array, index, ty } => {
+                write!(f, "ldelema {ty:08x}({array}, {index})")
+            }
+            ArrayLength(array) => write!(f, "ldlen({array})"),
+            NewArray { element, length } => write!(f, "newarr {element:08x}({length})"),
+            Box { ty, value } => write!(f, "box {ty:08x}({value})"),
+            Unbox { ty, object } => write!(f, "unbox {ty:08x}({object})"),
+            UnboxAny { ty, object } => write!(f, "unbox.any {ty:08x}({object})"),
+            CastClass { ty, object } => write!(f, "castclass {ty:08x}({object})"),
+            IsInstance { ty, object } => write!(f, "isinst {ty:08x}({object})"),
+            SizeOf(ty) => write!(f, "sizeof {ty:08x}"),
+            LoadToken(token) => write!(f, "ldtoken {token:08x}"),
+            FunctionPointer { method, object } => match object {
+                Some(object) => write!(f, "ldvirtftn {method:08x}({object})"),
+                None => write!(f, "ldftn {method:08x}"),
+            },
+            LocalAlloc(size) => write!(f, "localloc({size})"),
+            MakeTypedReference { ty, address } => write!(f, "mkrefany {ty:08x}({address})"),
+            TypedReferenceValue { ty, reference } => {
+                write!(f, "refanyval {ty:08x}({reference})")
+            }
+            TypedReferenceType(reference) => write!(f, "refanytype({reference})"),
+            ArgumentList => f.write_str("arglist"),
+            CaughtException => f.write_str("exception"),
+        }
+    }
+}
+
+impl Display for AccessType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Element(element) => f.write_str(element_name(element)),
+            Self::Token(token) => write!(f, "{token:08x}"),
+        }
+    }
+}
+
+fn write_list(f: &mut Formatter<'_>, expressions: &[Expression]) -> std::fmt::Result {
+    for (i, expression) in expressions.iter().enumerate() {
+        if i > 0 {
+            f.write_str(", ")?;
+        }
+        write!(f, "{expression}")?;
+    }
+    Ok(())
+}
+
+fn overflow_suffix(overflow: OverflowCheck) -> &'static str {
+    match overflow {
+        OverflowCheck::Off => "",
+        OverflowCheck::Signed => ".ovf",
+        OverflowCheck::Unsigned => ".ovf.un",
+    }
+}
+
+fn unsigned_suffix(unsigned: bool) -> &'static str {
+    if unsigned { ".un" } else { "" }
+}
+
+/// Opcode suffix of the elements used by conversions and typed memory accesses
+fn element_name(element: &Element) -> &'static str {
+    match element {
+        Element::I1 => "i1",
+        Element::U1 => "u1",
+        Element::I2 => "i2",
+        Element::U2 => "u2",
+        Element::I4 => "i4",
+        Element::U4 => "u4",
+        Element::I8 => "i8",
+        Element::U8 => "u8",
+        Element::R4 => "r4",
+        Element::R8 => "r8",
+        Element::IntPtr => "i",
+        Element::UIntPtr => "u",
+        Element::Object => "ref",
+        _ => "?",
+    }
+}
@@ -1,4 +1,5 @@
 pub mod error;
+pub mod ir;
 pub mod opcodes;
 
 pub use error::{Error, Result};
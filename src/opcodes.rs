@@ -2,7 +2,10 @@
 //!
 //! For example, opcode variants such as `ldarg.0`, `ldarg.1`, etc are represented as a single `LdArg(0)` and `LdArg(1)` respectively instead of having separate variants for each argument index.
 
-use cil::{meta::Token, opcodes::RawOpcode, signature::Element};
+use cil::{
+    meta::Token,
+    opcodes::{Instruction, RawOpcode},
+};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Opcode {
@@ -51,15 +54,15 @@ pub enum Opcode {
     Call(Token),
     Return,
 
-    /// Unconditional branch to a label
-    Branch(i32),
+    /// Unconditional branch to the instruction at the given offset
+    Branch(u32),
     BranchConditional {
-        offset: i32,
+        target: u32,
         comparison: Comparison,
         unsigned: bool,
     },
     Switch {
-        targets: Vec<i32>,
+        targets: Vec<u32>,
     },
 
     // Object manipulation
@@ -74,136 +77,142 @@ pub enum Opcode {
     ConvertToI8,
 }
 
-impl From<RawOpcode> for Opcode {
-    fn from(raw: RawOpcode) -> Self {
-        match raw {
+impl From<&Instruction> for Opcode {
+    fn from(instruction: &Instruction) -> Self {
+        let target = instruction
+            .branch_targets()
+            .first()
+            .copied()
+            .unwrap_or_default();
+
+        match instruction.opcode.clone() {
             RawOpcode::Add {} => Opcode::Add(OverflowCheck::Off),
             RawOpcode::Add_Ovf {} => Opcode::Add(OverflowCheck::Signed),
             RawOpcode::Add_Ovf_Unsigned {} => Opcode::Add(OverflowCheck::Unsigned),
             RawOpcode::And {} => Self::And,
             // RawOpcode::ArgList {} => todo!(),
-            RawOpcode::Beq { offset } => Self::BranchConditional {
-                offset,
+            RawOpcode::Beq { .. } => Self::BranchConditional {
+                target,
                 comparison: Comparison::Equal,
                 unsigned: false,
             },
-            RawOpcode::Beq_S { offset } => Self::BranchConditional {
-                offset: offset as i32,
+            RawOpcode::Beq_S { .. } => Self::BranchConditional {
+                target,
                 comparison: Comparison::Equal,
                 unsigned: false,
             },
-            RawOpcode::Bge { offset } => Self::BranchConditional {
-                offset,
+            RawOpcode::Bge { .. } => Self::BranchConditional {
+                target,
                 comparison: Comparison::GreaterOrEqual,
                 unsigned: false,
             },
-            RawOpcode::Bge_S { offset } => Self::BranchConditional {
-                offset: offset as i32,
+            RawOpcode::Bge_S { .. } => Self::BranchConditional {
+                target,
                 comparison: Comparison::GreaterOrEqual,
                 unsigned: false,
             },
-            RawOpcode::Bge_Un { offset } => Self::BranchConditional {
-                offset,
+            RawOpcode::Bge_Un { .. } => Self::BranchConditional {
+                target,
                 comparison: Comparison::GreaterOrEqual,
                 unsigned: true,
             },
-            RawOpcode::Bge_Un_S { offset } => Self::BranchConditional {
-                offset: offset as i32,
+            RawOpcode::Bge_Un_S { .. } => Self::BranchConditional {
+                target,
                 comparison: Comparison::GreaterOrEqual,
                 unsigned: true,
             },
-            RawOpcode::Bgt { offset } => Self::BranchConditional {
-                offset,
+            RawOpcode::Bgt { .. } => Self::BranchConditional {
+                target,
                 comparison: Comparison::Greater,
                 unsigned: false,
             },
-            RawOpcode::Bgt_S { offset } => Self::BranchConditional {
-                offset: offset as i32,
+            RawOpcode::Bgt_S { .. } => Self::BranchConditional {
+                target,
                 comparison: Comparison::Greater,
                 unsigned: false,
             },
-            RawOpcode::Bgt_Un { offset } => Self::BranchConditional {
-                offset,
+            RawOpcode::Bgt_Un { .. } => Self::BranchConditional {
+                target,
                 comparison: Comparison::Greater,
                 unsigned: true,
             },
-            RawOpcode::Bgt_Un_S { offset } => Self::BranchConditional {
-                offset: offset as i32,
+            RawOpcode::Bgt_Un_S { .. } => Self::BranchConditional {
+                target,
                 comparison: Comparison::Greater,
                 unsigned: true,
             },
-            RawOpcode::Ble { offset } => Self::BranchConditional {
-                offset,
+            RawOpcode::Ble { .. } => Self::BranchConditional {
+                target,
                 comparison: Comparison::LessOrEqual,
                 unsigned: false,
             },
-            RawOpcode::Ble_S { offset } => Self::BranchConditional {
-                offset: offset as i32,
+            RawOpcode::Ble_S { .. } => Self::BranchConditional {
+                target,
                 comparison: Comparison::LessOrEqual,
                 unsigned: false,
             },
-            RawOpcode::Ble_Un { offset } => Self::BranchConditional {
-                offset,
+            RawOpcode::Ble_Un { .. } => Self::BranchConditional {
+                target,
                 comparison: Comparison::LessOrEqual,
                 unsigned: true,
             },
-            RawOpcode::Ble_Un_S { offset } => Self::BranchConditional {
-                offset: offset as i32,
+            RawOpcode::Ble_Un_S { .. } => Self::BranchConditional {
+                target,
                 comparison: Comparison::LessOrEqual,
                 unsigned: true,
             },
-            RawOpcode::Blt { offset } => Self::BranchConditional {
-                offset,
+            RawOpcode::Blt { .. } => Self::BranchConditional {
+                target,
                 comparison: Comparison::Less,
                 unsigned: false,
             },
-            RawOpcode::Blt_S { offset } => Self::BranchConditional {
-                offset: offset as i32,
+            RawOpcode::Blt_S { .. } => Self::BranchConditional {
+                target,
                 comparison: Comparison::Less,
                 unsigned: false,
             },
-            RawOpcode::Blt_Un { offset } => Self::BranchConditional {
-                offset,
+            RawOpcode::Blt_Un { .. } => Self::BranchConditional {
+                target,
                 comparison: Comparison::Less,
                 unsigned: true,
             },
-            RawOpcode::Blt_Un_S { offset } => Self::BranchConditional {
-                offset: offset as i32,
+            RawOpcode::Blt_Un_S { .. } => Self::BranchConditional {
+                target,
                 comparison: Comparison::Less,
                 unsigned: true,
             },
-            RawOpcode::Bne_Un { offset } => Self::BranchConditional {
-                offset,
+            RawOpcode::Bne_Un { .. } => Self::BranchConditional {
+                target,
                 comparison: Comparison::NotEqual,
                 unsigned: true,
             },
-            RawOpcode::Bne_Un_S { offset } => Self::BranchConditional {
-                offset: offset as i32,
+            RawOpcode::Bne_Un_S { .. } => Self::BranchConditional {
+                target,
                 comparison: Comparison::NotEqual,
                 unsigned: true,
             },
-            RawOpcode::Br_False { offset } => Self::BranchConditional {
-                offset,
+            RawOpcode::Br_False { .. } => Self::BranchConditional {
+                target,
                 comparison: Comparison::Zero,
                 unsigned: false,
             },
-            RawOpcode::Br_False_S { offset } => Self::BranchConditional {
-                offset: offset as i32,
+            RawOpcode::Br_False_S { .. } => Self::BranchConditional {
+                target,
                 comparison: Comparison::Zero,
                 unsigned: false,
             },
-            RawOpcode::Br_True { offset } => Self::BranchConditional {
-                offset,
+            RawOpcode::Br_True { .. } => Self::BranchConditional {
+                target,
                 comparison: Comparison::One,
                 unsigned: false,
             },
-            RawOpcode::Br_True_S { offset } => Self::BranchConditional {
-                offset: offset as i32,
+            RawOpcode::Br_True_S { .. } => Self::BranchConditional {
+                target,
                 comparison: Comparison::One,
                 unsigned: false,
             },
-            RawOpcode::Br { offset } => Self::Branch(offset),
-            RawOpcode::Br_S { offset } => Self::Branch(offset as i32),
+            RawOpcode::Br { .. } => Self::Branch(target),
+            RawOpcode::Br_S { .. } => Self::Branch(target),
 
             // RawOpcode::Box { typeref } => todo!(),
             // RawOpcode::Break {} => todo!(),
@@ -223,7 +232,7 @@ impl From<RawOpcode> for Opcode {
                 comparison: Comparison::Greater,
                 unsigned: true,
             },
-            // RawOpcode::CkFinite { typeref } => todo!(),
+            // RawOpcode::CkFinite {} => todo!(),
             RawOpcode::Clt {} => Self::Compare {
                 comparison: Comparison::Less,
                 unsigned: false,
@@ -273,8 +282,6 @@ impl From<RawOpcode> for Opcode {
             // RawOpcode::Dup {} => todo!(),
             // RawOpcode::EndFaultOrFinally {} => todo!(),
             // RawOpcode::EndFilter {} => todo!(),
-            // RawOpcode::GetFld { field } => todo!(),
-            // RawOpcode::GetFlda { field } => todo!(),
             // RawOpcode::InitBlk {} => todo!(),
             // RawOpcode::InitObj { type_token } => todo!(),
             // RawOpcode::IsInst { typeref } => todo!(),
@@ -326,6 +333,8 @@ impl From<RawOpcode> for Opcode {
             // RawOpcode::LdNull {} => todo!(),
             // RawOpcode::LdObj { typeref } => todo!(),
             RawOpcode::LdStr { string } => Self::LoadString(string),
+            // RawOpcode::LdsFld { field } => todo!(),
+            // RawOpcode::LdsFlda { field } => todo!(),
             // RawOpcode::LdToken { token } => todo!(),
             // RawOpcode::LdVirtFtn { method } => todo!(),
             RawOpcode::Ldc_I4 { value } => Self::LoadConstantI4(value),
@@ -345,7 +354,7 @@ impl From<RawOpcode> for Opcode {
             RawOpcode::Ldc_R8 { value } => Self::LoadConstantR8(value),
             // RawOpcode::Leave { offset } => todo!(),
             // RawOpcode::Leave_S { offset } => todo!(),
-            // RawOpcode::LocAlloc { size } => todo!(),
+            // RawOpcode::LocAlloc {} => todo!(),
             // RawOpcode::MkRefAny { typeref } => todo!(),
             RawOpcode::Mul {} => Self::Multiply(OverflowCheck::Off),
             RawOpcode::Mul_Ovf {} => Self::Multiply(OverflowCheck::Signed),
@@ -357,10 +366,12 @@ impl From<RawOpcode> for Opcode {
             // RawOpcode::Not {} => todo!(),
             RawOpcode::Or {} => Self::Or,
             // RawOpcode::Pop {} => todo!(),
+            // RawOpcode::RefAnyType {} => todo!(),
             // RawOpcode::RefAnyVal { class } => todo!(),
             RawOpcode::Rem {} => Self::Remainder { unsigned: false },
             RawOpcode::RemUnsigned {} => Self::Remainder { unsigned: true },
             RawOpcode::Ret {} => Self::Return,
+            // RawOpcode::Rethrow {} => todo!(),
             RawOpcode::SetFld { field } => Self::SetField { field },
             RawOpcode::Shl {} => Self::ShiftLeft,
             RawOpcode::Shr {} => Self::ShiftRight,
@@ -396,10 +407,12 @@ impl From<RawOpcode> for Opcode {
             RawOpcode::Sub {} => Self::Subtract(OverflowCheck::Off),
             RawOpcode::Sub_Ovf {} => Self::Subtract(OverflowCheck::Signed),
             RawOpcode::Sub_Ovf_Unsigned {} => Self::Subtract(OverflowCheck::Unsigned),
-            RawOpcode::Switch { targets } => Self::Switch { targets },
+            RawOpcode::Switch { .. } => Self::Switch {
+                targets: instruction.branch_targets().to_vec(),
+            },
             // RawOpcode::Tail {} => todo!(),
             // RawOpcode::Throw {} => todo!(),
-            // RawOpcode::Unaligned {} => todo!(),
+            // RawOpcode::Unaligned { alignment } => todo!(),
             // RawOpcode::Unbox { typeref } => todo!(),
             // RawOpcode::Unbox_Any { typeref } => todo!(),
             // RawOpcode::Volatile {} => todo!(),
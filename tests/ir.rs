@@ -0,0 +1,116 @@
+use cascade::{
+    Error,
+    ir::{ExpressionKind, MethodBody, Statement, Terminator, VariableKind},
+};
+use cil::{
+    assembler::assemble,
+    flow::BlockId,
+    image::{CilImage, MethodHeader},
+    meta::MethodDefHandle,
+    verifier::VerificationErrorKind,
+};
+
+/// `Conditionals::TestComparisonOperators`, static with two `int32` locals
+const TWO_LOCALS: MethodDefHandle = MethodDefHandle(6);
+/// `Math::Add(int32, int32)`
+const ADD: MethodDefHandle = MethodDefHandle(4);
+
+fn load(name: &str) -> CilImage {
+    CilImage::load(format!("{}/tests/{name}", env!("CARGO_MANIFEST_DIR"))).unwrap()
+}
+
+/// Replaces the instructions of `handle` with `code`, keeping its locals
+fn replace_body(image: &mut CilImage, handle: MethodDefHandle, code: &str) {
+    let module = assemble(&format!(
+        ".class public A extends [mscorlib]System.Object {{
+            .method public static void M() cil managed {{ {code} }}
+        }}"
+    ))
+    .unwrap();
+    let (_, _, method) = module.methods().next().unwrap();
+    let (header, instructions) = method.body.clone().unwrap();
+
+    let (_, old_header, old_instructions) = &mut image.method_defs[handle.index()];
+    *old_header = MethodHeader {
+        local_var_sig_token: old_header.local_var_sig_token,
+        ..header
+    };
+    *old_instructions = instructions;
+}
+
+#[test]
+fn stack_values_are_spilled_before_their_source_changes() {
+    let mut image = load("Conditionals.dll");
+    replace_body(
+        &mut image,
+        TWO_LOCALS,
+        "ldloc.0 ldc.i4.1 stloc.0 stloc.1 ret",
+    );
+    let body = MethodBody::new(&image, TWO_LOCALS).unwrap();
+
+    // `stloc.1` stores the value local 0 had before `stloc.0`
+    assert_eq!(
+        body.to_string(),
+        "v0: int32 (local 0)
+v1: int32 (local 1)
+v2: int32 (temporary)
+
+block0: // IL_0000
+    v2 = v0
+    v0 = 1
+    v1 = v2
+    return
+"
+    );
+}
+
+#[test]
+fn join_points_share_temporaries() {
+    // `a > b ? b : a`
+    let mut image = load("Math.dll");
+    replace_body(
+        &mut image,
+        ADD,
+        "ldarg.0 ldarg.1 bgt.s a ldarg.0 br.s b a: ldarg.1 b: ret",
+    );
+    let body = MethodBody::new(&image, ADD).unwrap();
+    assert_eq!(body.blocks.len(), 4);
+
+    let assigned = |block: usize| match body.block(BlockId(block)).statements.as_slice() {
+        [Statement::Assign { variable, .. }] => *variable,
+        other => panic!("expected a single assignment, found {other:?}"),
+    };
+    let temporary = assigned(1);
+    assert_eq!(assigned(2), temporary);
+    assert_eq!(body.variable(temporary).kind, VariableKind::Temporary);
+    assert_eq!(
+        body.blocks
+            .iter()
+            .filter(|b| matches!(b.terminator, Terminator::Branch(BlockId(3))))
+            .count(),
+        2
+    );
+    assert!(matches!(
+        &body.block(BlockId(3)).terminator,
+        Terminator::Return(Some(value)) if value.kind == ExpressionKind::Load(temporary)
+    ));
+}
+
+#[test]
+fn bodies_that_fail_verification_are_rejected() {
+    let mut image = load("Math.dll");
+    replace_body(&mut image, ADD, "ldarg.0 add ret");
+    match MethodBody::new(&image, ADD) {
+        Err(Error::VerificationError(error)) => {
+            assert_eq!(error.offset, 1);
+            assert_eq!(
+                error.kind,
+                VerificationErrorKind::StackUnderflow {
+                    needed: 2,
+                    available: 1
+                }
+            );
+        }
+        other => panic!("expected a verification error, got {other:?}"),
+    }
+}